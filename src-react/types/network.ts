@@ -40,6 +40,8 @@ export interface PublicNetworkInfo {
   is_vpn?: boolean;
   is_proxy?: boolean;
   is_hosting?: boolean;
+  connection_type?: 'residential' | 'hosting' | 'vpn' | 'mobile' | 'unknown';
+  connection_type_reasons?: string[];
   location?: LocationInfo;
   dns_servers?: DNSServer[];
   error?: string;
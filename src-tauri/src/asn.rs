@@ -0,0 +1,130 @@
+// asn.rs - AS number to organization name lookup
+//
+// Names come from, in order: a dataset loaded with `load_asn_database`, names
+// already resolved this session, a small bundled table of common networks, and
+// finally Team Cymru's DNS service. Cymru answers are cached, so each ASN costs
+// at most one round trip.
+
+use crate::radar_debug;
+use crate::radar_info;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// Large ISPs, clouds and CDNs -> organization
+const ASN_NAMES: &[(u32, &str)] = &[
+    // North America
+    (7922, "Comcast Cable Communications"),
+    (7018, "AT&T Services"),
+    (701, "Verizon Business"),
+    (20115, "Charter Communications"),
+    (22773, "Cox Communications"),
+    (21928, "T-Mobile USA"),
+    (6167, "Verizon Wireless"),
+    (3356, "Lumen Technologies"),
+    (174, "Cogent Communications"),
+    (812, "Rogers Communications"),
+    (577, "Bell Canada"),
+    (6327, "Shaw Communications"),
+    // Europe
+    (3320, "Deutsche Telekom"),
+    (2856, "British Telecommunications"),
+    (5089, "Virgin Media"),
+    (5607, "Sky UK"),
+    (3215, "Orange"),
+    (3352, "Telefonica de Espana"),
+    (1136, "KPN"),
+    (3209, "Vodafone Germany"),
+    // Asia-Pacific
+    (1221, "Telstra"),
+    (4134, "China Telecom"),
+    (4837, "China Unicom"),
+    (55836, "Reliance Jio"),
+    (9498, "Bharti Airtel"),
+    (2914, "NTT America"),
+    (4713, "NTT Communications"),
+    // Clouds and CDNs
+    (15169, "Google"),
+    (16509, "Amazon"),
+    (14618, "Amazon"),
+    (8075, "Microsoft"),
+    (13335, "Cloudflare"),
+    (20940, "Akamai Technologies"),
+    (54113, "Fastly"),
+    (14061, "DigitalOcean"),
+    (24940, "Hetzner Online"),
+    (16276, "OVH"),
+    (63949, "Akamai Connected Cloud (Linode)"),
+];
+
+// Names from a loaded dataset and from earlier Cymru lookups
+static KNOWN_NAMES: Lazy<Mutex<HashMap<u32, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Parse "AS7922", "as7922" or "7922"
+fn parse_asn(asn: &str) -> Option<u32> {
+    let asn = asn.trim();
+    let digits = asn.strip_prefix("AS").or_else(|| asn.strip_prefix("as")).unwrap_or(asn);
+    digits.parse().ok()
+}
+
+// Name of an ASN known without a network round trip
+pub fn local_asn_name(asn: u32) -> Option<String> {
+    if let Some(name) = KNOWN_NAMES.lock().unwrap().get(&asn) {
+        return Some(name.clone());
+    }
+    ASN_NAMES
+        .iter()
+        .find(|(number, _)| *number == asn)
+        .map(|(_, name)| name.to_string())
+}
+
+// Name of an ASN, asking Cymru when it isn't known locally. Cymru answers
+// "7922 | US | arin | 1997-02-14 | COMCAST-7922 - Comcast Cable Communications, LLC, US".
+pub async fn asn_name(asn: u32) -> Option<String> {
+    if let Some(name) = local_asn_name(asn) {
+        return Some(name);
+    }
+
+    let query = format!("AS{}.asn.cymru.com.", asn);
+    let resolver = trust_dns_resolver::TokioAsyncResolver::tokio_from_system_conf().ok()?;
+    let response = resolver.txt_lookup(&query).await.ok()?;
+    let record = response.iter().find_map(|txt| txt.iter().next().map(|data| String::from_utf8_lossy(data).into_owned()))?;
+    let name = record.rsplit('|').next()?.trim().to_string();
+    if name.is_empty() {
+        return None;
+    }
+
+    radar_debug!("Resolved AS{} to {} via Cymru", asn, name);
+    KNOWN_NAMES.lock().unwrap().insert(asn, name.clone());
+    Some(name)
+}
+
+// Load ASN -> name mappings from a file, one per line as "<asn>,<name>" or
+// "<asn><TAB><name>", where the ASN may carry an "AS" prefix. Blank lines and
+// lines starting with '#' are skipped. Loaded names take precedence over the
+// bundled table. Returns how many mappings were loaded.
+pub fn load_asn_database(path: &str) -> Result<usize, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    let mut names = HashMap::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (asn, name) = line
+            .split_once('\t')
+            .or_else(|| line.split_once(','))
+            .ok_or_else(|| format!("Line {}: expected \"<asn>,<name>\"", index + 1))?;
+        let asn = parse_asn(asn).ok_or_else(|| format!("Line {}: invalid ASN \"{}\"", index + 1, asn.trim()))?;
+        let name = name.trim().trim_matches('"');
+        if !name.is_empty() {
+            names.insert(asn, name.to_string());
+        }
+    }
+
+    let count = names.len();
+    KNOWN_NAMES.lock().unwrap().extend(names);
+    radar_info!("Loaded {} ASN names from {}", count, path);
+    Ok(count)
+}
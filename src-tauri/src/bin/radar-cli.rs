@@ -0,0 +1,143 @@
+// radar-cli - Headless network scan that prints consolidated results as JSON
+//
+// Usage: radar-cli [--cidr 10.0.0.0/22] [--ports common|web|iot|22,80,443] [--timeout 500] [--tcp-timeout 500] [--udp-timeout 500] [--batch 256] [--batch-delay 500] [--sort ip|hostname|category|ports|seen] [--ipv6] [--ping-sweep] [--interfaces en0,eth1] [--rule 32400=Plex] [--record file] [--jsonl file] [--replay file] [--source-details] [--verbose]
+
+use radar_lib::network_scanner::{ports_for_profile, scan_network_services_impl, start_scan, ScanConfig, SortKey};
+use radar_lib::enricher::{register_enricher, PortRule};
+use radar_lib::sink::CollectingSink;
+
+const USAGE: &str = "Usage: radar-cli [options]
+
+Options:
+  --cidr <range>      Also scan every host in an IPv4 range, e.g. 10.0.0.0/22
+  --ports <ports>     Port profile (common, web, iot) or comma-separated TCP ports
+  --timeout <ms>      Per-port probe timeout in milliseconds (default 500)
+  --tcp-timeout <ms>  TCP connect timeout, overriding --timeout
+  --udp-timeout <ms>  UDP reply timeout, overriding --timeout
+  --batch <hosts>     Scan hosts in batches of this size (default: all at once)
+  --batch-delay <ms>  Pause between batches in milliseconds
+  --sort <key>        Order results by ip (default), hostname, category, ports or seen
+  --ipv6              Also scan IPv6 hosts found in the neighbor cache
+  --ping-sweep        Ping the local subnet first to find hosts missing from ARP
+  --interfaces <nics> Only sweep the subnets of these comma-separated interfaces
+  --rule <port=type>  Label devices with this port open as the given type (repeatable)
+  --record <file>     Save everything discovery found to a file
+  --jsonl <file>      Append each discovery to a JSON Lines file as it is found
+  --replay <file>     Consolidate a saved recording instead of scanning
+  --source-details    Include each discovery method's raw details in the output
+  --verbose           Log scan progress to stderr
+  --help              Show this help";
+
+fn parse_args(args: &[String]) -> Result<(ScanConfig, bool), String> {
+    let mut config = ScanConfig::default();
+    let mut verbose = false;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", name))
+        };
+
+        match arg.as_str() {
+            "--cidr" => {
+                let cidr = value("--cidr")?;
+                radar_lib::network_scanner::expand_cidr(&cidr)?;
+                config.cidr = Some(cidr);
+            }
+            "--ports" => {
+                let ports = value("--ports")?;
+                let list = match ports_for_profile(&ports) {
+                    Some(list) => list,
+                    None => ports
+                        .split(',')
+                        .map(|p| p.trim().parse::<u16>().map_err(|_| format!("Invalid port '{}'", p)))
+                        .collect::<Result<Vec<u16>, String>>()?,
+                };
+                config.tcp_ports = Some(list);
+                config.validate()?;
+            }
+            "--timeout" => {
+                let timeout = value("--timeout")?;
+                config.timeout_ms = Some(
+                    timeout
+                        .parse()
+                        .map_err(|_| format!("Invalid timeout '{}'", timeout))?,
+                );
+            }
+            "--tcp-timeout" => {
+                let timeout = value("--tcp-timeout")?;
+                config.tcp_timeout_ms = Some(timeout.parse().map_err(|_| format!("Invalid timeout '{}'", timeout))?);
+            }
+            "--udp-timeout" => {
+                let timeout = value("--udp-timeout")?;
+                config.udp_timeout_ms = Some(timeout.parse().map_err(|_| format!("Invalid timeout '{}'", timeout))?);
+            }
+            "--batch" => {
+                let size = value("--batch")?;
+                config.batch_size = Some(size.parse().map_err(|_| format!("Invalid batch size '{}'", size))?);
+            }
+            "--batch-delay" => {
+                let delay = value("--batch-delay")?;
+                config.batch_delay_ms = Some(delay.parse().map_err(|_| format!("Invalid batch delay '{}'", delay))?);
+            }
+            "--sort" => {
+                let key = value("--sort")?;
+                config.sort_by = SortKey::parse(&key).ok_or_else(|| format!("Invalid sort key '{}'", key))?;
+            }
+            "--ipv6" => config.ipv6 = true,
+            "--ping-sweep" => config.ping_sweep = true,
+            "--interfaces" => {
+                let interfaces = value("--interfaces")?;
+                config.interfaces = Some(interfaces.split(',').map(|i| i.trim().to_string()).collect());
+                config.validate()?;
+            }
+            "--rule" => {
+                let rule = PortRule::parse(&value("--rule")?)?;
+                register_enricher(Box::new(rule));
+            }
+            "--record" => config.record_to = Some(value("--record")?),
+            "--replay" => config.replay_from = Some(value("--replay")?),
+            "--jsonl" => config.stream_jsonl_to = Some(value("--jsonl")?),
+            "--source-details" => config.include_source_details = true,
+            "--verbose" => verbose = true,
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            other => return Err(format!("Unknown argument '{}'\n\n{}", other, USAGE)),
+        }
+    }
+
+    Ok((config, verbose))
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (config, verbose) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    // Logs go to stderr so stdout stays valid JSON
+    radar_lib::log::init_logger();
+    radar_lib::log::set_logging(verbose);
+
+    let sink = CollectingSink::new();
+    let scan = start_scan();
+    match scan_network_services_impl(&sink, &config, &scan.cancel).await {
+        Ok((services, _)) => match serde_json::to_string_pretty(&services) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Failed to serialize results: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Err(e) => {
+            eprintln!("Scan failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -1,9 +1,10 @@
 // commands.rs - Contains all Tauri commands
 
-use tauri::AppHandle;
-use tauri::Emitter;
+use tauri::{AppHandle, Manager, State};
 use crate::radar_info;
 use crate::NetworkService;
+use crate::public_network::{PublicField, PublicFieldValue, PublicNetworkState};
+use crate::sink::{ScanSink, ServiceSink, SilentSink};
 
 // Import only the macros we actually use
 
@@ -77,20 +78,172 @@ pub fn is_logging_enabled() -> bool {
 
 // Network commands
 #[tauri::command]
-pub async fn get_public_network_info() -> Result<crate::public_network::PublicNetworkInfo, String> {
+pub async fn get_public_network_info(
+    state: State<'_, PublicNetworkState>,
+    include_whois: Option<bool>,
+    force: Option<bool>,
+) -> Result<crate::public_network::PublicNetworkInfo, String> {
     #[cfg(feature = "command_logging")]
-    log_command("get_public_network_info", "no args".to_string());
+    log_command("get_public_network_info", format!("include_whois: {:?}, force: {:?}", include_whois, force));
     
-    let result = crate::public_network::get_public_network_info_internal()
+    // A fresh cached result is returned unless `force` asks for a new lookup
+    let cancel = crate::public_network::public_info_cancel_token();
+    let mut result = crate::public_network::get_public_network_info_internal(&state, force.unwrap_or(false), cancel.clone())
         .await
         .map_err(|e| e.to_string());
     
+    // WHOIS is an extra network round trip, so it only runs when asked for
+    // and the cached result doesn't have it yet
+    if include_whois.unwrap_or(false) {
+        if let Some(info) = result.as_mut().ok().filter(|info| info.whois.is_none()) {
+            match crate::public_network::refresh_field(info, PublicField::Whois, &cancel).await {
+                // Keep it in the cached info so the next call doesn't look it up again
+                Ok(_) => state.state.lock().unwrap().whois = info.whois.clone(),
+                Err(e) => radar_info!("Skipping WHOIS: {}", e),
+            }
+        }
+    }
+    
     #[cfg(feature = "command_logging")]
     log_result("get_public_network_info", format!("{:?}", result));
     
     result
 }
 
+// Like get_public_network_info, but emits `public-info-update` with the partial
+// info as each lookup finishes so the panel can fill in progressively
+#[tauri::command]
+pub async fn stream_public_network_info(
+    app_handle: AppHandle,
+    state: State<'_, PublicNetworkState>,
+    force: Option<bool>,
+) -> Result<crate::public_network::PublicNetworkInfo, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("stream_public_network_info", format!("force: {:?}", force));
+    
+    let cancel = crate::public_network::public_info_cancel_token();
+    let result = crate::public_network::stream_public_network_info(&app_handle, &state, force.unwrap_or(false), cancel)
+        .await
+        .map_err(|e| e.to_string());
+    
+    #[cfg(feature = "command_logging")]
+    log_result("stream_public_network_info", format!("{:?}", result));
+    
+    result
+}
+
+// Command to also write logs to a rotating file, or stop with None. Relative
+// paths are placed in the app data directory, e.g. "radar.log".
+#[tauri::command]
+pub fn set_log_file(app_handle: AppHandle, path: Option<String>) -> Result<Option<String>, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("set_log_file", format!("path: {:?}", path));
+    
+    let path = match path {
+        Some(path) if std::path::Path::new(&path).is_relative() => Some(
+            app_handle
+                .path()
+                .app_data_dir()
+                .map_err(|e| format!("Failed to locate app data directory: {}", e))?
+                .join(path),
+        ),
+        path => path.map(std::path::PathBuf::from),
+    };
+    let result = crate::log::set_log_file(path.as_deref()).map(|()| path.map(|p| p.display().to_string()));
+    
+    #[cfg(feature = "command_logging")]
+    log_result("set_log_file", format!("{:?}", result));
+    
+    result
+}
+
+// Command to set one module's log level, e.g. ("upnp", "warn") or ("network_scanner", "debug")
+#[tauri::command]
+pub fn set_module_log_level(module: String, level: String) -> Result<(), String> {
+    #[cfg(feature = "command_logging")]
+    log_command("set_module_log_level", format!("module: {}, level: {}", module, level));
+    
+    let result = crate::log::set_module_log_level(&module, &level).map(|_| ());
+    
+    #[cfg(feature = "command_logging")]
+    log_result("set_module_log_level", format!("{:?}", result));
+    
+    result
+}
+
+// Command to get the most recent log lines, oldest first, for an in-app log pane
+#[tauri::command]
+pub fn get_recent_logs() -> Vec<String> {
+    crate::log::recent_logs()
+}
+
+// Set how long get_public_network_info serves its cached result; 0 disables the cache
+#[tauri::command]
+pub fn set_public_info_cache_ttl(state: State<'_, PublicNetworkState>, ttl_secs: u64) {
+    #[cfg(feature = "command_logging")]
+    log_command("set_public_info_cache_ttl", format!("ttl_secs: {}", ttl_secs));
+    
+    state.set_ttl(std::time::Duration::from_secs(ttl_secs));
+}
+
+// Choose whether the public IP lookup races every STUN server or tries them in turn
+#[tauri::command]
+pub fn set_stun_mode(mode: crate::public_network::StunMode) {
+    #[cfg(feature = "command_logging")]
+    log_command("set_stun_mode", format!("mode: {:?}", mode));
+    
+    crate::public_network::set_stun_mode(mode);
+}
+
+// Re-run the lookup for one public network field and return its updated value
+#[tauri::command]
+pub async fn refresh_public_field(
+    state: State<'_, PublicNetworkState>,
+    field: PublicField,
+) -> Result<PublicFieldValue, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("refresh_public_field", format!("field: {:?}", field));
+    
+    let result = crate::public_network::refresh_public_field(&state, field)
+        .await
+        .map_err(|e| e.to_string());
+    
+    #[cfg(feature = "command_logging")]
+    log_result("refresh_public_field", format!("{:?}", result));
+    
+    result
+}
+
+// Fast path for just the public IP (e.g. for polling or dynamic DNS), without ASN/geo/router/DNS lookups
+#[tauri::command]
+pub async fn get_public_ip_only() -> Result<crate::public_network::PublicIpOnly, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("get_public_ip_only", "no args".to_string());
+    
+    let result = crate::public_network::get_public_ip_only(&crate::public_network::public_info_cancel_token())
+        .await
+        .map_err(|e| e.to_string());
+    
+    #[cfg(feature = "command_logging")]
+    log_result("get_public_ip_only", format!("{:?}", result));
+    
+    result
+}
+
+#[tauri::command]
+pub fn cancel_public_network_info() -> Result<(), String> {
+    #[cfg(feature = "command_logging")]
+    log_command("cancel_public_network_info", "no args".to_string());
+    
+    // In-flight get_public_network_info calls return a "Cancelled" error
+    crate::public_network::cancel_public_network_info();
+    
+    #[cfg(feature = "command_logging")]
+    log_result("cancel_public_network_info", "Cancelled lookups".to_string());
+    
+    Ok(())
+}
+
 #[tauri::command]
 pub fn set_network_logging(enable: bool) -> bool {
     #[cfg(feature = "command_logging")]
@@ -118,114 +271,659 @@ pub fn is_network_logging_enabled() -> bool {
 }
 
 // Scanner commands
+// Returns the scan's summary, the same `ScanResult` the `scan-complete` event carries;
+// every event the scan emits carries its ID as "scan_id".
+// `config` is optional; omitted fields use the scanner defaults.
 #[tauri::command]
-pub async fn run_network_scan(app_handle: AppHandle) -> Result<bool, String> {
-    radar_info!("Starting network scan");
+pub async fn run_network_scan(
+    app_handle: AppHandle,
+    config: Option<crate::network_scanner::ScanConfig>,
+) -> Result<crate::network_scanner::ScanResult, String> {
+    let config = config.unwrap_or_default();
+    config.validate()?;
+    let scan = crate::network_scanner::start_scan();
+    let scan_id = scan.id;
+    let sink = ScanSink::new(app_handle, scan_id);
+    radar_info!("Starting network scan {}", scan_id);
     
     // First, emit a scan-started event so the frontend knows to start listening
-    match app_handle.emit("scan-started", true) {
-        Ok(_) => radar_info!("Successfully emitted scan-started event"),
-        Err(e) => radar_info!("Failed to emit scan-started event: {}", e),
+    if config.emit_events() {
+        match sink.emit_event("scan-started", ()) {
+            Ok(_) => radar_info!("Successfully emitted scan-started event"),
+            Err(e) => radar_info!("Failed to emit scan-started event: {}", e),
+        }
     }
     
-    // Run mDNS and UPnP discovery first
-    radar_info!("Starting mDNS service discovery...");
-    let mdns_result = crate::discover_mdns_streaming(app_handle.clone()).await;
-    if let Ok(services) = &mdns_result {
-        radar_info!("Found {} mDNS services", services.len());
+    // Discovery emits individual service events as it goes, then stores the consolidated results
+    let (consolidated, mut scan_result) =
+        crate::network_scanner::scan_network_services_impl(&sink, &config, &scan.cancel).await?;
+    radar_info!("Network scan complete, found {} consolidated services", consolidated.len());
+    
+    // Finally emit scan-complete with the overall outcome, also keeping its ID
+    // on the stored copy get_last_scan_result returns
+    scan_result.scan_id = scan_id;
+    crate::network_scanner::LAST_SCAN_RESULT.lock().unwrap().scan_id = scan_id;
+    crate::network_scanner::record_scan_snapshot(&consolidated);
+    if config.emit_events() {
+        radar_info!("Emitting scan-complete event");
+        let _ = sink.emit_event("scan-complete", &scan_result);
     }
     
-    radar_info!("Starting UPnP service discovery...");
-    let upnp_result = crate::discover_upnp_streaming(app_handle.clone()).await;
-    if let Ok(services) = &upnp_result {
-        radar_info!("Found {} UPnP services", services.len());
+    Ok(scan_result)
+}
+
+#[tauri::command]
+pub async fn stop_network_scan() -> Result<(), String> {
+    #[cfg(feature = "command_logging")]
+    log_command("stop_network_scan", "no args".to_string());
+    
+    // The running scan stops probing and returns what it found so far, emitting
+    // its own scan-complete (with `cancelled` set) once the results are stored
+    crate::network_scanner::cancel_network_scan();
+    
+    #[cfg(feature = "command_logging")]
+    log_result("stop_network_scan", "Stopped scan".to_string());
+    
+    Ok(())
+}
+
+// `emit_events` defaults to true; pass false to only get the returned services
+#[tauri::command]
+pub async fn discover_mdns_streaming(
+    app_handle: AppHandle,
+    emit_events: Option<bool>,
+) -> Result<Vec<crate::NetworkService>, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("discover_mdns_streaming", format!("emit_events: {:?}", emit_events));
+    
+    let result = if emit_events.unwrap_or(true) {
+        crate::network_scanner::discover_mdns_streaming(app_handle).await
+    } else {
+        crate::network_scanner::discover_mdns_streaming(SilentSink).await
+    };
+    
+    #[cfg(feature = "command_logging")]
+    log_result("discover_mdns_streaming", format!("Found {} services", result.as_ref().map_or(0, |v| v.len())));
+    
+    result
+}
+
+// `emit_events` defaults to true; pass false to only get the returned services
+#[tauri::command]
+pub async fn discover_upnp_streaming(
+    app_handle: AppHandle,
+    emit_events: Option<bool>,
+) -> Result<Vec<crate::NetworkService>, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("discover_upnp_streaming", format!("emit_events: {:?}", emit_events));
+    
+    let result = if emit_events.unwrap_or(true) {
+        crate::network_scanner::discover_upnp_streaming(app_handle).await
+    } else {
+        crate::network_scanner::discover_upnp_streaming(SilentSink).await
+    };
+    
+    #[cfg(feature = "command_logging")]
+    log_result("discover_upnp_streaming", format!("Found {} services", result.as_ref().map_or(0, |v| v.len())));
+    
+    result
+}
+
+/// Add `.local` hostnames (e.g. from DHCP leases) to query directly during mDNS discovery
+#[tauri::command]
+pub fn add_mdns_hostnames(hostnames: Vec<String>) -> usize {
+    #[cfg(feature = "command_logging")]
+    log_command("add_mdns_hostnames", format!("hostnames: {:?}", hostnames));
+    
+    for hostname in &hostnames {
+        crate::network_scanner::add_mdns_candidate_hostname(hostname);
+    }
+    let result = crate::network_scanner::MDNS_CANDIDATE_HOSTNAMES.lock().unwrap().len();
+    
+    #[cfg(feature = "command_logging")]
+    log_result("add_mdns_hostnames", format!("{} candidate hostnames", result));
+    
+    result
+}
+
+/// Set the multicast TTL for SSDP searches and direct mDNS queries (1-255, default 1).
+/// Values above 1 only reach other segments on networks with multicast routing.
+#[tauri::command]
+pub fn set_multicast_ttl(ttl: u32) -> Result<(), String> {
+    #[cfg(feature = "command_logging")]
+    log_command("set_multicast_ttl", format!("ttl: {}", ttl));
+    
+    let result = crate::network_scanner::set_multicast_ttl(ttl);
+    
+    #[cfg(feature = "command_logging")]
+    log_result("set_multicast_ttl", format!("{:?}", result));
+    
+    result
+}
+
+#[tauri::command]
+pub fn get_multicast_ttl() -> u32 {
+    crate::network_scanner::get_multicast_ttl()
+}
+
+/// Set the User-Agent and extra headers sent when fetching UPnP descriptions
+#[tauri::command]
+pub fn set_http_settings(settings: crate::upnp::HttpSettings) -> Result<(), String> {
+    #[cfg(feature = "command_logging")]
+    log_command("set_http_settings", format!("{:?}", settings));
+    
+    let result = crate::upnp::set_http_settings(settings);
+    
+    #[cfg(feature = "command_logging")]
+    log_result("set_http_settings", format!("{:?}", result));
+    
+    result
+}
+
+#[tauri::command]
+pub fn get_http_settings() -> crate::upnp::HttpSettings {
+    crate::upnp::get_http_settings()
+}
+
+/// List the mDNS service types the scanner browses, with names, descriptions and risk levels
+#[tauri::command]
+pub fn get_supported_mdns_types() -> Vec<crate::network_scanner::MdnsServiceType> {
+    crate::network_scanner::MDNS_SERVICE_TYPES.to_vec()
+}
+
+// Command to set which mDNS service types scans browse, returning the resulting list
+#[tauri::command]
+pub fn set_mdns_service_types(
+    types: Vec<String>,
+    replace: Option<bool>,
+    discover: Option<bool>,
+) -> Result<Vec<String>, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("set_mdns_service_types", format!("types: {:?}, replace: {:?}, discover: {:?}", types, replace, discover));
+    
+    let result = crate::network_scanner::set_mdns_type_settings(crate::network_scanner::MdnsTypeSettings {
+        custom_types: types,
+        replace_builtin: replace.unwrap_or(false),
+        discover_types: discover.unwrap_or(false),
+    });
+    
+    #[cfg(feature = "command_logging")]
+    log_result("set_mdns_service_types", format!("{:?}", result));
+    
+    result
+}
+
+#[tauri::command]
+pub fn get_mdns_type_settings() -> crate::network_scanner::MdnsTypeSettings {
+    crate::network_scanner::get_mdns_type_settings()
+}
+
+/// Configure how long reverse-DNS results are cached (failures use the shorter negative TTL)
+#[tauri::command]
+pub fn set_reverse_dns_cache_ttl(ttl_secs: u64, negative_ttl_secs: Option<u64>) -> Result<(), String> {
+    #[cfg(feature = "command_logging")]
+    log_command("set_reverse_dns_cache_ttl", format!("ttl_secs: {}, negative_ttl_secs: {:?}", ttl_secs, negative_ttl_secs));
+    
+    let negative_ttl_secs = negative_ttl_secs.unwrap_or(ttl_secs / 10);
+    if negative_ttl_secs > ttl_secs {
+        return Err("Negative TTL must not exceed the TTL".to_string());
     }
+    crate::dns::set_ptr_cache_ttl(ttl_secs, negative_ttl_secs);
+    
+    #[cfg(feature = "command_logging")]
+    log_result("set_reverse_dns_cache_ttl", "Ok".to_string());
+    
+    Ok(())
+}
+
+// Command to list the actions a UPnP service supports, from its SCPD
+#[tauri::command]
+pub async fn get_upnp_actions(location_url: String, service_type: String) -> Result<Vec<crate::upnp::UpnpAction>, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("get_upnp_actions", format!("location_url: {}, service_type: {}", location_url, service_type));
+    
+    let result = crate::upnp::get_upnp_actions(&location_url, &service_type).await;
+    
+    #[cfg(feature = "command_logging")]
+    log_result("get_upnp_actions", format!("{:?}", result.as_ref().map(|actions| actions.len())));
+    
+    result
+}
+
+// Command to time A-record lookups against public and configured DNS resolvers
+#[tauri::command]
+pub async fn benchmark_dns_resolvers(names: Option<Vec<String>>) -> Vec<crate::dns::DnsBenchmarkResult> {
+    #[cfg(feature = "command_logging")]
+    log_command("benchmark_dns_resolvers", format!("names: {:?}", names));
+    
+    let result = crate::dns::benchmark_dns_resolvers(names).await;
+    
+    #[cfg(feature = "command_logging")]
+    log_result("benchmark_dns_resolvers", format!("{:?}", result));
+    
+    result
+}
+
+// Command to get the outcome of the most recent scan (warnings such as client isolation)
+#[tauri::command]
+pub fn get_last_scan_result() -> crate::network_scanner::ScanResult {
+    #[cfg(feature = "command_logging")]
+    log_command("get_last_scan_result", "no args".to_string());
+    
+    let result = crate::network_scanner::LAST_SCAN_RESULT.lock().unwrap().clone();
+    
+    #[cfg(feature = "command_logging")]
+    log_result("get_last_scan_result", format!("{:?}", result));
+    
+    result
+}
+
+// Command to estimate how much of the network the last scan found
+#[tauri::command]
+pub fn get_scan_completeness() -> Result<crate::network_scanner::ScanCompleteness, String> {
+    crate::network_scanner::LAST_SCAN_RESULT
+        .lock()
+        .unwrap()
+        .completeness
+        .clone()
+        .ok_or_else(|| "No scan has completed yet".to_string())
+}
+
+// Command to get the connected WiFi network, or None on Ethernet or unsupported platforms
+#[tauri::command]
+pub async fn get_wifi_info() -> Option<crate::wifi::WifiInfo> {
+    #[cfg(feature = "command_logging")]
+    log_command("get_wifi_info", "no args".to_string());
+    
+    // The platform tools block, so keep them off the async runtime
+    let result = tokio::task::spawn_blocking(crate::wifi::get_wifi_info).await.ok().flatten();
+    
+    #[cfg(feature = "command_logging")]
+    log_result("get_wifi_info", format!("{:?}", result));
     
-    // Now run the local network scan which will add to the shared network map
-    radar_info!("Scanning local network...");
-    let network_services = crate::network_scanner::scan_local_network(&app_handle).await;
-    radar_info!("Found {} network services", network_services.len());
+    result
+}
+
+// Command to watch one host's availability, emitting `host-status` every round
+// and `host-state-changed` when it goes up or down
+#[tauri::command]
+pub fn start_host_monitor(app_handle: AppHandle, address: String, interval_secs: u64, ports: Vec<u16>) -> Result<(), String> {
+    #[cfg(feature = "command_logging")]
+    log_command("start_host_monitor", format!("address: {}, interval_secs: {}, ports: {:?}", address, interval_secs, ports));
+    
+    let result = crate::monitor::start_host_monitor(app_handle, &address, interval_secs, ports);
+    
+    #[cfg(feature = "command_logging")]
+    log_result("start_host_monitor", format!("{:?}", result));
+    
+    result
+}
+
+// Command to stop watching a host; returns whether it was being watched
+#[tauri::command]
+pub fn stop_host_monitor(address: String) -> bool {
+    #[cfg(feature = "command_logging")]
+    log_command("stop_host_monitor", format!("address: {}", address));
+    
+    let result = crate::monitor::stop_host_monitor(&address);
+    
+    #[cfg(feature = "command_logging")]
+    log_result("stop_host_monitor", format!("{}", result));
+    
+    result
+}
+
+// Where the imported device inventory is kept between app runs
+pub fn saved_inventory_path(app_handle: &AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("inventory.json"))
+        .map_err(|e| format!("Failed to locate app data directory: {}", e))
+}
+
+// Command to bulk-load device labels, tags and trusted devices from a CSV file.
+// The merged inventory is saved so it survives a restart.
+#[tauri::command]
+pub fn import_inventory_csv(app_handle: AppHandle, path: String) -> Result<crate::inventory::InventoryImportReport, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("import_inventory_csv", format!("path: {}", path));
+    
+    let result = crate::inventory::import_inventory_csv(&path).and_then(|report| {
+        crate::inventory::save_inventory(&saved_inventory_path(&app_handle)?)?;
+        Ok(report)
+    });
+    
+    #[cfg(feature = "command_logging")]
+    log_result("import_inventory_csv", format!("{:?}", result));
     
-    // Now consolidate all services
-    let mut all_services = Vec::new();
-    if let Ok(services) = mdns_result {
-        all_services.extend(services);
+    result
+}
+
+// Command to load ASN -> organization names from a file, so public network
+// lookups can name more networks without asking Team Cymru
+#[tauri::command]
+pub fn load_asn_database(path: String) -> Result<usize, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("load_asn_database", format!("path: {}", path));
+    
+    let result = crate::asn::load_asn_database(&path);
+    
+    #[cfg(feature = "command_logging")]
+    log_result("load_asn_database", format!("{:?}", result));
+    
+    result
+}
+
+// Command to wake a host by MAC address with a Wake-on-LAN magic packet,
+// sent to the given broadcast address or 255.255.255.255
+#[tauri::command]
+pub fn wake_on_lan(mac: String, broadcast: Option<String>) -> Result<(), String> {
+    #[cfg(feature = "command_logging")]
+    log_command("wake_on_lan", format!("mac: {}, broadcast: {:?}", mac, broadcast));
+    
+    let result = crate::wol::wake_on_lan(&mac, broadcast.as_deref());
+    
+    #[cfg(feature = "command_logging")]
+    log_result("wake_on_lan", format!("{:?}", result));
+    
+    result
+}
+
+// Command to list the devices added, removed and changed since the end of the previous scan
+#[tauri::command]
+pub fn diff_last_scan() -> crate::network_scanner::ScanDiff {
+    #[cfg(feature = "command_logging")]
+    log_command("diff_last_scan", "no args".to_string());
+    
+    let result = crate::network_scanner::diff_last_scan();
+    
+    #[cfg(feature = "command_logging")]
+    log_result("diff_last_scan", format!(
+        "{} added, {} removed, {} changed",
+        result.added.len(),
+        result.removed.len(),
+        result.changed.len()
+    ));
+    
+    result
+}
+
+// Command to get every device in the inventory
+#[tauri::command]
+pub fn get_inventory() -> Vec<crate::inventory::DeviceEntry> {
+    crate::inventory::DEVICE_INVENTORY.lock().unwrap().values().cloned().collect()
+}
+
+// Command to check which of radar's own capabilities work on this machine
+#[tauri::command]
+pub async fn self_diagnostics() -> crate::diagnostics::DiagnosticsReport {
+    #[cfg(feature = "command_logging")]
+    log_command("self_diagnostics", "no args".to_string());
+    
+    let result = crate::diagnostics::self_diagnostics().await;
+    
+    #[cfg(feature = "command_logging")]
+    log_result("self_diagnostics", format!("{:?}", result));
+    
+    result
+}
+
+// Snapshots are kept under the app's data directory
+fn snapshot_dir(app_handle: &AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("snapshots"))
+        .map_err(|e| format!("Failed to locate app data directory: {}", e))
+}
+
+// Where discovered services are saved when no path is given
+pub fn saved_services_path(app_handle: &AppHandle) -> Result<std::path::PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join("services.json"))
+        .map_err(|e| format!("Failed to locate app data directory: {}", e))
+}
+
+// Command to save the current consolidated services as a snapshot
+#[tauri::command]
+pub fn save_snapshot(app_handle: AppHandle, label: Option<String>) -> Result<crate::history::SnapshotInfo, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("save_snapshot", format!("label: {:?}", label));
+    
+    let mut services: Vec<crate::ConsolidatedService> =
+        crate::CONSOLIDATED_SERVICES.lock().unwrap().values().cloned().collect();
+    services.sort_by(|a, b| a.identity_key.cmp(&b.identity_key));
+    let result = crate::history::save_snapshot(&snapshot_dir(&app_handle)?, &services, label);
+    
+    #[cfg(feature = "command_logging")]
+    log_result("save_snapshot", format!("{:?}", result));
+    
+    result
+}
+
+// Command to list saved snapshots, oldest first
+#[tauri::command]
+pub fn list_snapshots(app_handle: AppHandle) -> Result<Vec<crate::history::SnapshotInfo>, String> {
+    crate::history::list_snapshots(&snapshot_dir(&app_handle)?)
+}
+
+// Command to load the services of a saved snapshot for viewing
+#[tauri::command]
+pub fn load_snapshot(app_handle: AppHandle, id: u64) -> Result<Vec<crate::ConsolidatedService>, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("load_snapshot", format!("id: {}", id));
+    
+    let result = crate::history::load_snapshot(&snapshot_dir(&app_handle)?, id);
+    
+    #[cfg(feature = "command_logging")]
+    log_result("load_snapshot", format!("Loaded {} services", result.as_ref().map_or(0, |v| v.len())));
+    
+    result
+}
+
+// Command to re-check only the hosts from a saved snapshot
+#[tauri::command]
+pub async fn rescan_from_snapshot(
+    app_handle: AppHandle,
+    path: String,
+    config: Option<crate::network_scanner::ScanConfig>,
+) -> Result<crate::network_scanner::RescanReport, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("rescan_from_snapshot", format!("path: {}, config: {:?}", path, config));
+    
+    let config = config.unwrap_or_default();
+    config.validate()?;
+    let scan = crate::network_scanner::start_scan();
+    let scan_id = scan.id;
+    let sink = ScanSink::new(app_handle, scan_id);
+    if config.emit_events() {
+        let _ = sink.emit_event("scan-started", ());
     }
-    if let Ok(services) = upnp_result {
-        all_services.extend(services);
+    
+    let result = crate::network_scanner::rescan_from_snapshot(&sink, &path, &config, &scan.cancel).await;
+    
+    // The frontend ends a scan on `scan-complete`, so a rescan sends its summary there too
+    if config.emit_events() {
+        let scan_result = match &result {
+            Ok(report) => report.scan_result(scan_id),
+            Err(_) => crate::network_scanner::ScanResult { scan_id, ..Default::default() },
+        };
+        let _ = sink.emit_event("scan-complete", scan_result);
     }
-    all_services.extend(network_services);
     
-    // Consolidate services for reporting (but we've already emitted individual service events)
-    let consolidated = crate::network_scanner::consolidate_services(all_services.clone());
-    radar_info!("Network scan complete, found {} consolidated services", consolidated.len());
+    #[cfg(feature = "command_logging")]
+    log_result("rescan_from_snapshot", format!("{:?}", result.as_ref().map(|r| &r.unreachable)));
     
-    // Store in global state
-    {
-        let mut discovered_services = crate::DISCOVERED_SERVICES.lock().unwrap();
-        let mut consolidated_services = crate::CONSOLIDATED_SERVICES.lock().unwrap();
-        
-        // Update discovered services set
-        for service in &all_services {
-            discovered_services.insert(service.clone());
-        }
-        
-        // Update consolidated services map
-        for service in &consolidated {
-            let key = format!("{}:{}", service.address, service.port.unwrap_or(0));
-            consolidated_services.insert(key, service.clone());
+    result
+}
+
+// Command to give hosts the last scan likely missed a second, slower probe
+#[tauri::command]
+pub async fn retry_unreachable(app_handle: AppHandle) -> Result<Vec<crate::ConsolidatedService>, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("retry_unreachable", "app_handle provided".to_string());
+    
+    // Belongs to the last scan, but runs on its own so stopping a scan doesn't cancel it
+    let sink = ScanSink::new(app_handle, crate::network_scanner::current_scan_id());
+    let result = crate::network_scanner::retry_unreachable(&sink).await;
+    
+    #[cfg(feature = "command_logging")]
+    log_result("retry_unreachable", format!("Recovered {} hosts", result.as_ref().map_or(0, |v| v.len())));
+    
+    result
+}
+
+// Command to scan a host and, if it is a gateway, the subnets behind it, `depth` hops deep
+#[tauri::command]
+pub async fn scan_expand(
+    app_handle: AppHandle,
+    seed: String,
+    depth: u8,
+    config: Option<crate::network_scanner::ScanConfig>,
+) -> Result<crate::network_scanner::TopologyGraph, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("scan_expand", format!("seed: {}, depth: {}", seed, depth));
+    
+    let config = config.unwrap_or_default();
+    let result = match config.validate() {
+        Ok(()) => {
+            let scan = crate::network_scanner::start_scan();
+            let sink = ScanSink::new(app_handle, scan.id);
+            crate::network_scanner::scan_expand(&sink, &seed, depth, &config, &scan.cancel).await
         }
-    }
+        Err(e) => Err(e),
+    };
     
-    // Finally emit scan-complete
-    radar_info!("Emitting scan-complete event");
-    let _ = app_handle.emit("scan-complete", ());
+    #[cfg(feature = "command_logging")]
+    log_result("scan_expand", format!("{} hosts", result.as_ref().map_or(0, |g| g.nodes.len())));
     
-    Ok(true)
+    result
 }
 
+// Command to check the last scan against a golden inventory of expected devices
 #[tauri::command]
-pub async fn stop_network_scan(app_handle: AppHandle) -> Result<(), String> {
+pub fn audit_against_inventory(
+    app_handle: AppHandle,
+    inventory_path: String,
+) -> Result<crate::inventory::ComplianceReport, String> {
     #[cfg(feature = "command_logging")]
-    log_command("stop_network_scan", "app_handle provided".to_string());
+    log_command("audit_against_inventory", format!("inventory_path: {}", inventory_path));
     
-    // We currently don't have a way to cancel an in-progress scan,
-    // but we can emit the scan-complete event to tell the frontend to stop
-    app_handle.emit("scan-complete", ()).ok();
+    let sink = ScanSink::new(app_handle, crate::network_scanner::current_scan_id());
+    let result = crate::network_scanner::audit_against_inventory(&sink, &inventory_path);
     
     #[cfg(feature = "command_logging")]
-    log_result("stop_network_scan", "Stopped scan".to_string());
+    log_result("audit_against_inventory", format!("{} findings", result.as_ref().map_or(0, |r| r.findings.len())));
     
-    Ok(())
+    result
 }
 
+// Command to show how consolidation built the device with an identity key, for debugging odd results
 #[tauri::command]
-pub async fn discover_mdns_streaming(app_handle: AppHandle) -> Result<Vec<crate::NetworkService>, String> {
+pub fn explain_service(identity_key: String) -> Result<crate::network_scanner::ConsolidationTrace, String> {
     #[cfg(feature = "command_logging")]
-    log_command("discover_mdns_streaming", "app_handle provided".to_string());
+    log_command("explain_service", format!("identity_key: {}", identity_key));
     
-    let result = crate::network_scanner::discover_mdns_streaming(app_handle).await;
+    let result = crate::network_scanner::explain_service(&identity_key)
+        .ok_or_else(|| format!("No discovered records for {}", identity_key));
     
     #[cfg(feature = "command_logging")]
-    log_result("discover_mdns_streaming", format!("Found {} services", result.as_ref().map_or(0, |v| v.len())));
+    log_result("explain_service", format!("{:?}", result.as_ref().map(|t| t.records.len())));
     
     result
 }
 
+// Command to list stored devices that are probably duplicates of each other
+#[tauri::command]
+pub fn get_duplicate_report() -> crate::network_scanner::DuplicateReport {
+    crate::network_scanner::get_duplicate_report()
+}
+
+// Command to merge the groups `get_duplicate_report` finds
 #[tauri::command]
-pub async fn discover_upnp_streaming(app_handle: AppHandle) -> Result<Vec<crate::NetworkService>, String> {
+pub fn merge_duplicates() -> Vec<crate::network_scanner::DuplicateGroup> {
     #[cfg(feature = "command_logging")]
-    log_command("discover_upnp_streaming", "app_handle provided".to_string());
+    log_command("merge_duplicates", "no args".to_string());
     
-    let result = crate::network_scanner::discover_upnp_streaming(app_handle).await;
+    let result = crate::network_scanner::merge_duplicates();
     
     #[cfg(feature = "command_logging")]
-    log_result("discover_upnp_streaming", format!("Found {} services", result.as_ref().map_or(0, |v| v.len())));
+    log_result("merge_duplicates", format!("Merged {} groups", result.len()));
+    
+    result
+}
+
+// Command to export the consolidated services as CSV, for the frontend to download
+#[tauri::command]
+pub fn export_services_csv() -> Result<String, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("export_services_csv", "no args".to_string());
+    
+    let services: Vec<crate::ConsolidatedService> =
+        crate::CONSOLIDATED_SERVICES.lock().unwrap().values().cloned().collect();
+    let result = Ok(crate::export::services_to_csv(&services));
+    
+    #[cfg(feature = "command_logging")]
+    log_result("export_services_csv", format!("Exported {} services", services.len()));
+    
+    result
+}
+
+// Command to write the consolidated services to a JSON Lines file, one object
+// per line; `append` (default false) adds to an existing file instead of replacing it
+#[tauri::command]
+pub fn export_services_jsonl(path: String, append: Option<bool>) -> Result<usize, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("export_services_jsonl", format!("path: {}, append: {:?}", path, append));
+    
+    let services: Vec<crate::ConsolidatedService> =
+        crate::CONSOLIDATED_SERVICES.lock().unwrap().values().cloned().collect();
+    let result = crate::export::write_services_jsonl(&path, &services, append.unwrap_or(false));
+    
+    #[cfg(feature = "command_logging")]
+    log_result("export_services_jsonl", format!("{:?}", result));
     
     result
 }
 
-// Command for saved services removed - persistence not needed
+// Command to save the discovered services, by default to the app data directory
+#[tauri::command]
+pub fn save_discovered_services(app_handle: AppHandle, path: Option<String>) -> Result<usize, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("save_discovered_services", format!("path: {:?}", path));
+    
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => saved_services_path(&app_handle)?,
+    };
+    let result = crate::history::save_services(&path);
+    
+    #[cfg(feature = "command_logging")]
+    log_result("save_discovered_services", format!("{:?}", result));
+    
+    result
+}
+
+// Command to merge saved services back in; a missing or corrupt file loads nothing
+#[tauri::command]
+pub fn load_discovered_services(app_handle: AppHandle, path: Option<String>) -> Result<Vec<crate::ConsolidatedService>, String> {
+    #[cfg(feature = "command_logging")]
+    log_command("load_discovered_services", format!("path: {:?}", path));
+    
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => saved_services_path(&app_handle)?,
+    };
+    let result = crate::history::load_services(&path);
+    
+    #[cfg(feature = "command_logging")]
+    log_result("load_discovered_services", format!("Loaded {} services", result.len()));
+    
+    Ok(result)
+}
 
 /// Get macOS version information (macOS only)
 #[tauri::command]
@@ -0,0 +1,163 @@
+// diagnostics.rs - Quick self-checks of the capabilities discovery depends on
+//
+// Each check is time-boxed and independent, so one broken subsystem shows up
+// as a single failed line instead of a hung or empty scan.
+
+use crate::radar_info;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+// STUN server used for the reachability check
+const DIAGNOSTIC_STUN_SERVER: &str = "stun.l.google.com:19302";
+
+// Interface name prefixes used by VPN and tunnel drivers
+const TUNNEL_INTERFACE_PREFIXES: &[&str] = &["utun", "tun", "tap", "wg", "ppp", "ipsec", "gpd", "zt"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    // Works, but something may affect discovery (e.g. an active VPN tunnel)
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsReport {
+    pub checks: Vec<DiagnosticCheck>,
+    // True when no check failed (warnings allowed)
+    pub healthy: bool,
+}
+
+// Run one check under the timeout, recording how long it took
+async fn run_check<F>(name: &str, check: F) -> DiagnosticCheck
+where
+    F: Future<Output = (CheckStatus, String)>,
+{
+    let started = Instant::now();
+    let (status, detail) = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => (
+            CheckStatus::Fail,
+            format!("Timed out after {}s", CHECK_TIMEOUT.as_secs()),
+        ),
+    };
+
+    DiagnosticCheck {
+        name: name.to_string(),
+        status,
+        detail,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+async fn check_mdns_daemon() -> (CheckStatus, String) {
+    // Creating the daemon opens its multicast sockets and spawns a thread, so keep it off the runtime
+    let result = tokio::task::spawn_blocking(|| {
+        let daemon = mdns_sd::ServiceDaemon::new().map_err(|e| e.to_string())?;
+        let _ = daemon.shutdown();
+        Ok::<(), String>(())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(())) => (CheckStatus::Pass, "mDNS service daemon started".to_string()),
+        Ok(Err(e)) => (CheckStatus::Fail, format!("Failed to create mDNS service daemon: {}", e)),
+        Err(e) => (CheckStatus::Fail, format!("mDNS check panicked: {}", e)),
+    }
+}
+
+async fn check_udp_socket() -> (CheckStatus, String) {
+    match tokio::net::UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => match socket.local_addr() {
+            Ok(addr) => (CheckStatus::Pass, format!("Bound UDP socket on {}", addr)),
+            Err(_) => (CheckStatus::Pass, "Bound UDP socket".to_string()),
+        },
+        Err(e) => (CheckStatus::Fail, format!("Failed to bind UDP socket for SSDP/STUN: {}", e)),
+    }
+}
+
+async fn check_arp_table() -> (CheckStatus, String) {
+    match tokio::task::spawn_blocking(crate::network_scanner::read_arp_table).await {
+        Ok(hosts) if !hosts.is_empty() => (CheckStatus::Pass, format!("{} entries in the ARP table", hosts.len())),
+        Ok(_) => (
+            CheckStatus::Fail,
+            "ARP table is empty or `arp -a` could not be run".to_string(),
+        ),
+        Err(e) => (CheckStatus::Fail, format!("ARP check panicked: {}", e)),
+    }
+}
+
+async fn check_stun() -> (CheckStatus, String) {
+    match crate::public_network::get_ip_from_stun_server(DIAGNOSTIC_STUN_SERVER).await {
+        Ok(ip) => (CheckStatus::Pass, format!("{} reports public IP {}", DIAGNOSTIC_STUN_SERVER, ip)),
+        Err(e) => (CheckStatus::Fail, format!("{} unreachable: {}", DIAGNOSTIC_STUN_SERVER, e)),
+    }
+}
+
+async fn check_default_gateway() -> (CheckStatus, String) {
+    match default_net::get_default_gateway() {
+        Ok(gateway) => (
+            CheckStatus::Pass,
+            format!("Default gateway {} (MAC {})", gateway.ip_addr, gateway.mac_addr),
+        ),
+        Err(e) => (CheckStatus::Fail, format!("No default gateway: {}", e)),
+    }
+}
+
+async fn check_tunnel_interfaces() -> (CheckStatus, String) {
+    let interfaces = match get_if_addrs::get_if_addrs() {
+        Ok(interfaces) => interfaces,
+        Err(e) => return (CheckStatus::Fail, format!("Failed to list interfaces: {}", e)),
+    };
+
+    let mut tunnels: Vec<String> = interfaces
+        .into_iter()
+        .filter(|iface| !iface.is_loopback())
+        .filter(|iface| {
+            let name = iface.name.to_lowercase();
+            TUNNEL_INTERFACE_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+        })
+        .map(|iface| format!("{} ({})", iface.name, iface.ip()))
+        .collect();
+    tunnels.sort();
+    tunnels.dedup();
+
+    if tunnels.is_empty() {
+        (CheckStatus::Pass, "No tunnel interfaces active".to_string())
+    } else {
+        // A VPN can route local traffic away from the LAN or block multicast
+        (CheckStatus::Warn, format!("Tunnel interfaces active: {}", tunnels.join(", ")))
+    }
+}
+
+// Check every subsystem concurrently and report pass/warn/fail for each
+pub async fn self_diagnostics() -> DiagnosticsReport {
+    radar_info!("Running self diagnostics");
+
+    let (mdns, udp, arp, stun, gateway, tunnels) = tokio::join!(
+        run_check("mdns_daemon", check_mdns_daemon()),
+        run_check("udp_socket", check_udp_socket()),
+        run_check("arp_table", check_arp_table()),
+        run_check("stun_reachable", check_stun()),
+        run_check("default_gateway", check_default_gateway()),
+        run_check("tunnel_interfaces", check_tunnel_interfaces()),
+    );
+
+    let checks = vec![mdns, udp, arp, stun, gateway, tunnels];
+    let healthy = checks.iter().all(|check| check.status != CheckStatus::Fail);
+    radar_info!("Self diagnostics complete, healthy: {}", healthy);
+
+    DiagnosticsReport { checks, healthy }
+}
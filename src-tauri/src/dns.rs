@@ -0,0 +1,346 @@
+// dns.rs - Shared DNS helpers
+
+use crate::radar_debug;
+use crate::radar_info;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::TokioAsyncResolver;
+
+// Cached hostname (None for a failed lookup) and when it was resolved
+type PtrCache = HashMap<IpAddr, (Option<String>, Instant)>;
+
+// Reverse-DNS (PTR) cache shared by every module, so the same address is only
+// looked up once per TTL across scan phases and repeated scans.
+// Failed lookups are cached too, for a shorter time.
+static PTR_CACHE: Lazy<Mutex<PtrCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static PTR_CACHE_TTL_SECS: AtomicU64 = AtomicU64::new(600);
+static PTR_NEGATIVE_TTL_SECS: AtomicU64 = AtomicU64::new(60);
+
+// Limit concurrent lookups so a large scan doesn't flood the resolver
+static PTR_LOOKUP_PERMITS: Lazy<Semaphore> = Lazy::new(|| Semaphore::new(8));
+
+// Set how long successful and failed reverse lookups are cached
+pub fn set_ptr_cache_ttl(ttl_secs: u64, negative_ttl_secs: u64) {
+    PTR_CACHE_TTL_SECS.store(ttl_secs, Ordering::Relaxed);
+    PTR_NEGATIVE_TTL_SECS.store(negative_ttl_secs, Ordering::Relaxed);
+}
+
+fn cached_ptr(ip: &IpAddr) -> Option<Option<String>> {
+    let cache = PTR_CACHE.lock().unwrap();
+    let (hostname, resolved_at) = cache.get(ip)?;
+    let ttl = if hostname.is_some() {
+        PTR_CACHE_TTL_SECS.load(Ordering::Relaxed)
+    } else {
+        PTR_NEGATIVE_TTL_SECS.load(Ordering::Relaxed)
+    };
+
+    if resolved_at.elapsed() < Duration::from_secs(ttl) {
+        Some(hostname.clone())
+    } else {
+        None
+    }
+}
+
+// Reverse-resolve an address, consulting the shared cache first
+pub async fn reverse_lookup(ip: IpAddr) -> Option<String> {
+    if let Some(hostname) = cached_ptr(&ip) {
+        radar_debug!("Reverse DNS cache hit for {}: {:?}", ip, hostname);
+        return hostname;
+    }
+
+    let hostname = {
+        let _permit = PTR_LOOKUP_PERMITS.acquire().await.ok()?;
+
+        // Another task may have resolved it while we waited
+        if let Some(hostname) = cached_ptr(&ip) {
+            return hostname;
+        }
+
+        // dns_lookup blocks, so keep it off the async worker threads
+        tokio::task::spawn_blocking(move || dns_lookup::lookup_addr(&ip).ok())
+            .await
+            .ok()
+            .flatten()
+            // Resolvers without a PTR record often just echo the address back
+            .filter(|name| name.parse::<IpAddr>().is_err())
+    };
+
+    radar_debug!("Reverse DNS lookup for {}: {:?}", ip, hostname);
+    PTR_CACHE
+        .lock()
+        .unwrap()
+        .insert(ip, (hostname.clone(), Instant::now()));
+    hostname
+}
+
+// Well-known public resolvers always included in a benchmark
+const PUBLIC_RESOLVERS: &[(&str, &str)] = &[
+    ("8.8.8.8", "Google"),
+    ("1.1.1.1", "Cloudflare"),
+    ("9.9.9.9", "Quad9"),
+];
+
+// Names queried when the caller doesn't pick any
+const DEFAULT_BENCHMARK_NAMES: &[&str] = &["example.com", "google.com", "wikipedia.org"];
+
+const BENCHMARK_QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Minimum queries per resolver; names are reused when fewer are given
+const BENCHMARK_SAMPLES: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsBenchmarkResult {
+    pub resolver: String,
+    pub provider: Option<String>,
+    // Average over the successful samples, None if every query failed
+    pub latency_ms: Option<f64>,
+    pub succeeded: bool,
+}
+
+// Time one A-record query against a single resolver. A fresh resolver is used
+// for every sample so its cache never answers for the network.
+async fn time_a_query(resolver_ip: IpAddr, name: &str) -> Option<f64> {
+    let config = ResolverConfig::from_parts(
+        None,
+        vec![],
+        NameServerConfigGroup::from_ips_clear(&[resolver_ip], 53, true),
+    );
+    let mut opts = ResolverOpts::default();
+    opts.timeout = BENCHMARK_QUERY_TIMEOUT;
+    opts.attempts = 1;
+    opts.use_hosts_file = false;
+
+    let resolver = TokioAsyncResolver::tokio(config, opts).ok()?;
+    let started = Instant::now();
+    match resolver.ipv4_lookup(name).await {
+        Ok(_) => Some(started.elapsed().as_secs_f64() * 1000.0),
+        Err(e) => {
+            radar_debug!("DNS benchmark query {} via {} failed: {}", name, resolver_ip, e);
+            None
+        }
+    }
+}
+
+async fn benchmark_resolver(resolver_ip: IpAddr, provider: Option<String>, names: Vec<String>) -> DnsBenchmarkResult {
+    let mut samples = Vec::new();
+    for name in names.iter().cycle().take(names.len().max(BENCHMARK_SAMPLES)) {
+        if let Some(ms) = time_a_query(resolver_ip, name).await {
+            samples.push(ms);
+        }
+    }
+
+    let latency_ms = if samples.is_empty() {
+        None
+    } else {
+        Some(samples.iter().sum::<f64>() / samples.len() as f64)
+    };
+
+    DnsBenchmarkResult {
+        resolver: resolver_ip.to_string(),
+        provider,
+        latency_ms,
+        succeeded: latency_ms.is_some(),
+    }
+}
+
+// Benchmark the public resolvers plus the system's configured ones by timing the
+// same A-record queries against each. Fastest first; failed resolvers last.
+pub async fn benchmark_dns_resolvers(names: Option<Vec<String>>) -> Vec<DnsBenchmarkResult> {
+    let names = names
+        .filter(|names| !names.is_empty())
+        .unwrap_or_else(|| DEFAULT_BENCHMARK_NAMES.iter().map(|n| n.to_string()).collect());
+
+    let mut resolvers: Vec<(IpAddr, Option<String>)> = PUBLIC_RESOLVERS
+        .iter()
+        .filter_map(|(ip, provider)| Some((ip.parse().ok()?, Some(provider.to_string()))))
+        .collect();
+    for server in system_dns_servers(false) {
+        if let Ok(ip) = server.parse::<IpAddr>() {
+            if !resolvers.iter().any(|(known, _)| *known == ip) {
+                resolvers.push((ip, Some("System".to_string())));
+            }
+        }
+    }
+
+    radar_info!("Benchmarking {} DNS resolvers with {} names", resolvers.len(), names.len());
+    let mut results = futures::future::join_all(
+        resolvers
+            .into_iter()
+            .map(|(ip, provider)| benchmark_resolver(ip, provider, names.clone())),
+    )
+    .await;
+
+    results.sort_by(|a, b| match (a.latency_ms, b.latency_ms) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.resolver.cmp(&b.resolver),
+    });
+    results
+}
+
+// Public resolvers reported when the system configuration lists none
+const FALLBACK_DNS_SERVERS: &[&str] = &["8.8.8.8", "1.1.1.1"];
+
+// Nameservers from resolv.conf contents ("nameserver 192.168.1.1")
+pub fn parse_resolv_conf(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            (parts.next()? == "nameserver").then_some(parts.next()?.to_string())
+        })
+        .collect()
+}
+
+// Nameservers from `scutil --dns` ("  nameserver[0] : 192.168.1.1"), each once,
+// since every resolver scope repeats the shared ones
+pub fn parse_scutil_dns(output: &str) -> Vec<String> {
+    let mut servers: Vec<String> = Vec::new();
+    for line in output.lines() {
+        if !line.trim().starts_with("nameserver[") {
+            continue;
+        }
+        if let Some(server) = line.split_whitespace().last() {
+            if !servers.iter().any(|known| known == server) {
+                servers.push(server.to_string());
+            }
+        }
+    }
+    servers
+}
+
+// Nameservers from `ipconfig /all`, where further servers follow on their own lines:
+//    DNS Servers . . . . . . . . . . . : 192.168.1.1
+//                                        8.8.8.8
+// Only the text after the first ':' is the address, so IPv6 servers survive
+pub fn parse_ipconfig_dns(output: &str) -> Vec<String> {
+    let is_address = |text: &str| text.split('%').next().is_some_and(|ip| ip.parse::<std::net::IpAddr>().is_ok());
+    let mut servers = Vec::new();
+    let mut in_servers = false;
+    for line in output.lines() {
+        if line.contains("DNS Servers") {
+            in_servers = true;
+            if let Some((_, server)) = line.split_once(':') {
+                if !server.trim().is_empty() {
+                    servers.push(server.trim().to_string());
+                }
+            }
+        } else if in_servers && is_address(line.trim()) {
+            servers.push(line.trim().to_string());
+        } else {
+            in_servers = false;
+        }
+    }
+    servers
+}
+
+// DNS servers from the local system configuration: resolv.conf on Unix, with
+// `scutil --dns` as a fallback on macOS, and `ipconfig /all` on Windows. With
+// `fallback` set, well-known public resolvers are returned when none are found.
+pub fn system_dns_servers(fallback: bool) -> Vec<String> {
+    #[allow(unused_mut)]
+    let mut servers: Vec<String> = Vec::new();
+
+    #[cfg(unix)]
+    match std::fs::read_to_string("/etc/resolv.conf") {
+        Ok(contents) => servers = parse_resolv_conf(&contents),
+        Err(e) => radar_info!("Error reading resolv.conf: {}", e),
+    }
+
+    #[cfg(target_os = "macos")]
+    if servers.is_empty() {
+        match std::process::Command::new("scutil").arg("--dns").output() {
+            Ok(output) => servers = parse_scutil_dns(&String::from_utf8_lossy(&output.stdout)),
+            Err(e) => radar_info!("Error running scutil: {}", e),
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    match std::process::Command::new("ipconfig").arg("/all").output() {
+        Ok(output) => servers = parse_ipconfig_dns(&String::from_utf8_lossy(&output.stdout)),
+        Err(e) => radar_info!("Error running ipconfig: {}", e),
+    }
+
+    radar_debug!("System DNS servers: {:?}", servers);
+    if servers.is_empty() && fallback {
+        radar_info!("No DNS servers found, using fallback servers");
+        servers = FALLBACK_DNS_SERVERS.iter().map(|s| s.to_string()).collect();
+    }
+    servers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolv_conf_nameservers_are_listed() {
+        let contents = "# Generated by NetworkManager
+search home.arpa
+nameserver 192.168.1.1
+nameserver fd00::1
+# nameserver 10.0.0.1
+nameserver
+options edns0 trust-ad
+";
+        assert_eq!(parse_resolv_conf(contents), ["192.168.1.1", "fd00::1"]);
+    }
+
+    #[test]
+    fn scutil_nameservers_are_listed_once() {
+        let output = "DNS configuration
+
+resolver #1
+  search domain[0] : home.arpa
+  nameserver[0] : 192.168.1.1
+  nameserver[1] : fd00::1
+  if_index : 15 (en0)
+  flags    : Request A records, Request AAAA records
+  reach    : 0x00020002 (Reachable,Directly Reachable Address)
+
+resolver #2
+  domain   : local
+  options  : mdns
+  timeout  : 5
+
+DNS configuration (for scoped queries)
+
+resolver #1
+  search domain[0] : home.arpa
+  nameserver[0] : 192.168.1.1
+  if_index : 15 (en0)
+";
+        assert_eq!(parse_scutil_dns(output), ["192.168.1.1", "fd00::1"]);
+    }
+
+    #[test]
+    fn ipconfig_dns_servers_include_continuation_lines() {
+        let output = "Windows IP Configuration
+
+Ethernet adapter Ethernet:
+
+   Connection-specific DNS Suffix  . : home.arpa
+   IPv4 Address. . . . . . . . . . . : 192.168.1.5(Preferred)
+   Default Gateway . . . . . . . . . : 192.168.1.1
+   DNS Servers . . . . . . . . . . . : fec0:0:0:ffff::1%1
+                                       192.168.1.1
+                                       8.8.8.8
+   NetBIOS over Tcpip. . . . . . . . : Enabled
+
+Wireless LAN adapter Wi-Fi:
+
+   Media State . . . . . . . . . . . : Media disconnected
+";
+        assert_eq!(parse_ipconfig_dns(output), ["fec0:0:0:ffff::1%1", "192.168.1.1", "8.8.8.8"]);
+    }
+}
+
@@ -0,0 +1,108 @@
+// enricher.rs - Final-pass rules applied to every consolidated service
+//
+// Consolidation merges each device's records into one ConsolidatedService;
+// enrichers then run over the result to add conclusions that depend on the
+// whole device (security flags, "port 32400 means Plex"). Enrichers must be
+// compiled in, but downstream crates and the CLI can register their own.
+//
+// Built-ins: DeviceClassification (device type from the advertised services),
+// then SecurityFlags.
+//
+// Ordering: enrichers run in registration order, built-ins first, and each
+// sees the changes made by the ones before it. The pass can run more than once
+// on the same device (e.g. after merging dual-stack entries), so enrichers
+// should set fields rather than append to them blindly.
+
+use crate::radar_info;
+use crate::ConsolidatedService;
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+
+pub trait Enricher: Send + Sync {
+    // Short name for logs and `registered_enrichers`
+    fn name(&self) -> &str;
+    fn enrich(&self, service: &mut ConsolidatedService);
+}
+
+// Replaces the device type with the friendly class of everything the device
+// advertises (see `classify_device`), when there is one
+pub struct DeviceClassification;
+
+impl Enricher for DeviceClassification {
+    fn name(&self) -> &str {
+        "device-classification"
+    }
+
+    fn enrich(&self, service: &mut ConsolidatedService) {
+        if let Some(class) = crate::network_scanner::classify_device(&service.service_types, &service.txt_records) {
+            service.device_type = Some(class);
+        }
+    }
+}
+
+// Flags insecure-by-design services (see `FLAG_*` in network_scanner)
+pub struct SecurityFlags;
+
+impl Enricher for SecurityFlags {
+    fn name(&self) -> &str {
+        "security-flags"
+    }
+
+    fn enrich(&self, service: &mut ConsolidatedService) {
+        service.security_flags = crate::network_scanner::compute_security_flags(service);
+    }
+}
+
+// Sets the device type of any device with a given port open, e.g. 32400 -> "Plex Server"
+pub struct PortRule {
+    pub port: u16,
+    pub device_type: String,
+}
+
+impl PortRule {
+    // Parse "32400=Plex Server"
+    pub fn parse(rule: &str) -> Result<Self, String> {
+        let (port, device_type) = rule
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid rule '{}': expected <port>=<device type>", rule))?;
+        let port = port.trim().parse().map_err(|_| format!("Invalid port in rule '{}'", rule))?;
+        let device_type = device_type.trim();
+        if device_type.is_empty() {
+            return Err(format!("Missing device type in rule '{}'", rule));
+        }
+        Ok(Self { port, device_type: device_type.to_string() })
+    }
+}
+
+impl Enricher for PortRule {
+    fn name(&self) -> &str {
+        "port-rule"
+    }
+
+    fn enrich(&self, service: &mut ConsolidatedService) {
+        if service.open_ports.contains_key(&self.port) {
+            service.device_type = Some(self.device_type.clone());
+        }
+    }
+}
+
+static ENRICHERS: Lazy<RwLock<Vec<Box<dyn Enricher>>>> =
+    Lazy::new(|| RwLock::new(vec![Box::new(DeviceClassification), Box::new(SecurityFlags)]));
+
+// Add an enricher to run after every one already registered
+pub fn register_enricher(enricher: Box<dyn Enricher>) {
+    radar_info!("Registered enricher {}", enricher.name());
+    ENRICHERS.write().unwrap().push(enricher);
+}
+
+// Names of the registered enrichers, in the order they run
+pub fn registered_enrichers() -> Vec<String> {
+    ENRICHERS.read().unwrap().iter().map(|e| e.name().to_string()).collect()
+}
+
+// Run every registered enricher over a consolidated service
+pub fn apply_enrichers(service: &mut ConsolidatedService) {
+    for enricher in ENRICHERS.read().unwrap().iter() {
+        enricher.enrich(service);
+    }
+}
@@ -0,0 +1,96 @@
+// export.rs - Consolidated services as a CSV report or JSON Lines
+//
+// CSV has one row per device with the columns `address,mac,hostname,
+// device_type,open_ports,discovery_methods,friendly_description`. Open ports
+// are written as "port:service" pairs and discovery methods are joined with
+// ';'. Fields are quoted per RFC 4180 when they contain a comma, quote or line
+// break. The `mac` column lets the file be imported as a device inventory.
+//
+// JSON Lines has one complete `ConsolidatedService` object per line, so the
+// file can be appended to and streamed into tools like `jq`.
+
+use crate::network_scanner::{sort_services, SortKey};
+use crate::ConsolidatedService;
+use std::io::Write;
+
+const CSV_HEADER: &str = "address,mac,hostname,device_type,open_ports,discovery_methods,friendly_description";
+
+// Quote a field if needed, doubling any quotes inside it
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_row(service: &ConsolidatedService) -> String {
+    let mut ports: Vec<(&u16, &String)> = service.open_ports.iter().collect();
+    ports.sort();
+    let open_ports = ports
+        .iter()
+        .map(|(port, name)| format!("{}:{}", port, name))
+        .collect::<Vec<_>>()
+        .join(";");
+
+    [
+        service.address.as_str(),
+        service.mac_address.as_deref().unwrap_or_default(),
+        service.hostname.as_deref().unwrap_or_default(),
+        service.device_type.as_deref().unwrap_or_default(),
+        &open_ports,
+        &service.discovery_methods.join(";"),
+        &service.friendly_description,
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+// CSV of the services in IP order, with a header row and CRLF line endings
+pub fn services_to_csv(services: &[ConsolidatedService]) -> String {
+    let mut services = services.to_vec();
+    sort_services(&mut services, SortKey::Ip);
+
+    let mut csv = format!("{}\r\n", CSV_HEADER);
+    for service in &services {
+        csv.push_str(&csv_row(service));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+// One service as a newline-terminated JSON line. serde_json escapes control
+// characters inside strings, so names with line breaks stay on one line.
+pub fn jsonl_line(service: &ConsolidatedService) -> Result<String, String> {
+    serde_json::to_string(service)
+        .map(|json| json + "\n")
+        .map_err(|e| format!("Failed to serialize {}: {}", service.address, e))
+}
+
+// Open a JSON Lines file for writing, appending to it or starting it over
+pub fn open_jsonl(path: &str, append: bool) -> Result<std::fs::File, String> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path, e))
+}
+
+// Write the services to `path` as JSON Lines in IP order, flushing each line
+// as it is written. Returns the number of lines written.
+pub fn write_services_jsonl(path: &str, services: &[ConsolidatedService], append: bool) -> Result<usize, String> {
+    let mut services = services.to_vec();
+    sort_services(&mut services, SortKey::Ip);
+
+    let mut file = open_jsonl(path, append)?;
+    for service in &services {
+        file.write_all(jsonl_line(service)?.as_bytes())
+            .and_then(|_| file.flush())
+            .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+    Ok(services.len())
+}
@@ -0,0 +1,162 @@
+// history.rs - Saved scan snapshots, browsable as a timeline, and the
+// discovered services kept across app restarts
+//
+// Snapshots live in their own directory, one file per snapshot, next to a
+// `manifest.json` listing them. Each snapshot file is a JSON array of
+// consolidated services, the same format `rescan_from_snapshot` reads, so a
+// file can be handed to any command that takes a snapshot path.
+
+use crate::radar_info;
+use crate::radar_warn;
+use crate::ConsolidatedService;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub id: u64,
+    // RFC 3339, UTC
+    pub timestamp: String,
+    pub device_count: usize,
+    pub path: String,
+    pub label: Option<String>,
+}
+
+fn manifest_path(dir: &Path) -> PathBuf {
+    dir.join(MANIFEST_FILE)
+}
+
+fn read_manifest(dir: &Path) -> Result<Vec<SnapshotInfo>, String> {
+    let path = manifest_path(dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read snapshot manifest {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Invalid snapshot manifest {}: {}", path.display(), e))
+}
+
+// Write through a temporary file so a crash never leaves a truncated manifest
+fn write_manifest(dir: &Path, entries: &[SnapshotInfo]) -> Result<(), String> {
+    let path = manifest_path(dir);
+    let temp = dir.join(format!("{}.tmp", MANIFEST_FILE));
+    let json = serde_json::to_string_pretty(entries)
+        .map_err(|e| format!("Failed to serialize snapshot manifest: {}", e))?;
+    std::fs::write(&temp, json).map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+    std::fs::rename(&temp, &path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+// Save services as a new snapshot in `dir` and record it in the manifest
+pub fn save_snapshot(
+    dir: &Path,
+    services: &[ConsolidatedService],
+    label: Option<String>,
+) -> Result<SnapshotInfo, String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create snapshot directory {}: {}", dir.display(), e))?;
+
+    let mut entries = read_manifest(dir)?;
+    let id = entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+    let path = dir.join(format!("snapshot-{}.json", id));
+
+    let json = serde_json::to_string_pretty(services)
+        .map_err(|e| format!("Failed to serialize snapshot: {}", e))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write snapshot {}: {}", path.display(), e))?;
+
+    let info = SnapshotInfo {
+        id,
+        timestamp: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        device_count: services.len(),
+        path: path.to_string_lossy().to_string(),
+        label: label.filter(|l| !l.trim().is_empty()),
+    };
+    entries.push(info.clone());
+    write_manifest(dir, &entries)?;
+
+    radar_info!("Saved snapshot {} with {} devices to {}", id, services.len(), info.path);
+    Ok(info)
+}
+
+// Snapshots in `dir`, oldest first. Entries whose file was deleted are left out.
+pub fn list_snapshots(dir: &Path) -> Result<Vec<SnapshotInfo>, String> {
+    let mut entries = read_manifest(dir)?;
+    entries.retain(|entry| {
+        let exists = Path::new(&entry.path).exists();
+        if !exists {
+            radar_warn!("Snapshot {} is missing its file {}", entry.id, entry.path);
+        }
+        exists
+    });
+    entries.sort_by_key(|entry| entry.id);
+    Ok(entries)
+}
+
+// Load the services of one snapshot from `dir`
+pub fn load_snapshot(dir: &Path, id: u64) -> Result<Vec<ConsolidatedService>, String> {
+    let entry = read_manifest(dir)?
+        .into_iter()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| format!("No snapshot with id {}", id))?;
+    crate::network_scanner::load_snapshot(&entry.path)
+}
+
+// Discovered and consolidated services as saved between app runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedServices {
+    discovered: Vec<crate::NetworkService>,
+    consolidated: std::collections::HashMap<String, ConsolidatedService>,
+}
+
+// Save the discovered and consolidated services to `path`
+pub fn save_services(path: &Path) -> Result<usize, String> {
+    let saved = SavedServices {
+        discovered: crate::DISCOVERED_SERVICES.lock().unwrap().iter().cloned().collect(),
+        consolidated: crate::CONSOLIDATED_SERVICES.lock().unwrap().clone(),
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(&saved)
+        .map_err(|e| format!("Failed to serialize services: {}", e))?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    radar_info!("Saved {} services to {}", saved.consolidated.len(), path.display());
+    Ok(saved.consolidated.len())
+}
+
+// Merge services saved with `save_services` into the global state, keeping
+// any entry a scan has already produced. A missing or unreadable file loads
+// nothing. Returns the consolidated services that were loaded.
+pub fn load_services(path: &Path) -> Vec<ConsolidatedService> {
+    let saved = match std::fs::read_to_string(path) {
+        Ok(text) => match serde_json::from_str::<SavedServices>(&text) {
+            Ok(saved) => saved,
+            Err(e) => {
+                radar_warn!("Ignoring corrupt saved services {}: {}", path.display(), e);
+                return Vec::new();
+            }
+        },
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                radar_warn!("Failed to read saved services {}: {}", path.display(), e);
+            }
+            return Vec::new();
+        }
+    };
+
+    crate::DISCOVERED_SERVICES.lock().unwrap().extend(saved.discovered);
+    let mut store = crate::CONSOLIDATED_SERVICES.lock().unwrap();
+    let mut loaded = Vec::new();
+    for (key, service) in saved.consolidated {
+        if let std::collections::hash_map::Entry::Vacant(entry) = store.entry(key) {
+            loaded.push(service.clone());
+            entry.insert(service);
+        }
+    }
+
+    radar_info!("Loaded {} saved services from {}", loaded.len(), path.display());
+    loaded
+}
@@ -0,0 +1,495 @@
+// inventory.rs - User-supplied device metadata (labels, tags, trusted devices) keyed by MAC
+//
+// Inventory files are CSV with the columns `mac,label,tags,trusted,ports`. Tags
+// are separated by ';' inside their field, and `trusted` marks a device as known
+// (true/false, yes/no or 1/0). `ports` optionally lists the TCP/UDP ports the
+// device is expected to have open, also ';'-separated. A header row is
+// optional; it is recognized by naming a `mac` column, and columns are then
+// matched by name in any order. CSV exported by radar imports too, with the
+// hostname standing in for a missing label.
+
+use crate::radar_info;
+use crate::radar_warn;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceEntry {
+    pub mac: String,
+    pub label: Option<String>,
+    pub tags: Vec<String>,
+    pub trusted: bool,
+    // Ports the device may have open; None means any
+    #[serde(default)]
+    pub expected_ports: Option<Vec<u16>>,
+}
+
+// A row that couldn't be imported, with its 1-based line number in the file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedRow {
+    pub line: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InventoryImportReport {
+    pub imported: usize,
+    pub skipped: Vec<SkippedRow>,
+}
+
+// Known devices, keyed by normalized MAC address
+pub static DEVICE_INVENTORY: Lazy<Arc<Mutex<HashMap<String, DeviceEntry>>>> =
+    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+
+// Normalize a MAC address to lowercase colon-separated form ("aa:bb:cc:dd:ee:ff").
+// Accepts ':' or '-' separated octets (including macOS's unpadded "a:b:c:d:e:f"),
+// Cisco-style "aabb.ccdd.eeff" and bare "aabbccddeeff". Mixing ':' and '-' is rejected.
+pub fn normalize_mac(mac: &str) -> Option<String> {
+    let mac = mac.trim();
+    let octets: Vec<u8> = if mac.contains(':') || mac.contains('-') {
+        if mac.contains(':') && mac.contains('-') {
+            return None;
+        }
+        let parts: Vec<&str> = mac.split([':', '-']).collect();
+        if parts.len() != 6 || parts.iter().any(|p| p.is_empty() || p.len() > 2) {
+            return None;
+        }
+        parts
+            .iter()
+            .map(|p| u8::from_str_radix(p, 16).ok())
+            .collect::<Option<Vec<u8>>>()?
+    } else {
+        let hex: String = mac.chars().filter(|c| *c != '.').collect();
+        if hex.len() != 12 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        (0..6)
+            .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?
+    };
+
+    Some(
+        octets
+            .iter()
+            .map(|o| format!("{:02x}", o))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+// Split one CSV line into fields, honoring double-quoted fields with "" escapes
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+// "22;80;443" -> Some([22, 80, 443]); an empty field means no expectation
+fn parse_ports(value: &str) -> Option<Option<Vec<u16>>> {
+    if value.trim().is_empty() {
+        return Some(None);
+    }
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| p.parse().ok())
+        .collect::<Option<Vec<u16>>>()
+        .map(Some)
+}
+
+fn parse_trusted(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "" | "false" | "no" | "0" => Some(false),
+        "true" | "yes" | "1" => Some(true),
+        _ => None,
+    }
+}
+
+// Parse inventory CSV text into entries, reporting rows that were skipped
+pub fn parse_inventory_csv(text: &str) -> (Vec<DeviceEntry>, Vec<SkippedRow>) {
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+
+    // Column positions for mac, label, tags, trusted, ports
+    let mut columns = [Some(0), Some(1), Some(2), Some(3), Some(4)];
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = split_csv_line(line);
+        let position = |name: &str| fields.iter().position(|f| f.eq_ignore_ascii_case(name));
+        if entries.is_empty() && skipped.is_empty() && position("mac").is_some() {
+            let label = position("label").or_else(|| position("hostname"));
+            columns = [position("mac"), label, position("tags"), position("trusted"), position("ports")];
+            continue;
+        }
+
+        let field = |column: usize| -> &str {
+            columns[column]
+                .and_then(|i| fields.get(i))
+                .map(String::as_str)
+                .unwrap_or("")
+        };
+
+        let Some(mac) = normalize_mac(field(0)) else {
+            skipped.push(SkippedRow {
+                line: line_number,
+                reason: format!("Malformed MAC address '{}'", field(0)),
+            });
+            continue;
+        };
+        let Some(trusted) = parse_trusted(field(3)) else {
+            skipped.push(SkippedRow {
+                line: line_number,
+                reason: format!("Invalid trusted value '{}'", field(3)),
+            });
+            continue;
+        };
+        let Some(expected_ports) = parse_ports(field(4)) else {
+            skipped.push(SkippedRow {
+                line: line_number,
+                reason: format!("Invalid ports '{}'", field(4)),
+            });
+            continue;
+        };
+
+        let label = field(1);
+        entries.push(DeviceEntry {
+            mac,
+            label: (!label.is_empty()).then(|| label.to_string()),
+            tags: field(2)
+                .split(';')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect(),
+            trusted,
+            expected_ports,
+        });
+    }
+
+    (entries, skipped)
+}
+
+// Read an inventory CSV and merge it into the device inventory.
+// Rows for a MAC already in the inventory replace the existing entry.
+pub fn import_inventory_csv(path: &str) -> Result<InventoryImportReport, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read inventory {}: {}", path, e))?;
+    let (entries, skipped) = parse_inventory_csv(&text);
+
+    let mut inventory = DEVICE_INVENTORY.lock().unwrap();
+    let imported = entries.len();
+    for entry in entries {
+        inventory.insert(entry.mac.clone(), entry);
+    }
+
+    radar_info!("Imported {} inventory rows from {} ({} skipped)", imported, path, skipped.len());
+    Ok(InventoryImportReport { imported, skipped })
+}
+
+// Save the device inventory to `path` as JSON
+pub fn save_inventory(path: &Path) -> Result<usize, String> {
+    let mut entries: Vec<DeviceEntry> = DEVICE_INVENTORY.lock().unwrap().values().cloned().collect();
+    entries.sort_by(|a, b| a.mac.cmp(&b.mac));
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let json = serde_json::to_string_pretty(&entries)
+        .map_err(|e| format!("Failed to serialize inventory: {}", e))?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    radar_info!("Saved {} inventory entries to {}", entries.len(), path.display());
+    Ok(entries.len())
+}
+
+// Merge an inventory saved with `save_inventory` into the device inventory,
+// keeping entries already present. A missing or unreadable file loads nothing.
+// Returns the number of entries loaded.
+pub fn load_inventory(path: &Path) -> usize {
+    let entries = match std::fs::read_to_string(path) {
+        Ok(text) => match serde_json::from_str::<Vec<DeviceEntry>>(&text) {
+            Ok(entries) => entries,
+            Err(e) => {
+                radar_warn!("Ignoring corrupt saved inventory {}: {}", path.display(), e);
+                return 0;
+            }
+        },
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                radar_warn!("Failed to read saved inventory {}: {}", path.display(), e);
+            }
+            return 0;
+        }
+    };
+
+    let mut inventory = DEVICE_INVENTORY.lock().unwrap();
+    let loaded = entries.len();
+    for entry in entries {
+        inventory.entry(entry.mac.clone()).or_insert(entry);
+    }
+    loaded
+}
+
+// Look up the inventory entry for a MAC address in any common notation
+pub fn inventory_entry(mac: &str) -> Option<DeviceEntry> {
+    let mac = normalize_mac(mac)?;
+    DEVICE_INVENTORY.lock().unwrap().get(&mac).cloned()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    // In the inventory but not seen on the network
+    MissingDevice,
+    // On the network but not in the inventory
+    RogueDevice,
+    // In the inventory, but with ports open that it isn't expected to have
+    UnexpectedPorts,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComplianceFinding {
+    pub kind: FindingKind,
+    pub severity: Severity,
+    pub mac: String,
+    pub label: Option<String>,
+    // Where the device was seen; None for missing devices
+    pub address: Option<String>,
+    // The unexpected ports, for `UnexpectedPorts`
+    pub ports: Vec<u16>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ComplianceReport {
+    pub expected: usize,
+    pub seen: usize,
+    pub compliant: usize,
+    // Devices seen without a known MAC, which can't be matched against the inventory
+    pub unidentified: Vec<String>,
+    // Most severe first
+    pub findings: Vec<ComplianceFinding>,
+}
+
+// A device from a scan, as far as the audit cares
+#[derive(Debug, Clone)]
+pub struct ObservedDevice {
+    pub mac: String,
+    pub address: String,
+    pub open_ports: Vec<u16>,
+}
+
+// Compare the devices a scan saw against the expected inventory, keyed on MAC.
+// Unknown devices are the most serious finding; missing devices and extra
+// ports matter more for trusted devices.
+pub fn audit_inventory(expected: &[DeviceEntry], observed: &[ObservedDevice]) -> ComplianceReport {
+    let expected_by_mac: HashMap<&str, &DeviceEntry> = expected.iter().map(|e| (e.mac.as_str(), e)).collect();
+    let observed_by_mac: HashMap<&str, &ObservedDevice> = observed.iter().map(|o| (o.mac.as_str(), o)).collect();
+    let mut findings = Vec::new();
+    let mut compliant = 0;
+
+    for entry in expected {
+        let Some(device) = observed_by_mac.get(entry.mac.as_str()) else {
+            findings.push(ComplianceFinding {
+                kind: FindingKind::MissingDevice,
+                severity: if entry.trusted { Severity::Medium } else { Severity::Low },
+                mac: entry.mac.clone(),
+                label: entry.label.clone(),
+                address: None,
+                ports: Vec::new(),
+                message: format!("{} was not seen on the network", entry.label.as_deref().unwrap_or(&entry.mac)),
+            });
+            continue;
+        };
+
+        let mut unexpected: Vec<u16> = match &entry.expected_ports {
+            Some(allowed) => device.open_ports.iter().copied().filter(|p| !allowed.contains(p)).collect(),
+            None => Vec::new(),
+        };
+        if unexpected.is_empty() {
+            compliant += 1;
+            continue;
+        }
+        unexpected.sort_unstable();
+        findings.push(ComplianceFinding {
+            kind: FindingKind::UnexpectedPorts,
+            severity: if entry.trusted { Severity::High } else { Severity::Medium },
+            mac: entry.mac.clone(),
+            label: entry.label.clone(),
+            address: Some(device.address.clone()),
+            message: format!(
+                "{} at {} has unexpected open ports {:?}",
+                entry.label.as_deref().unwrap_or(&entry.mac),
+                device.address,
+                unexpected
+            ),
+            ports: unexpected,
+        });
+    }
+
+    for device in observed {
+        if !expected_by_mac.contains_key(device.mac.as_str()) {
+            findings.push(ComplianceFinding {
+                kind: FindingKind::RogueDevice,
+                severity: Severity::High,
+                mac: device.mac.clone(),
+                label: None,
+                address: Some(device.address.clone()),
+                ports: Vec::new(),
+                message: format!("Unknown device {} at {}", device.mac, device.address),
+            });
+        }
+    }
+
+    findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    ComplianceReport {
+        expected: expected.len(),
+        seen: observed.len(),
+        compliant,
+        unidentified: Vec::new(),
+        findings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mac_notations_are_normalized() {
+        for mac in ["AA:BB:CC:0D:EE:FF", "aa-bb-cc-0d-ee-ff", "aabbcc0deeff", "aabb.cc0d.eeff", " aa:bb:cc:d:ee:ff "] {
+            assert_eq!(normalize_mac(mac).as_deref(), Some("aa:bb:cc:0d:ee:ff"), "{}", mac);
+        }
+        for mac in ["aa:bb:cc:dd:ee", "aa:bb:cc:dd:ee:ff:00", "aa:bb:cc:dd:ee:gg", "aa:bb-cc:dd-ee:ff", "aa::cc:dd:ee:ff", "aabbccddee"] {
+            assert_eq!(normalize_mac(mac), None, "{}", mac);
+        }
+    }
+
+    #[test]
+    fn header_columns_are_matched_by_name() {
+        let csv = "Label,Trusted,MAC,Ports,Tags\nPrinter,yes,AA-BB-CC-DD-EE-01,9100;80,office;shared\n";
+        let (entries, skipped) = parse_inventory_csv(csv);
+        assert!(skipped.is_empty());
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.mac, "aa:bb:cc:dd:ee:01");
+        assert_eq!(entry.label.as_deref(), Some("Printer"));
+        assert!(entry.trusted);
+        assert_eq!(entry.expected_ports, Some(vec![9100, 80]));
+        assert_eq!(entry.tags, ["office", "shared"]);
+    }
+
+    #[test]
+    fn exported_csv_imports_with_hostnames_as_labels() {
+        let csv = "address,mac,hostname,device_type\r\n192.168.1.5,aa:bb:cc:dd:ee:06,nas.local,NAS\r\n";
+        let (entries, skipped) = parse_inventory_csv(csv);
+        assert!(skipped.is_empty());
+        assert_eq!(entries[0].mac, "aa:bb:cc:dd:ee:06");
+        assert_eq!(entries[0].label.as_deref(), Some("nas.local"));
+        assert!(!entries[0].trusted);
+    }
+
+    #[test]
+    fn quoted_fields_keep_commas_and_quotes() {
+        let csv = r#"aa:bb:cc:dd:ee:02,"Sam's ""work"" laptop, spare","laptop; travel",true"#;
+        let (entries, _) = parse_inventory_csv(csv);
+        assert_eq!(entries[0].label.as_deref(), Some(r#"Sam's "work" laptop, spare"#));
+        assert_eq!(entries[0].tags, ["laptop", "travel"]);
+        assert!(entries[0].trusted);
+    }
+
+    #[test]
+    fn bad_rows_are_skipped_and_the_rest_imported() {
+        let csv = "not-a-mac,Thing\n,,\naa:bb:cc:dd:ee:04,Lamp,,maybe\naa:bb:cc:dd:ee:05,Hub,,,80;http\n\naa:bb:cc:dd:ee:03,Short\n";
+        let (entries, skipped) = parse_inventory_csv(csv);
+        let lines: Vec<usize> = skipped.iter().map(|s| s.line).collect();
+        assert_eq!(lines, [1, 2, 3, 4]);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].mac, "aa:bb:cc:dd:ee:03");
+        assert_eq!(entries[0].label.as_deref(), Some("Short"));
+        assert_eq!(entries[0].expected_ports, None);
+    }
+
+    fn entry(mac: &str, trusted: bool, expected_ports: Option<Vec<u16>>) -> DeviceEntry {
+        DeviceEntry { mac: mac.to_string(), label: None, tags: Vec::new(), trusted, expected_ports }
+    }
+
+    fn observed(mac: &str, address: &str, open_ports: Vec<u16>) -> ObservedDevice {
+        ObservedDevice { mac: mac.to_string(), address: address.to_string(), open_ports }
+    }
+
+    #[test]
+    fn audit_reports_missing_devices() {
+        let expected = [entry("aa:bb:cc:dd:ee:01", true, None), entry("aa:bb:cc:dd:ee:02", false, None)];
+        let report = audit_inventory(&expected, &[observed("aa:bb:cc:dd:ee:02", "10.0.0.2", vec![22])]);
+        assert_eq!((report.expected, report.seen, report.compliant), (2, 1, 1));
+        assert_eq!(report.findings.len(), 1);
+        let finding = &report.findings[0];
+        assert_eq!(finding.kind, FindingKind::MissingDevice);
+        assert_eq!(finding.severity, Severity::Medium);
+        assert_eq!(finding.mac, "aa:bb:cc:dd:ee:01");
+        assert_eq!(finding.address, None);
+    }
+
+    #[test]
+    fn audit_reports_unknown_devices_first() {
+        let expected = [entry("aa:bb:cc:dd:ee:01", false, None)];
+        let report = audit_inventory(&expected, &[observed("aa:bb:cc:dd:ee:09", "10.0.0.9", Vec::new())]);
+        let kinds: Vec<FindingKind> = report.findings.iter().map(|f| f.kind).collect();
+        assert_eq!(kinds, [FindingKind::RogueDevice, FindingKind::MissingDevice]);
+        assert_eq!(report.findings[0].severity, Severity::High);
+        assert_eq!(report.findings[0].address.as_deref(), Some("10.0.0.9"));
+        assert_eq!(report.compliant, 0);
+    }
+
+    #[test]
+    fn audit_reports_ports_outside_the_expected_list() {
+        let expected = [
+            entry("aa:bb:cc:dd:ee:01", true, Some(vec![22, 443])),
+            entry("aa:bb:cc:dd:ee:02", false, Some(vec![80])),
+        ];
+        let seen = [
+            observed("aa:bb:cc:dd:ee:01", "10.0.0.1", vec![443, 8080, 22, 23]),
+            observed("aa:bb:cc:dd:ee:02", "10.0.0.2", vec![80]),
+        ];
+        let report = audit_inventory(&expected, &seen);
+        assert_eq!(report.compliant, 1);
+        assert_eq!(report.findings.len(), 1);
+        let finding = &report.findings[0];
+        assert_eq!(finding.kind, FindingKind::UnexpectedPorts);
+        assert_eq!(finding.severity, Severity::High);
+        assert_eq!(finding.address.as_deref(), Some("10.0.0.1"));
+        assert_eq!(finding.ports, [23, 8080]);
+    }
+}
@@ -1,6 +1,6 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 use tauri::Manager;
 
@@ -14,6 +14,36 @@ pub mod router_discovery;
 pub mod log;
 // Commands module
 pub mod commands;
+// Event sink module
+pub mod sink;
+// Shared DNS helpers
+pub mod dns;
+// UPnP description and SCPD helpers
+pub mod upnp;
+// Per-port enrichment probes
+pub mod port_probe;
+// User-supplied device labels, tags and trusted devices
+pub mod inventory;
+// Self-checks of the subsystems discovery depends on
+pub mod diagnostics;
+// WHOIS lookups for IP registration details
+pub mod whois;
+// Final-pass rules over consolidated services
+pub mod enricher;
+// Saved scan snapshots and their manifest
+pub mod history;
+// MAC address vendor lookup
+pub mod oui;
+// AS number to organization name lookup
+pub mod asn;
+// Connected WiFi network details
+pub mod wifi;
+// CSV reports of consolidated services
+pub mod export;
+// Uptime watch for individual hosts
+pub mod monitor;
+// Wake-on-LAN magic packets
+pub mod wol;
 
 // Import the functions from network_scanner module
 pub use network_scanner::{discover_mdns_streaming, discover_upnp_streaming, scan_local_network};
@@ -21,14 +51,48 @@ pub use network_scanner::{discover_mdns_streaming, discover_upnp_streaming, scan
 // Export other modules
 pub use router_discovery::get_router_and_isp_info;
 
+// IP version of a service's address
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AddressFamily {
+    V4,
+    V6,
+    // Placeholder addresses such as "Unknown"
+    #[default]
+    Unknown,
+}
+
+impl AddressFamily {
+    // Family of an address, ignoring an IPv6 zone ("fe80::1%en0") or brackets
+    pub fn of(address: &str) -> Self {
+        let address = address.trim_start_matches('[').trim_end_matches(']');
+        let address = address.split('%').next().unwrap_or(address);
+        match address.parse::<std::net::IpAddr>() {
+            Ok(std::net::IpAddr::V4(_)) => AddressFamily::V4,
+            Ok(std::net::IpAddr::V6(_)) => AddressFamily::V6,
+            Err(_) => AddressFamily::Unknown,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub struct NetworkService {
     pub name: String,
     pub service_type: String,
     pub address: String,
+    #[serde(default)]
+    pub address_family: AddressFamily,
     pub port: Option<u16>,
     pub discovery_method: String,
     pub details: Option<String>,
+    // mDNS TXT records by key; flag keys without a value map to ""
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub txt_records: BTreeMap<String, String>,
+    // What the port sweep's probe found, for services it created per port
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port_state: Option<network_scanner::PortState>,
+    // The port sweep flagged this host as throttling it
+    #[serde(default)]
+    pub possibly_rate_limited: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -36,6 +100,8 @@ pub struct ConsolidatedService {
     // Core identification
     pub name: String,
     pub address: String,
+    #[serde(default)]
+    pub address_family: AddressFamily,
     pub port: Option<u16>,
 
     // Service information
@@ -49,6 +115,10 @@ pub struct ConsolidatedService {
     pub service_types: Vec<String>,
     pub open_ports: HashMap<u16, String>, // port -> service name
 
+    // Stable identity used to recognize the same device across scans:
+    // "uuid:<UPnP UUID>", "mdns:<device id>" or "addr:<IP address>"
+    pub identity_key: String,
+
     // Additional metadata
     pub uuid: Option<String>,
     pub location_url: Option<String>,
@@ -56,6 +126,88 @@ pub struct ConsolidatedService {
 
     // Formatted details for display
     pub friendly_description: String,
+
+    // Insecure-by-design services found on the device, e.g. "telnet-exposed"
+    pub security_flags: Vec<String>,
+
+    // Decoded Matter TXT data for Matter/Thread devices
+    pub matter: Option<MatterInfo>,
+
+    // Vendor from the device's UPnP description, else from its MAC address OUI
+    #[serde(default)]
+    pub manufacturer: Option<String>,
+
+    // MAC address from the ARP table or IPv6 neighbor cache, normalized ("aa:bb:cc:dd:ee:ff")
+    #[serde(default)]
+    pub mac_address: Option<String>,
+
+    // Hardware model from the _device-info TXT "model" key, as a friendly name
+    // when the identifier is known (e.g. "MacBook Pro 14-inch 2021"), raw otherwise
+    pub hardware_model: Option<String>,
+    // OS version derived from the _device-info "osxvers" key (or a raw "version" key)
+    pub os_version: Option<String>,
+
+    // Infrastructure role ("sleep-proxy", "base-station", "device-sync") for hosts
+    // advertising network-infrastructure services, None for end-user devices
+    pub infrastructure: Option<String>,
+    // Bonjour Sleep Proxy ranking from its instance name; lower is preferred
+    pub sleep_proxy_priority: Option<u32>,
+
+    // Local interface the device was seen on, e.g. "en0". On multi-homed machines
+    // the same name can answer differently per interface, giving one entry each.
+    pub discovered_on_interface: Option<String>,
+
+    // Raw UPnP device type URN, e.g. "urn:schemas-upnp-org:device:MediaServer:1",
+    // and the broad category decoded from it ("Media", "Network", ...)
+    pub upnp_device_type: Option<String>,
+    pub device_category: Option<String>,
+
+    // The host stopped answering partway through its port sweep, so some of its
+    // ports may be missing even after the slower re-probe
+    #[serde(default)]
+    pub possibly_rate_limited: bool,
+
+    // When the device was first and most recently seen, in Unix epoch seconds.
+    // An entry the latest scan didn't find keeps its older `last_seen`.
+    #[serde(default)]
+    pub first_seen: Option<u64>,
+    #[serde(default)]
+    pub last_seen: Option<u64>,
+
+    // Every address the device was found at, `address` first. A UPnP device
+    // reachable at several IPs (e.g. wired and wireless) is one entry.
+    #[serde(default)]
+    pub addresses: Vec<String>,
+
+    // IPv6 addresses of the same device, matched to this address by MAC
+    #[serde(default)]
+    pub ipv6_addresses: Vec<String>,
+
+    // mDNS TXT records from every contributing service; the first value seen for a key wins
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub txt_records: BTreeMap<String, String>,
+
+    // Raw details from each contributing discovery, only kept when the scan asks for them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_details: Vec<SourceDetails>,
+}
+
+// Matter commissioning data advertised over mDNS (_matter._tcp / _matterc._udp)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MatterInfo {
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub vendor_name: Option<String>,
+    // "not commissioning", "basic" or "enhanced" (from the CM key)
+    pub commissioning_mode: Option<String>,
+    pub discriminator: Option<u16>,
+}
+
+// Details string as reported by one discovery method
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SourceDetails {
+    pub method: String,
+    pub details: String,
 }
 
 // Store discovered services
@@ -70,6 +222,8 @@ pub static CONSOLIDATED_SERVICES: once_cell::sync::Lazy<
 // Add UPnP XML structures
 #[derive(Debug, Deserialize)]
 pub struct DeviceDescription {
+    #[serde(rename = "URLBase")]
+    pub url_base: Option<String>,
     #[serde(rename = "device")]
     pub device: Device,
 }
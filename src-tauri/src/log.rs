@@ -2,8 +2,11 @@ use log::{LevelFilter, Log, Metadata, Record};
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
 use std::io::{self, Write};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Mutex;
 
 // Global flag to control logging
@@ -21,8 +24,49 @@ pub static SUPPRESSED_PATTERNS: Lazy<Mutex<Vec<String>>> = Lazy::new(|| {
 // Collection of explicitly enabled modules
 pub static ENABLED_MODULES: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
 
+// Per-module level overrides, keyed by a module path fragment such as
+// "network_scanner"; the longest fragment contained in a module path applies
+pub static MODULE_LEVELS: Lazy<Mutex<HashMap<String, LevelFilter>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Level set for the module with set_module_log_level, if any
+fn module_level(module_path: &str) -> Option<LevelFilter> {
+    MODULE_LEVELS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(module, _)| module_path.contains(module.as_str()))
+        .max_by_key(|(module, _)| module.len())
+        .map(|(_, level)| *level)
+}
+
+// Log `module` at `level` ("off", "error", "warn", "info", "debug" or "trace",
+// any case), overriding whether the module is enabled
+pub fn set_module_log_level(module: &str, level: &str) -> Result<LevelFilter, String> {
+    let module = module.trim();
+    if module.is_empty() {
+        return Err("Module must not be empty".to_string());
+    }
+    let level: LevelFilter = level
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid log level '{}': expected off, error, warn, info, debug or trace", level))?;
+
+    let mut levels = MODULE_LEVELS.lock().unwrap();
+    levels.insert(module.to_string(), level);
+
+    // The log crate drops anything above its max level before it reaches us
+    let max = levels.values().copied().max().unwrap_or(LevelFilter::Info).max(LevelFilter::Info);
+    log::set_max_level(max);
+    Ok(level)
+}
+
 // Helper function to check if module is enabled
 pub fn is_module_enabled(module_path: &str) -> bool {
+    // An explicit level decides on its own
+    if let Some(level) = module_level(module_path) {
+        return level != LevelFilter::Off;
+    }
+
     // Global enable flag takes precedence
     if is_logging_enabled() {
         // Only log module status once per module
@@ -30,7 +74,7 @@ pub fn is_module_enabled(module_path: &str) -> bool {
             Lazy::new(|| Mutex::new(HashSet::new()));
         let mut logged = LOGGED_MODULES.lock().unwrap();
         if !logged.contains(module_path) {
-            println!(
+            eprintln!(
                 "LOG DEBUG: Module '{}' enabled due to global flag",
                 module_path
             );
@@ -47,7 +91,7 @@ pub fn is_module_enabled(module_path: &str) -> bool {
             Lazy::new(|| Mutex::new(HashSet::new()));
         let mut logged = LOGGED_EXPLICIT_MODULES.lock().unwrap();
         if !logged.contains(module_path) {
-            println!("LOG DEBUG: Module '{}' explicitly enabled", module_path);
+            eprintln!("LOG DEBUG: Module '{}' explicitly enabled", module_path);
             logged.insert(module_path.to_string());
         }
         return true;
@@ -68,7 +112,7 @@ pub fn is_module_enabled(module_path: &str) -> bool {
             Lazy::new(|| Mutex::new(HashSet::new()));
         let mut logged = LOGGED_FEATURE_MODULES.lock().unwrap();
         if !logged.contains(module_path) {
-            println!(
+            eprintln!(
                 "LOG DEBUG: Module '{}' enabled by feature flag",
                 module_path
             );
@@ -96,7 +140,7 @@ pub fn should_suppress_message(module_path: &str, message: &str) -> bool {
 
                 // Only log every 100 suppressed messages
                 if *counter == 1 || *counter % 100 == 0 {
-                    println!(
+                    eprintln!(
                         "LOG DEBUG: Suppressed {} messages from '{}' with pattern '{}'",
                         *counter, module_path, pattern
                     );
@@ -116,7 +160,7 @@ pub fn should_suppress_message(module_path: &str, message: &str) -> bool {
 
         // Only log every 100 suppressed messages
         if *counter == 1 || *counter % 100 == 0 {
-            println!(
+            eprintln!(
                 "LOG DEBUG: Suppressed {} messages from disabled module '{}'",
                 *counter, module_path
             );
@@ -198,12 +242,112 @@ macro_rules! radar_trace {
     }
 }
 
+// Sequence number of the next log line, to order lines that share a millisecond
+static LOG_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+// Most recent log lines kept for get_recent_logs; older ones are dropped
+pub const RECENT_LOG_CAPACITY: usize = 500;
+
+// The last RECENT_LOG_CAPACITY lines written, oldest first, so the UI can show
+// logs without the app being started from a terminal
+static RECENT_LOGS: Lazy<Mutex<VecDeque<String>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(RECENT_LOG_CAPACITY)));
+
+fn remember_line(line: String) {
+    let mut recent = RECENT_LOGS.lock().unwrap();
+    if recent.len() == RECENT_LOG_CAPACITY {
+        recent.pop_front();
+    }
+    recent.push_back(line);
+}
+
+// Recently logged lines, oldest first
+pub fn recent_logs() -> Vec<String> {
+    RECENT_LOGS.lock().unwrap().iter().cloned().collect()
+}
+
+// The log file is rotated to .1 once it grows past this size; .1 moves to .2
+// and so on, keeping LOG_FILE_BACKUPS old files
+pub const LOG_FILE_MAX_BYTES: u64 = 5 * 1024 * 1024;
+pub const LOG_FILE_BACKUPS: usize = 3;
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+// Optional copy of the log on disk, set with set_log_file
+static LOG_FILE: Lazy<Mutex<Option<LogFile>>> = Lazy::new(|| Mutex::new(None));
+
+fn open_log_file(path: &Path) -> io::Result<LogFile> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok(LogFile { path: path.to_path_buf(), file, size })
+}
+
+// "radar.log" -> "radar.log.2"
+fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+// Shift every backup up by one, dropping the oldest, and start a new file
+fn rotate(log: &mut LogFile) -> io::Result<()> {
+    let _ = std::fs::remove_file(backup_path(&log.path, LOG_FILE_BACKUPS));
+    for index in (1..LOG_FILE_BACKUPS).rev() {
+        let from = backup_path(&log.path, index);
+        if from.exists() {
+            std::fs::rename(&from, backup_path(&log.path, index + 1))?;
+        }
+    }
+    std::fs::rename(&log.path, backup_path(&log.path, 1))?;
+    *log = open_log_file(&log.path)?;
+    Ok(())
+}
+
+fn write_to_file(line: &str) {
+    let mut log_file = LOG_FILE.lock().unwrap();
+    let Some(log) = log_file.as_mut() else {
+        return;
+    };
+    if log.size >= LOG_FILE_MAX_BYTES {
+        if let Err(e) = rotate(log) {
+            // Logging from here would recurse, so report straight to stderr and stop writing
+            eprintln!("LOG DEBUG: Failed to rotate {}: {}, disabling the log file", log.path.display(), e);
+            *log_file = None;
+            return;
+        }
+    }
+    if writeln!(log.file, "{}", line).is_ok() {
+        log.size += line.len() as u64 + 1;
+    }
+}
+
+// Also write log lines to `path`, or stop writing them to a file with None.
+// The file is appended to and rotated (see LOG_FILE_MAX_BYTES).
+pub fn set_log_file(path: Option<&Path>) -> Result<(), String> {
+    let log = match path {
+        Some(path) => {
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+            }
+            Some(open_log_file(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?)
+        }
+        None => None,
+    };
+    *LOG_FILE.lock().unwrap() = log;
+    Ok(())
+}
+
 // Simple logger that doesn't do additional filtering - we do it in macros
 struct SimpleLogger;
 
 impl Log for SimpleLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= log::Level::Info
+        let level = module_level(metadata.target()).unwrap_or(LevelFilter::Info);
+        metadata.level() <= level
     }
 
     fn log(&self, record: &Record) {
@@ -216,15 +360,21 @@ impl Log for SimpleLogger {
                 return;
             }
 
-            let mut stderr = io::stderr();
-            writeln!(
-                stderr,
-                "{} - [{}] {}",
+            // "2024-05-01T12:34:56.789+02:00 #42 INFO - [radar_lib::network_scanner] ..."
+            let timestamp = chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false);
+            let sequence = LOG_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+            let line = format!(
+                "{} #{} {} - [{}] {}",
+                timestamp,
+                sequence,
                 record.level(),
                 module_path,
-                record.args()
-            )
-            .ok();
+                message
+            );
+            let mut stderr = io::stderr();
+            writeln!(stderr, "{}", line).ok();
+            write_to_file(&line);
+            remember_line(line);
         }
     }
 
@@ -1,9 +1,6 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-// Create the log module
-mod log;
-
 use image;
 use tauri::image::Image;
 use tauri::menu::{MenuBuilder, MenuItemBuilder};
@@ -11,15 +8,14 @@ use tauri::tray::TrayIconBuilder;
 use tauri::Manager;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize our custom logger
-    log::init_logger();
+    // Initialize the library's logger, so its recent-lines buffer backs get_recent_logs
+    radar_lib::log::init_logger();
 
     // Enable application-wide logging if the all_logging feature is enabled
     #[cfg(feature = "all_logging")]
     {
         // Enable internal application logging by calling the log module directly
-        #[allow(dead_code)]
-        crate::log::set_logging(true);
+        radar_lib::log::set_logging(true);
         println!("All logging enabled - running with full logs");
     }
 
@@ -31,13 +27,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     ))]
     {
         // Let's use our radar macros here to prove they work
-        crate::radar_info!("Module-specific logging enabled for:");
+        radar_lib::radar_info!("Module-specific logging enabled for:");
         #[cfg(feature = "log_commands")]
-        crate::radar_info!("- commands module");
+        radar_lib::radar_info!("- commands module");
         #[cfg(feature = "log_network_scanner")]
-        crate::radar_info!("- network_scanner module");
+        radar_lib::radar_info!("- network_scanner module");
         #[cfg(feature = "log_public_network")]
-        crate::radar_info!("- public_network module");
+        radar_lib::radar_info!("- public_network module");
     }
 
     // Set up command logging filter from environment variables if present
@@ -66,6 +62,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .plugin(tauri_plugin_opener::init())
         // .plugin(tauri_plugin_notification::init()) // Temporarily disabled due to macOS crash
         .setup(|app| {
+            // Set up the cached public network state
+            radar_lib::public_network::init(app)?;
+
+            // Bring back the services saved by the last run
+            if let Ok(path) = radar_lib::commands::saved_services_path(app.handle()) {
+                radar_lib::history::load_services(&path);
+            }
+            if let Ok(path) = radar_lib::commands::saved_inventory_path(app.handle()) {
+                radar_lib::inventory::load_inventory(&path);
+            }
+
             // Store a handle to the main window for access throughout the app
             let main_window = app
                 .get_webview_window("main")
@@ -173,12 +180,49 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .invoke_handler(tauri::generate_handler![
             // Use commands from the commands module
             radar_lib::commands::get_public_network_info,
+            radar_lib::commands::stream_public_network_info,
+            radar_lib::commands::set_public_info_cache_ttl,
+            radar_lib::commands::set_stun_mode,
+            radar_lib::commands::get_recent_logs,
+            radar_lib::commands::set_log_file,
+            radar_lib::commands::set_module_log_level,
+            radar_lib::commands::cancel_public_network_info,
+            radar_lib::commands::refresh_public_field,
+            radar_lib::commands::get_public_ip_only,
             // Network scanning commands
             radar_lib::commands::run_network_scan,
             radar_lib::commands::stop_network_scan,
             // Removed get_new_services as it's not used by the UI
             radar_lib::commands::discover_mdns_streaming,
             radar_lib::commands::discover_upnp_streaming,
+            radar_lib::commands::add_mdns_hostnames,
+            radar_lib::commands::get_supported_mdns_types,
+            radar_lib::commands::set_mdns_service_types,
+            radar_lib::commands::get_mdns_type_settings,
+            radar_lib::commands::set_multicast_ttl,
+            radar_lib::commands::get_multicast_ttl,
+            radar_lib::commands::set_http_settings,
+            radar_lib::commands::get_http_settings,
+            radar_lib::commands::set_reverse_dns_cache_ttl,
+            radar_lib::commands::get_last_scan_result,
+            radar_lib::commands::diff_last_scan,
+            radar_lib::commands::get_scan_completeness,
+            radar_lib::commands::get_upnp_actions,
+            radar_lib::commands::benchmark_dns_resolvers,
+            radar_lib::commands::import_inventory_csv,
+            radar_lib::commands::get_inventory,
+            radar_lib::commands::self_diagnostics,
+            radar_lib::commands::get_wifi_info,
+            radar_lib::commands::save_snapshot,
+            radar_lib::commands::list_snapshots,
+            radar_lib::commands::load_snapshot,
+            radar_lib::commands::rescan_from_snapshot,
+            radar_lib::commands::explain_service,
+            radar_lib::commands::get_duplicate_report,
+            radar_lib::commands::merge_duplicates,
+            radar_lib::commands::retry_unreachable,
+            radar_lib::commands::audit_against_inventory,
+            radar_lib::commands::scan_expand,
             // Logging commands - only in debug builds
             #[cfg(debug_assertions)]
             radar_lib::commands::set_network_logging,
@@ -195,6 +239,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             radar_lib::commands::get_command_logging_filter,
             radar_lib::commands::get_macos_version,
             radar_lib::commands::get_discovered_services,
+            radar_lib::commands::export_services_csv,
+            radar_lib::commands::load_asn_database,
+            radar_lib::commands::wake_on_lan,
+            radar_lib::commands::export_services_jsonl,
+            radar_lib::commands::start_host_monitor,
+            radar_lib::commands::stop_host_monitor,
+            radar_lib::commands::save_discovered_services,
+            radar_lib::commands::load_discovered_services,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,140 @@
+// monitor.rs - Uptime watch for individual hosts
+//
+// Each monitor is a background task that probes one host's TCP ports on an
+// interval. Every round emits `host-status`; `host-state-changed` is emitted
+// only when the host goes from up to down or back.
+
+use crate::radar_debug;
+use crate::radar_info;
+use crate::sink::ServiceSink;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+// Most monitors that may run at once, so forgotten ones can't pile up
+const MAX_MONITORS: usize = 16;
+
+// Longest a single port probe may take; shorter intervals shorten it too
+const MAX_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Running monitors by address
+static MONITORS: Lazy<Mutex<HashMap<String, CancellationToken>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Result of one round of probes, emitted as `host-status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostStatus {
+    pub address: String,
+    // At least one of the monitored ports accepted a connection
+    pub up: bool,
+    pub open_ports: Vec<u16>,
+    pub closed_ports: Vec<u16>,
+    // RFC 3339, UTC
+    pub checked_at: String,
+}
+
+// Emitted as `host-state-changed` when a host goes up or down
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostStateChange {
+    pub address: String,
+    pub up: bool,
+    pub open_ports: Vec<u16>,
+    pub checked_at: String,
+}
+
+// Probe every monitored port at once
+async fn check_host(address: &str, ports: &[u16], timeout: Duration) -> HostStatus {
+    let results = futures::future::join_all(
+        ports
+            .iter()
+            .map(|&port| async move { (port, crate::network_scanner::check_tcp_port(address, port, timeout).await) }),
+    )
+    .await;
+
+    let (open, closed): (Vec<_>, Vec<_>) = results.into_iter().partition(|(_, open)| *open);
+    let open_ports: Vec<u16> = open.into_iter().map(|(port, _)| port).collect();
+    HostStatus {
+        address: address.to_string(),
+        up: !open_ports.is_empty(),
+        open_ports,
+        closed_ports: closed.into_iter().map(|(port, _)| port).collect(),
+        checked_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    }
+}
+
+async fn run_monitor<S: ServiceSink>(sink: S, address: String, interval: Duration, ports: Vec<u16>, cancel: CancellationToken) {
+    let timeout = MAX_PROBE_TIMEOUT.min(interval);
+    let mut previous: Option<bool> = None;
+
+    while !cancel.is_cancelled() {
+        let status = tokio::select! {
+            _ = cancel.cancelled() => break,
+            status = check_host(&address, &ports, timeout) => status,
+        };
+        radar_debug!("{} is {} (open: {:?})", address, if status.up { "up" } else { "down" }, status.open_ports);
+        let _ = sink.emit_event("host-status", &status);
+
+        // The first round only establishes the state; changes are reported from then on
+        if previous.is_some_and(|was_up| was_up != status.up) {
+            radar_info!("{} went {}", address, if status.up { "up" } else { "down" });
+            let change = HostStateChange {
+                address: address.clone(),
+                up: status.up,
+                open_ports: status.open_ports.clone(),
+                checked_at: status.checked_at.clone(),
+            };
+            let _ = sink.emit_event("host-state-changed", &change);
+        }
+        previous = Some(status.up);
+
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+    radar_info!("Stopped monitoring {}", address);
+}
+
+// Start watching `address`, probing `ports` every `interval_secs`. Starting a
+// monitor for an address that already has one replaces it.
+pub fn start_host_monitor<S: ServiceSink>(sink: S, address: &str, interval_secs: u64, ports: Vec<u16>) -> Result<(), String> {
+    let address = address.trim().to_string();
+    if ports.is_empty() {
+        return Err("At least one port is required".to_string());
+    }
+    if ports.contains(&0) {
+        return Err("Port 0 cannot be monitored".to_string());
+    }
+    if interval_secs == 0 {
+        return Err("interval_secs must be at least 1".to_string());
+    }
+    crate::port_probe::connect_target(&address, ports[0]).map_err(|e| e.to_string())?;
+
+    let cancel = CancellationToken::new();
+    {
+        let mut monitors = MONITORS.lock().unwrap();
+        if !monitors.contains_key(&address) && monitors.len() >= MAX_MONITORS {
+            return Err(format!("At most {} hosts can be monitored at once", MAX_MONITORS));
+        }
+        if let Some(previous) = monitors.insert(address.clone(), cancel.clone()) {
+            previous.cancel();
+        }
+    }
+
+    radar_info!("Monitoring {} ports {:?} every {}s", address, ports, interval_secs);
+    tauri::async_runtime::spawn(run_monitor(sink, address, Duration::from_secs(interval_secs), ports, cancel));
+    Ok(())
+}
+
+// Stop the monitor for `address`, returning whether one was running
+pub fn stop_host_monitor(address: &str) -> bool {
+    match MONITORS.lock().unwrap().remove(address.trim()) {
+        Some(cancel) => {
+            cancel.cancel();
+            true
+        }
+        None => false,
+    }
+}
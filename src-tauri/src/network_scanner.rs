@@ -1,37 +1,77 @@
-use crate::{ConsolidatedService, NetworkService, DISCOVERED_SERVICES, CONSOLIDATED_SERVICES};
-use std::collections::{HashMap, HashSet};
+use crate::{AddressFamily, ConsolidatedService, MatterInfo, NetworkService, SourceDetails, DISCOVERED_SERVICES, CONSOLIDATED_SERVICES};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::time::Duration;
 use mdns_sd::{ServiceDaemon, ServiceEvent};
 use ssdp_client::SearchTarget;
 use futures::StreamExt;
 use std::process::Command;
-use tauri::{AppHandle, Emitter};
-use crate::DeviceDescription;
+use crate::sink::{DedupSink, JsonlSink, ServiceSink, SilentSink};
+use crate::port_probe::{connect_target, enrich_port, PortConnection};
 use crate::radar_debug;
 use crate::radar_error;
 use crate::radar_info;
+use crate::radar_trace;
 use crate::radar_warn;
 use once_cell::sync::Lazy;
-use dns_lookup;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio_util::sync::CancellationToken;
+use serde::{Deserialize, Serialize};
 
 // NetworkHost struct to store information about discovered hosts
 #[derive(Debug, Clone)]
 pub struct NetworkHost {
     pub hostname: Option<String>,
+    // Every address the host was seen at (e.g. its IPv4 and IPv6 addresses)
+    pub addresses: HashSet<String>,
     pub tcp_ports: HashSet<u16>,
     pub udp_ports: HashSet<u16>,
+    // MAC address from the ARP table and the vendor its OUI is registered to
+    pub mac_address: Option<String>,
+    pub manufacturer: Option<String>,
 }
 
 impl NetworkHost {
     fn new(hostname: Option<String>) -> Self {
         Self {
             hostname,
+            addresses: HashSet::new(),
             tcp_ports: HashSet::new(),
             udp_ports: HashSet::new(),
+            mac_address: None,
+            manufacturer: None,
         }
     }
 
+    // Address to show the host under: the lowest IPv4 address, else the lowest address
+    pub fn primary_address(&self) -> Option<&String> {
+        self.addresses.iter().min_by(|a, b| compare_addresses(a, b))
+    }
+
+    fn merge(&mut self, other: NetworkHost) {
+        if self.hostname.is_none() {
+            self.hostname = other.hostname;
+        }
+        if self.mac_address.is_none() {
+            self.mac_address = other.mac_address;
+            self.manufacturer = other.manufacturer;
+        }
+        self.addresses.extend(other.addresses);
+        self.tcp_ports.extend(other.tcp_ports);
+        self.udp_ports.extend(other.udp_ports);
+    }
+
+    fn set_mac_address(&mut self, mac: String) {
+        self.manufacturer = crate::oui::lookup_oui(&mac);
+        self.mac_address = Some(mac);
+    }
+
+    // Detail lines naming the host's MAC address and vendor
+    fn hardware_details(&self) -> String {
+        hardware_details(self.mac_address.as_deref(), self.manufacturer.as_deref())
+    }
+
     fn add_tcp_port(&mut self, port: u16) {
         self.tcp_ports.insert(port);
     }
@@ -41,46 +81,414 @@ impl NetworkHost {
     }
 }
 
+// Discovered hosts, one entry per device however many addresses it has.
+// Hosts are keyed by MAC address once it is known ("mac:aa:bb:cc:dd:ee:ff"),
+// else by fully-qualified hostname ("host:nas.local"), else by their first
+// address ("addr:192.168.1.10"); every address points at its host's key.
+#[derive(Debug, Default)]
+pub struct NetworkMap {
+    hosts: HashMap<String, NetworkHost>,
+    by_address: HashMap<String, String>,
+}
+
+// Key for a fully-qualified hostname, ignoring case and a trailing dot
+// ("NAS.local." -> "host:nas.local"). Bare names ("nas") can repeat across
+// domains, and names that are just an IP address don't identify a host.
+fn hostname_key(hostname: &str) -> Option<String> {
+    let name = hostname.trim().trim_end_matches('.');
+    if !name.contains('.') || name.parse::<std::net::IpAddr>().is_ok() {
+        return None;
+    }
+    Some(format!("host:{}", name.to_lowercase()))
+}
+
+// First label of a hostname, ignoring case ("NAS.local." -> "nas")
+fn hostname_label(hostname: &str) -> Option<String> {
+    let name = hostname.trim().trim_end_matches('.');
+    if name.is_empty() || name.parse::<std::net::IpAddr>().is_ok() {
+        return None;
+    }
+    name.split('.').next().map(str::to_lowercase)
+}
+
+impl NetworkMap {
+    // The host seen at an address
+    pub fn host(&self, address: &str) -> Option<&NetworkHost> {
+        self.hosts.get(self.by_address.get(address)?)
+    }
+
+    pub fn hosts(&self) -> impl Iterator<Item = &NetworkHost> {
+        self.hosts.values()
+    }
+
+    // Record that the host at `addresses` exists, merging any entries those
+    // addresses (or the MAC address or hostname) already belong to into one
+    fn add(&mut self, addresses: &[&str], hostname: Option<String>, mac: Option<String>) -> &mut NetworkHost {
+        let mac_key = mac.as_deref().map(|mac| format!("mac:{}", mac));
+        let named_key = hostname.as_deref().and_then(hostname_key);
+        let mut keys: Vec<String> = addresses.iter().filter_map(|a| self.by_address.get(*a).cloned()).collect();
+        keys.extend(mac_key.iter().chain(&named_key).filter(|k| self.hosts.contains_key(*k)).cloned());
+        keys.sort();
+        keys.dedup();
+        
+        let key = mac_key
+            .or_else(|| keys.iter().find(|k| k.starts_with("mac:")).cloned())
+            .or(named_key)
+            .or_else(|| keys.iter().find(|k| k.starts_with("host:")).cloned())
+            .or_else(|| keys.first().cloned())
+            .unwrap_or_else(|| format!("addr:{}", addresses.first().copied().unwrap_or_default()));
+        
+        let mut host = self.hosts.remove(&key).unwrap_or_else(|| NetworkHost::new(None));
+        for other in keys.iter().filter(|k| **k != key) {
+            if let Some(other) = self.hosts.remove(other) {
+                host.merge(other);
+            }
+        }
+        if host.hostname.is_none() {
+            host.hostname = hostname;
+        }
+        if let Some(mac) = mac {
+            host.set_mac_address(mac);
+        }
+        host.addresses.extend(addresses.iter().map(|a| a.to_string()));
+        for address in &host.addresses {
+            self.by_address.insert(address.clone(), key.clone());
+        }
+        
+        self.hosts.entry(key).or_insert(host)
+    }
+    
+    // Record the host at `addresses` and the ports found open on it
+    fn add_ports(&mut self, addresses: &[&str], hostname: Option<String>, tcp_ports: Option<Vec<u16>>, udp_ports: Option<Vec<u16>>) {
+        if addresses.is_empty() {
+            return;
+        }
+        let host = self.add(addresses, hostname, None);
+        
+        // Add TCP ports if provided
+        if let Some(ports) = tcp_ports {
+            for port in ports {
+                host.add_tcp_port(port);
+            }
+        }
+        
+        // Add UDP ports if provided
+        if let Some(ports) = udp_ports {
+            for port in ports {
+                host.add_udp_port(port);
+            }
+        }
+    }
+}
+
 // Global NetworkMap to store discovered hosts
-pub static NETWORK_MAP: Lazy<Arc<Mutex<HashMap<String, NetworkHost>>>> = 
-    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+pub static NETWORK_MAP: Lazy<Arc<Mutex<NetworkMap>>> =
+    Lazy::new(|| Arc::new(Mutex::new(NetworkMap::default())));
+
+// Function to add or update a host in the network map. All `addresses` belong
+// to the same device, so their ports end up on one entry.
+pub fn add_to_network_map(addresses: &[&str], hostname: Option<String>, tcp_ports: Option<Vec<u16>>, udp_ports: Option<Vec<u16>>) {
+    NETWORK_MAP.lock().unwrap().add_ports(addresses, hostname, tcp_ports, udp_ports);
+}
+
+// Detail lines naming a MAC address and its vendor, where known
+fn hardware_details(mac: Option<&str>, manufacturer: Option<&str>) -> String {
+    let mut details = String::new();
+    if let Some(mac) = mac {
+        details.push_str(&format!("MAC Address: {}\n", mac));
+    }
+    if let Some(manufacturer) = manufacturer {
+        details.push_str(&format!("Vendor: {}\n", manufacturer));
+    }
+    details
+}
 
-// Function to add or update a host in the network map
-pub fn add_to_network_map(ip: &str, hostname: Option<String>, tcp_ports: Option<Vec<u16>>, udp_ports: Option<Vec<u16>>) {
-    let mut map = NETWORK_MAP.lock().unwrap();
+// Removed get_new_services function as it's not used by the UI
+
+pub async fn discover_mdns_streaming<S: ServiceSink>(sink: S) -> Result<Vec<NetworkService>, String> {
+    let (answers, hosts) = discover_mdns_types(sink, &mdns_browse_types(), &CancellationToken::new()).await?;
+    Ok(answers.iter().flat_map(MdnsAnswer::services).chain(hosts.iter().map(MdnsHostAnswer::service)).collect())
+}
+
+// Service types added from the frontend and how they combine with the built-in list
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MdnsTypeSettings {
+    pub custom_types: Vec<String>,
+    // Browse only `custom_types`, not the built-in list
+    pub replace_builtin: bool,
+    // Ask the network which types it advertises (the DNS-SD meta-query) and browse those too
+    pub discover_types: bool,
+}
+
+static MDNS_TYPE_SETTINGS: Lazy<Mutex<MdnsTypeSettings>> = Lazy::new(|| Mutex::new(MdnsTypeSettings::default()));
+
+// Replace the mDNS type settings. Every custom type must be a full DNS-SD
+// service type such as "_octoprint._tcp.local.".
+pub fn set_mdns_type_settings(mut settings: MdnsTypeSettings) -> Result<Vec<String>, String> {
+    for service_type in settings.custom_types.iter_mut() {
+        *service_type = service_type.trim().to_string();
+        if !service_type.ends_with(".local.") || parse_mdns_name(service_type).is_none_or(|n| n.instance.is_some()) {
+            return Err(format!(
+                "Invalid mDNS service type \"{}\": expected a type like \"_octoprint._tcp.local.\"",
+                service_type
+            ));
+        }
+    }
+    if settings.replace_builtin && settings.custom_types.is_empty() && !settings.discover_types {
+        return Err("Replacing the built-in types needs at least one custom type".to_string());
+    }
     
-    let host = map.entry(ip.to_string()).or_insert_with(|| NetworkHost::new(hostname.clone()));
+    *MDNS_TYPE_SETTINGS.lock().unwrap() = settings;
+    Ok(mdns_browse_types())
+}
+
+pub fn get_mdns_type_settings() -> MdnsTypeSettings {
+    MDNS_TYPE_SETTINGS.lock().unwrap().clone()
+}
+
+// Service types a scan browses: the built-in list unless replaced, plus the custom types
+pub fn mdns_browse_types() -> Vec<String> {
+    let settings = MDNS_TYPE_SETTINGS.lock().unwrap();
+    let builtin = MDNS_SERVICE_TYPES.iter().map(|t| t.service_type.to_string()).filter(|_| !settings.replace_builtin);
+    let mut types: Vec<String> = Vec::new();
+    for service_type in builtin.chain(settings.custom_types.iter().cloned()) {
+        if !types.contains(&service_type) {
+            types.push(service_type);
+        }
+    }
+    types
+}
+
+const MDNS_META_QUERY: &str = "_services._dns-sd._udp.local.";
+const MDNS_META_QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Service types that devices on the network advertise, from the DNS-SD meta-query
+async fn browse_advertised_types(mdns: &ServiceDaemon, cancel: &CancellationToken) -> Vec<String> {
+    let receiver = match mdns.browse(MDNS_META_QUERY) {
+        Ok(receiver) => receiver,
+        Err(e) => {
+            radar_warn!("Failed to send the mDNS meta-query: {}", e);
+            return Vec::new();
+        }
+    };
     
-    // If we have a new hostname and the current one is None, update it
-    if host.hostname.is_none() && hostname.is_some() {
-        host.hostname = hostname;
+    let mut types = Vec::new();
+    let deadline = std::time::Instant::now() + MDNS_META_QUERY_TIMEOUT;
+    while std::time::Instant::now() < deadline && !cancel.is_cancelled() {
+        match receiver.try_recv() {
+            // The pointer's alias is the advertised type, e.g. "_hap._tcp.local."
+            Ok(ServiceEvent::ServiceFound(_, service_type)) => {
+                if parse_mdns_name(&service_type).is_some_and(|n| n.instance.is_none()) && !types.contains(&service_type) {
+                    radar_debug!("Network advertises {}", service_type);
+                    types.push(service_type);
+                }
+            }
+            Ok(_) => {}
+            Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+        }
+    }
+    let _ = mdns.stop_browse(MDNS_META_QUERY);
+    types
+}
+
+// How often to retry a daemon shutdown its full command channel turned away
+const MDNS_SHUTDOWN_ATTEMPTS: usize = 10;
+
+// A discovery session's mDNS daemon, shut down with `shutdown` when the
+// session ends. Dropping a session that wasn't (e.g. on an early return) makes
+// one best-effort attempt, so repeated scans don't leave daemons running.
+struct MdnsSession {
+    daemon: ServiceDaemon,
+    shut_down: bool,
+}
+
+impl std::ops::Deref for MdnsSession {
+    type Target = ServiceDaemon;
+
+    fn deref(&self) -> &ServiceDaemon {
+        &self.daemon
+    }
+}
+
+impl MdnsSession {
+    // Shut the daemon down, waiting while its command channel is full
+    async fn shutdown(mut self) {
+        for _ in 0..MDNS_SHUTDOWN_ATTEMPTS {
+            match self.daemon.shutdown() {
+                Ok(_) => {
+                    radar_info!("mDNS service daemon shut down");
+                    self.shut_down = true;
+                    return;
+                }
+                Err(mdns_sd::Error::Again) => tokio::time::sleep(Duration::from_millis(10)).await,
+                Err(e) => {
+                    radar_warn!("Failed to shut down mDNS service daemon: {}", e);
+                    self.shut_down = true;
+                    return;
+                }
+            }
+        }
+        radar_warn!("mDNS service daemon still busy after {} shutdown attempts", MDNS_SHUTDOWN_ATTEMPTS);
+    }
+}
+
+impl Drop for MdnsSession {
+    fn drop(&mut self) {
+        if !self.shut_down {
+            if let Err(e) = self.daemon.shutdown() {
+                radar_warn!("Failed to shut down mDNS service daemon: {}", e);
+            }
+        }
+    }
+}
+
+// A resolved mDNS service as the daemon answered it, before any parsing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MdnsAnswer {
+    // The type browsed for, e.g. "_ipp._tcp.local."
+    pub service_type: String,
+    pub fullname: String,
+    pub hostname: String,
+    pub port: u16,
+    pub addresses: Vec<String>,
+    // TXT records in the order they were answered
+    pub txt: Vec<(String, String)>,
+    // Local interface each address came in on (see `interface_for_address`)
+    pub interfaces: BTreeMap<String, String>,
+}
+
+impl MdnsAnswer {
+    fn from_info(service_type: &str, info: &mdns_sd::ServiceInfo) -> Self {
+        let addresses: Vec<String> = info.get_addresses().iter().map(|addr| addr.to_string()).collect();
+        let interfaces = addresses
+            .iter()
+            .filter_map(|address| Some((address.clone(), interface_for_address(address)?)))
+            .collect();
+        let txt = info
+            .get_properties()
+            .iter()
+            .map(|property| {
+                let value = property.val().map(|v| String::from_utf8_lossy(v).into_owned()).unwrap_or_default();
+                (property.key().to_string(), value)
+            })
+            .collect();
+        Self {
+            service_type: service_type.to_string(),
+            fullname: info.get_fullname().to_string(),
+            hostname: info.get_hostname().to_string(),
+            port: info.get_port(),
+            addresses,
+            txt,
+            interfaces,
+        }
     }
     
-    // Add TCP ports if provided
-    if let Some(ports) = tcp_ports {
-        for port in ports {
-            host.add_tcp_port(port);
+    // Add the answering host and its port to a network map
+    fn add_to(&self, map: &mut NetworkMap) {
+        let addresses: Vec<&str> = self.addresses.iter().map(String::as_str).collect();
+        let port = Some(vec![self.port]);
+        match parse_mdns_name(&self.service_type).as_ref().map(|t| t.protocol.as_str()) {
+            Some("tcp") => map.add_ports(&addresses, Some(self.hostname.clone()), port, None),
+            Some("udp") => map.add_ports(&addresses, Some(self.hostname.clone()), None, port),
+            _ => {}
         }
     }
     
-    // Add UDP ports if provided
-    if let Some(ports) = udp_ports {
-        for port in ports {
-            host.add_udp_port(port);
+    // The services the answer describes, one per interface it came in on
+    fn services(&self) -> Vec<NetworkService> {
+        let address = self.addresses.first().cloned().unwrap_or_else(|| "Unknown".to_string());
+        
+        // Extract service name and create detailed description
+        let name = match parse_mdns_name(&self.fullname).and_then(|n| n.instance) {
+            Some(instance) => instance,
+            None => self.fullname.split('.').next().unwrap_or_default().to_string(),
+        };
+        
+        let mut details_parts = Vec::new();
+        
+        // Add basic service information
+        details_parts.push(format!("Host: {}", self.hostname));
+        details_parts.push(format!("Full Name: {}", self.fullname));
+        
+        // Parse and add service type information
+        let parsed_type = parse_mdns_name(&self.service_type);
+        let service_protocol = match parsed_type.as_ref().map(|t| t.protocol.as_str()) {
+            Some("tcp") => "TCP",
+            Some("udp") => "UDP",
+            _ => "Unknown",
+        };
+        
+        let clean_service_type = match parsed_type {
+            Some(parsed) => parsed.service,
+            None => self.service_type.replace("_", ""),
+        };
+        
+        details_parts.push(format!("Service Type: {}", clean_service_type));
+        details_parts.push(format!("Protocol: {}", service_protocol));
+        details_parts.push(format!("Port: {}", self.port));
+        
+        // Add TXT record information if available
+        if !self.txt.is_empty() {
+            details_parts.push("\nTXT Records:".to_string());
+            for (key, value) in &self.txt {
+                details_parts.push(format!("  {}: {}", key, value));
+            }
         }
+        
+        // Add all addresses
+        if self.addresses.len() > 1 {
+            details_parts.push("\nAll Addresses:".to_string());
+            for addr in &self.addresses {
+                details_parts.push(format!("  {}", addr));
+            }
+        }
+        
+        // Create a friendly name
+        let friendly_name = if name.is_empty() || name == self.service_type {
+            if self.hostname.contains('.') {
+                self.hostname.split('.').next().unwrap_or("Device").to_string()
+            } else {
+                format!("{} Device", clean_service_type.to_uppercase())
+            }
+        } else {
+            name
+        };
+        
+        // Create the service object, one per interface it answered on
+        let service = NetworkService {
+            name: friendly_name,
+            service_type: clean_service_type,
+            address_family: AddressFamily::of(&address),
+            address,
+            port: Some(self.port),
+            discovery_method: METHOD_MDNS.to_string(),
+            details: Some(details_parts.join("\n")),
+            txt_records: self.txt.iter().cloned().collect(),
+            port_state: None,
+            possibly_rate_limited: false,
+        };
+        split_by_interface(service, &self.addresses, &self.interfaces)
     }
 }
 
-// Removed get_new_services function as it's not used by the UI
-
-pub async fn discover_mdns_streaming(app_handle: AppHandle) -> Result<Vec<NetworkService>, String> {
-    // Services we'll discover
+// Browse for the given mDNS service types, emitting a `service-discovered` per
+// service as it resolves. Returns the raw answers, for recording, and the
+// hosts resolved by direct queries.
+async fn discover_mdns_types<S: ServiceSink>(
+    sink: S,
+    service_types: &[String],
+    cancel: &CancellationToken,
+) -> Result<(Vec<MdnsAnswer>, Vec<MdnsHostAnswer>), String> {
+    // Services we'll discover and the answers they came from
     let mut services = Vec::new();
+    let mut answers = Vec::new();
     
     // Create a new ServiceDaemon for each discovery session
     // The key is to use a new instance each time and ensure it lives long enough
     let mdns = match ServiceDaemon::new() {
-        Ok(daemon) => daemon,
+        Ok(daemon) => MdnsSession { daemon, shut_down: false },
         Err(e) => {
             // If we can't create the daemon, this is a critical error
             radar_error!("Failed to create mDNS service daemon: {}", e);
@@ -90,29 +498,37 @@ pub async fn discover_mdns_streaming(app_handle: AppHandle) -> Result<Vec<Networ
     
     radar_info!("Starting mDNS service discovery");
     
-    // Using a smaller set of the most common service types for reliability
-    let service_types = vec![
-        "_http._tcp.local.",
-        "_https._tcp.local.",
-        "_ssh._tcp.local.",
-        "_device-info._tcp.local.",
-        "_spotify-connect._tcp.local.",
-        "_airplay._tcp.local.",
-        "_googlecast._tcp.local.",
-        "_printer._tcp.local.",
-        "_ipp._tcp.local.",
-        "_homekit._tcp.local.",
-        "_companion-link._tcp.local.",
-    ];
+    // mdns-sd doesn't expose its sockets, so the daemon always uses the OS default
+    // multicast TTL; only our direct host queries honor a custom TTL
+    if get_multicast_ttl() != 1 {
+        radar_debug!("Multicast TTL {} applies to direct mDNS host queries only", get_multicast_ttl());
+    }
+    
+    // Add whatever else the network says it advertises
+    let mut service_types = service_types.to_vec();
+    if MDNS_TYPE_SETTINGS.lock().unwrap().discover_types {
+        let advertised = browse_advertised_types(&mdns, cancel).await;
+        let before = service_types.len();
+        for service_type in advertised {
+            if !service_types.contains(&service_type) {
+                service_types.push(service_type);
+            }
+        }
+        radar_info!("Meta-query found {} service types not in the browse list", service_types.len() - before);
+    }
     
     // Process each service type one by one
-    for service_type in service_types {
+    for service_type in service_types.iter().map(String::as_str) {
+        if cancel.is_cancelled() {
+            radar_info!("mDNS discovery stopped");
+            break;
+        }
         radar_debug!("Browsing for service type: {}", service_type);
         
         // Create a new receiver for this service type
         let receiver = match mdns.browse(service_type) {
             Ok(r) => {
-                radar_debug!("Successfully created browser for {}", service_type);
+                radar_trace!("Successfully created browser for {}", service_type);
                 r
             },
             Err(e) => {
@@ -131,7 +547,7 @@ pub async fn discover_mdns_streaming(app_handle: AppHandle) -> Result<Vec<Networ
         
         // Process responses until timeout
         loop {
-            if std::time::Instant::now() > deadline {
+            if std::time::Instant::now() > deadline || cancel.is_cancelled() {
                 break;
             }
             
@@ -139,134 +555,27 @@ pub async fn discover_mdns_streaming(app_handle: AppHandle) -> Result<Vec<Networ
             match receiver.try_recv() {
                 Ok(ServiceEvent::ServiceResolved(info)) => {
                     radar_debug!("Resolved service: {}", info.get_fullname());
+                    let answer = MdnsAnswer::from_info(service_type, &info);
                     
-                            // Get all addresses
-                            let addresses: Vec<String> = info.get_addresses()
-                                .iter()
-                                .map(|addr| addr.to_string())
-                                .collect();
-                            
-                            let address = addresses.first().cloned().unwrap_or_else(|| "Unknown".to_string());
-                            
-                    // Extract service name and create detailed description
-                            let fullname = info.get_fullname().to_string();
-                            let name = if let Some(idx) = fullname.find('.') {
-                                fullname[0..idx].to_string()
-                            } else {
-                                fullname.clone()
-                            };
-                            
-                            let mut details_parts = Vec::new();
-                            let mut open_ports = HashMap::new();
-                            
-                            // Add basic service information
-                            details_parts.push(format!("Host: {}", info.get_hostname()));
-                            details_parts.push(format!("Full Name: {}", info.get_fullname()));
-                            
-                            // Parse and add service type information
-                            let service_protocol = if service_type.contains("_tcp") {
-                                "TCP"
-                            } else if service_type.contains("_udp") {
-                                "UDP"
-                            } else {
-                                "Unknown"
-                            };
-                            
-                            let clean_service_type = service_type
-                                .replace("._tcp.local.", "")
-                                .replace("._udp.local.", "")
-                                .replace("_", "");
-                            
-                            details_parts.push(format!("Service Type: {}", clean_service_type));
-                            details_parts.push(format!("Protocol: {}", service_protocol));
-                            details_parts.push(format!("Port: {}", info.get_port()));
-                            
-                            // Add port to open ports
-                            open_ports.insert(info.get_port(), clean_service_type.clone());
-                            
-                            // Add TXT record information if available
-                            let txt_properties = info.get_properties();
-                            if !txt_properties.is_empty() {
-                                details_parts.push("\nTXT Records:".to_string());
-                                for i in 0..txt_properties.len() {
-                                    if let Some(property) = txt_properties.iter().nth(i) {
-                                        details_parts.push(format!("  {}: {}", property.key(), property.val_str()));
-                                    }
-                                }
-                            }
-                            
-                            // Add all addresses
-                            if addresses.len() > 1 {
-                                details_parts.push("\nAll Addresses:".to_string());
-                                for addr in &addresses {
-                                    details_parts.push(format!("  {}", addr));
-                                }
-                            }
-                            
-                            // Create a friendly name
-                            let friendly_name = if name.is_empty() || name == service_type {
-                                let hostname = info.get_hostname();
-                                if hostname.contains('.') {
-                                    hostname.split('.').next().unwrap_or("Device").to_string()
-                                } else {
-                                    format!("{} Device", clean_service_type.to_uppercase())
-                                }
-                            } else {
-                                name
-                            };
-                            
-                            // Create the service object
-                            let service = NetworkService {
-                                name: friendly_name,
-                                service_type: clean_service_type,
-                        address: address.clone(),
-                                port: Some(info.get_port()),
-                                discovery_method: "mDNS".to_string(),
-                                details: Some(details_parts.join("\n")),
-                            };
-                    
-                    // Add the host to the network map
-                    if service_protocol == "TCP" {
-                        // Add to network map with TCP port
-                        add_to_network_map(
-                            &address, 
-                            Some(info.get_hostname().to_string()),
-                            Some(vec![info.get_port()]),
-                            None
-                        );
-                    } else if service_protocol == "UDP" {
-                        // Add to network map with UDP port
-                        add_to_network_map(
-                            &address,
-                            Some(info.get_hostname().to_string()),
-                            None,
-                            Some(vec![info.get_port()])
-                        );
+                    // TXT values sometimes reference other hosts by their .local name
+                    for (_, value) in &answer.txt {
+                        if value.trim_end_matches('.').ends_with(".local") {
+                            add_mdns_candidate_hostname(value);
+                        }
                     }
-                            
-                            // Emit the service as an event
-                    // Using a match to prevent any errors from affecting our service collection
-                    match app_handle.emit("service-discovered", &service) {
-                        Ok(_) => {
-                            radar_debug!("Successfully emitted mDNS service: {}", service.name);
-                            
-                            // Extra debug info to verify event structure matches frontend expectations
-                            radar_info!("Event 'service-discovered' emitted with payload: {{");
-                            radar_info!("  name: {}", service.name);
-                            radar_info!("  service_type: {}", service.service_type);
-                            radar_info!("  address: {}", service.address);
-                            radar_info!("  port: {:?}", service.port);
-                            radar_info!("  discovery_method: {}", service.discovery_method);
-                            radar_info!("}}");
-                        },
-                        Err(e) => {
-                            // Just log the error but don't fail - the channel might be closed if UI is not listening
-                            radar_warn!("Failed to emit mDNS service event: {}", e);
+                    
+                    // Add the host to the network map under all of its addresses
+                    answer.add_to(&mut NETWORK_MAP.lock().unwrap());
+                    
+                    for service in answer.services() {
+                        // Just log a failed emit - the channel might be closed if UI is not listening
+                        match sink.emit_event("service-discovered", &service) {
+                            Ok(_) => radar_trace!("Successfully emitted mDNS service: {}", service.name),
+                            Err(e) => radar_warn!("Failed to emit mDNS service event: {}", e),
                         }
+                        services.push(service);
                     }
-                            
-                            // Add to our collection
-                            services.push(service);
+                    answers.push(answer);
                 },
                 Ok(_) => {
                     // Ignore other service events
@@ -292,15 +601,270 @@ pub async fn discover_mdns_streaming(app_handle: AppHandle) -> Result<Vec<Networ
         drop(receiver);
     }
     
-    radar_info!("mDNS discovery completed, found {} services", services.len());
+    // Directly query A/AAAA for candidate .local hostnames that didn't show up
+    // through service browsing (responders that don't advertise PTR records)
+    let known_addresses: HashSet<String> = services.iter().map(|s| s.address.clone()).collect();
+    let hosts = if cancel.is_cancelled() {
+        Vec::new()
+    } else {
+        resolve_candidate_mdns_hosts(&known_addresses, Duration::from_secs(2)).await
+    };
+    for host in &hosts {
+        host.add_to(&mut NETWORK_MAP.lock().unwrap());
+        let _ = sink.emit_event("service-discovered", &host.service());
+    }
     
-    // Return the discovered services
-    let result = Ok(services);
-    return result;
+    mdns.shutdown().await;
+    radar_info!("mDNS discovery completed, found {} services", services.len() + hosts.len());
+    Ok((answers, hosts))
 }
 
-pub async fn discover_upnp_streaming(app_handle: AppHandle) -> Result<Vec<NetworkService>, String> {
-    let mut services = Vec::new();
+pub async fn discover_upnp_streaming<S: ServiceSink>(sink: S) -> Result<Vec<NetworkService>, String> {
+    let answers = search_upnp(sink, &CancellationToken::new()).await?;
+    Ok(answers.iter().map(SsdpAnswer::service).collect())
+}
+
+// An SSDP search response and the device description at its location, before any parsing
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SsdpAnswer {
+    pub location: String,
+    pub server: String,
+    pub search_target: String,
+    pub usn: String,
+    // Raw description XML, when the location served one
+    pub description: Option<String>,
+}
+
+impl SsdpAnswer {
+    // Device address and port from the location URL
+    fn address_port(&self) -> (String, Option<u16>) {
+        let mut address = "Unknown".to_string();
+        let mut port = None;
+        
+        if let Some(without_prefix) = self.location.strip_prefix("http://") {
+            if let Some(host_end) = without_prefix.find('/') {
+                let host_part = &without_prefix[..host_end];
+                if let Some(port_idx) = host_part.find(':') {
+                    address = host_part[..port_idx].to_string();
+                    if let Ok(port_num) = host_part[port_idx+1..].parse::<u16>() {
+                        port = Some(port_num);
+                    }
+                } else {
+                    address = host_part.to_string();
+                    port = Some(80);
+                }
+            }
+        }
+        (address, port)
+    }
+    
+    fn add_to(&self, map: &mut NetworkMap) {
+        let (address, port) = self.address_port();
+        if address != "Unknown" {
+            // For UPnP, we generally know it's on HTTP which is TCP
+            map.add_ports(&[&address], Some(address.clone()), Some(vec![port.unwrap_or(80)]), None);
+        }
+    }
+    
+    // The service the response describes
+    fn service(&self) -> NetworkService {
+        let device_desc = self.description.as_deref().and_then(crate::upnp::parse_device_description);
+        let (address, port) = self.address_port();
+        let location_url = &self.location;
+        
+        let mut details_parts = Vec::new();
+        let mut open_ports = HashMap::new();
+        
+        // Add device description details if available
+        if let Some(ref desc) = device_desc {
+            // Extract device type with more detail
+            if let Some(device_type) = &desc.device.device_type {
+                details_parts.push(format!("Device Type: {}", device_type));
+                // Parse protocol from device type
+                if let Some(protocol) = device_type.split(':').nth(3) {
+                    details_parts.push(format!("Protocol: {}", protocol));
+                }
+            }
+            
+            // Add basic device info
+            if let Some(friendly_name) = &desc.device.friendly_name {
+                details_parts.push(format!("Name: {}", friendly_name));
+            }
+            if let Some(manufacturer) = &desc.device.manufacturer {
+                details_parts.push(format!("Manufacturer: {}", manufacturer));
+                if let Some(url) = &desc.device.manufacturer_url {
+                    details_parts.push(format!("Manufacturer URL: {}", url));
+                }
+            }
+            if let Some(model_name) = &desc.device.model_name {
+                details_parts.push(format!("Model: {}", model_name));
+                if let Some(desc_text) = &desc.device.model_description {
+                    details_parts.push(format!("Model Description: {}", desc_text));
+                }
+                if let Some(num) = &desc.device.model_number {
+                    details_parts.push(format!("Model Number: {}", num));
+                }
+                if let Some(url) = &desc.device.model_url {
+                    details_parts.push(format!("Model URL: {}", url));
+                }
+            }
+            if let Some(serial) = &desc.device.serial_number {
+                details_parts.push(format!("Serial Number: {}", serial));
+            }
+            if let Some(udn) = &desc.device.udn {
+                details_parts.push(format!("UDN: {}", udn));
+                // Extract UUID from UDN
+                if let Some(uuid) = udn.strip_prefix("uuid:") {
+                    details_parts.push(format!("UUID: {}", uuid));
+                }
+            }
+            if let Some(url) = &desc.device.presentation_url {
+                details_parts.push(format!("Web Interface: {}", url));
+                // Try to extract port from URL
+                if let Some(port) = url.split(':').nth(2).and_then(|p| p.split('/').next()) {
+                    if let Ok(port_num) = port.parse::<u16>() {
+                        open_ports.insert(port_num, "Web Interface".to_string());
+                    }
+                }
+            }
+            
+            // Add service list with enhanced details
+            if let Some(service_list) = &desc.device.service_list {
+                details_parts.push("\nServices:".to_string());
+                for service in &service_list.services {
+                    details_parts.push(format!("  Service Type: {}", service.service_type));
+                    
+                    // Extract protocol and service name from service type
+                    let parts: Vec<&str> = service.service_type.split(':').collect();
+                    if parts.len() >= 4 {
+                        details_parts.push(format!("    Protocol: {}", parts[1]));
+                        details_parts.push(format!("    Service: {}", parts[3]));
+                    }
+                    
+                    details_parts.push(format!("  Service ID: {}", service.service_id));
+                    
+                    // Add URLs with port information
+                    if let Some(url) = &service.control_url {
+                        details_parts.push(format!("    Control URL: {}", url));
+                        if url.contains(':') {
+                            if let Some(port) = url.split(':').nth(1).and_then(|p| p.split('/').next()) {
+                                if let Ok(port_num) = port.parse::<u16>() {
+                                    open_ports.insert(port_num, format!("{} Control", service.service_type));
+                                }
+                            }
+                        }
+                    }
+                    if let Some(url) = &service.event_sub_url {
+                        details_parts.push(format!("    Event Sub URL: {}", url));
+                    }
+                    if let Some(url) = &service.scpd_url {
+                        details_parts.push(format!("    SCPD URL: {}", url));
+                    }
+                    details_parts.push("".to_string());
+                }
+            }
+            
+            // Add embedded devices with enhanced details
+            if let Some(device_list) = &desc.device.device_list {
+                details_parts.push("\nEmbedded Devices:".to_string());
+                for device in &device_list.devices {
+                    details_parts.push("  Device:".to_string());
+                    if let Some(name) = &device.friendly_name {
+                        details_parts.push(format!("    Name: {}", name));
+                    }
+                    if let Some(type_) = &device.device_type {
+                        details_parts.push(format!("    Type: {}", type_));
+                        // Extract protocol from device type
+                        if let Some(protocol) = type_.split(':').nth(3) {
+                            details_parts.push(format!("    Protocol: {}", protocol));
+                        }
+                    }
+                    if let Some(model) = &device.model_name {
+                        details_parts.push(format!("    Model: {}", model));
+                    }
+                    if let Some(manufacturer) = &device.manufacturer {
+                        details_parts.push(format!("    Manufacturer: {}", manufacturer));
+                    }
+                    details_parts.push("".to_string());
+                }
+            }
+        }
+        
+        // Add enhanced SSDP response information
+        details_parts.push("\nSSDP Information:".to_string());
+        details_parts.push(format!("Location: {}", location_url));
+        
+        // Parse server string for OS and UPnP details
+        let server_info = &self.server;
+        details_parts.push(format!("Server: {}", server_info));
+        let server_parts: Vec<&str> = server_info.split('/').collect();
+        if server_parts.len() > 1 {
+            details_parts.push(format!("  OS: {}", server_parts[0].trim()));
+            if let Some(upnp_version) = server_info.find("UPnP").map(|i| &server_info[i..]) {
+                details_parts.push(format!("  UPnP Version: {}", upnp_version));
+            }
+        }
+        
+        // Parse search target for protocol information
+        let search_target = self.search_target.clone();
+        details_parts.push(format!("Search Target: {}", search_target));
+        if search_target.contains("urn:") {
+            let parts: Vec<&str> = search_target.split(':').collect();
+            if parts.len() >= 4 {
+                details_parts.push(format!("  Protocol: {}", parts[1]));
+                details_parts.push(format!("  Service Type: {}", parts[3]));
+            }
+        }
+        
+        // Parse USN for additional device information
+        let usn = &self.usn;
+        details_parts.push(format!("USN: {}", usn));
+        if let Some(uuid_end) = usn.find("::") {
+            if let Some(uuid) = usn[..uuid_end].strip_prefix("uuid:") {
+                details_parts.push(format!("  UUID: {}", uuid));
+            }
+        }
+        
+        // Create service name from device description or fallback to basic info
+        let name = if let Some(ref desc) = device_desc {
+            desc.device.friendly_name.clone()
+                .or(desc.device.model_name.clone())
+                .unwrap_or_else(|| format!("UPnP Device at {}", address))
+        } else {
+            format!("UPnP Device at {}", address)
+        };
+        
+        // Determine device type
+        let device_type = if let Some(ref desc) = device_desc {
+            desc.device.device_type
+                .clone()
+                .unwrap_or_else(|| "UPnP Device".to_string())
+        } else {
+            "UPnP Device".to_string()
+        };
+        
+        NetworkService {
+            name,
+            service_type: device_type,
+            address_family: AddressFamily::of(&address),
+            address,
+            port,
+            discovery_method: METHOD_UPNP.to_string(),
+            details: Some(details_parts.join("\n")),
+            txt_records: BTreeMap::new(),
+            port_state: None,
+            possibly_rate_limited: false,
+        }
+    }
+}
+
+// Run the SSDP searches, stopping early once `cancel` fires. Emits a
+// `service-discovered` per device and returns the raw answers, one per
+// address and port.
+async fn search_upnp<S: ServiceSink>(sink: S, cancel: &CancellationToken) -> Result<Vec<SsdpAnswer>, String> {
+    let mut answers = Vec::new();
+    let mut seen_addresses = HashSet::new();
     let search_targets = vec![
         SearchTarget::RootDevice,
         SearchTarget::All,
@@ -308,239 +872,41 @@ pub async fn discover_upnp_streaming(app_handle: AppHandle) -> Result<Vec<Networ
     ];
     
     for search_target in search_targets {
-        match ssdp_client::search(&search_target, Duration::from_secs(2), 1, None).await {
+        if cancel.is_cancelled() {
+            radar_info!("UPnP discovery stopped");
+            break;
+        }
+        match ssdp_client::search(&search_target, Duration::from_secs(2), 1, Some(get_multicast_ttl())).await {
             Ok(responses) => {
                 tokio::pin!(responses);
                 
-                while let Some(response_result) = responses.next().await {
+                while let Some(response_result) = tokio::select! {
+                    _ = cancel.cancelled() => None,
+                    next = responses.next() => next,
+                } {
                     if let Ok(response) = response_result {
-                        let location_url = response.location().to_string();
-                        
-                        // Try to fetch and parse device description
-                        let device_desc = if !location_url.is_empty() {
-                            fetch_device_description(&location_url).await
-                        } else {
-                            None
+                        let location = response.location().to_string();
+                        let mut answer = SsdpAnswer {
+                            location,
+                            server: response.server().to_string(),
+                            search_target: response.search_target().to_string(),
+                            usn: response.usn().to_string(),
+                            description: None,
                         };
                         
-                        // Extract IP and port from location URL
-                        let mut address = "Unknown".to_string();
-                        let mut port = None;
-                        
-                        let location = response.location();
-                        if location.starts_with("http://") {
-                            let without_prefix = location.trim_start_matches("http://");
-                            if let Some(host_end) = without_prefix.find('/') {
-                                let host_part = &without_prefix[..host_end];
-                                if let Some(port_idx) = host_part.find(':') {
-                                    address = host_part[..port_idx].to_string();
-                                    if let Ok(port_num) = host_part[port_idx+1..].parse::<u16>() {
-                                        port = Some(port_num);
-                                    }
-                                } else {
-                                    address = host_part.to_string();
-                                    port = Some(80);
-                                }
-                            }
-                        }
-                        
-                        let mut details_parts = Vec::new();
-                        let mut open_ports = HashMap::new();
-                        
-                        // Add device description details if available
-                        if let Some(ref desc) = device_desc {
-                            // Extract device type with more detail
-                            if let Some(device_type) = &desc.device.device_type {
-                                details_parts.push(format!("Device Type: {}", device_type));
-                                // Parse protocol from device type
-                                if let Some(protocol) = device_type.split(':').nth(3) {
-                                    details_parts.push(format!("Protocol: {}", protocol));
-                                }
-                            }
-                            
-                            // Add basic device info
-                            if let Some(friendly_name) = &desc.device.friendly_name {
-                                details_parts.push(format!("Name: {}", friendly_name));
-                            }
-                            if let Some(manufacturer) = &desc.device.manufacturer {
-                                details_parts.push(format!("Manufacturer: {}", manufacturer));
-                                if let Some(url) = &desc.device.manufacturer_url {
-                                    details_parts.push(format!("Manufacturer URL: {}", url));
-                                }
-                            }
-                            if let Some(model_name) = &desc.device.model_name {
-                                details_parts.push(format!("Model: {}", model_name));
-                                if let Some(desc_text) = &desc.device.model_description {
-                                    details_parts.push(format!("Model Description: {}", desc_text));
-                                }
-                                if let Some(num) = &desc.device.model_number {
-                                    details_parts.push(format!("Model Number: {}", num));
-                                }
-                                if let Some(url) = &desc.device.model_url {
-                                    details_parts.push(format!("Model URL: {}", url));
-                                }
-                            }
-                            if let Some(serial) = &desc.device.serial_number {
-                                details_parts.push(format!("Serial Number: {}", serial));
-                            }
-                            if let Some(udn) = &desc.device.udn {
-                                details_parts.push(format!("UDN: {}", udn));
-                                // Extract UUID from UDN
-                                if let Some(uuid) = udn.strip_prefix("uuid:") {
-                                    details_parts.push(format!("UUID: {}", uuid));
-                                }
-                            }
-                            if let Some(url) = &desc.device.presentation_url {
-                                details_parts.push(format!("Web Interface: {}", url));
-                                // Try to extract port from URL
-                                if let Some(port) = url.split(':').nth(2).and_then(|p| p.split('/').next()) {
-                                    if let Ok(port_num) = port.parse::<u16>() {
-                                        open_ports.insert(port_num, "Web Interface".to_string());
-                                    }
-                                }
-                            }
-                            
-                            // Add service list with enhanced details
-                            if let Some(service_list) = &desc.device.service_list {
-                                details_parts.push("\nServices:".to_string());
-                                for service in &service_list.services {
-                                    details_parts.push(format!("  Service Type: {}", service.service_type));
-                                    
-                                    // Extract protocol and service name from service type
-                                    let parts: Vec<&str> = service.service_type.split(':').collect();
-                                    if parts.len() >= 4 {
-                                        details_parts.push(format!("    Protocol: {}", parts[1]));
-                                        details_parts.push(format!("    Service: {}", parts[3]));
-                                    }
-                                    
-                                    details_parts.push(format!("  Service ID: {}", service.service_id));
-                                    
-                                    // Add URLs with port information
-                                    if let Some(url) = &service.control_url {
-                                        details_parts.push(format!("    Control URL: {}", url));
-                                        if url.contains(':') {
-                                            if let Some(port) = url.split(':').nth(1).and_then(|p| p.split('/').next()) {
-                                                if let Ok(port_num) = port.parse::<u16>() {
-                                                    open_ports.insert(port_num, format!("{} Control", service.service_type));
-                                                }
-                                            }
-                                        }
-                                    }
-                                    if let Some(url) = &service.event_sub_url {
-                                        details_parts.push(format!("    Event Sub URL: {}", url));
-                                    }
-                                    if let Some(url) = &service.scpd_url {
-                                        details_parts.push(format!("    SCPD URL: {}", url));
-                                    }
-                                    details_parts.push("".to_string());
-                                }
-                            }
-                            
-                            // Add embedded devices with enhanced details
-                            if let Some(device_list) = &desc.device.device_list {
-                                details_parts.push("\nEmbedded Devices:".to_string());
-                                for device in &device_list.devices {
-                                    details_parts.push("  Device:".to_string());
-                                    if let Some(name) = &device.friendly_name {
-                                        details_parts.push(format!("    Name: {}", name));
-                                    }
-                                    if let Some(type_) = &device.device_type {
-                                        details_parts.push(format!("    Type: {}", type_));
-                                        // Extract protocol from device type
-                                        if let Some(protocol) = type_.split(':').nth(3) {
-                                            details_parts.push(format!("    Protocol: {}", protocol));
-                                        }
-                                    }
-                                    if let Some(model) = &device.model_name {
-                                        details_parts.push(format!("    Model: {}", model));
-                                    }
-                                    if let Some(manufacturer) = &device.manufacturer {
-                                        details_parts.push(format!("    Manufacturer: {}", manufacturer));
-                                    }
-                                    details_parts.push("".to_string());
-                                }
-                            }
+                        // Devices answer every search target, so keep one answer per address and port
+                        if !seen_addresses.insert(answer.address_port()) {
+                            continue;
                         }
                         
-                        // Add enhanced SSDP response information
-                        details_parts.push("\nSSDP Information:".to_string());
-                        details_parts.push(format!("Location: {}", location_url));
-                        
-                        // Parse server string for OS and UPnP details
-                        let server_info = response.server();
-                        details_parts.push(format!("Server: {}", server_info));
-                        let server_parts: Vec<&str> = server_info.split('/').collect();
-                        if server_parts.len() > 1 {
-                            details_parts.push(format!("  OS: {}", server_parts[0].trim()));
-                            if let Some(upnp_version) = server_info.find("UPnP").map(|i| &server_info[i..]) {
-                                details_parts.push(format!("  UPnP Version: {}", upnp_version));
-                            }
+                        // Try to fetch the device description
+                        if !answer.location.is_empty() {
+                            answer.description = crate::upnp::fetch_text(&answer.location).await.ok();
                         }
                         
-                        // Parse search target for protocol information
-                        let search_target = response.search_target().to_string();
-                        details_parts.push(format!("Search Target: {}", search_target));
-                        if search_target.contains("urn:") {
-                            let parts: Vec<&str> = search_target.split(':').collect();
-                            if parts.len() >= 4 {
-                                details_parts.push(format!("  Protocol: {}", parts[1]));
-                                details_parts.push(format!("  Service Type: {}", parts[3]));
-                            }
-                        }
-                        
-                        // Parse USN for additional device information
-                        let usn = response.usn();
-                        details_parts.push(format!("USN: {}", usn));
-                        if let Some(uuid_end) = usn.find("::") {
-                            if let Some(uuid) = usn[..uuid_end].strip_prefix("uuid:") {
-                                details_parts.push(format!("  UUID: {}", uuid));
-                            }
-                        }
-                        
-                        // Create service name from device description or fallback to basic info
-                        let name = if let Some(ref desc) = device_desc {
-                            desc.device.friendly_name.clone()
-                                .or(desc.device.model_name.clone())
-                                .unwrap_or_else(|| format!("UPnP Device at {}", address))
-                        } else {
-                            format!("UPnP Device at {}", address)
-                        };
-                        
-                        // Determine device type
-                        let device_type = if let Some(ref desc) = device_desc {
-                            desc.device.device_type
-                                .clone()
-                                .unwrap_or_else(|| "UPnP Device".to_string())
-                        } else {
-                            "UPnP Device".to_string()
-                        };
-                        
-                        // Create the service object
-                        let service = NetworkService {
-                            name,
-                            service_type: device_type,
-                            address: address.clone(),
-                            port,
-                            discovery_method: "UPnP".to_string(),
-                            details: Some(details_parts.join("\n")),
-                        };
-                        
-                        // Emit the service as an event
-                        let _ = app_handle.emit("service-discovered", &service);
-                        
-                        // Add to our collection
-                        services.push(service);
-
-                        // Add to network map
-                        if address != "Unknown" {
-                            // For UPnP, we generally know it's on HTTP which is TCP
-                            add_to_network_map(
-                                &address,
-                                Some(address.split(':').next().unwrap_or("Unknown").to_string()),
-                                Some(vec![port.unwrap_or(80)]),
-                                None
-                            );
-                        }
+                        answer.add_to(&mut NETWORK_MAP.lock().unwrap());
+                        let _ = sink.emit_event("service-discovered", &answer.service());
+                        answers.push(answer);
                     }
                 }
             }
@@ -550,402 +916,4236 @@ pub async fn discover_upnp_streaming(app_handle: AppHandle) -> Result<Vec<Networ
         }
     }
     
-    // Deduplicate services
-    let mut unique_services = Vec::new();
-    let mut seen_addresses = HashSet::new();
+    Ok(answers)
+}
+
+// An mDNS service type the scanner browses, with a human-friendly label for the UI
+#[derive(Debug, Clone, Serialize)]
+pub struct MdnsServiceType {
+    pub service_type: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    // "high" for services that send credentials in cleartext, None otherwise
+    pub risk: Option<&'static str>,
+    // Infrastructure role of hosts advertising this type ("sleep-proxy", "base-station", ...),
+    // None for services offered by end-user devices
+    pub infrastructure: Option<&'static str>,
+}
+
+const fn mdns_type(service_type: &'static str, name: &'static str, description: &'static str) -> MdnsServiceType {
+    MdnsServiceType { service_type, name, description, risk: None, infrastructure: None }
+}
+
+// Built-in service types browsed during mDNS discovery, in browse order.
+// Labels live here next to the types so the two can't drift apart.
+pub const MDNS_SERVICE_TYPES: &[MdnsServiceType] = &[
+    mdns_type("_http._tcp.local.", "HTTP", "Web servers and device admin pages"),
+    mdns_type("_https._tcp.local.", "HTTPS", "Web servers over TLS"),
+    mdns_type("_ssh._tcp.local.", "SSH", "Secure shell remote login"),
+    mdns_type("_sftp-ssh._tcp.local.", "SFTP", "File transfer over SSH"),
+    MdnsServiceType {
+        risk: Some("high"),
+        ..mdns_type("_telnet._tcp.local.", "Telnet", "Unencrypted remote login")
+    },
+    MdnsServiceType {
+        risk: Some("high"),
+        ..mdns_type("_ftp._tcp.local.", "FTP", "Unencrypted file transfer")
+    },
+    mdns_type("_device-info._tcp.local.", "Device Info", "Hardware model advertised by Apple devices"),
+    mdns_type("_spotify-connect._tcp.local.", "Spotify Connect", "Speakers and players controllable from Spotify"),
+    mdns_type("_airplay._tcp.local.", "AirPlay", "AirPlay (screen/audio streaming)"),
+    mdns_type("_googlecast._tcp.local.", "Google Cast", "Chromecast and Cast-enabled speakers and TVs"),
+    mdns_type("_printer._tcp.local.", "Printer", "LPD network printers"),
+    mdns_type("_ipp._tcp.local.", "IPP Printer", "Internet Printing Protocol printers"),
+    mdns_type("_homekit._tcp.local.", "HomeKit", "HomeKit accessories"),
+    mdns_type("_companion-link._tcp.local.", "Companion Link", "Apple devices signed in to the same account"),
+    mdns_type("_matter._tcp.local.", "Matter", "Commissioned Matter smart home devices"),
+    mdns_type("_matterc._udp.local.", "Matter (commissionable)", "Matter devices waiting to be paired"),
+    MdnsServiceType {
+        infrastructure: Some("sleep-proxy"),
+        ..mdns_type("_sleep-proxy._udp.local.", "Sleep Proxy", "Answers for sleeping Macs and wakes them on demand")
+    },
+    MdnsServiceType {
+        infrastructure: Some("base-station"),
+        ..mdns_type("_airport._tcp.local.", "AirPort", "Apple AirPort base stations")
+    },
+    MdnsServiceType {
+        infrastructure: Some("device-sync"),
+        ..mdns_type("_apple-mobdev2._tcp.local.", "Apple Mobile Device", "Wi-Fi sync and pairing for iPhone and iPad")
+    },
+];
+
+// Local interface an address was seen on: the zone of a scoped IPv6 address,
+// else the interface whose subnet holds it
+pub fn interface_for_address(address: &str) -> Option<String> {
+    if let Some((_, zone)) = address.split_once('%') {
+        return Some(zone.to_string());
+    }
+    let ip: std::net::IpAddr = address.parse().ok()?;
+    get_if_addrs::get_if_addrs().ok()?.into_iter().find_map(|iface| {
+        let on_subnet = match (&iface.addr, ip) {
+            (get_if_addrs::IfAddr::V4(v4), std::net::IpAddr::V4(ip)) => {
+                let mask = u32::from(v4.netmask);
+                u32::from(v4.ip) & mask == u32::from(ip) & mask
+            }
+            (get_if_addrs::IfAddr::V6(v6), std::net::IpAddr::V6(ip)) => {
+                let mask = u128::from(v6.netmask);
+                u128::from(v6.ip) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        };
+        (on_subnet && !iface.is_loopback()).then_some(iface.name)
+    })
+}
+
+// One copy of a resolved mDNS service per interface its addresses came in on,
+// tagged with an "Interface:" detail line. A multi-homed machine can see the
+// same name answered differently on each network; keeping a record per
+// interface stops one answer from hiding the other.
+fn split_by_interface(service: NetworkService, addresses: &[String], interfaces: &BTreeMap<String, String>) -> Vec<NetworkService> {
+    let mut groups: Vec<(Option<String>, &String)> = Vec::new();
+    for address in addresses {
+        let interface = interfaces.get(address).cloned();
+        if !groups.iter().any(|(i, _)| *i == interface) {
+            groups.push((interface, address));
+        }
+    }
     
-    for service in services {
-        let key = (service.address.clone(), service.port);
-        if !seen_addresses.contains(&key) {
-            seen_addresses.insert(key);
-            unique_services.push(service);
+    let with_interface = |mut service: NetworkService, interface: Option<String>| {
+        if let (Some(interface), Some(details)) = (interface, service.details.as_mut()) {
+            details.push_str(&format!("\nInterface: {}", interface));
         }
+        service
+    };
+    if groups.len() <= 1 {
+        let interface = groups.into_iter().next().and_then(|(i, _)| i);
+        return vec![with_interface(service, interface)];
     }
     
-    let result = Ok(unique_services);
-    return result;
+    radar_info!("{} answered on {} interfaces", service.name, groups.len());
+    groups
+        .into_iter()
+        .map(|(interface, address)| with_interface(NetworkService { address: address.clone(), address_family: AddressFamily::of(address), ..service.clone() }, interface))
+        .collect()
 }
 
-// Function to get local network hosts using ARP table
-async fn get_arp_hosts() -> Vec<(String, Option<String>)> {
-    let mut hosts = Vec::new();
-    
-    // Run "arp -a" command to get ARP table
-    let output = Command::new("arp")
-        .arg("-a")
-        .output();
-    
-    match output {
-        Ok(output) => {
-            if output.status.success() {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                for line in stdout.lines() {
-                    // Parse each line of ARP output
-                    if let Some(ip_start) = line.find('(') {
-                        if let Some(ip_end) = line.find(')') {
-                            if ip_start < ip_end {
-                                let hostname = line[0..ip_start].trim().to_string();
-                                let ip = line[ip_start+1..ip_end].trim().to_string();
-                                
-                                // Only add valid IPv4 addresses
-                                if ip.split('.').count() == 4 {
-                                    let hostname = if hostname.is_empty() || hostname == "?" {
-                                        None
-                                    } else {
-                                        Some(hostname)
-                                    };
-                                    
-                                    hosts.push((ip, hostname));
-                                }
-                            }
-                        }
-                    }
+// A DNS-SD name split into its parts, e.g. "Office._ipp._tcp.local." is
+// instance "Office", service "ipp", protocol "tcp", domain "local"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdnsName {
+    pub instance: Option<String>,
+    pub service: String,
+    // "tcp" or "udp"
+    pub protocol: String,
+    pub domain: String,
+}
+
+// Split a name into labels on unescaped dots, so "Living Room\.2" stays one label
+fn dns_labels(name: &str) -> Vec<String> {
+    let mut labels = Vec::new();
+    let mut label = String::new();
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    label.push(escaped);
                 }
             }
+            '.' => labels.push(std::mem::take(&mut label)),
+            _ => label.push(c),
+        }
+    }
+    labels.push(label);
+    labels
+}
+
+// Parse a service type ("_ipp._tcp.local.") or a full service instance name.
+// The protocol is the last "_tcp"/"_udp" label, so those strings inside an
+// instance name can't be mistaken for it. Subtype ("_printer._sub._http._tcp")
+// and meta-query ("_services._dns-sd._udp") prefixes are not instance names.
+pub fn parse_mdns_name(name: &str) -> Option<MdnsName> {
+    let labels = dns_labels(name.trim().trim_end_matches('.'));
+    let protocol_index = labels
+        .iter()
+        .rposition(|l| l.eq_ignore_ascii_case("_tcp") || l.eq_ignore_ascii_case("_udp"))?;
+    let service = labels[..protocol_index].last()?.strip_prefix('_').filter(|s| !s.is_empty())?;
+
+    let domain = labels[protocol_index + 1..].join(".");
+    let prefix = &labels[..protocol_index - 1];
+    let instance = match prefix {
+        [] => None,
+        [.., sub] if sub.eq_ignore_ascii_case("_sub") => None,
+        [only] if only.starts_with('_') => None,
+        _ => Some(prefix.join(".")),
+    };
+
+    Some(MdnsName {
+        instance,
+        service: service.to_string(),
+        protocol: labels[protocol_index][1..].to_lowercase(),
+        domain: if domain.is_empty() { "local".to_string() } else { domain },
+    })
+}
+
+// Multicast TTL used for SSDP searches and our own mDNS queries.
+// 1 keeps queries on the local link; higher values only help on networks with
+// multicast routing between segments (e.g. VLANs bridged by a multicast router).
+static MULTICAST_TTL: AtomicU32 = AtomicU32::new(1);
+
+// Set the multicast TTL, rejecting values outside 1-255
+pub fn set_multicast_ttl(ttl: u32) -> Result<(), String> {
+    if !(1..=255).contains(&ttl) {
+        return Err(format!("Invalid multicast TTL {}: must be between 1 and 255", ttl));
+    }
+    MULTICAST_TTL.store(ttl, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn get_multicast_ttl() -> u32 {
+    MULTICAST_TTL.load(Ordering::Relaxed)
+}
+
+// Hostnames learned from other sources (ARP, TXT records, user input) that we
+// query directly over mDNS, to catch devices that don't advertise any services
+pub static MDNS_CANDIDATE_HOSTNAMES: Lazy<Arc<Mutex<HashSet<String>>>> =
+    Lazy::new(|| Arc::new(Mutex::new(HashSet::new())));
+
+const MDNS_MULTICAST_ADDR: &str = "224.0.0.251:5353";
+const DNS_TYPE_A: u16 = 1;
+const DNS_TYPE_AAAA: u16 = 28;
+const DNS_TYPE_PTR: u16 = 12;
+
+// How long the multicast probe waits for any mDNS answer
+const MULTICAST_PROBE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+// Normalize and remember a candidate hostname, e.g. "NAS" or "nas.local." -> "nas.local"
+pub fn add_mdns_candidate_hostname(hostname: &str) {
+    let name = hostname.trim().trim_end_matches('.').to_lowercase();
+    if name.is_empty() || name.parse::<std::net::IpAddr>().is_ok() {
+        return;
+    }
+    
+    let name = if name.ends_with(".local") {
+        name
+    } else if !name.contains('.') {
+        format!("{}.local", name)
+    } else {
+        // Not an mDNS name, nothing to query
+        return;
+    };
+    
+    MDNS_CANDIDATE_HOSTNAMES.lock().unwrap().insert(name);
+}
+
+// Build an mDNS query with one question per record type for `name`.
+// The QU bit is set on each question so responders answer us directly
+// instead of multicasting the reply to port 5353.
+fn build_mdns_query(name: &str, qtypes: &[u16]) -> Vec<u8> {
+    let mut packet = vec![
+        0x00, 0x00, // Transaction ID (always 0 for mDNS)
+        0x00, 0x00, // Flags: standard query
+    ];
+    packet.extend_from_slice(&(qtypes.len() as u16).to_be_bytes()); // Questions
+    packet.extend_from_slice(&[
+        0x00, 0x00, // Answer RRs
+        0x00, 0x00, // Authority RRs
+        0x00, 0x00, // Additional RRs
+    ]);
+    
+    for &qtype in qtypes {
+        for label in name.split('.').filter(|l| !l.is_empty()) {
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
         }
+        packet.push(0);
+        packet.extend_from_slice(&qtype.to_be_bytes());
+        packet.extend_from_slice(&0x8001u16.to_be_bytes()); // IN class with QU bit
+    }
+    
+    packet
+}
+
+// Build an mDNS query asking for the A and AAAA records of a hostname
+fn build_mdns_host_query(hostname: &str) -> Vec<u8> {
+    build_mdns_query(hostname, &[DNS_TYPE_A, DNS_TYPE_AAAA])
+}
+
+// Check whether multicast works on this network by asking for the DNS-SD
+// service list and waiting for any answer at all. The local machine's own
+// responder counts, so a false result means multicast is blocked or no
+// device on the network speaks mDNS.
+pub async fn probe_multicast() -> bool {
+    let socket = match tokio::net::UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
         Err(e) => {
-            radar_error!("Failed to run arp command: {}", e);
+            radar_warn!("Failed to bind socket for the multicast probe: {}", e);
+            return false;
+        }
+    };
+    if let Err(e) = socket.set_multicast_ttl_v4(get_multicast_ttl()) {
+        radar_debug!("Failed to set multicast TTL on the probe socket: {}", e);
+    }
+    
+    let query = build_mdns_query("_services._dns-sd._udp.local", &[DNS_TYPE_PTR]);
+    if let Err(e) = socket.send_to(&query, MDNS_MULTICAST_ADDR).await {
+        radar_info!("Multicast probe could not be sent: {}", e);
+        return false;
+    }
+    
+    let mut buf = [0u8; 1500];
+    match tokio::time::timeout(MULTICAST_PROBE_TIMEOUT, socket.recv_from(&mut buf)).await {
+        Ok(Ok((_, from))) => {
+            radar_debug!("Multicast probe answered by {}", from);
+            true
+        },
+        Ok(Err(e)) => {
+            radar_info!("Multicast probe failed: {}", e);
+            false
+        },
+        Err(_) => {
+            radar_info!("No answer to the multicast probe within {}ms", MULTICAST_PROBE_TIMEOUT.as_millis());
+            false
         }
     }
+}
+
+// Read a (possibly compressed) DNS name starting at `pos`.
+// Returns the name and the position right after it in the original record.
+fn read_dns_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    let mut jumps = 0;
     
-    // Fallback for systems where arp -a doesn't work well
-    if hosts.is_empty() {
-        // Try to get the local IP address
-        if let Some(local_ip) = get_local_ip() {
-            // Create a network range by replacing the last octet with a range
-            let network_prefix = local_ip.split('.').take(3).collect::<Vec<_>>().join(".");
-            
-            for i in 1..255 {
-                let ip = format!("{}.{}", network_prefix, i);
-                hosts.push((ip, None));
+    loop {
+        let len = *packet.get(pos)? as usize;
+        if len == 0 {
+            pos += 1;
+            break;
+        } else if len & 0xC0 == 0xC0 {
+            // Compression pointer
+            let offset = ((len & 0x3F) << 8) | *packet.get(pos + 1)? as usize;
+            if end.is_none() {
+                end = Some(pos + 2);
+            }
+            jumps += 1;
+            if jumps > 16 {
+                return None;
             }
+            pos = offset;
+        } else {
+            let label = packet.get(pos + 1..pos + 1 + len)?;
+            labels.push(String::from_utf8_lossy(label).to_string());
+            pos += 1 + len;
         }
     }
     
-    hosts
+    Some((labels.join("."), end.unwrap_or(pos)))
 }
 
-// Function to check if a TCP port is open
-async fn check_tcp_port(ip: &str, port: u16, timeout: Duration) -> bool {
-    match tokio::time::timeout(
-        timeout,
-        tokio::net::TcpStream::connect(format!("{}:{}", ip, port))
-    ).await {
-        Ok(Ok(_)) => true,
-        _ => false
+// Extract all A/AAAA records (answers and additionals) from an mDNS response
+fn parse_mdns_address_records(packet: &[u8]) -> Vec<(String, std::net::IpAddr)> {
+    let mut records = Vec::new();
+    if packet.len() < 12 {
+        return records;
     }
+    
+    let count = |i: usize| ((packet[i] as usize) << 8) | packet[i + 1] as usize;
+    let questions = count(4);
+    let resource_records = count(6) + count(8) + count(10);
+    let mut pos = 12;
+    
+    // Skip the question section
+    for _ in 0..questions {
+        match read_dns_name(packet, pos) {
+            Some((_, next)) => pos = next + 4,
+            None => return records,
+        }
+    }
+    
+    for _ in 0..resource_records {
+        let (name, next) = match read_dns_name(packet, pos) {
+            Some(v) => v,
+            None => break,
+        };
+        let header = match packet.get(next..next + 10) {
+            Some(h) => h,
+            None => break,
+        };
+        let rtype = ((header[0] as u16) << 8) | header[1] as u16;
+        let rdlength = ((header[8] as usize) << 8) | header[9] as usize;
+        let rdata = match packet.get(next + 10..next + 10 + rdlength) {
+            Some(d) => d,
+            None => break,
+        };
+        
+        let name = name.to_lowercase();
+        if rtype == DNS_TYPE_A && rdlength == 4 {
+            let octets: [u8; 4] = rdata.try_into().unwrap();
+            records.push((name, std::net::IpAddr::from(octets)));
+        } else if rtype == DNS_TYPE_AAAA && rdlength == 16 {
+            let octets: [u8; 16] = rdata.try_into().unwrap();
+            records.push((name, std::net::IpAddr::from(octets)));
+        }
+        
+        pos = next + 10 + rdlength;
+    }
+    
+    records
 }
 
-// Function to check if a UDP port is open
-async fn check_udp_port(ip: &str, port: u16, timeout: Duration) -> bool {
-    match tokio::time::timeout(
-        timeout,
-        tokio::net::UdpSocket::bind("0.0.0.0:0")
-    ).await {
-        Ok(Ok(socket)) => {
-            match socket.connect(format!("{}:{}", ip, port)).await {
-                Ok(_) => {
-                    // Send a small UDP packet
-                    match socket.send(&[0, 1, 2, 3]).await {
-                        Ok(_) => {
-                            // Try to receive any response
-                            let mut buf = [0; 10];
-                            match tokio::time::timeout(
-                                timeout,
-                                socket.recv(&mut buf)
-                            ).await {
-                                Ok(_) => true, // Got response, port is likely open
-                                Err(_) => {
-                                    // No response, but we could send data, so the port might be open
-                                    // UDP is connectionless, so this is a best effort estimate
-                                    true
-                                }
-                            }
-                        },
-                        Err(_) => false
-                    }
-                },
-                Err(_) => false
+// Query candidate .local hostnames directly for A/AAAA records and return the
+// ones that answer at a new address
+async fn resolve_candidate_mdns_hosts(known_addresses: &HashSet<String>, timeout: Duration) -> Vec<MdnsHostAnswer> {
+    let candidates: Vec<String> = MDNS_CANDIDATE_HOSTNAMES.lock().unwrap().iter().cloned().collect();
+    if candidates.is_empty() {
+        return Vec::new();
+    }
+    
+    radar_info!("Querying {} candidate hostnames directly over mDNS", candidates.len());
+    
+    let socket = match tokio::net::UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
+        Err(e) => {
+            radar_warn!("Failed to bind socket for mDNS host queries: {}", e);
+            return Vec::new();
+        }
+    };
+    if let Err(e) = socket.set_multicast_ttl_v4(get_multicast_ttl()) {
+        radar_debug!("Failed to set multicast TTL on mDNS query socket: {}", e);
+    }
+    
+    for hostname in &candidates {
+        if let Err(e) = socket.send_to(&build_mdns_host_query(hostname), MDNS_MULTICAST_ADDR).await {
+            radar_debug!("Failed to send mDNS host query for {}: {}", hostname, e);
+        }
+    }
+    
+    let mut resolved: HashMap<String, HashSet<std::net::IpAddr>> = HashMap::new();
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut buf = [0u8; 1500];
+    
+    while let Ok(Ok((size, _))) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        for (name, addr) in parse_mdns_address_records(&buf[..size]) {
+            if candidates.contains(&name) {
+                resolved.entry(name).or_default().insert(addr);
             }
-        },
-        _ => false
+        }
+    }
+    
+    let mut answers = Vec::new();
+    for (hostname, addrs) in resolved {
+        // Prefer IPv4 as the primary address, like the rest of the scanner
+        let mut addrs: Vec<std::net::IpAddr> = addrs.into_iter().collect();
+        addrs.sort_by_key(|a| a.is_ipv6());
+        
+        if addrs.iter().any(|a| known_addresses.contains(&a.to_string())) {
+            continue;
+        }
+        
+        radar_debug!("Resolved {} to {} via direct mDNS query", hostname, addrs[0]);
+        answers.push(MdnsHostAnswer { hostname, addresses: addrs.iter().map(|a| a.to_string()).collect() });
     }
+    
+    answers
 }
 
-// Common ports to scan
-const COMMON_TCP_PORTS: &[u16] = &[
-    20, 21, 22, 23, 25, 53, 80, 110, 443, 587, 993, 995, 3306, 3389, 5432, 8080, 8443, 
-];
-
-const COMMON_UDP_PORTS: &[u16] = &[
-    53, 67, 68, 69, 123, 161, 162, 1900, 5353,
-];
+// A hostname answered by a direct mDNS A/AAAA query, IPv4 addresses first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MdnsHostAnswer {
+    pub hostname: String,
+    pub addresses: Vec<String>,
+}
 
-// Get the local IP address
-fn get_local_ip() -> Option<String> {
-    // Try to get a socket connection to a public address
-    // This won't actually connect but will determine local interface
-    match std::net::UdpSocket::bind("0.0.0.0:0") {
-        Ok(socket) => {
-            match socket.connect("8.8.8.8:80") {
-                Ok(_) => {
-                    match socket.local_addr() {
-                        Ok(addr) => {
-                            Some(addr.ip().to_string())
-                        },
-                        Err(_) => None
-                    }
-                },
-                Err(_) => None
+impl MdnsHostAnswer {
+    fn add_to(&self, map: &mut NetworkMap) {
+        let addresses: Vec<&str> = self.addresses.iter().map(String::as_str).collect();
+        map.add_ports(&addresses, Some(self.hostname.clone()), None, None);
+    }
+    
+    fn service(&self) -> NetworkService {
+        let address = self.addresses.first().cloned().unwrap_or_else(|| "Unknown".to_string());
+        let mut details_parts = vec![
+            format!("Host: {}", self.hostname),
+            "Resolved via direct mDNS A/AAAA query".to_string(),
+        ];
+        if self.addresses.len() > 1 {
+            details_parts.push("\nAll Addresses:".to_string());
+            for addr in &self.addresses {
+                details_parts.push(format!("  {}", addr));
             }
-        },
-        Err(_) => None
+        }
+        
+        NetworkService {
+            name: self.hostname.trim_end_matches(".local").to_string(),
+            service_type: "host".to_string(),
+            address_family: AddressFamily::of(&address),
+            address,
+            port: None,
+            discovery_method: METHOD_MDNS.to_string(),
+            details: Some(details_parts.join("\n")),
+            txt_records: BTreeMap::new(),
+            port_state: None,
+            possibly_rate_limited: false,
+        }
     }
 }
 
-pub async fn scan_local_network(app_handle: &AppHandle) -> Vec<NetworkService> {
-    let mut services = Vec::new();
-    radar_info!("Starting local network scan");
+// One IPv4 host in the ARP table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ArpEntry {
+    pub ip: String,
+    pub hostname: Option<String>,
+    // Normalized MAC address, unless the entry is still incomplete
+    pub mac: Option<String>,
+}
+
+// Read the hosts currently in the ARP table, or nothing if it can't be read
+pub(crate) fn read_arp_table() -> Vec<ArpEntry> {
+    try_read_arp_table().unwrap_or_else(|e| {
+        radar_error!("{}", e);
+        Vec::new()
+    })
+}
+
+// Read the ARP table with `arp -a`
+fn try_read_arp_table() -> Result<Vec<ArpEntry>, String> {
+    let output = Command::new("arp")
+        .arg("-a")
+        .output()
+        .map_err(|e| format!("Failed to run arp command: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("arp -a failed ({})", output.status));
+    }
     
-    // Get hosts from ARP table
-    let arp_hosts = get_arp_hosts().await;
-    radar_info!("Found {} hosts in ARP table", arp_hosts.len());
+    let mut hosts = Vec::new();
+    let stdout = String::from_utf8_lossy(&output.stdout);
     
-    // Add all hosts to network map initially
-    for (ip, hostname) in &arp_hosts {
-        add_to_network_map(ip, hostname.clone(), None, None);
-        
-        // If we don't have a hostname yet, try to look it up
-        if hostname.is_none() {
-            if let Some(resolved_hostname) = get_hostname_from_ip(ip).await {
-                add_to_network_map(ip, Some(resolved_hostname), None, None);
+    // Windows lists bare columns without host names
+    #[cfg(target_os = "windows")]
+    hosts.extend(
+        parse_windows_arp(&stdout).into_iter().map(|(ip, mac)| ArpEntry { ip, hostname: None, mac: Some(mac) }),
+    );
+    
+    for entry in stdout.lines().filter_map(parse_arp_line) {
+        if let Some(hostname) = &entry.hostname {
+            if hostname.trim_end_matches('.').ends_with(".local") {
+                add_mdns_candidate_hostname(hostname);
             }
         }
+        hosts.push(entry);
     }
+    Ok(hosts)
+}
+
+// Parse one line of Unix `arp -a` output ("host (192.168.1.10) at aa:bb:cc:dd:ee:ff on en0")
+fn parse_arp_line(line: &str) -> Option<ArpEntry> {
+    let (hostname, rest) = line.split_once('(')?;
+    let (ip, _) = rest.split_once(')')?;
+    let ip = ip.trim();
     
-    // Create scanner tasks
-    let mut join_handles = Vec::new();
+    // Only add valid IPv4 addresses
+    if ip.split('.').count() != 4 {
+        return None;
+    }
     
-    // Scan each host for open ports
-    for (ip, hostname) in arp_hosts {
-        let app_handle_clone = app_handle.clone();
-        let ip_clone = ip.clone();
-        
-        // Spawn a separate task for each host
-        let handle = tokio::spawn(async move {
-            let mut open_tcp_ports = Vec::new();
-            let mut open_udp_ports = Vec::new();
-            
-            // Scan common TCP ports
-            for &port in COMMON_TCP_PORTS {
-                if check_tcp_port(&ip_clone, port, Duration::from_millis(500)).await {
-                    radar_debug!("Found open TCP port {}:{}", ip_clone, port);
-                    open_tcp_ports.push(port);
-                    
-                    // Create a service for each open port
-                    let service_type = match port {
-                        20 | 21 => "ftp",
-                        22 => "ssh",
-                        23 => "telnet",
-                        25 | 587 => "smtp",
-                        53 => "dns",
-                        80 | 8080 => "http",
-                        110 => "pop3",
-                        443 | 8443 => "https",
-                        993 => "imaps",
-                        995 => "pop3s",
-                        3306 => "mysql",
-                        3389 => "rdp",
-                        5432 => "postgresql",
-                        _ => "unknown",
-                    }.to_string();
-                    
-                    let service = NetworkService {
-                        name: format!("{} ({}) on port {}", service_type.to_uppercase(), ip_clone, port),
-                        service_type: service_type.clone(),
-                        address: ip_clone.clone(),
-                        port: Some(port),
-                        discovery_method: "Network Scan".to_string(),
-                        details: Some(format!("TCP service discovered on {}:{}\nType: {}", ip_clone, port, service_type)),
-                    };
-                    
-                    // Emit the service
-                    let _ = app_handle_clone.emit("service-discovered", &service);
-                }
-            }
-            
-            // Scan common UDP ports
-            for &port in COMMON_UDP_PORTS {
-                if check_udp_port(&ip_clone, port, Duration::from_millis(500)).await {
-                    radar_debug!("Found open UDP port {}:{}", ip_clone, port);
-                    open_udp_ports.push(port);
-                    
-                    // Create a service for each open port
-                    let service_type = match port {
-                        53 => "dns",
-                        67 | 68 => "dhcp",
-                        69 => "tftp",
-                        123 => "ntp",
-                        161 | 162 => "snmp",
-                        1900 => "upnp",
-                        5353 => "mdns",
-                        _ => "unknown",
-                    }.to_string();
-                    
-    let service = NetworkService {
-                        name: format!("{} ({}) on port {}/udp", service_type.to_uppercase(), ip_clone, port),
-                        service_type: service_type.clone(),
-                        address: ip_clone.clone(),
-                        port: Some(port),
-                        discovery_method: "Network Scan".to_string(),
-                        details: Some(format!("UDP service discovered on {}:{}\nType: {}", ip_clone, port, service_type)),
-                    };
-                    
-                    // Emit the service
-                    let _ = app_handle_clone.emit("service-discovered", &service);
-                }
+    // Some systems print the IP itself where no reverse name exists; that's
+    // no hostname, so leave it to reverse DNS
+    let hostname = hostname.trim();
+    let hostname = if hostname.is_empty() || hostname == "?" || hostname.parse::<std::net::IpAddr>().is_ok() {
+        None
+    } else {
+        Some(hostname.to_string())
+    };
+    // Unresolved entries read "at (incomplete)"
+    let mac = rest
+        .split_once(" at ")
+        .and_then(|(_, rest)| rest.split_whitespace().next())
+        .and_then(crate::inventory::normalize_mac);
+    Some(ArpEntry { ip: ip.to_string(), hostname, mac })
+}
+
+// Parse Windows `arp -a` output into (IP, normalized MAC) pairs:
+//
+//   Interface: 192.168.1.5 --- 0x4
+//     Internet Address      Physical Address      Type
+//     192.168.1.1           00-11-22-33-44-55     dynamic
+//
+// Interface and header lines don't start with an address and are skipped, as
+// are broadcast (ff-ff-ff-ff-ff-ff) and multicast entries, which aren't hosts.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn parse_windows_arp(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let ip: std::net::Ipv4Addr = columns.next()?.parse().ok()?;
+            let mac = crate::inventory::normalize_mac(columns.next()?)?;
+            if ip.is_multicast() || ip.is_broadcast() || mac == "ff:ff:ff:ff:ff:ff" {
+                return None;
             }
-            
-            // Update network map with discovered ports
-            add_to_network_map(&ip_clone, None, Some(open_tcp_ports), Some(open_udp_ports));
-            
-            // Return the hostname and IP
-            (ip_clone, hostname)
-        });
-        
-        join_handles.push(handle);
+            Some((ip.to_string(), mac))
+        })
+        .collect()
+}
+
+// IPv4 addresses of each MAC address in the ARP table, keyed by normalized MAC.
+// A device can answer on several addresses; they're listed lowest first.
+pub(crate) fn read_arp_macs() -> HashMap<String, Vec<String>> {
+    let mut macs: HashMap<String, Vec<String>> = HashMap::new();
+    for entry in read_arp_table() {
+        let Some(mac) = entry.mac else {
+            continue;
+        };
+        let addresses = macs.entry(mac).or_default();
+        if !addresses.contains(&entry.ip) {
+            addresses.push(entry.ip);
+        }
+    }
+    for addresses in macs.values_mut() {
+        addresses.sort_by(|a, b| compare_addresses(a, b));
+    }
+    macs
+}
+
+// The MAC address at each address of a `read_arp_macs` table
+fn macs_by_address(macs: &HashMap<String, Vec<String>>) -> HashMap<String, String> {
+    macs.iter()
+        .flat_map(|(mac, addresses)| addresses.iter().map(move |address| (address.clone(), mac.clone())))
+        .collect()
+}
+
+// A host from the IPv6 neighbor (NDP) cache
+#[derive(Debug, Clone)]
+struct Ipv6Neighbor {
+    address: String,
+    mac: Option<String>,
+}
+
+// Drop an interface zone ("fe80::1%en0" -> "fe80::1") and parse the address
+fn parse_ipv6(address: &str) -> Option<std::net::Ipv6Addr> {
+    address.split('%').next()?.parse().ok()
+}
+
+fn is_link_local(ip: &std::net::Ipv6Addr) -> bool {
+    ip.segments()[0] & 0xffc0 == 0xfe80
+}
+
+// Address to connect to for an IPv6 host seen on `interface`. Link-local
+// addresses are only reachable with the interface as a zone ("fe80::1%en0"),
+// so they need one; other addresses are returned as-is.
+pub fn scoped_address(address: &str, interface: Option<&str>) -> Option<String> {
+    let ip = parse_ipv6(address)?;
+    if !is_link_local(&ip) {
+        return Some(ip.to_string());
+    }
+    let zone = address.split_once('%').map(|(_, zone)| zone).or(interface)?;
+    (!zone.is_empty()).then(|| format!("{}%{}", ip, zone))
+}
+
+// One line of `ip -6 neigh`, `ndp -an` or `netsh interface ipv6 show neighbors`.
+// Entries that never resolved (FAILED, incomplete, unreachable) are skipped.
+fn parse_neighbor_line(line: &str) -> Option<Ipv6Neighbor> {
+    let lower = line.to_lowercase();
+    if lower.contains("failed") || lower.contains("incomplete") || lower.contains("unreachable") {
+        return None;
     }
     
-    // Wait for all scan tasks to complete
-    for handle in join_handles {
-        if let Ok((ip, hostname)) = handle.await {
-            // Create a basic service for each host even if no ports were found
-            let service = NetworkService {
-                name: hostname.unwrap_or_else(|| format!("Device at {}", ip)),
-        service_type: "host".to_string(),
-                address: ip.clone(),
-        port: None,
-        discovery_method: "Network Scan".to_string(),
-                details: Some(format!("Host discovered on network at {}", ip)),
-            };
-            
-            services.push(service);
+    let mut tokens = line.split_whitespace();
+    let address = tokens.next()?;
+    parse_ipv6(address)?;
+    // Linux names the interface after "dev"; macOS writes it as the zone already
+    let interface = line.split_once(" dev ").and_then(|(_, rest)| rest.split_whitespace().next());
+    let mac = match line.split_once(" lladdr ") {
+        // Linux: "2001:db8::5 dev eth0 lladdr 00:11:22:33:44:55 REACHABLE"
+        Some((_, rest)) => rest.split_whitespace().next(),
+        // macOS and Windows put the link-layer address right after the IP
+        None => tokens.next(),
+    }
+    .and_then(crate::inventory::normalize_mac);
+    
+    // Link-local entries without a known interface stay unscoped and aren't scanned
+    let address = scoped_address(address, interface).unwrap_or_else(|| address.to_string());
+    Some(Ipv6Neighbor { address, mac })
+}
+
+// Read the IPv6 neighbor cache
+fn read_ndp_neighbors() -> Vec<Ipv6Neighbor> {
+    #[cfg(target_os = "macos")]
+    let output = Command::new("ndp").arg("-an").output();
+    #[cfg(target_os = "windows")]
+    let output = Command::new("netsh").args(["interface", "ipv6", "show", "neighbors"]).output();
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let output = Command::new("ip").args(["-6", "neigh", "show"]).output();
+    
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(parse_neighbor_line)
+            .collect(),
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            radar_warn!("Failed to read the IPv6 neighbor cache: {}", e);
+            Vec::new()
         }
     }
+}
+
+const ALL_NODES_PING_TIMEOUT: Duration = Duration::from_secs(4);
+
+// Ping ff02::1 (all nodes) on every IPv6 interface. Responders end up in the
+// neighbor cache; their addresses are also returned in case the cache misses them.
+async fn ping_all_nodes() -> HashSet<String> {
+    let mut interfaces: Vec<String> = get_if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|iface| !iface.is_loopback() && iface.ip().is_ipv6())
+        .map(|iface| iface.name)
+        .collect();
+    interfaces.sort();
+    interfaces.dedup();
     
-    // Get all entries from network map
-    let network_map = NETWORK_MAP.lock().unwrap();
-    for (ip, host) in network_map.iter() {
-        // For each host, create a service with the collected information
-        if !host.tcp_ports.is_empty() || !host.udp_ports.is_empty() {
-            let mut details = format!("Host: {}\n", ip);
-            
-            if let Some(hostname) = &host.hostname {
-                details.push_str(&format!("Hostname: {}\n", hostname));
-            }
-            
-            if !host.tcp_ports.is_empty() {
-                details.push_str("\nOpen TCP ports:\n");
-                for port in &host.tcp_ports {
-                    details.push_str(&format!("  {}\n", port));
-                }
+    let pings = interfaces.into_iter().map(|iface| async move {
+        let target = format!("ff02::1%{}", iface);
+        #[cfg(target_os = "macos")]
+        let mut command = tokio::process::Command::new("ping6");
+        #[cfg(target_os = "macos")]
+        command.args(["-c", "2", &target]);
+        #[cfg(not(target_os = "macos"))]
+        let mut command = tokio::process::Command::new("ping");
+        #[cfg(target_os = "windows")]
+        command.args(["-6", "-n", "2", &target]);
+        #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+        command.args(["-6", "-c", "2", &target]);
+        command.kill_on_drop(true);
+        
+        let output = match tokio::time::timeout(ALL_NODES_PING_TIMEOUT, command.output()).await {
+            Ok(Ok(output)) => String::from_utf8_lossy(&output.stdout).into_owned(),
+            _ => String::new(),
+        };
+        (iface, output)
+    });
+    
+    // "64 bytes from fe80::1%en0: icmp_seq=0 hlim=64 time=1.2 ms"
+    futures::future::join_all(pings)
+        .await
+        .iter()
+        .flat_map(|(iface, output)| output.lines().map(move |line| (iface, line)))
+        .filter_map(|(iface, line)| {
+            let from = line.split_once(" from ")?.1.split_whitespace().next()?;
+            let from = from.strip_suffix(':').unwrap_or(from);
+            scoped_address(from, Some(iface))
+        })
+        .collect()
+}
+
+// Find IPv6 hosts and port-scan them. Sweeping an IPv6 prefix is infeasible
+// (a /64 alone has 2^64 addresses), so discovery is neighbor-based: ping ff02::1
+// to populate the NDP cache, then read it. Link-local addresses are scanned
+// through the interface they were seen on ("fe80::1%en0"). Every neighbor
+// with a MAC that is also in the ARP table is mapped to that IPv4 address, so
+// dual-stack devices can be shown as one. Returns what the scan observed and
+// the IPv6 -> IPv4 address map.
+async fn scan_ipv6_neighbors<S: ServiceSink>(
+    sink: &S,
+    config: &ScanConfig,
+    cancel: &CancellationToken,
+) -> (SweepCapture, HashMap<String, String>) {
+    radar_info!("Starting IPv6 neighbor scan");
+    
+    let responders = ping_all_nodes().await;
+    let mut neighbors = read_ndp_neighbors();
+    for address in responders {
+        if !neighbors.iter().any(|n| n.address == address) {
+            neighbors.push(Ipv6Neighbor { address, mac: None });
+        }
+    }
+    radar_info!("Found {} IPv6 neighbors", neighbors.len());
+    
+    let arp_macs = read_arp_macs();
+    let ipv6_to_ipv4: HashMap<String, String> = neighbors
+        .iter()
+        .filter_map(|n| Some((n.address.clone(), arp_macs.get(n.mac.as_ref()?)?.first()?.clone())))
+        .collect();
+    
+    let ports = Arc::new(config.probe_ports());
+    let port_timeout = config.port_timeout();
+    let limiter = Arc::new(ProbeLimiter::new(config, cancel));
+    
+    let mut tasks = ScanTasks::new();
+    for neighbor in neighbors {
+        let Some(ip) = parse_ipv6(&neighbor.address) else {
+            continue;
+        };
+        // Multicast addresses, and link-local (fe80::/10) ones without a zone, aren't scanned
+        if (is_link_local(&ip) && !neighbor.address.contains('%')) || ip.is_multicast() {
+            continue;
+        }
+        
+        let sink_clone = sink.clone();
+        let ports = ports.clone();
+        let limiter = limiter.clone();
+        tasks.spawn(async move {
+            let address = neighbor.address;
+            let scan = scan_host(&sink_clone, &address, &ports, port_timeout, &limiter).await;
+            let hostname = get_hostname_from_ip(&address).await;
+            add_to_network_map(&[&address], hostname.clone(), Some(scan.tcp_ports), Some(scan.udp_ports));
+            
+            let host = HostObservation {
+                address,
+                hostname,
+                mac: neighbor.mac,
+                responds_to_ping: false,
+                possibly_rate_limited: scan.possibly_rate_limited,
+            };
+            (host, scan.observations)
+        });
+    }
+    
+    let mut capture = SweepCapture::default();
+    while let Some(result) = tasks.join_next().await {
+        if let Ok((host, observations)) = result {
+            capture.hosts.push(host);
+            capture.ports.extend(observations);
+        }
+    }
+    
+    radar_info!("IPv6 neighbor scan complete, {} hosts, {} dual-stack addresses",
+        capture.hosts.len(), ipv6_to_ipv4.len());
+    (capture, ipv6_to_ipv4)
+}
+
+// Fold IPv6 entries into the IPv4 entry of the same device and list every
+// IPv6 address of a device on its IPv4 entry
+fn merge_dual_stack(
+    consolidated: Vec<ConsolidatedService>,
+    ipv6_to_ipv4: &HashMap<String, String>,
+) -> Vec<ConsolidatedService> {
+    let addresses: HashSet<String> = consolidated.iter().map(|s| s.address.clone()).collect();
+    let (ipv6_entries, mut merged): (Vec<_>, Vec<_>) = consolidated
+        .into_iter()
+        .partition(|s| ipv6_to_ipv4.get(&s.address).is_some_and(|ipv4| addresses.contains(ipv4)));
+    
+    for service in merged.iter_mut() {
+        for (ipv6, ipv4) in ipv6_to_ipv4 {
+            if *ipv4 == service.address && !service.ipv6_addresses.contains(ipv6) {
+                service.ipv6_addresses.push(ipv6.clone());
+            }
+        }
+        service.ipv6_addresses.sort();
+    }
+    
+    for ipv6_entry in ipv6_entries {
+        let ipv4 = &ipv6_to_ipv4[&ipv6_entry.address];
+        let Some(target) = merged.iter_mut().find(|s| s.address == *ipv4) else {
+            continue;
+        };
+        absorb_service(target, ipv6_entry);
+    }
+    
+    merged
+}
+
+// Root device UUID from a service's details ("UUID: ..."), lowercased
+fn service_uuid(service: &NetworkService) -> Option<String> {
+    service.details.as_deref()?.lines().find_map(|line| {
+        let uuid = line.strip_prefix("UUID:")?.trim();
+        (!uuid.is_empty()).then(|| uuid.to_lowercase())
+    })
+}
+
+// Fold another consolidated entry for the same device into `target`
+fn absorb_service(target: &mut ConsolidatedService, other: ConsolidatedService) {
+    for address in other.addresses {
+        if !target.addresses.contains(&address) {
+            target.addresses.push(address);
+        }
+    }
+    for (port, name) in other.open_ports {
+        target.open_ports.entry(port).or_insert(name);
+    }
+    for service_type in other.service_types {
+        if !target.service_types.contains(&service_type) {
+            target.service_types.push(service_type);
+        }
+    }
+    for method in other.discovery_methods {
+        if !target.discovery_methods.contains(&method) {
+            target.discovery_methods.push(method);
+        }
+    }
+    for (key, value) in other.txt_records {
+        target.txt_records.entry(key).or_insert(value);
+    }
+    if target.hostname.is_none() {
+        target.hostname = other.hostname;
+    }
+    if target.mac_address.is_none() {
+        target.mac_address = other.mac_address;
+    }
+    target.possibly_rate_limited |= other.possibly_rate_limited;
+    target.first_seen = earliest(target.first_seen, other.first_seen);
+    target.last_seen = target.last_seen.max(other.last_seen);
+    crate::enricher::apply_enrichers(target);
+}
+
+// Function to get local network hosts using ARP table, and whether they are a
+// blind sweep because the ARP table was empty. With `interfaces`, only ARP
+// entries on those interfaces' subnets count. An unreadable ARP table is
+// reported as a scan error and swept blindly.
+async fn get_arp_hosts<S: ServiceSink>(sink: &S, interfaces: Option<&[String]>) -> (Vec<(String, Option<String>)>, bool) {
+    let mut hosts: Vec<(String, Option<String>)> = stage_services(sink, "ARP", try_read_arp_table())
+        .into_iter()
+        .map(|entry| (entry.ip, entry.hostname))
+        .collect();
+    if let Some(names) = interfaces {
+        let networks = interface_networks(names);
+        hosts.retain(|(ip, _)| {
+            ip.parse::<std::net::Ipv4Addr>()
+                .is_ok_and(|ip| networks.iter().any(|(network, mask)| u32::from(ip) & mask == *network))
+        });
+    }
+    let blind_sweep = hosts.is_empty();
+    
+    // Fallback for systems where arp -a doesn't work well
+    if blind_sweep {
+        hosts = local_subnet_addresses(interfaces).into_iter().map(|ip| (ip, None)).collect();
+    }
+    
+    (hosts, blind_sweep)
+}
+
+// Names of the non-loopback interfaces with a routable IPv4 address
+fn ipv4_interface_names() -> Vec<String> {
+    let mut names: Vec<String> = get_if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|iface| matches!(&iface.addr, get_if_addrs::IfAddr::V4(v4) if !v4.ip.is_loopback() && !v4.ip.is_link_local()))
+        .map(|iface| iface.name)
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+// Network address and netmask of each named interface's IPv4 subnet
+fn interface_networks(names: &[String]) -> Vec<(u32, u32)> {
+    get_if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|iface| names.contains(&iface.name))
+        .filter_map(|iface| match iface.addr {
+            get_if_addrs::IfAddr::V4(v4) if !v4.ip.is_loopback() => {
+                let mask = u32::from(v4.netmask);
+                Some((u32::from(v4.ip) & mask, mask))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// Ranges to sweep on the local interfaces, or only the named ones: each
+// interface's whole subnet when it's small enough (e.g. a /22), otherwise the
+// /24 around its address. Interfaces on the same subnet share one range.
+fn local_sweep_ranges(interfaces: Option<&[String]>) -> Vec<String> {
+    let mut ranges: Vec<String> = get_if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|iface| interfaces.is_none_or(|names| names.contains(&iface.name)))
+        .filter_map(|iface| match iface.addr {
+            get_if_addrs::IfAddr::V4(v4) if !v4.ip.is_loopback() && !v4.ip.is_link_local() => {
+                let prefix = u32::from(v4.netmask).count_ones() as u8;
+                let prefix = if prefix >= 22 { prefix } else { 24 };
+                let mask = u32::MAX << (32 - prefix as u32);
+                Some(format!("{}/{}", std::net::Ipv4Addr::from(u32::from(v4.ip) & mask), prefix))
+            }
+            _ => None,
+        })
+        .collect();
+    ranges.sort();
+    ranges.dedup();
+    ranges
+}
+
+// Every address of the local subnets (see local_sweep_ranges), each once even
+// where ranges overlap. Without any usable interface, a /24 is guessed around
+// the local IP address.
+fn local_subnet_addresses(interfaces: Option<&[String]>) -> Vec<String> {
+    let ranges = local_sweep_ranges(interfaces);
+    if !ranges.is_empty() {
+        radar_debug!("Local sweep ranges: {}", ranges.join(", "));
+        let mut seen = HashSet::new();
+        return ranges
+            .iter()
+            .flat_map(|range| expand_cidr(range).unwrap_or_default())
+            .filter(|ip| seen.insert(ip.clone()))
+            .collect();
+    }
+    if interfaces.is_some() {
+        return Vec::new();
+    }
+    
+    // Create a network range by replacing the last octet with a range
+    let Some(local_ip) = get_local_ip() else {
+        return Vec::new();
+    };
+    let network_prefix = local_ip.split('.').take(3).collect::<Vec<_>>().join(".");
+    (1..255).map(|i| format!("{}.{}", network_prefix, i)).collect()
+}
+
+// How long to wait for each echo reply, and how many pings run at once
+const PING_TIMEOUT: Duration = Duration::from_secs(1);
+const PING_SWEEP_CONCURRENCY: usize = 64;
+
+// Send one echo request with the system `ping`, which (unlike a raw ICMP
+// socket) needs no extra privileges. Err when `ping` couldn't be run.
+async fn ping_host(ip: &str) -> Result<bool, String> {
+    let mut command = tokio::process::Command::new("ping");
+    #[cfg(target_os = "windows")]
+    command.args(["-n", "1", "-w", "1000", ip]);
+    #[cfg(target_os = "macos")]
+    command.args(["-c", "1", "-W", "1000", ip]);
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    command.args(["-c", "1", "-W", "1", ip]);
+    command.kill_on_drop(true).stdin(std::process::Stdio::null()).stderr(std::process::Stdio::null());
+    
+    // Allow for process startup on top of the reply timeout
+    match tokio::time::timeout(PING_TIMEOUT * 2, command.output()).await {
+        // Windows' ping succeeds on "Destination host unreachable" too, so look for an actual reply
+        #[cfg(target_os = "windows")]
+        Ok(Ok(output)) => Ok(output.status.success() && String::from_utf8_lossy(&output.stdout).contains("TTL=")),
+        #[cfg(not(target_os = "windows"))]
+        Ok(Ok(output)) => Ok(output.status.success()),
+        Ok(Err(e)) => Err(format!("Failed to run ping: {}", e)),
+        Err(_) => Ok(false),
+    }
+}
+
+// Ping every address of the local subnets, returning those that replied. Hosts
+// the ARP table has forgotten still answer pings unless a firewall drops them.
+// Err when no ping could be sent at all.
+async fn ping_sweep(cancel: &CancellationToken, interfaces: Option<&[String]>) -> Result<HashSet<String>, String> {
+    let addresses = local_subnet_addresses(interfaces);
+    radar_info!("Ping sweeping {} addresses", addresses.len());
+    
+    let pings = futures::stream::iter(addresses)
+        .map(|ip| async move {
+            let replied = ping_host(&ip).await;
+            (ip, replied)
+        })
+        .buffer_unordered(PING_SWEEP_CONCURRENCY)
+        .collect::<Vec<(String, Result<bool, String>)>>();
+    
+    let results = tokio::select! {
+        _ = cancel.cancelled() => return Ok(HashSet::new()),
+        results = pings => results,
+    };
+    let mut responders = HashSet::new();
+    let mut failure = None;
+    let mut sent = false;
+    for (ip, replied) in results {
+        match replied {
+            Ok(true) => {
+                responders.insert(ip);
+                sent = true;
             }
+            Ok(false) => sent = true,
+            Err(e) => failure = Some(e),
+        }
+    }
+    match failure {
+        Some(e) if !sent => Err(e),
+        _ => Ok(responders),
+    }
+}
+
+// Outcome of a single port probe
+enum PortProbe<T> {
+    Open(T),
+    // Refused or unreachable before the timeout
+    Closed,
+    // No answer at all within the timeout
+    TimedOut,
+    // The OS ran out of sockets/file descriptors before the probe could run
+    Exhausted,
+}
+
+// Whether an I/O error means the process ran out of sockets (EMFILE/ENFILE)
+fn is_resource_exhaustion(e: &std::io::Error) -> bool {
+    match e.raw_os_error() {
+        #[cfg(unix)]
+        Some(code) => code == libc::EMFILE || code == libc::ENFILE,
+        #[cfg(windows)]
+        Some(code) => code == 10024, // WSAEMFILE
+        _ => false,
+    }
+}
+
+// Connect to a TCP port, returning the open connection so probes can reuse it.
+// Ports that refused are told apart from ones that never answered.
+async fn open_tcp_port(ip: &str, port: u16, timeout: Duration) -> PortProbe<tokio::net::TcpStream> {
+    let Ok(target) = connect_target(ip, port) else {
+        return PortProbe::Closed;
+    };
+    match tokio::time::timeout(
+        timeout,
+        tokio::net::TcpStream::connect(target)
+    ).await {
+        Ok(Ok(stream)) => PortProbe::Open(stream),
+        Ok(Err(e)) if is_resource_exhaustion(&e) => PortProbe::Exhausted,
+        Ok(Err(_)) => PortProbe::Closed,
+        Err(_) => PortProbe::TimedOut,
+    }
+}
+
+// Whether a TCP port accepts connections; the connection is closed right away
+pub(crate) async fn check_tcp_port(ip: &str, port: u16, timeout: Duration) -> bool {
+    matches!(open_tcp_port(ip, port, timeout).await, PortProbe::Open(_))
+}
+
+// What a port probe learned about an answering port. Silence proves nothing
+// for UDP: the service may have ignored the probe, or a firewall may have
+// dropped it. A port that refused (ICMP port unreachable) is `PortProbe::Closed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortState {
+    // A reply came back
+    Open,
+    // No reply before the timeout (nmap's "open|filtered")
+    Filtered,
+}
+
+impl PortState {
+    // How the state reads in logs ("open|filtered")
+    pub fn label(self) -> &'static str {
+        match self {
+            PortState::Open => "open",
+            PortState::Filtered => "open|filtered",
+        }
+    }
+}
+
+// SNMPv1 GetRequest for sysDescr.0 with the "public" community
+const SNMP_SYSDESCR_PROBE: &[u8] = &[
+    0x30, 0x26, 0x02, 0x01, 0x00, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c',
+    0xa0, 0x19, 0x02, 0x01, 0x01, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00,
+    0x30, 0x0e, 0x30, 0x0c, 0x06, 0x08, 0x2b, 0x06, 0x01, 0x02, 0x01, 0x01, 0x01, 0x00, 0x05, 0x00,
+];
+
+// A request the service on a well-known UDP port answers, so a real responder
+// shows up as open instead of filtered
+fn builtin_udp_probe(port: u16) -> Option<Vec<u8>> {
+    match port {
+        // DNS query for the root NS records, recursion desired
+        53 => Some(vec![
+            0x52, 0x44, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x02, 0x00, 0x01,
+        ]),
+        // NTPv3 client request
+        123 => {
+            let mut request = vec![0u8; 48];
+            request[0] = 0x1b;
+            Some(request)
+        },
+        161 => Some(SNMP_SYSDESCR_PROBE.to_vec()),
+        // Unicast DNS-SD service list query, answered directly to our port
+        5353 => Some(build_mdns_query("_services._dns-sd._udp.local", &[DNS_TYPE_PTR])),
+        _ => None,
+    }
+}
+
+// Probe a UDP port. A custom probe's payload is sent and a reply must start
+// with its expected prefix; otherwise the built-in request for the port, or a
+// few bytes, is sent and any reply counts. Only a reply makes the port open.
+async fn check_udp_port(ip: &str, port: u16, timeout: Duration, probe: Option<&UdpPayload>) -> PortProbe<PortState> {
+    let Ok(target) = connect_target(ip, port) else {
+        return PortProbe::Closed;
+    };
+    let bind_address = if target.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+    let socket = match tokio::time::timeout(timeout, tokio::net::UdpSocket::bind(bind_address)).await {
+        Ok(Ok(socket)) => socket,
+        Ok(Err(e)) if is_resource_exhaustion(&e) => return PortProbe::Exhausted,
+        _ => return PortProbe::Closed,
+    };
+    if socket.connect(target).await.is_err() {
+        return PortProbe::Closed;
+    }
+    
+    let payload = match probe {
+        Some(probe) => probe.payload.clone(),
+        None => builtin_udp_probe(port).unwrap_or_else(|| vec![0, 1, 2, 3]),
+    };
+    if socket.send(&payload).await.is_err() {
+        return PortProbe::Closed;
+    }
+    
+    let mut buf = [0; 1500];
+    match tokio::time::timeout(timeout, socket.recv(&mut buf)).await {
+        Ok(Ok(size)) => match probe.and_then(|probe| probe.expect_prefix.as_ref()) {
+            Some(prefix) if !buf[..size].starts_with(prefix) => PortProbe::Closed,
+            _ => PortProbe::Open(PortState::Open),
+        },
+        // ICMP port unreachable surfaces as a refused receive on a connected socket
+        Ok(Err(_)) => PortProbe::Closed,
+        Err(_) => PortProbe::Open(PortState::Filtered),
+    }
+}
+
+const DEFAULT_MAX_CONCURRENT_PROBES: usize = 256;
+const DEFAULT_MAX_CONCURRENT_HOSTS: usize = 50;
+// Backoff never shrinks the probe limit below this
+const MIN_CONCURRENT_PROBES: usize = 8;
+const EXHAUSTION_RETRY_DELAY: Duration = Duration::from_millis(250);
+const MAX_EXHAUSTION_RETRIES: u32 = 5;
+
+// Bounds how many port probes are in flight at once. When the OS runs out of
+// sockets the limit is halved by permanently retiring permits.
+struct ProbeLimiter {
+    permits: Semaphore,
+    // Hosts being scanned at once; each host task holds one for its whole scan
+    hosts: Semaphore,
+    limit: AtomicUsize,
+    backed_off: AtomicBool,
+    // Retry exhausted probes after backing off instead of counting them as closed
+    retry_exhausted: bool,
+    // Probes stop once the scan is cancelled
+    cancel: CancellationToken,
+}
+
+impl ProbeLimiter {
+    fn new(config: &ScanConfig, cancel: &CancellationToken) -> Self {
+        let limit = config.max_concurrent_probes.unwrap_or(DEFAULT_MAX_CONCURRENT_PROBES).max(1);
+        Self {
+            permits: Semaphore::new(limit),
+            hosts: Semaphore::new(config.max_concurrent_hosts.unwrap_or(DEFAULT_MAX_CONCURRENT_HOSTS).max(1)),
+            limit: AtomicUsize::new(limit),
+            backed_off: AtomicBool::new(false),
+            retry_exhausted: config.retry_on_socket_exhaustion,
+            cancel: cancel.clone(),
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    // Run a probe under a permit, returning what it found open along with the
+    // permit, which the caller holds for as long as it uses the connection
+    async fn probe<T, F, Fut, S>(&self, sink: &S, run: F) -> Option<(T, SemaphorePermit<'_>)>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = PortProbe<T>>,
+        S: ServiceSink,
+    {
+        match self.probe_outcome(sink, run).await? {
+            (PortProbe::Open(value), permit) => Some((value, permit)),
+            _ => None,
+        }
+    }
+    
+    // Run a probe under a permit, backing off and retrying while sockets are
+    // exhausted. None when the scan was cancelled or sockets stayed exhausted.
+    // The permit is returned with the answer so an open connection stays
+    // counted against the limit until it's dropped.
+    async fn probe_outcome<T, F, Fut, S>(&self, sink: &S, mut run: F) -> Option<(PortProbe<T>, SemaphorePermit<'_>)>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = PortProbe<T>>,
+        S: ServiceSink,
+    {
+        let mut attempts = 0;
+        loop {
+            let permit = tokio::select! {
+                _ = self.cancel.cancelled() => return None,
+                permit = self.permits.acquire() => permit.ok()?,
+            };
+            let outcome = tokio::select! {
+                _ = self.cancel.cancelled() => return None,
+                outcome = run() => outcome,
+            };
             
-            if !host.udp_ports.is_empty() {
-                details.push_str("\nOpen UDP ports:\n");
-                for port in &host.udp_ports {
-                    details.push_str(&format!("  {}\n", port));
+            match outcome {
+                PortProbe::Exhausted if !self.retry_exhausted || attempts >= MAX_EXHAUSTION_RETRIES => {
+                    radar_warn!("Ran out of sockets, counting probe as closed");
+                    return None;
+                }
+                PortProbe::Exhausted => {
+                    drop(permit);
+                    attempts += 1;
+                    self.back_off(sink).await;
+                    tokio::time::sleep(EXHAUSTION_RETRY_DELAY * attempts).await;
                 }
+                answer => return Some((answer, permit)),
             }
-            
-            let service = NetworkService {
-                name: host.hostname.clone().unwrap_or_else(|| format!("Network Device at {}", ip)),
-                service_type: "network_device".to_string(),
-                address: ip.clone(),
-                port: None,
-                discovery_method: "Network Scan".to_string(),
-                details: Some(details),
+        }
+    }
+
+    // Halve the concurrency limit, warning the user the first time it happens
+    async fn back_off<S: ServiceSink>(&self, sink: &S) {
+        let current = self.limit.load(Ordering::SeqCst);
+        let target = (current / 2).max(MIN_CONCURRENT_PROBES);
+        if target >= current
+            || self.limit.compare_exchange(current, target, Ordering::SeqCst, Ordering::SeqCst).is_err()
+        {
+            // Already at the floor, or another probe is backing off right now
+            return;
+        }
+        
+        radar_warn!("Ran out of sockets, reducing probe concurrency from {} to {}", current, target);
+        if let Ok(retired) = self.permits.acquire_many((current - target) as u32).await {
+            retired.forget();
+        }
+        
+        if !self.backed_off.swap(true, Ordering::SeqCst) {
+            let warning = ScanWarning {
+                code: "socket-exhaustion".to_string(),
+                message: format!(
+                    "The system ran out of sockets during the port scan, so concurrency was reduced to {}. Lower max_concurrent_probes to avoid this.",
+                    target
+                ),
             };
-            
-            // Don't add if we already have this IP in services
-            if !services.iter().any(|s| s.address == *ip) {
-    services.push(service);
+            let _ = sink.emit_event("scan-warning", &warning);
+        }
+    }
+}
+
+// Common ports to scan
+const COMMON_TCP_PORTS: &[u16] = &[
+    20, 21, 22, 23, 25, 53, 80, 110, 139, 443, 445, 587, 993, 995, 3306, 3389, 5432, 8080, 8443, 
+];
+
+// Web servers and admin interfaces
+const WEB_TCP_PORTS: &[u16] = &[
+    80, 443, 3000, 5000, 8000, 8008, 8080, 8081, 8443, 8888, 9000,
+];
+
+// Smart home, media and IoT services
+const IOT_TCP_PORTS: &[u16] = &[
+    80, 443, 554, 1883, 5000, 5683, 6053, 8008, 8009, 8080, 8123, 8883, 9000, 32400,
+];
+
+const DEFAULT_PORT_TIMEOUT_MS: u64 = 500;
+
+// How long a single port probe waits, per protocol
+#[derive(Debug, Clone, Copy)]
+struct PortTimeouts {
+    tcp: Duration,
+    udp: Duration,
+}
+
+impl std::ops::Mul<u32> for PortTimeouts {
+    type Output = PortTimeouts;
+
+    fn mul(self, factor: u32) -> PortTimeouts {
+        PortTimeouts { tcp: self.tcp * factor, udp: self.udp * factor }
+    }
+}
+
+// Options for a local network scan. Every field is optional and falls back to
+// the scanner's defaults, so `ScanConfig::default()` is a plain ARP-based scan.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanConfig {
+    // Extra range to sweep in addition to ARP hosts, e.g. "10.0.0.0/22"
+    pub cidr: Option<String>,
+    // TCP ports to probe instead of the common port list
+    pub tcp_ports: Option<Vec<u16>>,
+    // Per-port probe timeout in milliseconds, for both TCP and UDP
+    pub timeout_ms: Option<u64>,
+    // TCP connect and UDP reply timeouts in milliseconds, overriding `timeout_ms`.
+    // Raise them for high-latency links such as VPNs; lower them on a fast LAN.
+    pub tcp_timeout_ms: Option<u64>,
+    pub udp_timeout_ms: Option<u64>,
+    // Keep each discovery method's raw details on the consolidated services
+    pub include_source_details: bool,
+    // Emit one `service-discovered` per device and `service-updated` for later
+    // sightings, instead of one event per discovery record
+    pub dedupe_service_events: bool,
+    // Upper bound on port probes in flight at once
+    pub max_concurrent_probes: Option<usize>,
+    // Upper bound on hosts being port-scanned at once (default 50)
+    pub max_concurrent_hosts: Option<usize>,
+    // On "too many open files", reduce concurrency and retry the probe instead
+    // of counting the port as closed
+    pub retry_on_socket_exhaustion: bool,
+    // mDNS service types to browse instead of the configured list (see `mdns_browse_types`)
+    pub mdns_service_types: Option<Vec<String>>,
+    // Order of the returned consolidated services
+    pub sort_by: SortKey,
+    // Also find IPv6 hosts from the neighbor cache and port-scan them
+    pub ipv6: bool,
+    // Ping every address of the local subnet first, so hosts missing from the
+    // ARP table are scanned too
+    pub ping_sweep: bool,
+    // Network interfaces (e.g. "en0") whose subnets the ARP hosts, blind and
+    // ping sweeps cover; unset covers every non-loopback IPv4 interface
+    pub interfaces: Option<Vec<String>>,
+    // Scan hosts in batches of this many, each finishing before the next starts.
+    // Never smaller than the probe concurrency limit; unset scans everything in one batch.
+    pub batch_size: Option<usize>,
+    // Pause between batches in milliseconds
+    pub batch_delay_ms: Option<u64>,
+    // Emit discovery events while scanning (default true). When false the scan
+    // only returns its results.
+    pub emit_events: Option<bool>,
+    // Write everything discovery found to this file (see `ScanRecording`)
+    pub record_to: Option<String>,
+    // Skip the network and consolidate a recording made with `record_to` instead
+    pub replay_from: Option<String>,
+    // Custom UDP probes for services the built-in probe can't detect
+    pub udp_probes: Vec<UdpProbe>,
+    // Append each discovery to this JSON Lines file while the scan runs
+    pub stream_jsonl_to: Option<String>,
+}
+
+// A user-supplied UDP probe: `payload_hex` is sent to each of `ports`, which
+// count as open when the reply starts with `expect_prefix_hex`, or on any
+// reply when no prefix is given. Hex may contain spaces ("de ad be ef").
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UdpProbe {
+    pub ports: Vec<u16>,
+    pub payload_hex: String,
+    pub expect_prefix_hex: Option<String>,
+}
+
+// A UDP probe's decoded bytes
+#[derive(Debug, Clone)]
+struct UdpPayload {
+    payload: Vec<u8>,
+    expect_prefix: Option<Vec<u8>>,
+}
+
+// Ports to probe on each host. UDP ports with a payload use that probe;
+// the rest get the built-in best-effort probe.
+struct ProbePorts {
+    tcp: Vec<u16>,
+    udp: Vec<(u16, Option<UdpPayload>)>,
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let digits: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid hex \"{}\": expected pairs of hex digits", hex));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
+
+impl UdpProbe {
+    fn decode(&self) -> Result<UdpPayload, String> {
+        if self.ports.is_empty() {
+            return Err("UDP probe has no ports".to_string());
+        }
+        if self.ports.contains(&0) {
+            return Err("Invalid UDP port 0: ports must be between 1 and 65535".to_string());
+        }
+        let payload = decode_hex(&self.payload_hex)?;
+        if payload.is_empty() {
+            return Err("UDP probe payload is empty".to_string());
+        }
+        let expect_prefix = self.expect_prefix_hex.as_deref().map(decode_hex).transpose()?;
+        Ok(UdpPayload { payload, expect_prefix })
+    }
+}
+
+// Port scan progress, emitted as `scan-progress` each time a host's port sweep finishes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanProgress {
+    pub batch: usize,
+    pub batch_count: usize,
+    // Hosts whose sweep has finished, out of every ARP/CIDR host to scan
+    pub scanned: usize,
+    pub total: usize,
+    // The host that just finished
+    pub current_ip: String,
+    // Percentage of hosts scanned, 0-100
+    pub progress: f64,
+}
+
+// Sort order for consolidated results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    // Numeric IP order, so 192.168.1.2 comes before 192.168.1.10
+    #[default]
+    Ip,
+    Hostname,
+    // Device type, e.g. all "Infrastructure" hosts together
+    Category,
+    // Most open ports first
+    OpenPorts,
+    // Most recently seen first
+    LastSeen,
+}
+
+impl SortKey {
+    pub fn parse(key: &str) -> Option<Self> {
+        match key.to_lowercase().as_str() {
+            "ip" | "address" => Some(SortKey::Ip),
+            "hostname" | "name" => Some(SortKey::Hostname),
+            "category" | "type" => Some(SortKey::Category),
+            "ports" | "open_ports" => Some(SortKey::OpenPorts),
+            "seen" | "last_seen" => Some(SortKey::LastSeen),
+            _ => None,
+        }
+    }
+}
+
+// Compare addresses as parsed IPs (IPv4 before IPv6); anything unparseable sorts last
+fn compare_addresses(a: &str, b: &str) -> std::cmp::Ordering {
+    match (a.parse::<std::net::IpAddr>(), b.parse::<std::net::IpAddr>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+        (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+// Compare optional strings case-insensitively, with missing values last
+fn compare_optional(a: Option<&String>, b: Option<&String>) -> std::cmp::Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    }
+}
+
+// Sort consolidated services by the given key, breaking ties by IP
+pub fn sort_services(services: &mut [ConsolidatedService], key: SortKey) {
+    services.sort_by(|a, b| {
+        let primary = match key {
+            SortKey::Ip => std::cmp::Ordering::Equal,
+            SortKey::Hostname => compare_optional(a.hostname.as_ref(), b.hostname.as_ref()),
+            SortKey::Category => compare_optional(a.device_type.as_ref(), b.device_type.as_ref()),
+            SortKey::OpenPorts => b.open_ports.len().cmp(&a.open_ports.len()),
+            SortKey::LastSeen => b.last_seen.cmp(&a.last_seen),
+        };
+        primary.then_with(|| compare_addresses(&a.address, &b.address))
+    });
+}
+
+impl ScanConfig {
+    // Reject settings a scan can't use
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(cidr) = &self.cidr {
+            expand_cidr(cidr)?;
+        }
+        if let Some(interfaces) = &self.interfaces {
+            let available = ipv4_interface_names();
+            if let Some(unknown) = interfaces.iter().find(|name| !available.contains(name)) {
+                return Err(format!(
+                    "Unknown network interface '{}': expected one of {}",
+                    unknown,
+                    available.join(", ")
+                ));
+            }
+        }
+        if self.tcp_ports.as_ref().is_some_and(|ports| ports.contains(&0)) {
+            return Err("Invalid TCP port 0: ports must be between 1 and 65535".to_string());
+        }
+        // A zero timeout would count every port as silent
+        for (name, timeout) in [
+            ("timeout_ms", self.timeout_ms),
+            ("tcp_timeout_ms", self.tcp_timeout_ms),
+            ("udp_timeout_ms", self.udp_timeout_ms),
+        ] {
+            if timeout == Some(0) {
+                return Err(format!("Invalid {} 0: timeouts must be at least 1 ms", name));
+            }
+        }
+        for probe in &self.udp_probes {
+            probe.decode()?;
+        }
+        Ok(())
+    }
+
+    pub fn emit_events(&self) -> bool {
+        self.emit_events.unwrap_or(true)
+    }
+
+    fn mdns_service_types(&self) -> Vec<String> {
+        self.mdns_service_types.clone().unwrap_or_else(mdns_browse_types)
+    }
+
+    // Hosts per batch for a scan of `total_hosts`. A batch smaller than the probe
+    // limit would leave permits idle, so that is the floor.
+    fn batch_size(&self, total_hosts: usize) -> usize {
+        let floor = self.max_concurrent_probes.unwrap_or(DEFAULT_MAX_CONCURRENT_PROBES);
+        self.batch_size
+            .map(|size| size.max(floor))
+            .unwrap_or(total_hosts)
+            .max(1)
+    }
+
+    fn tcp_ports(&self) -> Vec<u16> {
+        self.tcp_ports.clone().unwrap_or_else(|| COMMON_TCP_PORTS.to_vec())
+    }
+
+    // The common UDP ports plus every custom probe's, the last probe naming a port winning
+    fn probe_ports(&self) -> ProbePorts {
+        let mut payloads = HashMap::new();
+        for probe in &self.udp_probes {
+            match probe.decode() {
+                Ok(payload) => {
+                    for &port in &probe.ports {
+                        payloads.insert(port, payload.clone());
+                    }
+                }
+                Err(e) => radar_warn!("Ignoring UDP probe: {}", e),
+            }
+        }
+        let mut udp: Vec<u16> = COMMON_UDP_PORTS.iter().chain(payloads.keys()).copied().collect();
+        udp.sort_unstable();
+        udp.dedup();
+        ProbePorts {
+            tcp: self.tcp_ports(),
+            udp: udp.into_iter().map(|port| (port, payloads.remove(&port))).collect(),
+        }
+    }
+
+    fn port_timeout(&self) -> PortTimeouts {
+        let millis = |specific: Option<u64>| {
+            Duration::from_millis(specific.or(self.timeout_ms).unwrap_or(DEFAULT_PORT_TIMEOUT_MS))
+        };
+        PortTimeouts {
+            tcp: millis(self.tcp_timeout_ms),
+            udp: millis(self.udp_timeout_ms),
+        }
+    }
+}
+
+// Look up a named TCP port profile ("common", "web", "iot")
+pub fn ports_for_profile(profile: &str) -> Option<Vec<u16>> {
+    match profile.to_lowercase().as_str() {
+        "common" | "default" => Some(COMMON_TCP_PORTS.to_vec()),
+        "web" => Some(WEB_TCP_PORTS.to_vec()),
+        "iot" => Some(IOT_TCP_PORTS.to_vec()),
+        _ => None,
+    }
+}
+
+// Largest range expand_cidr accepts; a /16 is already 65k hosts
+const MIN_CIDR_PREFIX_LEN: u32 = 16;
+
+// Expand an IPv4 CIDR range like "192.168.1.0/24" into its host addresses
+pub fn expand_cidr(cidr: &str) -> Result<Vec<String>, String> {
+    let (network, prefix) = cidr
+        .trim()
+        .split_once('/')
+        .ok_or_else(|| format!("Invalid CIDR '{}': expected address/prefix", cidr))?;
+    let network: std::net::Ipv4Addr = network
+        .parse()
+        .map_err(|_| format!("Invalid CIDR '{}': bad IPv4 address", cidr))?;
+    let prefix: u32 = prefix
+        .parse()
+        .ok()
+        .filter(|p| *p <= 32)
+        .ok_or_else(|| format!("Invalid CIDR '{}': prefix must be 0-32", cidr))?;
+    if prefix < MIN_CIDR_PREFIX_LEN {
+        return Err(format!("CIDR '{}' is too large: the prefix must be /{} or longer", cidr, MIN_CIDR_PREFIX_LEN));
+    }
+    
+    let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+    let start = u32::from(network) & mask;
+    let end = start | !mask;
+    
+    // Skip the network and broadcast addresses unless the range is too small to have them
+    let (first, last) = if prefix >= 31 { (start, end) } else { (start + 1, end - 1) };
+    
+    Ok((first..=last).map(|ip| std::net::Ipv4Addr::from(ip).to_string()).collect())
+}
+
+const COMMON_UDP_PORTS: &[u16] = &[
+    53, 67, 68, 69, 123, 161, 162, 1900, 5353,
+];
+
+// Get the local IP address
+fn get_local_ip() -> Option<String> {
+    // Try to get a socket connection to a public address
+    // This won't actually connect but will determine local interface
+    match std::net::UdpSocket::bind("0.0.0.0:0") {
+        Ok(socket) => {
+            match socket.connect("8.8.8.8:80") {
+                Ok(_) => {
+                    match socket.local_addr() {
+                        Ok(addr) => {
+                            Some(addr.ip().to_string())
+                        },
+                        Err(_) => None
+                    }
+                },
+                Err(_) => None
+            }
+        },
+        Err(_) => None
+    }
+}
+
+// Tasks spawned for a scan, joined in the order they finish. Any still running
+// when the set is dropped (an early return, or the scan future itself being
+// dropped) are aborted, so they stop probing and release their sockets instead
+// of outliving the scan.
+type ScanTasks<T> = tokio::task::JoinSet<T>;
+
+// Ports found open on one host, what the probes saw on them and the services
+// emitted for them
+struct HostScan {
+    tcp_ports: Vec<u16>,
+    udp_ports: Vec<u16>,
+    observations: Vec<PortObservation>,
+    services: Vec<NetworkService>,
+    // The host answered early probes but went silent partway through, so its
+    // closed ports may only have been throttled
+    possibly_rate_limited: bool,
+}
+
+// A host counts as rate-limiting when at least this many probes were refused,
+// with no timeouts among them, right before a run of at least this many
+// timeouts that lasted to the end of the sweep
+const RATE_LIMIT_MIN_REFUSALS: usize = 3;
+const RATE_LIMIT_MIN_TIMEOUTS: usize = 3;
+// Timed-out ports of a rate-limiting host are re-probed one at a time, this far
+// apart and with a longer timeout
+const RATE_LIMIT_RETRY_DELAY: Duration = Duration::from_millis(500);
+const RATE_LIMIT_RETRY_TIMEOUT_FACTOR: u32 = 3;
+
+// Whether the ports that timed out look throttled rather than filtered
+fn looks_rate_limited(refused: usize, trailing_timeouts: &[u16]) -> bool {
+    refused >= RATE_LIMIT_MIN_REFUSALS && trailing_timeouts.len() >= RATE_LIMIT_MIN_TIMEOUTS
+}
+
+// A port that answered a probe, with what the per-port probes read from it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortObservation {
+    pub address: String,
+    pub port: u16,
+    // "tcp" or "udp"
+    pub protocol: String,
+    pub state: PortState,
+    // Lines the enrichment probes produced (banners, HTTP headers, ...)
+    #[serde(default)]
+    pub probe_lines: Vec<String>,
+}
+
+impl PortObservation {
+    // Probe what's behind an open TCP port over the connection that found it open
+    async fn tcp(ip: &str, port: u16, timeout: Duration, stream: tokio::net::TcpStream) -> Self {
+        let mut conn = PortConnection::with_stream(ip, port, timeout, stream);
+        let probe_lines = enrich_port(&mut conn, ip, port).await;
+        Self { address: ip.to_string(), port, protocol: "tcp".to_string(), state: PortState::Open, probe_lines }
+    }
+    
+    fn udp(ip: &str, port: u16, state: PortState) -> Self {
+        Self { address: ip.to_string(), port, protocol: "udp".to_string(), state, probe_lines: Vec::new() }
+    }
+    
+    // The service for the port
+    fn service(&self) -> NetworkService {
+        let (ip, port) = (self.address.as_str(), self.port);
+        if self.protocol == "udp" {
+            let service_type = match port {
+                53 => "dns",
+                67 | 68 => "dhcp",
+                69 => "tftp",
+                123 => "ntp",
+                161 | 162 => "snmp",
+                1900 => "upnp",
+                5353 => "mdns",
+                _ => "unknown",
+            }.to_string();
+            
+            return NetworkService {
+                name: format!("{} ({}) on port {}/udp", service_type.to_uppercase(), ip, port),
+                service_type: service_type.clone(),
+                address: ip.to_string(),
+                address_family: AddressFamily::of(ip),
+                port: Some(port),
+                discovery_method: METHOD_NETWORK_SCAN.to_string(),
+                details: Some(format!(
+                    "UDP service discovered on {}:{}\nType: {}",
+                    ip, port, service_type
+                )),
+                txt_records: BTreeMap::new(),
+                port_state: Some(self.state),
+                possibly_rate_limited: false,
+            };
+        }
+        
+        let service_type = match port {
+            20 | 21 => "ftp",
+            22 => "ssh",
+            23 => "telnet",
+            25 | 587 => "smtp",
+            53 => "dns",
+            80 | 8080 => "http",
+            110 => "pop3",
+            139 => "netbios-ssn",
+            443 | 8443 => "https",
+            445 => "smb",
+            993 => "imaps",
+            995 => "pop3s",
+            3306 => "mysql",
+            3389 => "rdp",
+            5432 => "postgresql",
+            _ => "unknown",
+        }.to_string();
+        
+        let mut details = format!("TCP service discovered on {}:{}\nType: {}", ip, port, service_type);
+        for line in &self.probe_lines {
+            details.push('\n');
+            details.push_str(line);
+        }
+        
+        NetworkService {
+            name: format!("{} ({}) on port {}", service_type.to_uppercase(), ip, port),
+            service_type,
+            address: ip.to_string(),
+            address_family: AddressFamily::of(ip),
+            port: Some(port),
+            discovery_method: METHOD_NETWORK_SCAN.to_string(),
+            details: Some(details),
+            txt_records: BTreeMap::new(),
+            port_state: Some(self.state),
+            possibly_rate_limited: false,
+        }
+    }
+}
+
+// A host the sweep probed, with what ARP, ping and reverse DNS said about it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostObservation {
+    pub address: String,
+    pub hostname: Option<String>,
+    pub mac: Option<String>,
+    pub responds_to_ping: bool,
+    pub possibly_rate_limited: bool,
+}
+
+impl HostObservation {
+    fn add_to(&self, map: &mut NetworkMap) {
+        map.add(&[&self.address], self.hostname.clone(), self.mac.clone());
+    }
+    
+    // The basic service for a swept host, listed even if no ports were found
+    fn service(&self) -> NetworkService {
+        let ip = &self.address;
+        let manufacturer = self.mac.as_deref().and_then(crate::oui::lookup_oui);
+        let details = format!(
+            "Host discovered on network at {}\n{}{}",
+            ip,
+            if self.responds_to_ping { "Responds to ping\n" } else { "" },
+            hardware_details(self.mac.as_deref(), manufacturer.as_deref()),
+        );
+        NetworkService {
+            name: self.hostname.clone().unwrap_or_else(|| format!("Device at {}", ip)),
+            service_type: "host".to_string(),
+            address: ip.clone(),
+            address_family: AddressFamily::of(ip),
+            port: None,
+            discovery_method: METHOD_NETWORK_SCAN.to_string(),
+            details: Some(details.trim_end().to_string()),
+            txt_records: BTreeMap::new(),
+            port_state: None,
+            possibly_rate_limited: self.possibly_rate_limited,
+        }
+    }
+    
+    // The service for a host from the IPv6 neighbor cache
+    fn ipv6_service(&self) -> NetworkService {
+        let address = &self.address;
+        let mut details = format!("Host discovered via the IPv6 neighbor cache at {}", address);
+        if let Some(mac) = &self.mac {
+            details.push_str(&format!("\nMAC: {}", mac));
+        }
+        NetworkService {
+            name: self.hostname.clone().unwrap_or_else(|| format!("Device at {}", address)),
+            service_type: "host".to_string(),
+            address_family: AddressFamily::of(address),
+            address: address.clone(),
+            port: None,
+            discovery_method: METHOD_IPV6_NEIGHBOR.to_string(),
+            details: Some(details),
+            txt_records: BTreeMap::new(),
+            port_state: None,
+            possibly_rate_limited: self.possibly_rate_limited,
+        }
+    }
+}
+
+// What a sweep observed: the hosts it probed and the ports that answered
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SweepCapture {
+    pub hosts: Vec<HostObservation>,
+    pub ports: Vec<PortObservation>,
+}
+
+impl SweepCapture {
+    fn add_to(&self, map: &mut NetworkMap) {
+        for host in &self.hosts {
+            host.add_to(map);
+        }
+        for port in &self.ports {
+            let ports = Some(vec![port.port]);
+            match port.protocol.as_str() {
+                "udp" => map.add_ports(&[&port.address], None, None, ports),
+                _ => map.add_ports(&[&port.address], None, ports, None),
+            }
+        }
+    }
+    
+    // A host service per swept host, plus a `network_device` summary for every
+    // other host in `map` with open ports
+    fn sweep_services(&self, map: &NetworkMap) -> Vec<NetworkService> {
+        let mut services: Vec<NetworkService> = self.hosts.iter().map(HostObservation::service).collect();
+        let summaries = network_device_services(map, &services);
+        services.extend(summaries);
+        services
+    }
+    
+    // Every port and host service, as the IPv6 neighbor scan reports them
+    fn ipv6_services(&self) -> Vec<NetworkService> {
+        self.ports.iter().map(PortObservation::service).chain(self.hosts.iter().map(HostObservation::ipv6_service)).collect()
+    }
+}
+
+// Probe one host's TCP and UDP ports, emitting a `service-discovered` per open port
+async fn scan_host<S: ServiceSink>(
+    sink: &S,
+    ip: &str,
+    ports: &ProbePorts,
+    port_timeout: PortTimeouts,
+    limiter: &ProbeLimiter,
+) -> HostScan {
+    let mut open_tcp_ports = Vec::new();
+    let mut open_udp_ports = Vec::new();
+    let mut observations = Vec::new();
+    
+    // Wait for a host slot, so a large range doesn't start every host at once
+    let _host_permit = tokio::select! {
+        _ = limiter.cancel.cancelled() => None,
+        permit = limiter.hosts.acquire() => permit.ok(),
+    };
+    
+    // Scan common TCP ports, counting the refusals since the last timeout and
+    // the run of timeouts since the last answer
+    let mut refused = 0;
+    let mut trailing_timeouts = Vec::new();
+    for &port in &ports.tcp {
+        if limiter.is_cancelled() {
+            break;
+        }
+        let outcome = limiter.probe_outcome(sink, || open_tcp_port(ip, port, port_timeout.tcp)).await;
+        // An answer after silence means the silence wasn't the end of the sweep
+        if matches!(outcome, Some((PortProbe::Open(_) | PortProbe::Closed, _))) && !trailing_timeouts.is_empty() {
+            refused = 0;
+            trailing_timeouts.clear();
+        }
+        match outcome {
+            Some((PortProbe::Open(stream), permit)) => {
+                radar_debug!("Found open TCP port {}:{}", ip, port);
+                open_tcp_ports.push(port);
+                
+                // Banner grabbing reuses the connection, so it still holds the permit
+                let observation = PortObservation::tcp(ip, port, port_timeout.tcp, stream).await;
+                drop(permit);
+                let _ = sink.emit_event("service-discovered", &observation.service());
+                observations.push(observation);
+            }
+            Some((PortProbe::Closed, _)) => refused += 1,
+            Some((PortProbe::TimedOut, _)) => trailing_timeouts.push(port),
+            _ => {}
+        }
+    }
+    
+    // A host that answered quickly and then went silent may be throttling the
+    // scan, so give the silent ports another, slower chance
+    let possibly_rate_limited = !limiter.is_cancelled() && looks_rate_limited(refused, &trailing_timeouts);
+    if possibly_rate_limited {
+        radar_warn!("{} stopped answering after refusing {} probes, re-probing {} ports slowly",
+            ip, refused, trailing_timeouts.len());
+        let retry_timeout = port_timeout.tcp * RATE_LIMIT_RETRY_TIMEOUT_FACTOR;
+        for port in trailing_timeouts {
+            tokio::select! {
+                _ = limiter.cancel.cancelled() => break,
+                _ = tokio::time::sleep(RATE_LIMIT_RETRY_DELAY) => {}
+            }
+            if let Some((stream, permit)) = limiter.probe(sink, || open_tcp_port(ip, port, retry_timeout)).await {
+                radar_debug!("Found open TCP port {}:{} on the slow re-probe", ip, port);
+                open_tcp_ports.push(port);
+                
+                let observation = PortObservation::tcp(ip, port, retry_timeout, stream).await;
+                drop(permit);
+                let _ = sink.emit_event("service-discovered", &observation.service());
+                observations.push(observation);
+            }
+        }
+    }
+    
+    // Scan common UDP ports and any with a custom probe
+    for (port, probe) in &ports.udp {
+        let port = *port;
+        if limiter.is_cancelled() {
+            break;
+        }
+        if let Some((state, _permit)) = limiter.probe(sink, || check_udp_port(ip, port, port_timeout.udp, probe.as_ref())).await {
+            radar_debug!("UDP port {}:{} is {}", ip, port, state.label());
+            // Only a reply proves the port open; silent ports aren't reported
+            if state != PortState::Open {
+                continue;
+            }
+            open_udp_ports.push(port);
+            
+            let observation = PortObservation::udp(ip, port, state);
+            let _ = sink.emit_event("service-discovered", &observation.service());
+            observations.push(observation);
+        }
+    }
+    
+    HostScan {
+        tcp_ports: open_tcp_ports,
+        udp_ports: open_udp_ports,
+        services: observations.iter().map(PortObservation::service).collect(),
+        observations,
+        possibly_rate_limited,
+    }
+}
+
+pub async fn scan_local_network<S: ServiceSink>(sink: &S, config: &ScanConfig) -> Vec<NetworkService> {
+    let (capture, _) = sweep_local_network(sink, config, &CancellationToken::new()).await;
+    let services = capture.sweep_services(&NETWORK_MAP.lock().unwrap());
+    radar_info!("Network scan complete, found {} hosts", services.len());
+    services
+}
+
+// Port-scan the local network, returning what the sweep observed and how well it went
+async fn sweep_local_network<S: ServiceSink>(
+    sink: &S,
+    config: &ScanConfig,
+    cancel: &CancellationToken,
+) -> (SweepCapture, SweepStats) {
+    let mut capture = SweepCapture::default();
+    radar_info!("Starting local network scan");
+    
+    // Get hosts from ARP table
+    let (mut arp_hosts, blind_sweep) = get_arp_hosts(sink, config.interfaces.as_deref()).await;
+    let arp_known: Vec<String> = if blind_sweep {
+        radar_info!("ARP table is empty, sweeping {} addresses blindly", arp_hosts.len());
+        Vec::new()
+    } else {
+        radar_info!("Found {} hosts in ARP table", arp_hosts.len());
+        arp_hosts.iter().map(|(ip, _)| ip.clone()).collect()
+    };
+    let mut stats = SweepStats { arp_hosts: arp_known.len(), blind_sweep, ..Default::default() };
+    
+    // Ping the subnet for hosts ARP doesn't list. A blind sweep already covers it.
+    let mut ping_responders = HashSet::new();
+    if config.ping_sweep && !blind_sweep {
+        ping_responders = stage_services(sink, "Ping sweep", ping_sweep(cancel, config.interfaces.as_deref()).await);
+        let before = arp_hosts.len();
+        let known: HashSet<String> = arp_hosts.iter().map(|(ip, _)| ip.clone()).collect();
+        let mut missed: Vec<&String> = ping_responders.iter().filter(|ip| !known.contains(*ip)).collect();
+        missed.sort_by(|a, b| compare_addresses(a, b));
+        arp_hosts.extend(missed.into_iter().map(|ip| (ip.clone(), None)));
+        stats.ping_responders = ping_responders.len();
+        radar_info!("{} hosts answered the ping sweep, {} of them not in the ARP table",
+            ping_responders.len(), arp_hosts.len() - before);
+    }
+    
+    // Add hosts from the configured CIDR range that ARP doesn't know about
+    if let Some(cidr) = &config.cidr {
+        match expand_cidr(cidr) {
+            Ok(range_hosts) => {
+                let known: HashSet<String> = arp_hosts.iter().map(|(ip, _)| ip.clone()).collect();
+                let before = arp_hosts.len();
+                arp_hosts.extend(
+                    range_hosts.into_iter().filter(|ip| !known.contains(ip)).map(|ip| (ip, None)),
+                );
+                radar_info!("Added {} hosts from CIDR range {}", arp_hosts.len() - before, cidr);
+            }
+            Err(message) => {
+                radar_warn!("Ignoring CIDR range: {}", message);
+                let _ = sink.emit_event("scan-error", ScanError { stage: "CIDR range".to_string(), message });
+            }
+        }
+    }
+    
+    let ports = Arc::new(config.probe_ports());
+    let port_timeout = config.port_timeout();
+    let limiter = Arc::new(ProbeLimiter::new(config, cancel));
+    
+    // Look up the names ARP doesn't know and add all hosts to the network map
+    for (ip, hostname) in arp_hosts.iter_mut() {
+        if hostname.is_none() && !limiter.is_cancelled() {
+            *hostname = get_hostname_from_ip(ip).await;
+        }
+        add_to_network_map(&[ip], hostname.clone(), None, None);
+    }
+    
+    // Attach each ARP host's MAC address and vendor, as one host however many addresses it has
+    let arp_macs = read_arp_macs();
+    {
+        let mut map = NETWORK_MAP.lock().unwrap();
+        for (mac, addresses) in &arp_macs {
+            let addresses: Vec<&str> = addresses.iter().map(String::as_str).collect();
+            map.add(&addresses, None, Some(mac.clone()));
+        }
+    }
+    let arp_macs = macs_by_address(&arp_macs);
+    
+    // Scan hosts in batches, finishing each batch before starting the next
+    let total_hosts = arp_hosts.len();
+    let batch_size = config.batch_size(total_hosts);
+    let batch_count = total_hosts.div_ceil(batch_size);
+    let mut hosts = arp_hosts.into_iter().peekable();
+    let mut batch = 0;
+    let mut scanned = 0;
+    
+    while hosts.peek().is_some() {
+        if limiter.is_cancelled() {
+            radar_info!("Network scan stopped after {}/{} hosts", scanned, total_hosts);
+            break;
+        }
+        batch += 1;
+        
+        // Create scanner tasks, aborted if the scan is dropped before they finish
+        let mut tasks = ScanTasks::new();
+        
+        // Scan each host for open ports
+        for (ip, hostname) in hosts.by_ref().take(batch_size) {
+            let sink_clone = sink.clone();
+            let ip_clone = ip.clone();
+            let ports = ports.clone();
+            let limiter = limiter.clone();
+            
+            // Spawn a separate task for each host
+            tasks.spawn(async move {
+                let scan = scan_host(&sink_clone, &ip_clone, &ports, port_timeout, &limiter).await;
+                
+                // Update network map with discovered ports
+                add_to_network_map(&[&ip_clone], None, Some(scan.tcp_ports), Some(scan.udp_ports));
+                
+                (ip_clone, hostname, scan.observations, scan.possibly_rate_limited)
+            });
+        }
+        
+        // Wait for all scan tasks in this batch to complete
+        while let Some(result) = tasks.join_next().await {
+            if let Ok((ip, hostname, observations, rate_limited)) = result {
+                scanned += 1;
+                if !observations.is_empty() {
+                    stats.hosts_responding += 1;
+                }
+                if rate_limited {
+                    stats.rate_limited_hosts += 1;
+                }
+                let progress = ScanProgress {
+                    batch,
+                    batch_count,
+                    scanned,
+                    total: total_hosts,
+                    current_ip: ip.clone(),
+                    progress: scanned as f64 * 100.0 / total_hosts as f64,
+                };
+                let _ = sink.emit_event("scan-progress", &progress);
+                
+                capture.hosts.push(HostObservation {
+                    mac: arp_macs.get(&ip).cloned(),
+                    responds_to_ping: ping_responders.contains(&ip),
+                    address: ip,
+                    hostname,
+                    possibly_rate_limited: rate_limited,
+                });
+                capture.ports.extend(observations);
+            }
+        }
+        
+        radar_debug!("Finished batch {}/{} ({}/{} hosts)", batch, batch_count, scanned, total_hosts);
+        
+        if hosts.peek().is_some() {
+            if let Some(delay) = config.batch_delay_ms.filter(|ms| *ms > 0) {
+                tokio::select! {
+                    _ = limiter.cancel.cancelled() => {}
+                    _ = tokio::time::sleep(Duration::from_millis(delay)) => {}
+                }
+            }
+        }
+    }
+    
+    let network_map = NETWORK_MAP.lock().unwrap();
+    
+    // Hosts ARP knows exist but that showed no ports and no name were likely missed
+    let missed: Vec<String> = arp_known
+        .into_iter()
+        .filter(|ip| {
+            network_map.host(ip).is_none_or(|host| {
+                host.tcp_ports.is_empty() && host.udp_ports.is_empty() && host.hostname.is_none()
+            })
+        })
+        .collect();
+    radar_debug!("{} hosts qualify for a retry", missed.len());
+    stats.silent_arp_hosts = missed.len();
+    *RETRY_CANDIDATES.lock().unwrap() = Some(RetryCandidates { config: config.clone(), hosts: missed });
+    
+    stats.hosts_scanned = scanned;
+    stats.socket_exhaustion = limiter.backed_off.load(Ordering::SeqCst);
+    
+    (capture, stats)
+}
+
+// A `network_device` service for every host in the map with open ports, except
+// hosts one of `existing` is already at
+fn network_device_services(map: &NetworkMap, existing: &[NetworkService]) -> Vec<NetworkService> {
+    let mut services = Vec::new();
+    for host in map.hosts() {
+        let Some(ip) = host.primary_address() else {
+            continue;
+        };
+        
+        // For each host, create a service with the collected information
+        if !host.tcp_ports.is_empty() || !host.udp_ports.is_empty() {
+            let mut details = format!("Host: {}\n", ip);
+            
+            if let Some(hostname) = &host.hostname {
+                details.push_str(&format!("Hostname: {}\n", hostname));
+            }
+            details.push_str(&host.hardware_details());
+            
+            if host.addresses.len() > 1 {
+                let mut addresses: Vec<&String> = host.addresses.iter().collect();
+                addresses.sort_by(|a, b| compare_addresses(a, b));
+                details.push_str("\nAddresses:\n");
+                for address in addresses {
+                    details.push_str(&format!("  {}\n", address));
+                }
+            }
+            
+            if !host.tcp_ports.is_empty() {
+                details.push_str("\nOpen TCP ports:\n");
+                for port in &host.tcp_ports {
+                    details.push_str(&format!("  {}\n", port));
+                }
+            }
+            
+            if !host.udp_ports.is_empty() {
+                details.push_str("\nOpen UDP ports:\n");
+                for port in &host.udp_ports {
+                    details.push_str(&format!("  {}\n", port));
+                }
+            }
+            
+            let service = NetworkService {
+                name: host.hostname.clone().unwrap_or_else(|| format!("Network Device at {}", ip)),
+                service_type: "network_device".to_string(),
+                address: ip.clone(),
+                address_family: AddressFamily::of(ip),
+                port: None,
+                discovery_method: METHOD_NETWORK_SCAN.to_string(),
+                details: Some(details),
+                txt_records: BTreeMap::new(),
+                port_state: None,
+                possibly_rate_limited: false,
+            };
+            
+            // Don't add if we already have one of this host's addresses in services
+            if !existing.iter().any(|s| host.addresses.contains(&s.address)) {
+                services.push(service);
+            }
+        }
+    }
+    services
+}
+
+// The devices in scan results, one per MAC address, and the addresses of
+// entries without one. Entries of the same device (one per interface, or not
+// merged) pool their open ports under the first entry's address.
+fn observed_devices(consolidated: &[ConsolidatedService]) -> (Vec<crate::inventory::ObservedDevice>, Vec<String>) {
+    let mut observed: Vec<crate::inventory::ObservedDevice> = Vec::new();
+    let mut unidentified = Vec::new();
+    for service in consolidated {
+        let Some(mac) = &service.mac_address else {
+            unidentified.push(service.address.clone());
+            continue;
+        };
+        let index = match observed.iter().position(|device| device.mac == *mac) {
+            Some(index) => index,
+            None => {
+                observed.push(crate::inventory::ObservedDevice {
+                    mac: mac.clone(),
+                    address: service.address.clone(),
+                    open_ports: Vec::new(),
+                });
+                observed.len() - 1
+            }
+        };
+        let device = &mut observed[index];
+        for port in service.open_ports.keys() {
+            if !device.open_ports.contains(port) {
+                device.open_ports.push(*port);
+            }
+        }
+    }
+    (observed, unidentified)
+}
+
+// Audit the last scan against an expected inventory CSV (see `inventory.rs`),
+// matching the consolidated devices by the MAC the scan found for them. Each
+// finding is also emitted as a `compliance-alert` event.
+pub fn audit_against_inventory<S: ServiceSink>(
+    sink: &S,
+    inventory_path: &str,
+) -> Result<crate::inventory::ComplianceReport, String> {
+    let text = std::fs::read_to_string(inventory_path)
+        .map_err(|e| format!("Failed to read inventory {}: {}", inventory_path, e))?;
+    let (expected, skipped) = crate::inventory::parse_inventory_csv(&text);
+    if !skipped.is_empty() {
+        radar_warn!("Skipped {} malformed rows in {}", skipped.len(), inventory_path);
+    }
+    
+    let mut consolidated: Vec<ConsolidatedService> = CONSOLIDATED_SERVICES.lock().unwrap().values().cloned().collect();
+    if consolidated.is_empty() {
+        return Err("No scan results to audit; run a scan first".to_string());
+    }
+    sort_services(&mut consolidated, SortKey::Ip);
+    let (observed, unidentified) = observed_devices(&consolidated);
+    
+    let mut report = crate::inventory::audit_inventory(&expected, &observed);
+    report.unidentified = unidentified;
+    radar_info!("Inventory audit: {}/{} expected devices compliant, {} findings",
+        report.compliant, report.expected, report.findings.len());
+    for finding in &report.findings {
+        let _ = sink.emit_event("compliance-alert", finding);
+    }
+    Ok(report)
+}
+
+// Hosts from the last scan that are worth a second look, and the config they were scanned with
+struct RetryCandidates {
+    config: ScanConfig,
+    hosts: Vec<String>,
+}
+
+static RETRY_CANDIDATES: Lazy<Mutex<Option<RetryCandidates>>> = Lazy::new(|| Mutex::new(None));
+
+// Retries wait this many times longer per port than the original scan
+const RETRY_TIMEOUT_MULTIPLIER: u32 = 4;
+
+// Re-probe the hosts the last scan likely missed (no open ports, no hostname) with
+// longer timeouts. Hosts that now answer are merged into the stored results and
+// emitted as `service-updated`; the rest stay queued for the next retry.
+pub async fn retry_unreachable<S: ServiceSink>(sink: &S) -> Result<Vec<ConsolidatedService>, String> {
+    let Some(candidates) = RETRY_CANDIDATES.lock().unwrap().take() else {
+        return Err("No scan has run yet".to_string());
+    };
+    radar_info!("Retrying {} hosts with no open ports or hostname", candidates.hosts.len());
+    
+    let ports = Arc::new(candidates.config.probe_ports());
+    let port_timeout = candidates.config.port_timeout() * RETRY_TIMEOUT_MULTIPLIER;
+    let limiter = Arc::new(ProbeLimiter::new(&candidates.config, &CancellationToken::new()));
+    
+    let mut tasks = ScanTasks::new();
+    for ip in candidates.hosts {
+        let sink_clone = sink.clone();
+        let ports = ports.clone();
+        let limiter = limiter.clone();
+        tasks.spawn(async move {
+            let scan = scan_host(&sink_clone, &ip, &ports, port_timeout, &limiter).await;
+            let hostname = get_hostname_from_ip(&ip).await;
+            (ip, hostname, scan)
+        });
+    }
+    
+    let mut still_missed = Vec::new();
+    let mut services = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        let Ok((ip, hostname, scan)) = result else {
+            continue;
+        };
+        if scan.services.is_empty() && hostname.is_none() {
+            still_missed.push(ip);
+            continue;
+        }
+        
+        radar_info!("{} answered on retry", ip);
+        add_to_network_map(&[&ip], hostname.clone(), Some(scan.tcp_ports), Some(scan.udp_ports));
+        services.extend(scan.services);
+        services.push(NetworkService {
+            name: hostname.unwrap_or_else(|| format!("Device at {}", ip)),
+            service_type: "host".to_string(),
+            address: ip.clone(),
+            address_family: AddressFamily::of(&ip),
+            port: None,
+            discovery_method: METHOD_NETWORK_SCAN.to_string(),
+            details: Some(format!("Host discovered on network at {}", ip)),
+            txt_records: BTreeMap::new(),
+            port_state: None,
+            possibly_rate_limited: scan.possibly_rate_limited,
+        });
+    }
+    
+    // Re-consolidate everything known about the recovered hosts, so the stored
+    // entries keep what earlier discovery phases found about them
+    let host_services: Vec<NetworkService> = {
+        let mut discovered_services = DISCOVERED_SERVICES.lock().unwrap();
+        let addresses: HashSet<String> = services.iter().map(|s| s.address.clone()).collect();
+        for service in services {
+            discovered_services.insert(service);
+        }
+        discovered_services.iter().filter(|s| addresses.contains(&s.address)).cloned().collect()
+    };
+    
+    let mut recovered = consolidate_services(host_services);
+    if !candidates.config.include_source_details {
+        for service in &mut recovered {
+            service.source_details.clear();
+        }
+    }
+    store_consolidated_services(&mut CONSOLIDATED_SERVICES.lock().unwrap(), &mut recovered);
+    for service in &recovered {
+        let _ = sink.emit_event("service-updated", service);
+    }
+    
+    radar_info!("Retry recovered {} hosts, {} still unresponsive", recovered.len(), still_missed.len());
+    *RETRY_CANDIDATES.lock().unwrap() = Some(RetryCandidates { config: candidates.config, hosts: still_missed });
+    Ok(recovered)
+}
+
+// Outcome of re-checking the hosts from a saved snapshot
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RescanReport {
+    // Every device from the snapshot; reachable ones carry fresh port results,
+    // unreachable ones are kept as they were last seen
+    pub services: Vec<ConsolidatedService>,
+    // Snapshot addresses that didn't answer this time
+    pub unreachable: Vec<String>,
+    // Port scan records the rescan found, before consolidation
+    #[serde(default)]
+    pub records: usize,
+    #[serde(default)]
+    pub duration_ms: u64,
+    // Stopped before every host was probed; the snapshot was left as it was
+    #[serde(default)]
+    pub cancelled: bool,
+}
+
+impl RescanReport {
+    // The rescan's outcome in the shape `scan-complete` carries, with
+    // completeness judged by how many snapshot hosts answered
+    pub fn scan_result(&self, scan_id: u64) -> ScanResult {
+        let total = self.services.len();
+        let reachable = total - self.unreachable.len();
+        let mut factors = Vec::new();
+        if let Some(impact) = (self.unreachable.len() * 30).checked_div(total) {
+            factors.push(CompletenessFactor {
+                code: "unreachable".to_string(),
+                impact: impact as u8,
+                detail: format!("{} of {} snapshot hosts didn't answer", self.unreachable.len(), total),
+            });
+        }
+        if self.cancelled {
+            factors.push(CompletenessFactor {
+                code: "cancelled".to_string(),
+                impact: 30,
+                detail: "The rescan was stopped before every host was probed".to_string(),
+            });
+        }
+        let lost: u32 = factors.iter().map(|f| f.impact as u32).sum();
+        let score = 100u32.saturating_sub(lost) as u8;
+
+        ScanResult {
+            scan_id,
+            method_counts: std::collections::BTreeMap::from([(METHOD_NETWORK_SCAN.to_string(), self.records)]),
+            device_count: reachable,
+            duration_ms: self.duration_ms,
+            cancelled: self.cancelled,
+            completeness: Some(ScanCompleteness { score, factors, rescan_suggested: score < RESCAN_SUGGESTED_BELOW }),
+            ..Default::default()
+        }
+    }
+}
+
+// Load a snapshot saved as a JSON array of consolidated services
+pub fn load_snapshot(path: &str) -> Result<Vec<ConsolidatedService>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read snapshot {}: {}", path, e))?;
+    serde_json::from_str(&text).map_err(|e| format!("Invalid snapshot {}: {}", path, e))
+}
+
+// Fold a fresh port scan into a snapshot entry. Only the probed ports are
+// replaced, so ports learned from mDNS/UPnP in the snapshot are kept.
+fn merge_rescanned(service: &mut ConsolidatedService, fresh: Option<&ConsolidatedService>, probed: &HashSet<u16>) {
+    service.open_ports.retain(|port, _| !probed.contains(port));
+    if let Some(fresh) = fresh {
+        for (port, name) in &fresh.open_ports {
+            service.open_ports.insert(*port, name.clone());
+        }
+        for service_type in &fresh.service_types {
+            if !service.service_types.contains(service_type) {
+                service.service_types.push(service_type.clone());
+            }
+        }
+        for method in &fresh.discovery_methods {
+            if !service.discovery_methods.contains(method) {
+                service.discovery_methods.push(method.clone());
+            }
+        }
+    }
+    crate::enricher::apply_enrichers(service);
+}
+
+// Port-scan only the hosts in a saved snapshot and merge the results into it.
+// A host counts as reachable if any port answered or it is in the ARP table;
+// the rest are reported with a `device-disappeared` event.
+pub async fn rescan_from_snapshot<S: ServiceSink>(
+    sink: &S,
+    path: &str,
+    config: &ScanConfig,
+    cancel: &CancellationToken,
+) -> Result<RescanReport, String> {
+    let start = std::time::Instant::now();
+    let snapshot = load_snapshot(path)?;
+    let mut addresses: Vec<String> = snapshot.iter().map(|s| s.address.clone()).collect();
+    addresses.sort();
+    addresses.dedup();
+    radar_info!("Rescanning {} hosts from snapshot {}", addresses.len(), path);
+
+    let ports = Arc::new(config.probe_ports());
+    let port_timeout = config.port_timeout();
+    let limiter = Arc::new(ProbeLimiter::new(config, cancel));
+    let probed: HashSet<u16> = ports.tcp.iter().copied().chain(ports.udp.iter().map(|(port, _)| *port)).collect();
+
+    let mut tasks = ScanTasks::new();
+    for ip in addresses.iter().cloned() {
+        let sink_clone = sink.clone();
+        let ports = ports.clone();
+        let limiter = limiter.clone();
+        tasks.spawn(async move {
+            let scan = scan_host(&sink_clone, &ip, &ports, port_timeout, &limiter).await;
+            (ip, scan)
+        });
+    }
+
+    let mut reachable = HashSet::new();
+    let mut fresh_services = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        if let Ok((ip, scan)) = result {
+            if !scan.services.is_empty() {
+                reachable.insert(ip.clone());
+            }
+            add_to_network_map(&[&ip], None, Some(scan.tcp_ports), Some(scan.udp_ports));
+            fresh_services.extend(scan.services);
+        }
+    }
+
+    let mut report = RescanReport {
+        records: fresh_services.len(),
+        ..Default::default()
+    };
+
+    // Results of a stopped rescan are incomplete, so they don't replace the snapshot's
+    if cancel.is_cancelled() {
+        radar_info!("Snapshot rescan stopped, keeping the snapshot as it was");
+        report.services = snapshot;
+        report.cancelled = true;
+        report.duration_ms = start.elapsed().as_millis() as u64;
+        return Ok(report);
+    }
+
+    // Hosts with every probed port closed are still present while ARP knows them
+    let snapshot_addresses: HashSet<&String> = addresses.iter().collect();
+    reachable.extend(
+        read_arp_table()
+            .into_iter()
+            .map(|entry| entry.ip)
+            .filter(|ip| snapshot_addresses.contains(ip)),
+    );
+
+    let fresh: HashMap<String, ConsolidatedService> = consolidate_services(fresh_services)
+        .into_iter()
+        .map(|s| (s.address.clone(), s))
+        .collect();
+
+    let mut refreshed = Vec::new();
+    for mut service in snapshot {
+        if reachable.contains(&service.address) {
+            let update = fresh.get(&service.address);
+            merge_rescanned(&mut service, update, &probed);
+            refreshed.push(service.clone());
+        } else {
+            radar_info!("Snapshot host {} is unreachable", service.address);
+            let _ = sink.emit_event("device-disappeared", &service);
+            if !report.unreachable.contains(&service.address) {
+                report.unreachable.push(service.address.clone());
+            }
+        }
+        report.services.push(service);
+    }
+
+    store_consolidated_services(&mut CONSOLIDATED_SERVICES.lock().unwrap(), &mut refreshed);
+    radar_info!("Snapshot rescan complete, {} of {} hosts unreachable",
+        report.unreachable.len(), addresses.len());
+    report.duration_ms = start.elapsed().as_millis() as u64;
+    Ok(report)
+}
+
+// Bounds on `scan_expand`, so a seed with many routes can't turn into a runaway scan
+pub const MAX_EXPAND_DEPTH: u8 = 3;
+const MAX_EXPAND_HOSTS: usize = 1024;
+// Smallest prefix length expanded into; anything larger than a /22 is skipped
+const MIN_EXPAND_PREFIX_LEN: u8 = 22;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyNode {
+    pub address: String,
+    pub hostname: Option<String>,
+    pub open_ports: Vec<u16>,
+    pub is_gateway: bool,
+    // Hops from the seed (the seed is 0)
+    pub depth: u8,
+}
+
+// A host reached through a gateway's downstream subnet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyEdge {
+    pub gateway: String,
+    pub subnet: String,
+    pub host: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TopologyGraph {
+    pub seed: String,
+    pub nodes: Vec<TopologyNode>,
+    pub edges: Vec<TopologyEdge>,
+    // True when the host limit stopped expansion early
+    pub truncated: bool,
+}
+
+// The IPv4 routing table as the OS prints it
+fn read_routing_table() -> String {
+    #[cfg(target_os = "macos")]
+    let output = Command::new("netstat").args(["-rn", "-f", "inet"]).output();
+    #[cfg(target_os = "windows")]
+    let output = Command::new("route").args(["print", "-4"]).output();
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let output = Command::new("ip").args(["-4", "route", "show"]).output();
+    
+    output.map(|o| String::from_utf8_lossy(&o.stdout).into_owned()).unwrap_or_default()
+}
+
+// IPv4 subnets a routing table sends through `gateway`, as CIDR ("192.168.2.0/24").
+// The default route is left out.
+fn routes_via(routing_table: &str, gateway: &str) -> Vec<String> {
+    let mut subnets: Vec<String> = routing_table
+        .lines()
+        .filter_map(|line| parse_route_line(line, gateway))
+        .collect();
+    subnets.sort();
+    subnets.dedup();
+    subnets
+}
+
+// Destination of one routing table line whose gateway is `gateway`:
+//   Linux    "192.168.2.0/24 via 192.168.1.2 dev eth0"
+//   macOS    "192.168.2          192.168.1.2        UGSc   en0" (octets given set the prefix)
+//   Windows  "192.168.2.0    255.255.255.0    192.168.1.2    192.168.1.10    25"
+fn parse_route_line(line: &str, gateway: &str) -> Option<String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let destination = *tokens.first()?;
+    if destination == "default" || destination.starts_with("0.0.0.0") {
+        return None;
+    }
+    
+    let (network, prefix) = if let Some(via) = tokens.iter().position(|t| *t == "via") {
+        if tokens.get(via + 1) != Some(&gateway) {
+            return None;
+        }
+        match destination.split_once('/') {
+            Some((network, prefix)) => (network.to_string(), prefix.parse().ok()?),
+            None => (destination.to_string(), 32),
+        }
+    } else if tokens.len() >= 3 && tokens[1].parse::<std::net::Ipv4Addr>().is_ok() && tokens[2] == gateway {
+        let mask: u32 = tokens[1].parse::<std::net::Ipv4Addr>().ok()?.into();
+        (destination.to_string(), mask.count_ones() as u8)
+    } else if tokens.get(1) == Some(&gateway) {
+        let (network, prefix) = match destination.split_once('/') {
+            Some((network, prefix)) => (network, prefix.parse().ok()?),
+            None => (destination, 8 * destination.split('.').count() as u8),
+        };
+        let mut octets: Vec<&str> = network.split('.').collect();
+        octets.resize(4, "0");
+        (octets.join("."), prefix)
+    } else {
+        return None;
+    };
+    
+    network.parse::<std::net::Ipv4Addr>().ok()?;
+    Some(format!("{}/{}", network, prefix))
+}
+
+// What expansion knows about routing: the default gateway and the routing table
+struct Routing {
+    default_gateway: Option<String>,
+    table: String,
+}
+
+impl Routing {
+    fn read() -> Self {
+        Self {
+            default_gateway: default_net::get_default_gateway().ok().map(|g| g.ip_addr.to_string()),
+            table: read_routing_table(),
+        }
+    }
+}
+
+// Whether an address is a gateway: the default gateway, the next hop of any
+// route, or a device consolidation already identified as a router
+fn is_gateway(routing: &Routing, address: &str) -> bool {
+    if routing.default_gateway.as_deref() == Some(address) || !routes_via(&routing.table, address).is_empty() {
+        return true;
+    }
+    CONSOLIDATED_SERVICES.lock().unwrap().values().any(|s| {
+        s.address == address
+            && (s.device_type.as_deref() == Some("Router")
+                || s.security_flags.iter().any(|f| f == "upnp-igd-exposed"))
+    })
+}
+
+// Subnets behind a gateway: the routes through it, or for the default
+// gateway (whose downstream is our own network) the local subnet
+fn downstream_subnets(routing: &Routing, gateway: &str) -> Vec<String> {
+    let mut subnets = routes_via(&routing.table, gateway);
+    if subnets.is_empty() && routing.default_gateway.as_deref() == Some(gateway) {
+        if let Some((network, prefix)) = local_ipv4_network() {
+            subnets.push(format!("{}/{}", network, prefix));
+        }
+    }
+    subnets
+}
+
+// A host to scan during expansion: address, hops from the seed, and the
+// (gateway, subnet) it was reached through
+type ExpandTarget = (String, u8, Option<(String, String)>);
+
+// Scan a seed host and, if it is a gateway, the subnets behind it, repeating
+// for gateways found there until `depth` hops from the seed. Depth is capped at
+// MAX_EXPAND_DEPTH and the whole expansion at MAX_EXPAND_HOSTS hosts.
+pub async fn scan_expand<S: ServiceSink>(
+    sink: &S,
+    seed: &str,
+    depth: u8,
+    config: &ScanConfig,
+    cancel: &CancellationToken,
+) -> Result<TopologyGraph, String> {
+    seed.parse::<std::net::Ipv4Addr>().map_err(|_| format!("Invalid seed address '{}'", seed))?;
+    let depth = depth.min(MAX_EXPAND_DEPTH);
+    radar_info!("Expanding from {} up to {} hops", seed, depth);
+    
+    let ports = Arc::new(config.probe_ports());
+    let port_timeout = config.port_timeout();
+    let limiter = Arc::new(ProbeLimiter::new(config, cancel));
+    
+    let routing = Routing::read();
+    let mut graph = TopologyGraph { seed: seed.to_string(), ..Default::default() };
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut expanded_subnets: HashSet<String> = HashSet::new();
+    let mut frontier: Vec<ExpandTarget> = vec![(seed.to_string(), 0, None)];
+    
+    while !frontier.is_empty() {
+        let level = std::mem::take(&mut frontier);
+        let mut tasks = ScanTasks::new();
+        for (address, hops, via) in level {
+            if !visited.insert(address.clone()) {
+                continue;
+            }
+            if visited.len() > MAX_EXPAND_HOSTS {
+                graph.truncated = true;
+                break;
+            }
+            
+            let sink_clone = sink.clone();
+            let ports = ports.clone();
+            let limiter = limiter.clone();
+            tasks.spawn(async move {
+                let scan = scan_host(&sink_clone, &address, &ports, port_timeout, &limiter).await;
+                let hostname = get_hostname_from_ip(&address).await;
+                (address, hops, via, hostname, scan)
+            });
+        }
+        
+        while let Some(result) = tasks.join_next().await {
+            let Ok((address, hops, via, hostname, scan)) = result else {
+                continue;
+            };
+            // Subnet sweeps find mostly empty addresses; only keep hosts that answered
+            let answered = !scan.services.is_empty() || hostname.is_some();
+            if via.is_some() && !answered {
+                continue;
+            }
+            add_to_network_map(&[&address], hostname.clone(), Some(scan.tcp_ports.clone()), Some(scan.udp_ports.clone()));
+            
+            let gateway = is_gateway(&routing, &address);
+            if let Some((from, subnet)) = via {
+                graph.edges.push(TopologyEdge { gateway: from, subnet, host: address.clone() });
+            }
+            let mut open_ports: Vec<u16> = scan.tcp_ports.iter().chain(&scan.udp_ports).copied().collect();
+            open_ports.sort_unstable();
+            graph.nodes.push(TopologyNode { address: address.clone(), hostname, open_ports, is_gateway: gateway, depth: hops });
+            
+            if !gateway || hops >= depth {
+                continue;
+            }
+            for subnet in downstream_subnets(&routing, &address) {
+                let too_large = parse_ipv4_network(&subnet).is_none_or(|(_, prefix)| prefix < MIN_EXPAND_PREFIX_LEN);
+                if too_large || !expanded_subnets.insert(subnet.clone()) {
+                    radar_debug!("Not expanding into {} behind {}", subnet, address);
+                    continue;
+                }
+                radar_info!("Expanding into {} behind {}", subnet, address);
+                for host in expand_cidr(&subnet).unwrap_or_default() {
+                    if !visited.contains(&host) {
+                        frontier.push((host, hops + 1, Some((address.clone(), subnet.clone()))));
+                    }
+                }
+            }
+        }
+        
+        if graph.truncated {
+            radar_warn!("Expansion from {} stopped at {} hosts", seed, MAX_EXPAND_HOSTS);
+            break;
+        }
+    }
+    
+    radar_info!("Expansion from {} found {} hosts behind {} gateways",
+        seed, graph.nodes.len(), graph.nodes.iter().filter(|n| n.is_gateway).count());
+    Ok(graph)
+}
+
+// Non-fatal condition noticed during a scan, surfaced to the user as a `scan-warning` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanWarning {
+    pub code: String,
+    pub message: String,
+}
+
+// A discovery stage that failed, surfaced as a `scan-error` event while the
+// other stages carry on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanError {
+    pub stage: String,
+    pub message: String,
+}
+
+// Overall outcome of a scan, emitted with `scan-complete` and returned by `run_network_scan`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanResult {
+    // ID carried by every event of the scan
+    pub scan_id: u64,
+    // Raw records found by each discovery method ("mDNS", "UPnP", "Network Scan", "IPv6 Neighbor"), before consolidation
+    #[serde(default)]
+    pub method_counts: std::collections::BTreeMap<String, usize>,
+    // Devices left after consolidation
+    #[serde(default)]
+    pub device_count: usize,
+    // Wall-clock time of the discovery phases and consolidation, in milliseconds
+    #[serde(default)]
+    pub duration_ms: u64,
+    // Only the gateway answered, which usually means a guest network with client isolation
+    pub client_isolation_suspected: bool,
+    // Everything also emitted as a `scan-warning` event
+    pub warnings: Vec<ScanWarning>,
+    // How full the scanned subnet is; None when the subnet couldn't be determined
+    pub utilization: Option<SubnetUtilization>,
+    // Stopped before every phase finished; the results are partial
+    pub cancelled: bool,
+    // Something answered the multicast probe, so mDNS and UPnP discovery can work
+    pub multicast_available: bool,
+    // How likely the scan is to have found everything; None before any scan
+    pub completeness: Option<ScanCompleteness>,
+}
+
+// How the port sweep of the local network went, for judging completeness
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SweepStats {
+    // Hosts the ARP table listed before the sweep
+    pub arp_hosts: usize,
+    // The ARP table was empty, so addresses were probed blindly
+    pub blind_sweep: bool,
+    pub hosts_scanned: usize,
+    // Hosts with at least one open probed port
+    pub hosts_responding: usize,
+    // Hosts that answered the ping sweep, if one ran
+    pub ping_responders: usize,
+    // ARP-listed hosts that showed no open port and no name
+    pub silent_arp_hosts: usize,
+    // Probe concurrency was cut after running out of sockets
+    pub socket_exhaustion: bool,
+    // Hosts that stopped answering partway through their port sweep
+    pub rate_limited_hosts: usize,
+}
+
+// Score below which a rescan with other settings is suggested
+const RESCAN_SUGGESTED_BELOW: u8 = 70;
+
+// One signal behind a completeness score; `impact` is the points it cost (0 for none)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletenessFactor {
+    pub code: String,
+    pub impact: u8,
+    pub detail: String,
+}
+
+// Heuristic estimate of how much of the network a scan saw, 0-100
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanCompleteness {
+    pub score: u8,
+    pub factors: Vec<CompletenessFactor>,
+    // Low enough that different settings (more ports, a CIDR range, lower
+    // concurrency) might find more
+    pub rescan_suggested: bool,
+}
+
+// Share of a subnet's host addresses that answered during the scan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubnetUtilization {
+    pub subnet: String,
+    // Usable host addresses in the subnet
+    pub capacity: u64,
+    pub live_hosts: usize,
+    pub percent: f64,
+}
+
+// Scan IDs are handed out in increasing order, starting at 1
+static SCAN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// Cancellation tokens of the scans in progress, keyed by scan ID. Scan phases,
+// host batches and port probes check their scan's token so a stopped scan
+// winds down within a probe timeout.
+static SCAN_TOKENS: Lazy<Mutex<HashMap<u64, CancellationToken>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// A registered scan. Its token is unregistered when this is dropped, so
+// `cancel_network_scan` only reaches scans that are still running.
+pub struct ActiveScan {
+    pub id: u64,
+    pub cancel: CancellationToken,
+}
+
+impl Drop for ActiveScan {
+    fn drop(&mut self) {
+        SCAN_TOKENS.lock().unwrap().remove(&self.id);
+    }
+}
+
+// Allocate the ID and cancellation token for a new scan
+pub fn start_scan() -> ActiveScan {
+    let id = SCAN_COUNTER.fetch_add(1, Ordering::SeqCst) + 1;
+    let cancel = CancellationToken::new();
+    SCAN_TOKENS.lock().unwrap().insert(id, cancel.clone());
+    ActiveScan { id, cancel }
+}
+
+// Stop the scans in progress; each returns whatever it found so far
+pub fn cancel_network_scan() {
+    for (id, cancel) in SCAN_TOKENS.lock().unwrap().iter() {
+        radar_info!("Cancelling network scan {}", id);
+        cancel.cancel();
+    }
+}
+
+// ID of the most recently started scan (0 if none has run yet)
+pub fn current_scan_id() -> u64 {
+    SCAN_COUNTER.load(Ordering::SeqCst)
+}
+
+// Discovery method names, as carried by each record and counted in `ScanResult`
+pub const METHOD_MDNS: &str = "mDNS";
+pub const METHOD_UPNP: &str = "UPnP";
+pub const METHOD_NETWORK_SCAN: &str = "Network Scan";
+pub const METHOD_IPV6_NEIGHBOR: &str = "IPv6 Neighbor";
+
+// Outcome of the most recent scan
+pub static LAST_SCAN_RESULT: Lazy<Arc<Mutex<ScanResult>>> =
+    Lazy::new(|| Arc::new(Mutex::new(ScanResult::default())));
+
+// Only subnets at least this large are expected to have other DHCP clients on them
+const ISOLATION_MAX_PREFIX_LEN: u8 = 24;
+
+// Network address and prefix length of the interface holding the local IP address
+fn local_ipv4_network() -> Option<(std::net::Ipv4Addr, u8)> {
+    let local_ip = get_local_ip()?;
+    get_if_addrs::get_if_addrs().ok()?.into_iter().find_map(|iface| match iface.addr {
+        get_if_addrs::IfAddr::V4(v4) if v4.ip.to_string() == local_ip => {
+            let network = std::net::Ipv4Addr::from(u32::from(v4.ip) & u32::from(v4.netmask));
+            Some((network, u32::from(v4.netmask).count_ones() as u8))
+        }
+        _ => None,
+    })
+}
+
+// Prefix length of the interface holding the local IP address
+fn local_ipv4_prefix_len() -> Option<u8> {
+    local_ipv4_network().map(|(_, prefix)| prefix)
+}
+
+// Parse "a.b.c.d/prefix" into its network address and prefix length
+fn parse_ipv4_network(cidr: &str) -> Option<(std::net::Ipv4Addr, u8)> {
+    let (network, prefix) = cidr.trim().split_once('/')?;
+    let network: std::net::Ipv4Addr = network.parse().ok()?;
+    let prefix: u8 = prefix.parse().ok().filter(|p| *p <= 32)?;
+    let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+    Some((std::net::Ipv4Addr::from(u32::from(network) & mask), prefix))
+}
+
+// Usable host addresses in a subnet (network and broadcast excluded, like expand_cidr)
+fn subnet_host_capacity(prefix: u8) -> u64 {
+    let total = 1u64 << (32 - prefix as u32);
+    if prefix >= 31 { total } else { total - 2 }
+}
+
+// Work out how much of the scanned subnet is in use. The subnet is the configured
+// CIDR range, or the local interface's subnet for ARP-only scans. A host counts as
+// live if it is in the ARP table after the scan, had an open port, or answered
+// mDNS/UPnP discovery; addresses swept without any answer don't count.
+fn subnet_utilization(config: &ScanConfig, services: &[NetworkService]) -> Option<SubnetUtilization> {
+    let (network, prefix) = match &config.cidr {
+        Some(cidr) => parse_ipv4_network(cidr)?,
+        None => local_ipv4_network()?,
+    };
+    let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix) };
+    let in_subnet = |ip: &str| {
+        ip.parse::<std::net::Ipv4Addr>()
+            .is_ok_and(|ip| u32::from(ip) & mask == u32::from(network))
+    };
+    
+    let mut live: HashSet<String> = read_arp_table().into_iter().map(|entry| entry.ip).collect();
+    live.extend(
+        NETWORK_MAP
+            .lock()
+            .unwrap()
+            .hosts()
+            .filter(|host| !host.tcp_ports.is_empty() || !host.udp_ports.is_empty())
+            .flat_map(|host| host.addresses.iter().cloned()),
+    );
+    live.extend(
+        services
+            .iter()
+            .filter(|s| s.discovery_method != METHOD_NETWORK_SCAN)
+            .map(|s| s.address.clone()),
+    );
+    live.retain(|ip| in_subnet(ip));
+    
+    let capacity = subnet_host_capacity(prefix);
+    let live_hosts = live.len();
+    Some(SubnetUtilization {
+        subnet: format!("{}/{}", network, prefix),
+        capacity,
+        live_hosts,
+        percent: live_hosts as f64 * 100.0 / capacity as f64,
+    })
+}
+
+// Guess whether the network isolates clients from each other (typical for guest WiFi).
+// Deliberately conservative: the gateway must be reachable on a DHCP-sized subnet, yet
+// multicast discovery got no answers at all (not even from the router) and no other host
+// showed up in the ARP table or the port scan.
+fn detect_client_isolation(multicast_services: usize, network_services: &[NetworkService]) -> bool {
+    if multicast_services > 0 {
+        return false;
+    }
+    
+    let gateway = match default_net::get_default_gateway() {
+        Ok(gateway) => gateway.ip_addr.to_string(),
+        Err(_) => return false,
+    };
+    
+    match local_ipv4_prefix_len() {
+        Some(prefix) if prefix <= ISOLATION_MAX_PREFIX_LEN => {}
+        _ => return false,
+    }
+    
+    let local_ip = get_local_ip();
+    let gateway_seen = network_services.iter().any(|s| s.address == gateway);
+    let other_hosts = network_services
+        .iter()
+        .filter(|s| s.address != gateway && Some(&s.address) != local_ip.as_ref())
+        .count();
+    
+    gateway_seen && other_hosts == 0
+}
+
+// Build the scan result, emitting a `scan-warning` for anything the user should know about
+fn assess_scan<S: ServiceSink>(
+    sink: &S,
+    multicast_available: bool,
+    multicast_services: usize,
+    network_services: &[NetworkService],
+) -> ScanResult {
+    let mut result = ScanResult { multicast_available, ..Default::default() };
+    
+    if !multicast_available {
+        radar_warn!("Nothing answered the multicast probe");
+        result.warnings.push(ScanWarning {
+            code: "multicast-unavailable".to_string(),
+            message: "No device answered a multicast query, so this network probably blocks multicast. mDNS and UPnP discovery won't find anything here; devices can only be found by the port scan.".to_string(),
+        });
+    }
+    
+    if detect_client_isolation(multicast_services, network_services) {
+        radar_warn!("Only the gateway is visible; the network likely isolates clients");
+        result.client_isolation_suspected = true;
+        result.warnings.push(ScanWarning {
+            code: "client-isolation".to_string(),
+            message: "Only the gateway responded. This looks like a guest network with client isolation, so other devices cannot be seen.".to_string(),
+        });
+    }
+    
+    for warning in &result.warnings {
+        let _ = sink.emit_event("scan-warning", warning);
+    }
+    
+    result
+}
+
+// Estimate how much of the network the scan saw from what each phase observed.
+// Every signal starts from a perfect 100 and costs points when it suggests
+// devices were missed.
+fn assess_completeness(sweep: &SweepStats, result: &ScanResult) -> ScanCompleteness {
+    let mut factors = Vec::new();
+    let mut add = |code: &str, impact: u8, detail: String| {
+        factors.push(CompletenessFactor { code: code.to_string(), impact, detail });
+    };
+    
+    if sweep.blind_sweep {
+        add("arp", 25, "The ARP table was empty, so hosts were found by blindly probing addresses".to_string());
+    } else if sweep.arp_hosts < 3 {
+        add("arp", 10, format!("The ARP table listed only {} hosts", sweep.arp_hosts));
+    } else {
+        add("arp", 0, format!("The ARP table listed {} hosts", sweep.arp_hosts));
+    }
+    
+    let count = |method: &str| result.method_counts.get(method).copied().unwrap_or_default();
+    match (count(METHOD_MDNS), count(METHOD_UPNP)) {
+        (0, 0) => add("multicast", 20, "Neither mDNS nor UPnP answered; multicast may be blocked".to_string()),
+        (0, upnp) => add("multicast", 5, format!("UPnP found {} services but mDNS found none", upnp)),
+        (mdns, 0) => add("multicast", 5, format!("mDNS found {} services but UPnP found none", mdns)),
+        (mdns, upnp) => add("multicast", 0, format!("mDNS found {} and UPnP {} services", mdns, upnp)),
+    }
+    
+    // Hosts known to be there that the port sweep couldn't see into
+    if let Some(impact) = (sweep.silent_arp_hosts * 15).checked_div(sweep.arp_hosts) {
+        add("silent-hosts", impact as u8, format!(
+            "{} of {} ARP hosts showed no open port; they may drop probes or use ports outside the scanned list",
+            sweep.silent_arp_hosts, sweep.arp_hosts
+        ));
+    }
+    if sweep.blind_sweep && sweep.hosts_scanned > 0 && sweep.hosts_responding == 0 {
+        add("blind-sweep", 10, format!("None of the {} blindly probed addresses answered", sweep.hosts_scanned));
+    }
+    
+    if sweep.rate_limited_hosts > 0 {
+        add("rate-limiting", 10, format!(
+            "{} hosts stopped answering partway through their port sweep; some of their ports may be missing",
+            sweep.rate_limited_hosts
+        ));
+    }
+    if sweep.socket_exhaustion {
+        add("socket-exhaustion", 15, "The system ran out of sockets, so some probes may have been lost".to_string());
+    }
+    if result.client_isolation_suspected {
+        add("client-isolation", 30, "The network appears to isolate clients from each other".to_string());
+    }
+    if result.cancelled {
+        add("cancelled", 30, "The scan was stopped before every phase finished".to_string());
+    }
+    
+    let lost: u32 = factors.iter().map(|f| f.impact as u32).sum();
+    let score = 100u32.saturating_sub(lost) as u8;
+    radar_info!("Scan completeness estimated at {}/100", score);
+    ScanCompleteness { score, factors, rescan_suggested: score < RESCAN_SUGGESTED_BELOW }
+}
+
+// Implementation function for network scanning, returning the consolidated
+// services and the scan's outcome. The scan stops early once `cancel` fires.
+pub async fn scan_network_services_impl<S: ServiceSink>(
+    sink: &S,
+    config: &ScanConfig,
+    cancel: &CancellationToken,
+) -> Result<(Vec<ConsolidatedService>, ScanResult), String> {
+    config.validate()?;
+    if !config.emit_events() {
+        return run_streamed_scan_phases(&SilentSink, config, cancel).await;
+    }
+    if config.dedupe_service_events {
+        return run_streamed_scan_phases(&DedupSink::new(sink.clone()), config, cancel).await;
+    }
+    run_streamed_scan_phases(sink, config, cancel).await
+}
+
+// Run the scan, also appending discoveries to `stream_jsonl_to` when set
+async fn run_streamed_scan_phases<S: ServiceSink>(
+    sink: &S,
+    config: &ScanConfig,
+    cancel: &CancellationToken,
+) -> Result<(Vec<ConsolidatedService>, ScanResult), String> {
+    match &config.stream_jsonl_to {
+        Some(path) => run_scan_phases(&JsonlSink::open(sink.clone(), path)?, config, cancel).await,
+        None => run_scan_phases(sink, config, cancel).await,
+    }
+}
+
+// Version of the recording format; recordings of any other version are refused
+const RECORDING_VERSION: u32 = 2;
+
+// The raw inputs the discovery phases saw, before any parsing: mDNS answers,
+// SSDP responses with the descriptions they pointed at, and what the sweeps
+// learned about each host and port. Saved with `record_to` and fed back with
+// `replay_from`, which runs the same parsers and consolidation over them, so
+// both can be reproduced from a real network without touching it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanRecording {
+    pub version: u32,
+    // Config of the recorded scan, for reference
+    pub config: ScanConfig,
+    pub mdns: Vec<MdnsAnswer>,
+    pub mdns_hosts: Vec<MdnsHostAnswer>,
+    pub ssdp: Vec<SsdpAnswer>,
+    pub network_scan: SweepCapture,
+    pub ipv6: SweepCapture,
+    // IPv6 -> IPv4 addresses of dual-stack devices
+    pub ipv6_to_ipv4: HashMap<String, String>,
+    pub sweep: SweepStats,
+    // Result of the multicast probe; recordings made before the probe existed assume it worked
+    #[serde(default = "multicast_assumed")]
+    pub multicast_available: bool,
+}
+
+fn multicast_assumed() -> bool {
+    true
+}
+
+impl ScanRecording {
+    // The hosts the recorded discoveries describe, as a live scan fills `NETWORK_MAP`
+    fn network_map(&self) -> NetworkMap {
+        let mut map = NetworkMap::default();
+        for answer in &self.mdns {
+            answer.add_to(&mut map);
+        }
+        for answer in &self.mdns_hosts {
+            answer.add_to(&mut map);
+        }
+        for answer in &self.ssdp {
+            answer.add_to(&mut map);
+        }
+        self.network_scan.add_to(&mut map);
+        self.ipv6.add_to(&mut map);
+        map
+    }
+    
+    // Parse the recorded inputs into the services each discovery method reports
+    fn services(&self) -> Vec<(&'static str, Vec<NetworkService>)> {
+        let mdns = self.mdns.iter().flat_map(MdnsAnswer::services).chain(self.mdns_hosts.iter().map(MdnsHostAnswer::service));
+        vec![
+            (METHOD_MDNS, mdns.collect()),
+            (METHOD_UPNP, self.ssdp.iter().map(SsdpAnswer::service).collect()),
+            (METHOD_NETWORK_SCAN, self.network_scan.sweep_services(&self.network_map())),
+            (METHOD_IPV6_NEIGHBOR, self.ipv6.ipv6_services()),
+        ]
+    }
+}
+
+pub fn load_recording(path: &str) -> Result<ScanRecording, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read recording {}: {}", path, e))?;
+    let recording: ScanRecording = serde_json::from_str(&text).map_err(|e| format!("Invalid recording {}: {}", path, e))?;
+    if recording.version != RECORDING_VERSION {
+        return Err(format!(
+            "Recording {} is format version {}, expected {}; record it again",
+            path, recording.version, RECORDING_VERSION
+        ));
+    }
+    Ok(recording)
+}
+
+fn save_recording(path: &str, recording: &ScanRecording) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(recording)
+        .map_err(|e| format!("Failed to serialize recording: {}", e))?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write recording {}: {}", path, e))
+}
+
+// What a discovery stage found, or nothing after emitting a `scan-error` for its failure
+fn stage_services<S: ServiceSink, T: Default>(sink: &S, stage: &str, result: Result<T, String>) -> T {
+    result.unwrap_or_else(|message| {
+        radar_warn!("{} discovery failed: {}", stage, message);
+        let _ = sink.emit_event("scan-error", ScanError { stage: stage.to_string(), message });
+        T::default()
+    })
+}
+
+// Run the discovery phases against the network
+async fn discover_live<S: ServiceSink>(sink: &S, config: &ScanConfig, cancel: &CancellationToken) -> ScanRecording {
+    // Find out up front whether multicast discovery can find anything here
+    let multicast_available = probe_multicast().await;
+    
+    // Discover mDNS services and emit events as they're found
+    let (mdns, mdns_hosts) = stage_services(sink, METHOD_MDNS, discover_mdns_types(sink.clone(), &config.mdns_service_types(), cancel).await);
+    
+    // Discover UPnP services and emit events as they're found
+    let ssdp = stage_services(sink, METHOD_UPNP, search_upnp(sink.clone(), cancel).await);
+    
+    // Scan network and emit events as hosts are found
+    let (network_scan, sweep) = sweep_local_network(sink, config, cancel).await;
+    
+    // IPv6 hosts come from the neighbor cache, not a sweep
+    let (ipv6, ipv6_to_ipv4) = if config.ipv6 && !cancel.is_cancelled() {
+        scan_ipv6_neighbors(sink, config, cancel).await
+    } else {
+        (SweepCapture::default(), HashMap::new())
+    };
+    
+    ScanRecording {
+        version: RECORDING_VERSION,
+        config: config.clone(),
+        mdns,
+        mdns_hosts,
+        ssdp,
+        network_scan,
+        ipv6,
+        ipv6_to_ipv4,
+        sweep,
+        multicast_available,
+    }
+}
+
+// Load a recording and emit what it parses to as a live scan would have
+fn discover_replayed<S: ServiceSink>(sink: &S, path: &str) -> Result<ScanRecording, String> {
+    let recording = load_recording(path)?;
+    radar_info!("Replaying scan recording {}", path);
+    // The IPv4 sweep streams its open ports but reports only hosts
+    let ports = recording.network_scan.ports.iter().map(PortObservation::service);
+    for service in ports.chain(recording.services().into_iter().flat_map(|(_, services)| services)) {
+        let _ = sink.emit_event("service-discovered", &service);
+    }
+    Ok(recording)
+}
+
+// Run every discovery phase, then consolidate and store the results
+async fn run_scan_phases<S: ServiceSink>(
+    sink: &S,
+    config: &ScanConfig,
+    cancel: &CancellationToken,
+) -> Result<(Vec<ConsolidatedService>, ScanResult), String> {
+    let started = std::time::Instant::now();
+    let recording = match &config.replay_from {
+        Some(path) => discover_replayed(sink, path)?,
+        None => discover_live(sink, config, cancel).await,
+    };
+    // A recording that can't be written doesn't throw away the scan itself
+    if let Some(path) = &config.record_to {
+        match save_recording(path, &recording) {
+            Ok(()) => radar_info!("Saved scan recording to {}", path),
+            Err(message) => {
+                radar_warn!("{}", message);
+                let _ = sink.emit_event("scan-error", ScanError { stage: "Recording".to_string(), message });
+            }
+        }
+    }
+    
+    // Run the parsers over everything discovery saw
+    let services_by_method = recording.services();
+    let found_by = |method: &str| {
+        services_by_method.iter().find(|(m, _)| *m == method).map(|(_, s)| s.as_slice()).unwrap_or_default()
+    };
+    let multicast_services = found_by(METHOD_MDNS).len() + found_by(METHOD_UPNP).len();
+    let mut scan_result = assess_scan(sink, recording.multicast_available, multicast_services, found_by(METHOD_NETWORK_SCAN));
+    scan_result.cancelled = config.replay_from.is_none() && cancel.is_cancelled();
+    scan_result.method_counts = services_by_method
+        .iter()
+        .map(|(method, services)| (method.to_string(), services.len()))
+        .collect();
+    scan_result.completeness = Some(assess_completeness(&recording.sweep, &scan_result));
+    let all_services: Vec<NetworkService> = services_by_method.into_iter().flat_map(|(_, services)| services).collect();
+    let ipv6_to_ipv4 = recording.ipv6_to_ipv4;
+    
+    // Measure how full the subnet is now that every phase has run
+    scan_result.utilization = subnet_utilization(config, &all_services);
+    if let Some(utilization) = &scan_result.utilization {
+        radar_info!("Subnet {} utilization: {}/{} hosts ({:.1}%)",
+            utilization.subnet, utilization.live_hosts, utilization.capacity, utilization.percent);
+    }
+
+    // Store services in the global state
+    let mut discovered_services = DISCOVERED_SERVICES.lock().unwrap();
+    let mut consolidated_services = CONSOLIDATED_SERVICES.lock().unwrap();
+    
+    // Add all services to the discovered set
+    for service in &all_services {
+        discovered_services.insert(service.clone());
+    }
+    
+    // Consolidate services, showing dual-stack devices once and dropping the raw
+    // per-method details unless asked for
+    let mut consolidated = merge_dual_stack(consolidate_services(all_services), &ipv6_to_ipv4);
+    if !config.include_source_details {
+        for service in &mut consolidated {
+            service.source_details.clear();
+        }
+    }
+    sort_services(&mut consolidated, config.sort_by);
+    
+    // Update the consolidated services map
+    store_consolidated_services(&mut consolidated_services, &mut consolidated);
+    scan_result.device_count = consolidated.len();
+    scan_result.duration_ms = started.elapsed().as_millis() as u64;
+    *LAST_SCAN_RESULT.lock().unwrap() = scan_result.clone();
+
+    Ok((consolidated, scan_result))
+}
+
+// Current time in Unix epoch seconds, for first_seen/last_seen
+fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+// The earlier of two optional timestamps, ignoring a missing one
+fn earliest(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        _ => a.or(b),
+    }
+}
+
+// Merge freshly consolidated services into the global store, keyed on device
+// identity (never on port, since every port already lives in `open_ports`).
+// The previous entry for the same device is replaced, which updates its address
+// if it moved; the services take the earlier `first_seen` of the entries they replace.
+pub fn store_consolidated_services(
+    store: &mut HashMap<String, ConsolidatedService>,
+    consolidated: &mut [ConsolidatedService],
+) {
+    for service in consolidated.iter_mut() {
+        let mut key = service.identity_key.clone();
+        
+        // The same device at another address on another interface is split
+        // horizon, not a move, so each interface keeps its own entry
+        if let Some(previous) = store.get(&key) {
+            if let (Some(before), Some(now)) = (&previous.discovered_on_interface, &service.discovered_on_interface) {
+                if before != now && previous.address != service.address {
+                    radar_debug!("{} answers differently on {} and {}", key, before, now);
+                    key = format!("{}@{}", key, now);
+                }
+            }
+        }
+        
+        if let Some(previous) = store.get(&key) {
+            if previous.address != service.address {
+                radar_info!("Device {} moved from {} to {}", key, previous.address, service.address);
+            }
+            service.first_seen = earliest(service.first_seen, previous.first_seen);
+        }
+        
+        // A device first stored by address is now known by a stronger identity
+        let address_key = format!("addr:{}", service.address);
+        if key != address_key {
+            if let Some(previous) = store.remove(&address_key) {
+                radar_debug!("Re-keyed {} as {}", address_key, key);
+                service.first_seen = earliest(service.first_seen, previous.first_seen);
+            }
+        }
+        
+        store.insert(key, service.clone());
+    }
+}
+
+// What the last two scans returned, for diff_last_scan
+#[derive(Default)]
+struct ScanSnapshots {
+    previous: Option<Vec<ConsolidatedService>>,
+    latest: Option<Vec<ConsolidatedService>>,
+}
+
+static SCAN_SNAPSHOTS: Lazy<Mutex<ScanSnapshots>> = Lazy::new(|| Mutex::new(ScanSnapshots::default()));
+
+// A device seen by both scans that now shows more
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostChange {
+    pub address: String,
+    pub uuid: Option<String>,
+    pub new_ports: Vec<u16>,
+    pub new_discovery_methods: Vec<String>,
+}
+
+// What changed between the end of the previous scan and now
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanDiff {
+    pub added: Vec<ConsolidatedService>,
+    pub removed: Vec<ConsolidatedService>,
+    pub changed: Vec<HostChange>,
+}
+
+// Record what a scan returned, keeping the scan before it
+pub fn record_scan_snapshot(consolidated: &[ConsolidatedService]) {
+    let mut snapshots = SCAN_SNAPSHOTS.lock().unwrap();
+    snapshots.previous = snapshots.latest.replace(consolidated.to_vec());
+}
+
+// Compare what the last scan returned against the scan before it, matching
+// devices by identity key. Before a second scan there's nothing to compare
+// with, so everything is new.
+pub fn diff_last_scan() -> ScanDiff {
+    let by_identity = |services: &Option<Vec<ConsolidatedService>>| -> HashMap<String, ConsolidatedService> {
+        services.iter().flatten().map(|s| (s.identity_key.clone(), s.clone())).collect()
+    };
+    let (before, now) = {
+        let snapshots = SCAN_SNAPSHOTS.lock().unwrap();
+        (by_identity(&snapshots.previous), by_identity(&snapshots.latest))
+    };
+
+    let mut diff = ScanDiff::default();
+    for (key, service) in &now {
+        let Some(old) = before.get(key) else {
+            diff.added.push(service.clone());
+            continue;
+        };
+        let mut new_ports: Vec<u16> = service.open_ports.keys().filter(|p| !old.open_ports.contains_key(p)).copied().collect();
+        new_ports.sort_unstable();
+        let new_discovery_methods: Vec<String> = service
+            .discovery_methods
+            .iter()
+            .filter(|m| !old.discovery_methods.contains(m))
+            .cloned()
+            .collect();
+        if !new_ports.is_empty() || !new_discovery_methods.is_empty() {
+            diff.changed.push(HostChange {
+                address: service.address.clone(),
+                uuid: service.uuid.clone(),
+                new_ports,
+                new_discovery_methods,
+            });
+        }
+    }
+    diff.removed = before
+        .into_iter()
+        .filter(|(key, _)| !now.contains_key(key))
+        .map(|(_, service)| service)
+        .collect();
+
+    diff.added.sort_by(|a, b| a.address.cmp(&b.address));
+    diff.removed.sort_by(|a, b| a.address.cmp(&b.address));
+    diff.changed.sort_by(|a, b| a.address.cmp(&b.address));
+    diff
+}
+
+// Stored entries that are probably one device, and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    // Store keys of the entries, strongest identity first
+    pub keys: Vec<String>,
+    pub addresses: Vec<String>,
+    // e.g. "same MAC aa:bb:cc:dd:ee:ff", "same UUID ...", "similar hostnames nas / NAS.local"
+    pub signals: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuplicateReport {
+    pub entries_checked: usize,
+    pub groups: Vec<DuplicateGroup>,
+}
+
+// Why two stored entries look like the same device, if they do
+fn duplicate_signals(a: &ConsolidatedService, b: &ConsolidatedService, macs: &HashMap<String, String>) -> Vec<String> {
+    // Split-horizon entries are kept apart on purpose
+    if let (Some(x), Some(y)) = (&a.discovered_on_interface, &b.discovered_on_interface) {
+        if x != y {
+            return Vec::new();
+        }
+    }
+    
+    let mut signals = Vec::new();
+    if let (Some(x), Some(y)) = (macs.get(&a.address), macs.get(&b.address)) {
+        if x == y {
+            signals.push(format!("same MAC {}", x));
+        }
+    }
+    let addresses = |s: &ConsolidatedService| -> HashSet<String> {
+        std::iter::once(s.address.clone()).chain(s.ipv6_addresses.iter().cloned()).collect()
+    };
+    if let Some(shared) = addresses(a).intersection(&addresses(b)).next() {
+        signals.push(format!("shared address {}", shared));
+    }
+    if let (Some(x), Some(y)) = (&a.uuid, &b.uuid) {
+        if x.eq_ignore_ascii_case(y) {
+            signals.push(format!("same UUID {}", x));
+        }
+    }
+    if let (Some(x), Some(y)) = (&a.hostname, &b.hostname) {
+        if hostname_label(x).is_some() && hostname_label(x) == hostname_label(y) {
+            signals.push(format!("similar hostnames {} / {}", x, y));
+        }
+    }
+    signals
+}
+
+// Rank store keys by identity strength, so merges keep the best one
+fn identity_rank(key: &str) -> u8 {
+    if key.starts_with("uuid:") {
+        0
+    } else if key.starts_with("mdns:") {
+        1
+    } else if key.starts_with("addr:") {
+        3
+    } else {
+        2
+    }
+}
+
+// Find stored entries that are probably the same device but weren't merged by
+// consolidation: same MAC (from the ARP table), an address in common, the same
+// UUID, or hostnames that match ignoring case and domain
+pub fn get_duplicate_report() -> DuplicateReport {
+    let macs = macs_by_address(&read_arp_macs());
+    let store = CONSOLIDATED_SERVICES.lock().unwrap();
+    let mut entries: Vec<(&String, &ConsolidatedService)> = store.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    
+    // Union-find over entry indices
+    let mut parent: Vec<usize> = (0..entries.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    
+    let mut signals: HashMap<usize, Vec<String>> = HashMap::new();
+    for i in 0..entries.len() {
+        for j in i + 1..entries.len() {
+            let found = duplicate_signals(entries[i].1, entries[j].1, &macs);
+            if found.is_empty() {
+                continue;
+            }
+            let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+            parent[rj] = ri;
+            let mut moved = signals.remove(&rj).unwrap_or_default();
+            let group_signals = signals.entry(ri).or_default();
+            group_signals.append(&mut moved);
+            for signal in found {
+                if !group_signals.contains(&signal) {
+                    group_signals.push(signal);
+                }
+            }
+        }
+    }
+    
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..entries.len() {
+        let r = root(&mut parent, i);
+        members.entry(r).or_default().push(i);
+    }
+    
+    let mut groups: Vec<DuplicateGroup> = members
+        .into_iter()
+        .filter(|(_, indices)| indices.len() > 1)
+        .map(|(r, indices)| {
+            let mut keys: Vec<String> = indices.iter().map(|&i| entries[i].0.clone()).collect();
+            keys.sort_by_key(|k| (identity_rank(k), k.clone()));
+            let mut addresses: Vec<String> = indices.iter().map(|&i| entries[i].1.address.clone()).collect();
+            addresses.sort_by(|a, b| compare_addresses(a, b));
+            addresses.dedup();
+            DuplicateGroup { keys, addresses, signals: signals.remove(&r).unwrap_or_default() }
+        })
+        .collect();
+    groups.sort_by(|a, b| a.keys.cmp(&b.keys));
+    
+    radar_info!("Found {} suspected duplicate groups among {} entries", groups.len(), entries.len());
+    DuplicateReport { entries_checked: entries.len(), groups }
+}
+
+// Merge every group from `get_duplicate_report` into the entry with the
+// strongest identity, returning the groups that were merged
+pub fn merge_duplicates() -> Vec<DuplicateGroup> {
+    let groups = get_duplicate_report().groups;
+    let mut store = CONSOLIDATED_SERVICES.lock().unwrap();
+    for group in &groups {
+        let Some((keep, rest)) = group.keys.split_first() else {
+            continue;
+        };
+        let Some(mut target) = store.remove(keep) else {
+            continue;
+        };
+        for key in rest {
+            let Some(other) = store.remove(key) else {
+                continue;
+            };
+            if other.address != target.address && other.address.contains(':') && !target.ipv6_addresses.contains(&other.address) {
+                target.ipv6_addresses.push(other.address.clone());
+            }
+            absorb_service(&mut target, other);
+        }
+        radar_info!("Merged {} entries into {}", group.keys.len(), keep);
+        store.insert(keep.clone(), target);
+    }
+    groups
+}
+
+// Value of a TXT record key as listed in service details ("  key: value")
+fn txt_value<'a>(details: &'a str, key: &str) -> Option<&'a str> {
+    details.lines().find_map(|line| {
+        let (k, v) = line.trim().split_once(':')?;
+        (k.trim() == key).then(|| v.trim())
+    })
+}
+
+// Vendor named in a service's details: the UPnP manufacturer, else the MAC vendor
+fn service_manufacturer(details: &str) -> Option<String> {
+    txt_value(details, "Manufacturer")
+        .or_else(|| txt_value(details, "Vendor"))
+        .map(str::to_string)
+}
+
+// Common Matter vendor IDs from the CSA registry
+const MATTER_VENDORS: &[(u16, &str)] = &[
+    (0x100B, "Signify (Philips Hue)"),
+    (0x110A, "Samsung SmartThings"),
+    (0x115A, "Nanoleaf"),
+    (0x115F, "Aqara"),
+    (0x117C, "IKEA"),
+    (0x1217, "Amazon"),
+    (0x130A, "Eve Systems"),
+    (0x1349, "Apple"),
+    (0x6006, "Google"),
+    (0xFFF1, "Test Vendor"),
+    (0xFFF2, "Test Vendor"),
+    (0xFFF3, "Test Vendor"),
+    (0xFFF4, "Test Vendor"),
+];
+
+pub fn matter_vendor_name(vendor_id: u16) -> Option<&'static str> {
+    MATTER_VENDORS.iter().find(|(id, _)| *id == vendor_id).map(|(_, name)| *name)
+}
+
+// Parse the Matter "VP" TXT value: decimal "vendorid+productid", product optional
+pub fn parse_matter_vp(value: &str) -> Option<(u16, Option<u16>)> {
+    let (vendor, product) = match value.trim().split_once('+') {
+        Some((vendor, product)) => (vendor, Some(product.trim().parse().ok()?)),
+        None => (value.trim(), None),
+    };
+    Some((vendor.trim().parse().ok()?, product))
+}
+
+fn is_matter_service_type(service_type: &str) -> bool {
+    service_type == "matter" || service_type == "matterc"
+}
+
+// Decode the Matter TXT keys (VP, CM, D) from a Matter service's details
+fn extract_matter_info(details: &str) -> Option<MatterInfo> {
+    let vp = txt_value(details, "VP").and_then(parse_matter_vp);
+    let commissioning_mode = txt_value(details, "CM").and_then(|v| v.parse::<u8>().ok());
+    let discriminator = txt_value(details, "D").and_then(|v| v.parse::<u16>().ok());
+    
+    if vp.is_none() && commissioning_mode.is_none() && discriminator.is_none() {
+        return None;
+    }
+    
+    Some(MatterInfo {
+        vendor_id: vp.map(|(vendor, _)| vendor),
+        product_id: vp.and_then(|(_, product)| product),
+        vendor_name: vp.and_then(|(vendor, _)| matter_vendor_name(vendor)).map(str::to_string),
+        commissioning_mode: commissioning_mode.map(|cm| match cm {
+            0 => "not commissioning".to_string(),
+            1 => "basic".to_string(),
+            2 => "enhanced".to_string(),
+            other => format!("unknown ({})", other),
+        }),
+        discriminator,
+    })
+}
+
+// Standard UPnP device classes with a friendly name and category
+const UPNP_DEVICE_TYPES: &[(&str, &str, &str)] = &[
+    ("InternetGatewayDevice", "Router", "Network"),
+    ("WANDevice", "WAN Interface", "Network"),
+    ("WANConnectionDevice", "WAN Connection", "Network"),
+    ("LANDevice", "LAN Interface", "Network"),
+    ("WLANAccessPointDevice", "Wireless Access Point", "Network"),
+    ("MediaServer", "Media Server", "Media"),
+    ("MediaRenderer", "Media Renderer", "Media"),
+    ("RemoteUIServerDevice", "Remote UI Server", "Media"),
+    ("RemoteUIClientDevice", "Remote UI Client", "Media"),
+    ("Printer", "Printer", "Printing"),
+    ("Scanner", "Scanner", "Printing"),
+    ("BinaryLight", "Light", "Lighting"),
+    ("DimmableLight", "Dimmable Light", "Lighting"),
+    ("HVAC_System", "HVAC System", "Home Automation"),
+    ("SensorManagement", "Sensor Hub", "Home Automation"),
+    ("SolarProtectionBlind", "Window Blind", "Home Automation"),
+    ("DigitalSecurityCamera", "Security Camera", "Security"),
+    ("TelephonyServer", "Telephony Server", "Telephony"),
+    ("Basic", "Basic Device", "Other"),
+];
+
+fn is_upnp_device_urn(service_type: &str) -> bool {
+    service_type.starts_with("urn:") && service_type.contains(":device:")
+}
+
+// Friendly name and category for a device type URN ("urn:<domain>:device:<class>:<version>").
+// Vendor classes not in the table get their class token split into words
+// ("ZonePlayer" -> "Zone Player") and no category.
+pub fn decode_upnp_device_type(urn: &str) -> Option<(String, Option<&'static str>)> {
+    let class = urn.split(':').nth(3).filter(|c| !c.is_empty())?;
+    if let Some((_, name, category)) = UPNP_DEVICE_TYPES.iter().find(|(c, _, _)| c.eq_ignore_ascii_case(class)) {
+        return Some((name.to_string(), Some(*category)));
+    }
+    
+    let mut name = String::new();
+    let mut previous: Option<char> = None;
+    for c in class.chars() {
+        if c == '_' || c == '-' {
+            name.push(' ');
+        } else {
+            if c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase()) {
+                name.push(' ');
             }
+            name.push(c);
         }
+        previous = Some(c);
     }
-    
-    radar_info!("Network scan complete, found {} hosts", services.len());
-    services
+    Some((name, None))
 }
 
-// Implementation function for network scanning
-pub async fn scan_network_services_impl(
-    app_handle: &AppHandle,
-) -> Result<Vec<ConsolidatedService>, String> {
-    // Start with empty services
-    let mut all_services = Vec::new();
-    
-    // Discover mDNS services and emit events as they're found
-    let mdns_services = crate::discover_mdns_streaming(app_handle.clone()).await.unwrap_or_else(|_| vec![]);
-    all_services.extend(mdns_services);
-    
-    // Discover UPnP services and emit events as they're found
-    let upnp_services = crate::discover_upnp_streaming(app_handle.clone()).await.unwrap_or_else(|_| vec![]);
-    all_services.extend(upnp_services);
-    
-    // Scan network and emit events as hosts are found
-    let network_scan_services = crate::scan_local_network(app_handle).await;
-    all_services.extend(network_scan_services);
+// Infrastructure role for a cleaned mDNS service type (e.g. "sleep-proxy"), if it has one
+fn infrastructure_role(service_type: &str) -> Option<&'static str> {
+    MDNS_SERVICE_TYPES
+        .iter()
+        .find(|t| parse_mdns_name(t.service_type).is_some_and(|n| n.service == service_type))
+        .and_then(|t| t.infrastructure)
+}
 
-    // Store services in the global state
-    let mut discovered_services = DISCOVERED_SERVICES.lock().unwrap();
-    let mut consolidated_services = CONSOLIDATED_SERVICES.lock().unwrap();
-    
-    // Add all services to the discovered set
-    for service in &all_services {
-        discovered_services.insert(service.clone());
+// Bonjour Sleep Proxy ranking from an instance name like "70-35-60-63.1 Apple TV".
+// The pairs are intent, portability, marginal power and total power, so the
+// concatenated number (70356063) orders proxies with lower meaning preferred.
+fn parse_sleep_proxy_priority(details: &str) -> Option<u32> {
+    let metric = txt_value(details, "Full Name")?.split('.').next()?;
+    let pairs: Vec<&str> = metric.split('-').collect();
+    if pairs.len() != 4 || pairs.iter().any(|p| p.len() != 2) {
+        return None;
     }
-    
-    // Consolidate services
-    let consolidated = consolidate_services(all_services);
-    
-    // Update the consolidated services map
-    for service in &consolidated {
-        let key = format!("{}:{}", service.address, service.port.unwrap_or(0));
-        consolidated_services.insert(key, service.clone());
+    pairs.concat().parse().ok()
+}
+
+// AirPort base stations report their firmware version in the "syVs" TXT key
+fn extract_airport_firmware(details: &str) -> Option<String> {
+    txt_value(details, "syVs")
+        .filter(|v| !v.is_empty())
+        .map(|v| format!("AirPort firmware {}", v))
+}
+
+// Apple model identifiers (as advertised in _device-info "model") and their marketing names
+const APPLE_MODELS: &[(&str, &str)] = &[
+    ("MacBookPro18,1", "MacBook Pro 16-inch 2021"),
+    ("MacBookPro18,2", "MacBook Pro 16-inch 2021"),
+    ("MacBookPro18,3", "MacBook Pro 14-inch 2021"),
+    ("MacBookPro18,4", "MacBook Pro 14-inch 2021"),
+    ("MacBookPro17,1", "MacBook Pro 13-inch M1 2020"),
+    ("MacBookPro16,1", "MacBook Pro 16-inch 2019"),
+    ("Mac14,2", "MacBook Air 13-inch M2 2022"),
+    ("Mac14,5", "MacBook Pro 14-inch 2023"),
+    ("Mac14,6", "MacBook Pro 16-inch 2023"),
+    ("Mac14,7", "MacBook Pro 13-inch M2 2022"),
+    ("Mac14,9", "MacBook Pro 14-inch 2023"),
+    ("Mac14,10", "MacBook Pro 16-inch 2023"),
+    ("Mac14,15", "MacBook Air 15-inch M2 2023"),
+    ("Mac14,3", "Mac mini M2 2023"),
+    ("Mac14,12", "Mac mini M2 Pro 2023"),
+    ("Mac14,13", "Mac Studio M2 Max 2023"),
+    ("Mac14,14", "Mac Studio M2 Ultra 2023"),
+    ("Mac15,3", "MacBook Pro 14-inch M3 2023"),
+    ("Mac15,12", "MacBook Air 13-inch M3 2024"),
+    ("Mac15,13", "MacBook Air 15-inch M3 2024"),
+    ("MacBookAir10,1", "MacBook Air M1 2020"),
+    ("Macmini9,1", "Mac mini M1 2020"),
+    ("iMac21,1", "iMac 24-inch M1 2021"),
+    ("iMac21,2", "iMac 24-inch M1 2021"),
+    ("Mac13,1", "Mac Studio M1 Max 2022"),
+    ("Mac13,2", "Mac Studio M1 Ultra 2022"),
+    ("J413AP", "MacBook Air 13-inch M2 2022"),
+    ("J314sAP", "MacBook Pro 14-inch 2021"),
+    ("J316sAP", "MacBook Pro 16-inch 2021"),
+    ("J274AP", "Mac mini M1 2020"),
+    ("J517AP", "iPad Pro 11-inch (3rd generation)"),
+    ("J522AP", "iPad Pro 12.9-inch (5th generation)"),
+    ("J307AP", "iPad Air (4th generation)"),
+    ("J181AP", "iPad (9th generation)"),
+    ("J310AP", "iPad mini (6th generation)"),
+    ("AppleTV6,2", "Apple TV 4K"),
+    ("AppleTV11,1", "Apple TV 4K (2nd generation)"),
+    ("AppleTV14,1", "Apple TV 4K (3rd generation)"),
+    ("AudioAccessory1,1", "HomePod"),
+    ("AudioAccessory5,1", "HomePod mini"),
+    ("AudioAccessory6,1", "HomePod (2nd generation)"),
+];
+
+// Friendly name for an Apple model identifier, or the identifier itself when unknown
+pub fn apple_model_name(model: &str) -> String {
+    APPLE_MODELS
+        .iter()
+        .find(|(id, _)| id.eq_ignore_ascii_case(model))
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| model.to_string())
+}
+
+// macOS version for a Darwin major version ("osxvers"), e.g. 21 -> "macOS 12"
+fn macos_version_from_darwin(darwin: u32) -> Option<String> {
+    match darwin {
+        20.. => Some(format!("macOS {}", darwin - 9)),
+        5..=19 => Some(format!("macOS 10.{}", darwin - 4)),
+        _ => None,
+    }
+}
+
+// Decode the hardware model and OS version from a _device-info service's details
+fn extract_device_info(details: &str) -> (Option<String>, Option<String>) {
+    let model = txt_value(details, "model")
+        .filter(|m| !m.is_empty())
+        .map(apple_model_name);
+    let os_version = txt_value(details, "osxvers")
+        .and_then(|v| v.parse::<u32>().ok())
+        .and_then(macos_version_from_darwin)
+        .or_else(|| txt_value(details, "version").filter(|v| !v.is_empty()).map(str::to_string));
+    (model, os_version)
+}
+
+fn is_device_info_service_type(service_type: &str) -> bool {
+    service_type == "device-info"
+}
+
+// Apple model identifier prefixes (TXT "model", or "am" on AirPlay) -> device class
+const APPLE_MODEL_CLASSES: &[(&str, &str)] = &[
+    ("AppleTV", "Apple TV"),
+    ("AudioAccessory", "HomePod"),
+    ("AirPort", "AirPort base station"),
+    ("iPhone", "iPhone"),
+    ("iPad", "iPad"),
+    ("MacBook", "Mac"),
+    ("iMac", "Mac"),
+    ("Macmini", "Mac"),
+    ("MacPro", "Mac"),
+    ("Mac", "Mac"),
+];
+
+// Friendly device class from the mDNS services a device advertises (cleaned,
+// e.g. "airplay", or full types like "_airplay._tcp.local.") and its TXT
+// records. A model hint in the TXT data wins over what the services suggest.
+pub fn classify_device(services: &[String], txt: &BTreeMap<String, String>) -> Option<String> {
+    let services: HashSet<String> = services
+        .iter()
+        .map(|s| match parse_mdns_name(s) {
+            Some(name) => name.service.to_lowercase(),
+            None => s.trim_start_matches('_').to_lowercase(),
+        })
+        .collect();
+    let has = |service: &str| services.contains(service);
+    let txt_hint = |key: &str| txt.get(key).map(|v| v.trim()).filter(|v| !v.is_empty());
+
+    if let Some(class) = ["model", "am"]
+        .iter()
+        .filter_map(|key| txt_hint(key))
+        .find_map(|model| APPLE_MODEL_CLASSES.iter().find(|(prefix, _)| model.starts_with(prefix)))
+    {
+        return Some(class.1.to_string());
+    }
+
+    if has("googlecast") {
+        // Cast speakers and displays name themselves in "md" ("Google Nest Hub")
+        return Some(match txt_hint("md") {
+            Some(model) if model.contains("Google Home") || model.contains("Nest") => "Google Home / Nest device".to_string(),
+            _ => "Chromecast".to_string(),
+        });
+    }
+    if has("airplay") && has("raop") {
+        return Some("Apple TV / AirPlay speaker".to_string());
+    }
+    if has("raop") {
+        return Some("AirPlay speaker".to_string());
     }
+    if has("airplay") {
+        return Some("AirPlay receiver".to_string());
+    }
+    if ["ipp", "ipps", "printer", "pdl-datastream"].iter().any(|s| has(s)) {
+        return Some("Printer".to_string());
+    }
+    if has("uscan") || has("scanner") {
+        return Some("Scanner".to_string());
+    }
+    if has("hap") || has("homekit") {
+        return Some("HomeKit accessory".to_string());
+    }
+    if has("spotify-connect") || has("sonos") {
+        return Some("Speaker".to_string());
+    }
+    None
+}
+
+// Run the registered enrichers mid-consolidation, so the description built
+// next reflects the device type they settle on
+fn apply_enrichers_traced(consolidated: &mut ConsolidatedService, mut trace: Option<&mut ConsolidationTrace>) {
+    let before = consolidated.device_type.clone();
+    crate::enricher::apply_enrichers(consolidated);
+    if consolidated.device_type != before {
+        if let Some(device_type) = &consolidated.device_type {
+            note(&mut trace, |t| t.device_type_steps.push(format!(
+                "Enrichers classified services {:?} as '{}'",
+                consolidated.service_types, device_type
+            )));
+        }
+    }
+}
+
+// MAC address a sweep put in service details ("MAC Address: ..." or, for IPv6 neighbors, "MAC: ...")
+fn details_mac(details: &str) -> Option<String> {
+    details.lines().find_map(|line| {
+        let mac = line.strip_prefix("MAC Address:").or_else(|| line.strip_prefix("MAC:"))?;
+        crate::inventory::normalize_mac(mac.trim())
+    })
+}
+
+// Extract the mDNS device id (e.g. the AirPlay "deviceid" TXT key) from service details
+fn extract_mdns_device_id(details: &str) -> Option<String> {
+    details.lines().find_map(|line| {
+        let (key, value) = line.trim().split_once(':')?;
+        if key.trim().eq_ignore_ascii_case("deviceid") && !value.trim().is_empty() {
+            Some(value.trim().to_lowercase())
+        } else {
+            None
+        }
+    })
+}
 
-    let result = Ok(consolidated);
-    return result;
+// Pick the strongest identity available: UPnP UUID, then mDNS device id, then address
+fn resolve_identity_key(current: Option<&str>, uuid: Option<&str>, details: Option<&str>, address: &str) -> String {
+    if let Some(uuid) = uuid.filter(|u| !u.is_empty()) {
+        return format!("uuid:{}", uuid.to_lowercase());
+    }
+    if let Some(current) = current.filter(|k| !k.starts_with("addr:")) {
+        return current.to_string();
+    }
+    if let Some(device_id) = details.and_then(extract_mdns_device_id) {
+        return format!("mdns:{}", device_id);
+    }
+    format!("addr:{}", address)
 }
 
 // Function to consolidate services from different discovery methods
 pub fn consolidate_services(services: Vec<NetworkService>) -> Vec<ConsolidatedService> {
     let mut service_map: HashMap<String, ConsolidatedService> = HashMap::new();
     
-    // First pass: Create or update services by IP address
+    // First pass: Create or update services by UPnP UUID, so one device found at
+    // several IPs stays one entry and separate devices sharing an IP stay apart.
+    // Everything without a UUID is keyed on its IP address.
     for service in services {
-        // Use IP address as the primary key for consolidation
-        let key = service.address.clone();
+        let key = match service_uuid(&service) {
+            Some(uuid) => format!("uuid:{}", uuid),
+            None => format!("addr:{}", service.address),
+        };
         
         if let Some(existing) = service_map.get_mut(&key) {
             // Update existing consolidated service
-            update_consolidated_service(existing, &service);
+            update_consolidated_service(existing, &service, None);
         } else {
             // Create new consolidated service
-            let consolidated = create_consolidated_service(&service);
+            let consolidated = create_consolidated_service(&service, None);
             service_map.insert(key, consolidated);
         }
     }
     
+    // Second pass: fold what other methods found at an address (open ports,
+    // hostnames, mDNS) into the UPnP device there. With several devices on one
+    // IP, the first by UUID takes it.
+    let mut address_keys: Vec<String> = service_map.keys().filter(|k| k.starts_with("addr:")).cloned().collect();
+    address_keys.sort();
+    for address_key in address_keys {
+        let address = &address_key["addr:".len()..];
+        let owner = service_map
+            .iter()
+            .filter(|(key, service)| key.starts_with("uuid:") && service.addresses.iter().any(|a| a == address))
+            .map(|(key, _)| key.clone())
+            .min();
+        let Some(owner) = owner else {
+            continue;
+        };
+        if let Some(other) = service_map.remove(&address_key) {
+            if let Some(target) = service_map.get_mut(&owner) {
+                absorb_service(target, other);
+            }
+        }
+    }
+    
+    // Risk flags and other enrichers depend on everything known about the device, so run them last
+    for consolidated in service_map.values_mut() {
+        crate::enricher::apply_enrichers(consolidated);
+    }
+    
     // Convert map to vector and sort by address for consistent display
     let mut consolidated: Vec<ConsolidatedService> = service_map.into_values().collect();
-    consolidated.sort_by(|a, b| a.address.cmp(&b.address));
+    sort_services(&mut consolidated, SortKey::Ip);
     consolidated
 }
 
-// Function to create a new consolidated service from a network service
-fn create_consolidated_service(service: &NetworkService) -> ConsolidatedService {
+// Security flags reported on a consolidated service:
+//   telnet-exposed    Telnet (23/tcp or _telnet._tcp) sends credentials in cleartext
+//   ftp-exposed       FTP (20-21/tcp or _ftp._tcp) sends credentials in cleartext
+//   smb1-possible     NetBIOS session service (139/tcp) is open, which usually means SMBv1 is enabled
+//   upnp-igd-exposed  The device is a UPnP Internet Gateway that lets LAN clients open router ports
+pub const FLAG_TELNET_EXPOSED: &str = "telnet-exposed";
+pub const FLAG_FTP_EXPOSED: &str = "ftp-exposed";
+pub const FLAG_SMB1_POSSIBLE: &str = "smb1-possible";
+pub const FLAG_UPNP_IGD_EXPOSED: &str = "upnp-igd-exposed";
+
+// Work out which insecure-by-design services a device exposes
+pub(crate) fn compute_security_flags(service: &ConsolidatedService) -> Vec<String> {
+    let has_port = |ports: &[u16]| ports.iter().any(|p| service.open_ports.contains_key(p));
+    let has_type = |types: &[&str]| {
+        service.service_types.iter().any(|t| types.contains(&t.to_lowercase().as_str()))
+    };
+    
+    let mut flags = Vec::new();
+    
+    if has_port(&[23]) || has_type(&["telnet"]) {
+        flags.push(FLAG_TELNET_EXPOSED.to_string());
+    }
+    
+    if has_port(&[20, 21]) || has_type(&["ftp"]) {
+        flags.push(FLAG_FTP_EXPOSED.to_string());
+    }
+    
+    if has_port(&[139]) {
+        flags.push(FLAG_SMB1_POSSIBLE.to_string());
+    }
+    
+    let is_igd = service.service_types.iter().any(|t| {
+        t.contains("InternetGatewayDevice") || t.contains("WANIPConnection") || t.contains("WANPPPConnection")
+    });
+    if is_igd {
+        flags.push(FLAG_UPNP_IGD_EXPOSED.to_string());
+    }
+    
+    flags
+}
+
+// Record of how consolidation built one device, returned by `explain_service`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConsolidationTrace {
+    pub identity_key: String,
+    pub address: String,
+    // Raw records that were merged, in the order they were applied
+    pub records: Vec<NetworkService>,
+    // Details lines that set a field on the consolidated service
+    pub parsed_lines: Vec<ParsedLine>,
+    // How device_type was derived, one step per decision
+    pub device_type_steps: Vec<String>,
+    // How device_category was derived
+    pub category_steps: Vec<String>,
+    // How the final friendly description was put together
+    pub description_steps: Vec<String>,
+    pub result: Option<ConsolidatedService>,
+}
+
+// One details line and the field it was parsed into
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParsedLine {
+    pub method: String,
+    pub line: String,
+    pub field: String,
+    pub value: String,
+}
+
+impl ConsolidationTrace {
+    fn parsed(&mut self, service: &NetworkService, line: &str, field: &str, value: impl Into<String>) {
+        self.parsed_lines.push(ParsedLine {
+            method: service.discovery_method.clone(),
+            line: line.trim().to_string(),
+            field: field.to_string(),
+            value: value.into(),
+        });
+    }
+}
+
+// Record a consolidation decision when a trace is being collected
+fn note(trace: &mut Option<&mut ConsolidationTrace>, record: impl FnOnce(&mut ConsolidationTrace)) {
+    if let Some(trace) = trace.as_deref_mut() {
+        record(trace);
+    }
+}
+
+// Replay consolidation of every discovered record of the stored device with an
+// identity key, recording each decision. The device's records are those at any
+// of its addresses, less the ones another UPnP device on the same IP claims.
+// Records carrying the device's UUID go first, as in consolidation; the rest
+// are grouped by discovery method, which may differ from the order the scan saw them.
+pub fn explain_service(identity_key: &str) -> Option<ConsolidationTrace> {
+    let device = {
+        let store = CONSOLIDATED_SERVICES.lock().unwrap();
+        store.get(identity_key).or_else(|| store.values().find(|s| s.identity_key == identity_key)).cloned()?
+    };
+    let belongs = |service: &NetworkService| {
+        let at_address = device.addresses.contains(&service.address)
+            || device.ipv6_addresses.contains(&service.address)
+            || device.address == service.address;
+        let claimed_elsewhere = service_uuid(service)
+            .is_some_and(|uuid| !device.uuid.as_deref().is_some_and(|own| own.eq_ignore_ascii_case(&uuid)));
+        at_address && !claimed_elsewhere
+    };
+    let mut records: Vec<NetworkService> = DISCOVERED_SERVICES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|s| belongs(s))
+        .cloned()
+        .collect();
+    records.sort_by(|a, b| {
+        (service_uuid(a).is_none(), &a.discovery_method, &a.service_type, a.port)
+            .cmp(&(service_uuid(b).is_none(), &b.discovery_method, &b.service_type, b.port))
+    });
+    let (first, rest) = records.split_first()?;
+    
+    let mut trace = ConsolidationTrace {
+        identity_key: device.identity_key.clone(),
+        address: device.address.clone(),
+        records: records.clone(),
+        ..ConsolidationTrace::default()
+    };
+    let mut consolidated = create_consolidated_service(first, Some(&mut trace));
+    for service in rest {
+        update_consolidated_service(&mut consolidated, service, Some(&mut trace));
+    }
+    crate::enricher::apply_enrichers(&mut consolidated);
+    trace.result = Some(consolidated);
+    Some(trace)
+}
+
+// Trace step for the category a UPnP device type decodes to
+fn category_step(urn: &str, category: Option<&str>) -> String {
+    match category {
+        Some(category) => format!("UPnP device type '{}' is in category '{}'", urn, category),
+        None => format!("UPnP device type '{}' has no known category", urn),
+    }
+}
+
+// A UDP port that never replied may not be open at all, so consolidation
+// doesn't list it among the open ports
+fn is_unconfirmed_port(service: &NetworkService) -> bool {
+    service.port_state == Some(PortState::Filtered)
+}
+
+fn create_consolidated_service(service: &NetworkService, mut trace: Option<&mut ConsolidationTrace>) -> ConsolidatedService {
     let mut hostname = None;
     let mut uuid = None;
     let mut location_url = None;
@@ -958,17 +5158,22 @@ fn create_consolidated_service(service: &NetworkService) -> ConsolidatedService
         for line in details.lines() {
             if line.starts_with("Host:") || line.starts_with("Hostname:") {
                 hostname = line.split(':').nth(1).map(|s| s.trim().to_string());
+                note(&mut trace, |t| t.parsed(service, line, "hostname", hostname.clone().unwrap_or_default()));
             } else if line.starts_with("UUID:") {
                 uuid = line.split(':').nth(1).map(|s| s.trim().to_string());
+                note(&mut trace, |t| t.parsed(service, line, "uuid", uuid.clone().unwrap_or_default()));
             } else if line.starts_with("Location:") {
                 location_url = line.split(':').nth(1).map(|s| s.trim().to_string());
+                note(&mut trace, |t| t.parsed(service, line, "location_url", location_url.clone().unwrap_or_default()));
             } else if line.starts_with("Server:") {
                 server_info = line.split(':').nth(1).map(|s| s.trim().to_string());
+                note(&mut trace, |t| t.parsed(service, line, "server_info", server_info.clone().unwrap_or_default()));
             } else if line.contains("://") && line.contains(":") {
                 // This might be a URL with port
                 if let Some(port_str) = line.split(':').last() {
                     if let Ok(port) = port_str.trim().parse::<u16>() {
                         open_ports.insert(port, "Web Interface".to_string());
+                        note(&mut trace, |t| t.parsed(service, line, "open_ports", format!("{} -> Web Interface", port)));
                     }
                 }
             } else if line.contains(":") && !line.starts_with("IP:") && !line.starts_with("Type:") && 
@@ -978,6 +5183,7 @@ fn create_consolidated_service(service: &NetworkService) -> ConsolidatedService
                 if parts.len() == 2 {
                     if let Ok(port) = parts[0].trim().parse::<u16>() {
                         open_ports.insert(port, parts[1].trim().to_string());
+                        note(&mut trace, |t| t.parsed(service, line, "open_ports", format!("{} -> {}", port, parts[1].trim())));
                     }
                 }
             }
@@ -985,7 +5191,7 @@ fn create_consolidated_service(service: &NetworkService) -> ConsolidatedService
     }
     
     // Add the current service's port if it exists
-    if let Some(port) = service.port {
+    if let Some(port) = service.port.filter(|_| !is_unconfirmed_port(service)) {
         if !open_ports.contains_key(&port) {
             open_ports.insert(port, service.service_type.clone());
         }
@@ -998,6 +5204,63 @@ fn create_consolidated_service(service: &NetworkService) -> ConsolidatedService
     } else {
         Some(service.service_type.clone())
     };
+    note(&mut trace, |t| t.device_type_steps.push(format!(
+        "{} record of type '{}' gave initial device type '{}'",
+        service.discovery_method, service.service_type, device_type.clone().unwrap_or_default()
+    )));
+    
+    // Matter devices are classified as smart home regardless of what they advertise
+    let matter = if is_matter_service_type(&service.service_type) {
+        service.details.as_deref().and_then(extract_matter_info)
+    } else {
+        None
+    };
+    let infrastructure = infrastructure_role(&service.service_type);
+    let upnp_device = if is_upnp_device_urn(&service.service_type) {
+        decode_upnp_device_type(&service.service_type)
+    } else {
+        None
+    };
+    if let Some((_, category)) = upnp_device {
+        note(&mut trace, |t| t.category_steps.push(category_step(&service.service_type, category)));
+    }
+    let device_type = if is_matter_service_type(&service.service_type) {
+        note(&mut trace, |t| t.device_type_steps.push(format!("'{}' is a Matter service: SmartHome", service.service_type)));
+        Some("SmartHome".to_string())
+    } else if let Some(role) = infrastructure {
+        note(&mut trace, |t| t.device_type_steps.push(format!("'{}' is a {} service: Infrastructure", service.service_type, role)));
+        Some("Infrastructure".to_string())
+    } else if let Some((name, _)) = &upnp_device {
+        note(&mut trace, |t| t.device_type_steps.push(format!("UPnP device type '{}' decoded as '{}'", service.service_type, name)));
+        Some(name.clone())
+    } else {
+        device_type
+    };
+    let device_type = match classify_device(std::slice::from_ref(&service.service_type), &service.txt_records) {
+        Some(class) => {
+            note(&mut trace, |t| t.device_type_steps.push(format!("'{}' classified as '{}'", service.service_type, class)));
+            Some(class)
+        }
+        None => device_type,
+    };
+    let sleep_proxy_priority = match (infrastructure, service.details.as_deref()) {
+        (Some("sleep-proxy"), Some(details)) => parse_sleep_proxy_priority(details),
+        _ => None,
+    };
+    
+    let (hardware_model, os_version) = match service.details.as_deref() {
+        Some(details) if is_device_info_service_type(&service.service_type) => extract_device_info(details),
+        Some(details) if infrastructure == Some("base-station") => (None, extract_airport_firmware(details)),
+        _ => (None, None),
+    };
+    if let Some(model) = &hardware_model {
+        note(&mut trace, |t| t.parsed(service, "TXT model", "hardware_model", model.clone()));
+    }
+    if let Some(version) = &os_version {
+        note(&mut trace, |t| t.parsed(service, "TXT osxvers/version/syVs", "os_version", version.clone()));
+    }
+    
+    let identity_key = resolve_identity_key(None, uuid.as_deref(), service.details.as_deref(), &service.address);
     
     // Create friendly description
     let friendly_description = create_friendly_description(
@@ -1008,26 +5271,58 @@ fn create_consolidated_service(service: &NetworkService) -> ConsolidatedService
         &device_type,
         &open_ports,
         &service_details,
+        trace,
     );
     
     ConsolidatedService {
         name: service.name.clone(),
         address: service.address.clone(),
+        address_family: service.address_family,
         port: service.port,
         hostname,
         device_type,
         discovery_methods: vec![service.discovery_method.clone()],
         service_types: vec![service.service_type.clone()],
         open_ports,
+        identity_key,
         uuid,
         location_url,
         server_info,
         friendly_description,
+        security_flags: Vec::new(),
+        matter,
+        manufacturer: service.details.as_deref().and_then(service_manufacturer),
+        mac_address: service.details.as_deref().and_then(details_mac),
+        hardware_model,
+        os_version,
+        infrastructure: infrastructure.map(str::to_string),
+        sleep_proxy_priority,
+        discovered_on_interface: service.details.as_deref().and_then(|d| txt_value(d, "Interface")).map(str::to_string),
+        possibly_rate_limited: service.possibly_rate_limited,
+        first_seen: Some(unix_now()),
+        last_seen: Some(unix_now()),
+        upnp_device_type: upnp_device.is_some().then(|| service.service_type.clone()),
+        device_category: upnp_device.and_then(|(_, category)| category).map(str::to_string),
+        addresses: vec![service.address.clone()],
+        ipv6_addresses: Vec::new(),
+        txt_records: service.txt_records.clone(),
+        source_details: service
+            .details
+            .iter()
+            .map(|details| SourceDetails {
+                method: service.discovery_method.clone(),
+                details: details.clone(),
+            })
+            .collect(),
     }
 }
 
 // Function to update an existing consolidated service with information from another service
-fn update_consolidated_service(consolidated: &mut ConsolidatedService, service: &NetworkService) {
+fn update_consolidated_service(
+    consolidated: &mut ConsolidatedService,
+    service: &NetworkService,
+    mut trace: Option<&mut ConsolidationTrace>,
+) {
     // Add discovery method if not already present
     if !consolidated.discovery_methods.contains(&service.discovery_method) {
         consolidated.discovery_methods.push(service.discovery_method.clone());
@@ -1038,8 +5333,18 @@ fn update_consolidated_service(consolidated: &mut ConsolidatedService, service:
         consolidated.service_types.push(service.service_type.clone());
     }
     
+    consolidated.possibly_rate_limited |= service.possibly_rate_limited;
+    if consolidated.mac_address.is_none() {
+        consolidated.mac_address = service.details.as_deref().and_then(details_mac);
+    }
+    consolidated.last_seen = Some(unix_now());
+    consolidated.first_seen.get_or_insert(unix_now());
+    if !consolidated.addresses.contains(&service.address) {
+        consolidated.addresses.push(service.address.clone());
+    }
+    
     // Add port if it exists and is not already present
-    if let Some(port) = service.port {
+    if let Some(port) = service.port.filter(|_| !is_unconfirmed_port(service)) {
         if !consolidated.open_ports.contains_key(&port) {
             consolidated.open_ports.insert(port, service.service_type.clone());
         }
@@ -1053,28 +5358,34 @@ fn update_consolidated_service(consolidated: &mut ConsolidatedService, service:
             if line.starts_with("Host:") || line.starts_with("Hostname:") {
                 if consolidated.hostname.is_none() {
                     consolidated.hostname = Some(line.split(':').nth(1).unwrap_or("").trim().to_string());
+                    note(&mut trace, |t| t.parsed(service, line, "hostname", consolidated.hostname.clone().unwrap_or_default()));
                 }
             } else if line.starts_with("UUID:") {
                 if consolidated.uuid.is_none() {
                     consolidated.uuid = Some(line.split(':').nth(1).unwrap_or("").trim().to_string());
+                    note(&mut trace, |t| t.parsed(service, line, "uuid", consolidated.uuid.clone().unwrap_or_default()));
                 }
             } else if line.starts_with("Location:") {
                 if consolidated.location_url.is_none() {
                     consolidated.location_url = Some(line.split(':').nth(1).unwrap_or("").trim().to_string());
+                    note(&mut trace, |t| t.parsed(service, line, "location_url", consolidated.location_url.clone().unwrap_or_default()));
                 }
             } else if line.starts_with("Server:") {
                 if consolidated.server_info.is_none() {
                     consolidated.server_info = Some(line.split(':').nth(1).unwrap_or("").trim().to_string());
+                    note(&mut trace, |t| t.parsed(service, line, "server_info", consolidated.server_info.clone().unwrap_or_default()));
                 }
             } else if line.starts_with("Model:") || line.starts_with("Manufacturer:") || 
                       line.starts_with("Device Type:") || line.starts_with("Service Type:") {
                 service_details.push(line.trim().to_string());
+                note(&mut trace, |t| t.parsed(service, line, "friendly_description", line.trim()));
             } else if line.contains("://") && line.contains(":") {
                 // This might be a URL with port
                 if let Some(port_str) = line.split(':').last() {
                     if let Ok(port) = port_str.trim().parse::<u16>() {
                         if !consolidated.open_ports.contains_key(&port) {
                             consolidated.open_ports.insert(port, "Web Interface".to_string());
+                            note(&mut trace, |t| t.parsed(service, line, "open_ports", format!("{} -> Web Interface", port)));
                         }
                     }
                 }
@@ -1086,6 +5397,7 @@ fn update_consolidated_service(consolidated: &mut ConsolidatedService, service:
                     if let Ok(port) = parts[0].trim().parse::<u16>() {
                         if !consolidated.open_ports.contains_key(&port) {
                             consolidated.open_ports.insert(port, parts[1].trim().to_string());
+                            note(&mut trace, |t| t.parsed(service, line, "open_ports", format!("{} -> {}", port, parts[1].trim())));
                         }
                     }
                 }
@@ -1093,6 +5405,98 @@ fn update_consolidated_service(consolidated: &mut ConsolidatedService, service:
         }
     }
     
+    if is_matter_service_type(&service.service_type) {
+        consolidated.device_type = Some("SmartHome".to_string());
+        note(&mut trace, |t| t.device_type_steps.push(format!("'{}' is a Matter service: SmartHome", service.service_type)));
+        if consolidated.matter.is_none() {
+            consolidated.matter = service.details.as_deref().and_then(extract_matter_info);
+        }
+    }
+    
+    // A UPnP description names the device's maker; the OUI may only name its network chip's
+    if let Some(details) = &service.details {
+        if let Some(manufacturer) = txt_value(details, "Manufacturer") {
+            consolidated.manufacturer = Some(manufacturer.to_string());
+        } else if consolidated.manufacturer.is_none() {
+            consolidated.manufacturer = service_manufacturer(details);
+        }
+    }
+    
+    if consolidated.discovered_on_interface.is_none() {
+        consolidated.discovered_on_interface =
+            service.details.as_deref().and_then(|d| txt_value(d, "Interface")).map(str::to_string);
+    }
+    
+    if is_upnp_device_urn(&service.service_type) && consolidated.upnp_device_type.is_none() {
+        if let Some((name, category)) = decode_upnp_device_type(&service.service_type) {
+            consolidated.upnp_device_type = Some(service.service_type.clone());
+            consolidated.device_category = category.map(str::to_string);
+            note(&mut trace, |t| t.category_steps.push(category_step(&service.service_type, category)));
+            
+            // A UPnP description says more than a bare host or port-scan guess
+            let generic = matches!(
+                consolidated.device_type.as_deref(),
+                None | Some("host") | Some("network_device") | Some("unknown")
+            ) || consolidated.discovery_methods == [METHOD_NETWORK_SCAN];
+            if generic {
+                note(&mut trace, |t| t.device_type_steps.push(format!("UPnP device type '{}' decoded as '{}'", service.service_type, name)));
+                consolidated.device_type = Some(name);
+            }
+        }
+    }
+    
+    if is_device_info_service_type(&service.service_type) {
+        if let Some(details) = &service.details {
+            let (hardware_model, os_version) = extract_device_info(details);
+            if consolidated.hardware_model.is_none() {
+                if let Some(model) = &hardware_model {
+                    note(&mut trace, |t| t.parsed(service, "TXT model", "hardware_model", model.clone()));
+                }
+            }
+            consolidated.hardware_model = consolidated.hardware_model.take().or(hardware_model);
+            consolidated.os_version = consolidated.os_version.take().or(os_version);
+        }
+    }
+    
+    if let Some(role) = infrastructure_role(&service.service_type) {
+        consolidated.device_type = Some("Infrastructure".to_string());
+        note(&mut trace, |t| t.device_type_steps.push(format!("'{}' is a {} service: Infrastructure", service.service_type, role)));
+        consolidated.infrastructure.get_or_insert_with(|| role.to_string());
+        if let Some(details) = &service.details {
+            match role {
+                "sleep-proxy" if consolidated.sleep_proxy_priority.is_none() => {
+                    consolidated.sleep_proxy_priority = parse_sleep_proxy_priority(details);
+                }
+                "base-station" if consolidated.os_version.is_none() => {
+                    consolidated.os_version = extract_airport_firmware(details);
+                }
+                _ => {}
+            }
+        }
+    }
+    
+    if let Some(details) = &service.details {
+        let source = SourceDetails {
+            method: service.discovery_method.clone(),
+            details: details.clone(),
+        };
+        if !consolidated.source_details.contains(&source) {
+            consolidated.source_details.push(source);
+        }
+    }
+    
+    for (key, value) in &service.txt_records {
+        consolidated.txt_records.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+    apply_enrichers_traced(consolidated, trace.as_deref_mut());
+    
+    consolidated.identity_key = resolve_identity_key(
+        Some(&consolidated.identity_key),
+        consolidated.uuid.as_deref(),
+        service.details.as_deref(),
+        &consolidated.address,
+    );
+    
     // Update friendly description
     consolidated.friendly_description = create_friendly_description(
         &consolidated.name,
@@ -1102,10 +5506,12 @@ fn update_consolidated_service(consolidated: &mut ConsolidatedService, service:
         &consolidated.device_type,
         &consolidated.open_ports,
         &service_details,
+        trace,
     );
 }
 
 // Function to create a friendly description for a service
+#[allow(clippy::too_many_arguments)]
 fn create_friendly_description(
     name: &str,
     address: &str,
@@ -1114,12 +5520,18 @@ fn create_friendly_description(
     device_type: &Option<String>,
     open_ports: &HashMap<u16, String>,
     service_details: &[String],
+    mut trace: Option<&mut ConsolidationTrace>,
 ) -> String {
     let mut parts = Vec::new();
+    // Only the latest assembly matters, since the description is rebuilt on every update
+    note(&mut trace, |t| t.description_steps.clear());
     
     // Add name if it's not too long or complex
     if name.len() < 30 && !name.contains(".") {
         parts.push(format!("{}", name));
+        note(&mut trace, |t| t.description_steps.push(format!("name '{}'", name)));
+    } else {
+        note(&mut trace, |t| t.description_steps.push(format!("skipped name '{}' (30+ chars or contains '.')", name)));
     }
     
     // Add hostname if available
@@ -1129,12 +5541,16 @@ fn create_friendly_description(
         } else {
             parts.push(host.clone());
         }
+        note(&mut trace, |t| t.description_steps.push(format!("hostname '{}'", host)));
     }
     
     // Add device type if available
     if let Some(dtype) = device_type {
         if dtype != "host" && dtype != "unknown" {
             parts.push(format!("[{}]", dtype));
+            note(&mut trace, |t| t.description_steps.push(format!("device type [{}]", dtype)));
+        } else {
+            note(&mut trace, |t| t.description_steps.push(format!("skipped generic device type '{}'", dtype)));
         }
     }
     
@@ -1142,6 +5558,7 @@ fn create_friendly_description(
     let mut description = if !parts.is_empty() {
         parts.join(" ")
     } else {
+        note(&mut trace, |t| t.description_steps.push("no name, hostname or type: 'Device at <address>'".to_string()));
         format!("Device at {}", address)
     };
     
@@ -1160,10 +5577,12 @@ fn create_friendly_description(
             .collect();
         
         description.push_str(&format!(" - Services: {}", services.join(", ")));
+        note(&mut trace, |t| t.description_steps.push(format!("{} open ports", open_ports.len())));
     }
     
     // Add additional service details
     if !service_details.is_empty() {
+        note(&mut trace, |t| t.description_steps.push(format!("details: {}", service_details.join(", "))));
         description.push_str("\nDetails: ");
         description.push_str(&service_details.join(", "));
     }
@@ -1171,45 +5590,416 @@ fn create_friendly_description(
     description
 }
 
-async fn fetch_device_description(location_url: &str) -> Option<DeviceDescription> {
-    match reqwest::get(location_url).await {
-        Ok(response) => {
-            if let Ok(text) = response.text().await {
-                match quick_xml::de::from_str(&text) {
-                    Ok(desc) => Some(desc),
-                    Err(_) => None,
-                }
-            } else {
-                None
-            }
-        }
-        Err(_) => None,
-    }
-}
-
 // Initialize the network scanner module
 pub fn init<R: tauri::Runtime>(_app: &mut tauri::App<R>) -> std::result::Result<(), Box<dyn std::error::Error>> {
     radar_info!("Initializing network scanner module");
     Ok(())
 }
 
-// Function to get hostname from IP address
+// Function to get hostname from IP address (cached, see dns::reverse_lookup)
 async fn get_hostname_from_ip(ip: &str) -> Option<String> {
-    if let Ok(ip_addr) = ip.parse::<std::net::IpAddr>() {
-        match tokio::net::lookup_host(format!("{}:0", ip)).await {
-            Ok(addrs) => {
-                for addr in addrs {
-                    if addr.ip() == ip_addr {
-                        if let Ok(hostname) = dns_lookup::lookup_addr(&ip_addr) {
-                            return Some(hostname);
-                        }
-                    }
-                }
-                None
+    match ip.parse::<std::net::IpAddr>() {
+        Ok(ip_addr) => crate::dns::reverse_lookup(ip_addr).await,
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(address: &str, port: Option<u16>, details: &str) -> NetworkService {
+        NetworkService {
+            name: format!("Device at {}", address),
+            service_type: "host".to_string(),
+            address: address.to_string(),
+            address_family: AddressFamily::of(address),
+            port,
+            discovery_method: METHOD_UPNP.to_string(),
+            details: Some(details.to_string()),
+            txt_records: BTreeMap::new(),
+            port_state: None,
+            possibly_rate_limited: false,
+        }
+    }
+
+    #[test]
+    fn identity_key_prefers_uuid_then_device_id_then_address() {
+        assert_eq!(resolve_identity_key(None, Some("ABC-1"), Some("deviceid: AA:BB"), "10.0.0.2"), "uuid:abc-1");
+        assert_eq!(resolve_identity_key(None, None, Some("deviceid: AA:BB"), "10.0.0.2"), "mdns:aa:bb");
+        assert_eq!(resolve_identity_key(Some("mdns:aa:bb"), None, None, "10.0.0.2"), "mdns:aa:bb");
+        assert_eq!(resolve_identity_key(Some("addr:10.0.0.1"), None, None, "10.0.0.2"), "addr:10.0.0.2");
+    }
+
+    #[test]
+    fn one_uuid_at_two_addresses_is_one_device() {
+        let consolidated = consolidate_services(vec![
+            service("10.0.0.2", None, "UUID: abc-1"),
+            service("10.0.0.3", None, "UUID: ABC-1"),
+        ]);
+        assert_eq!(consolidated.len(), 1);
+        assert_eq!(consolidated[0].identity_key, "uuid:abc-1");
+    }
+
+    #[test]
+    fn stored_address_entry_is_rekeyed_by_uuid() {
+        let mut store = HashMap::new();
+        store_consolidated_services(&mut store, &mut consolidate_services(vec![service("10.0.0.2", None, "")]));
+        assert!(store.contains_key("addr:10.0.0.2"));
+
+        store_consolidated_services(&mut store, &mut consolidate_services(vec![service("10.0.0.2", None, "UUID: abc-1")]));
+        assert_eq!(store.len(), 1);
+        assert!(store.contains_key("uuid:abc-1"));
+    }
+
+    #[test]
+    fn port_change_replaces_the_stored_device() {
+        let mut store = HashMap::new();
+        store_consolidated_services(&mut store, &mut consolidate_services(vec![service("10.0.0.2", Some(8080), "")]));
+        store_consolidated_services(&mut store, &mut consolidate_services(vec![service("10.0.0.2", Some(8081), "")]));
+
+        assert_eq!(store.len(), 1);
+        let device = &store["addr:10.0.0.2"];
+        assert!(device.open_ports.contains_key(&8081));
+        assert!(!device.open_ports.contains_key(&8080));
+    }
+
+    #[test]
+    fn matter_vp_splits_vendor_and_optional_product() {
+        assert_eq!(parse_matter_vp("4937+32769"), Some((0x1349, Some(0x8001))));
+        assert_eq!(parse_matter_vp("65521"), Some((0xFFF1, None)));
+        assert_eq!(parse_matter_vp("4937+"), None);
+        assert_eq!(parse_matter_vp("apple"), None);
+    }
+
+    #[test]
+    fn matter_txt_keys_are_decoded() {
+        let info = extract_matter_info("Full Name: A1B2._matter._tcp.local.\n  VP: 4937+32769\n  CM: 2\n  D: 3840").unwrap();
+        assert_eq!(info.vendor_id, Some(0x1349));
+        assert_eq!(info.product_id, Some(0x8001));
+        assert_eq!(info.vendor_name.as_deref(), Some("Apple"));
+        assert_eq!(info.commissioning_mode.as_deref(), Some("enhanced"));
+        assert_eq!(info.discriminator, Some(3840));
+
+        assert!(extract_matter_info("Full Name: printer._ipp._tcp.local.").is_none());
+    }
+
+    #[test]
+    fn standard_upnp_device_types_have_a_category() {
+        assert_eq!(
+            decode_upnp_device_type("urn:schemas-upnp-org:device:InternetGatewayDevice:1"),
+            Some(("Router".to_string(), Some("Network")))
+        );
+        assert_eq!(
+            decode_upnp_device_type("urn:schemas-upnp-org:device:mediarenderer:3"),
+            Some(("Media Renderer".to_string(), Some("Media")))
+        );
+    }
+
+    #[test]
+    fn vendor_upnp_device_types_are_split_into_words() {
+        assert_eq!(
+            decode_upnp_device_type("urn:schemas-upnp-org:device:ZonePlayer:1"),
+            Some(("Zone Player".to_string(), None))
+        );
+        assert_eq!(
+            decode_upnp_device_type("urn:dial-multiscreen-org:device:dial_receiver:1"),
+            Some(("dial receiver".to_string(), None))
+        );
+        assert_eq!(decode_upnp_device_type("urn:schemas-upnp-org:device"), None);
+    }
+
+    #[test]
+    fn mdns_service_type_has_no_instance() {
+        let name = parse_mdns_name("_ipp._tcp.local.").unwrap();
+        assert_eq!(name.instance, None);
+        assert_eq!(name.service, "ipp");
+        assert_eq!(name.protocol, "tcp");
+        assert_eq!(name.domain, "local");
+    }
+
+    #[test]
+    fn mdns_protocol_comes_from_the_last_protocol_label() {
+        // "_udp" inside the instance name isn't the protocol
+        let name = parse_mdns_name("Backup _udp box._smb._TCP.local.").unwrap();
+        assert_eq!(name.instance.as_deref(), Some("Backup _udp box"));
+        assert_eq!(name.service, "smb");
+        assert_eq!(name.protocol, "tcp");
+
+        let name = parse_mdns_name("Living Room\\.2._airplay._tcp.local.").unwrap();
+        assert_eq!(name.instance.as_deref(), Some("Living Room.2"));
+    }
+
+    #[test]
+    fn mdns_subtype_and_meta_queries_have_no_instance() {
+        let name = parse_mdns_name("_printer._sub._http._tcp.local.").unwrap();
+        assert_eq!(name.instance, None);
+        assert_eq!(name.service, "http");
+
+        let name = parse_mdns_name("_services._dns-sd._udp.local.").unwrap();
+        assert_eq!(name.instance, None);
+        assert_eq!(name.service, "dns-sd");
+        assert_eq!(name.protocol, "udp");
+
+        assert_eq!(parse_mdns_name("printer.local."), None);
+        assert_eq!(parse_mdns_name("_tcp.local."), None);
+    }
+
+    #[test]
+    fn link_local_addresses_get_the_interface_zone() {
+        assert_eq!(scoped_address("fe80::1", Some("en0")).as_deref(), Some("fe80::1%en0"));
+        // A zone already on the address wins over the interface
+        assert_eq!(scoped_address("fe80::1%eth1", Some("en0")).as_deref(), Some("fe80::1%eth1"));
+        // Unreachable without knowing the interface
+        assert_eq!(scoped_address("fe80::1", None), None);
+        assert_eq!(scoped_address("2001:db8::1", Some("en0")).as_deref(), Some("2001:db8::1"));
+    }
+
+    #[test]
+    fn arp_hostnames_that_are_addresses_are_dropped() {
+        assert_eq!(
+            parse_arp_line("printer.lan (192.168.1.20) at aa:bb:cc:dd:ee:ff on en0 ifscope [ethernet]"),
+            Some(ArpEntry {
+                ip: "192.168.1.20".to_string(),
+                hostname: Some("printer.lan".to_string()),
+                mac: Some("aa:bb:cc:dd:ee:ff".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_arp_line("? (192.168.1.1) at 00:11:22:33:44:55 on en0 ifscope [ethernet]"),
+            Some(ArpEntry { ip: "192.168.1.1".to_string(), hostname: None, mac: Some("00:11:22:33:44:55".to_string()) })
+        );
+        assert_eq!(
+            parse_arp_line("192.168.1.30 (192.168.1.30) at 00:11:22:33:44:66 [ether] on eth0"),
+            Some(ArpEntry { ip: "192.168.1.30".to_string(), hostname: None, mac: Some("00:11:22:33:44:66".to_string()) })
+        );
+        assert_eq!(
+            parse_arp_line("? (192.168.1.40) at (incomplete) on en0 ifscope [ethernet]"),
+            Some(ArpEntry { ip: "192.168.1.40".to_string(), hostname: None, mac: None })
+        );
+        assert_eq!(parse_arp_line("Interface: 192.168.1.5 --- 0x4"), None);
+    }
+
+    #[test]
+    fn ip_sort_is_numeric() {
+        let mut services = consolidate_services(vec![
+            service("192.168.1.10", None, ""),
+            service("192.168.1.2", None, ""),
+        ]);
+        sort_services(&mut services, SortKey::Ip);
+        let addresses: Vec<&str> = services.iter().map(|s| s.address.as_str()).collect();
+        assert_eq!(addresses, ["192.168.1.2", "192.168.1.10"]);
+    }
+
+    #[test]
+    fn last_seen_sort_puts_most_recent_first() {
+        let mut services = consolidate_services(vec![
+            service("192.168.1.2", None, ""),
+            service("192.168.1.3", None, ""),
+            service("192.168.1.4", None, ""),
+        ]);
+        services[0].last_seen = Some(100);
+        services[1].last_seen = None;
+        services[2].last_seen = Some(200);
+        sort_services(&mut services, SortKey::LastSeen);
+        let addresses: Vec<&str> = services.iter().map(|s| s.address.as_str()).collect();
+        assert_eq!(addresses, ["192.168.1.4", "192.168.1.2", "192.168.1.3"]);
+    }
+
+    #[test]
+    fn network_map_keys_hosts_on_mac_when_known() {
+        let mut map = NetworkMap::default();
+        map.add(&["192.168.1.20"], Some("nas.local".to_string()), None);
+        map.add(&["fe80::1%en0"], None, Some("aa:bb:cc:dd:ee:ff".to_string()));
+        map.add(&["192.168.1.20"], None, Some("aa:bb:cc:dd:ee:ff".to_string()));
+
+        assert_eq!(map.hosts().count(), 1);
+        let host = map.host("fe80::1%en0").unwrap();
+        assert!(host.addresses.contains("192.168.1.20"));
+        assert_eq!(host.hostname.as_deref(), Some("nas.local"));
+        assert_eq!(map.by_address["192.168.1.20"], "mac:aa:bb:cc:dd:ee:ff");
+    }
+
+    #[test]
+    fn network_map_only_merges_fully_qualified_hostnames() {
+        let mut map = NetworkMap::default();
+        map.add(&["192.168.1.20"], Some("nas.home.arpa".to_string()), None);
+        map.add(&["10.0.0.20"], Some("nas.office.example".to_string()), None);
+        map.add(&["192.168.1.30"], Some("printer".to_string()), None);
+        map.add(&["192.168.1.31"], Some("printer".to_string()), None);
+        assert_eq!(map.hosts().count(), 4);
+
+        map.add(&["fd00::20"], Some("NAS.home.arpa.".to_string()), None);
+        assert_eq!(map.hosts().count(), 4);
+        assert!(map.host("fd00::20").unwrap().addresses.contains("192.168.1.20"));
+    }
+
+    #[test]
+    fn windows_arp_output_lists_unicast_hosts() {
+        let output = "
+Interface: 192.168.1.5 --- 0x4
+  Internet Address      Physical Address      Type
+  192.168.1.1           14-cc-20-aa-bb-cc     dynamic
+  192.168.1.20          00-11-32-0a-0b-0c     dynamic
+  192.168.1.255         ff-ff-ff-ff-ff-ff     static
+  224.0.0.22            01-00-5e-00-00-16     static
+  239.255.255.250       01-00-5e-7f-ff-fa     static
+  255.255.255.255       ff-ff-ff-ff-ff-ff     static
+
+Interface: 172.20.0.1 --- 0x11
+  Internet Address      Physical Address      Type
+  172.20.0.9            00-15-5d-01-02-03     dynamic
+";
+        assert_eq!(
+            parse_windows_arp(output),
+            [
+                ("192.168.1.1".to_string(), "14:cc:20:aa:bb:cc".to_string()),
+                ("192.168.1.20".to_string(), "00:11:32:0a:0b:0c".to_string()),
+                ("172.20.0.9".to_string(), "00:15:5d:01:02:03".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn recorded_answers_parse_into_services() {
+        let recording = ScanRecording {
+            version: RECORDING_VERSION,
+            mdns: vec![MdnsAnswer {
+                service_type: "_ipp._tcp.local.".to_string(),
+                fullname: "Office._ipp._tcp.local.".to_string(),
+                hostname: "printer.local.".to_string(),
+                port: 631,
+                addresses: vec!["192.168.1.20".to_string(), "10.0.0.20".to_string()],
+                txt: vec![("ty".to_string(), "LaserJet".to_string())],
+                interfaces: BTreeMap::from([
+                    ("192.168.1.20".to_string(), "en0".to_string()),
+                    ("10.0.0.20".to_string(), "en1".to_string()),
+                ]),
+            }],
+            ssdp: vec![SsdpAnswer {
+                location: "http://192.168.1.1:5000/rootDesc.xml".to_string(),
+                server: "Linux/5.4 UPnP/1.0 MiniUPnPd/2.2".to_string(),
+                search_target: "upnp:rootdevice".to_string(),
+                usn: "uuid:1234::upnp:rootdevice".to_string(),
+                description: Some(
+                    "<root><device><deviceType>urn:schemas-upnp-org:device:InternetGatewayDevice:1</deviceType>\
+                     <friendlyName>Home Router</friendlyName></device></root>".to_string(),
+                ),
+            }],
+            ..ScanRecording::default()
+        };
+        let services = recording.services();
+        
+        let mdns = &services.iter().find(|(method, _)| *method == METHOD_MDNS).unwrap().1;
+        assert_eq!(mdns.len(), 2);
+        assert_eq!(mdns[0].name, "Office");
+        assert_eq!(mdns[0].service_type, "ipp");
+        assert_eq!(mdns[0].txt_records.get("ty").map(String::as_str), Some("LaserJet"));
+        assert!(mdns[0].details.as_deref().unwrap().ends_with("Interface: en0"));
+        assert_eq!(mdns[1].address, "10.0.0.20");
+        
+        let upnp = &services.iter().find(|(method, _)| *method == METHOD_UPNP).unwrap().1;
+        assert_eq!(upnp[0].name, "Home Router");
+        assert_eq!(upnp[0].service_type, "urn:schemas-upnp-org:device:InternetGatewayDevice:1");
+        assert_eq!((upnp[0].address.as_str(), upnp[0].port), ("192.168.1.1", Some(5000)));
+    }
+
+    #[test]
+    fn recorded_sweep_rebuilds_hosts_and_summaries() {
+        let recording = ScanRecording {
+            version: RECORDING_VERSION,
+            mdns: vec![MdnsAnswer {
+                service_type: "_ssh._tcp.local.".to_string(),
+                fullname: "nas._ssh._tcp.local.".to_string(),
+                hostname: "nas.local.".to_string(),
+                port: 22,
+                addresses: vec!["192.168.1.30".to_string()],
+                ..MdnsAnswer::default()
+            }],
+            network_scan: SweepCapture {
+                hosts: vec![HostObservation {
+                    address: "192.168.1.10".to_string(),
+                    mac: Some("aa:bb:cc:dd:ee:ff".to_string()),
+                    responds_to_ping: true,
+                    ..HostObservation::default()
+                }],
+                ports: vec![PortObservation {
+                    address: "192.168.1.10".to_string(),
+                    port: 22,
+                    protocol: "tcp".to_string(),
+                    state: PortState::Open,
+                    probe_lines: vec!["Banner: SSH-2.0-OpenSSH_9.6".to_string()],
+                }],
             },
-            Err(_) => None
+            ..ScanRecording::default()
+        };
+        let services = recording.services();
+        let network_scan = &services.iter().find(|(method, _)| *method == METHOD_NETWORK_SCAN).unwrap().1;
+        
+        let host = network_scan.iter().find(|s| s.address == "192.168.1.10").unwrap();
+        assert_eq!(host.service_type, "host");
+        let details = host.details.as_deref().unwrap();
+        assert!(details.contains("Responds to ping"));
+        assert!(details.contains("MAC Address: aa:bb:cc:dd:ee:ff"));
+        
+        // The mDNS-only host with an open port gets a summary; the swept one doesn't
+        let summary = network_scan.iter().find(|s| s.address == "192.168.1.30").unwrap();
+        assert_eq!(summary.service_type, "network_device");
+        assert!(summary.details.as_deref().unwrap().contains("Open TCP ports:\n  22"));
+        assert_eq!(network_scan.len(), 2);
+        
+        let port = recording.network_scan.ports[0].service();
+        assert_eq!(port.service_type, "ssh");
+        assert!(port.details.as_deref().unwrap().ends_with("Banner: SSH-2.0-OpenSSH_9.6"));
+    }
+
+    #[test]
+    fn audit_observes_one_device_per_mac() {
+        let consolidated = consolidate_services(vec![
+            service("192.168.1.10", Some(22), "MAC Address: aa:bb:cc:dd:ee:ff"),
+            service("192.168.1.11", Some(80), "MAC Address: aa:bb:cc:dd:ee:ff"),
+            service("192.168.1.12", Some(443), "Host: printer.local"),
+        ]);
+        let (observed, unidentified) = observed_devices(&consolidated);
+        
+        assert_eq!(observed.len(), 1);
+        assert_eq!(observed[0].mac, "aa:bb:cc:dd:ee:ff");
+        assert_eq!(observed[0].address, "192.168.1.10");
+        let mut ports = observed[0].open_ports.clone();
+        ports.sort_unstable();
+        assert_eq!(ports, vec![22, 80]);
+        assert_eq!(unidentified, vec!["192.168.1.12".to_string()]);
+    }
+
+    #[test]
+    fn zero_timeouts_are_rejected() {
+        assert!(ScanConfig::default().validate().is_ok());
+        for config in [
+            ScanConfig { timeout_ms: Some(0), ..Default::default() },
+            ScanConfig { tcp_timeout_ms: Some(0), ..Default::default() },
+            ScanConfig { udp_timeout_ms: Some(0), ..Default::default() },
+        ] {
+            assert!(config.validate().is_err());
         }
-    } else {
-        None
+        assert!(ScanConfig { timeout_ms: Some(1), ..Default::default() }.validate().is_ok());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn rescan_summary_counts_reachable_snapshot_hosts() {
+        let report = RescanReport {
+            services: consolidate_services(vec![
+                service("10.0.0.2", Some(80), ""),
+                service("10.0.0.3", None, ""),
+                service("10.0.0.4", None, ""),
+            ]),
+            unreachable: vec!["10.0.0.4".to_string()],
+            records: 2,
+            ..Default::default()
+        };
+        let result = report.scan_result(7);
+        assert_eq!(result.scan_id, 7);
+        assert_eq!(result.device_count, 2);
+        assert_eq!(result.method_counts.get(METHOD_NETWORK_SCAN), Some(&2));
+        assert_eq!(result.completeness.unwrap().score, 90);
+    }
+}
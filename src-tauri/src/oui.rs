@@ -0,0 +1,222 @@
+// oui.rs - MAC address vendor lookup against a bundled OUI table
+//
+// The table is a small subset of the IEEE OUI registry covering common home
+// and office hardware, not the full registry, so unknown prefixes return None.
+
+// First three octets of a MAC address -> vendor
+const OUI_VENDORS: &[(u32, &str)] = &[
+    // Apple
+    (0x000393, "Apple"),
+    (0x000A95, "Apple"),
+    (0x0017F2, "Apple"),
+    (0x001EC2, "Apple"),
+    (0x002500, "Apple"),
+    (0x28CFE9, "Apple"),
+    (0x3C0754, "Apple"),
+    (0xA45E60, "Apple"),
+    (0xACBC32, "Apple"),
+    (0xF01898, "Apple"),
+    // Raspberry Pi
+    (0xB827EB, "Raspberry Pi Foundation"),
+    (0xDCA632, "Raspberry Pi Trading"),
+    (0xE45F01, "Raspberry Pi Trading"),
+    (0x28CDC1, "Raspberry Pi Trading"),
+    (0xD83ADD, "Raspberry Pi Trading"),
+    // Google and Nest
+    (0xF4F5D8, "Google"),
+    (0x546009, "Google"),
+    (0xF88FCA, "Google"),
+    (0x3C5AB4, "Google"),
+    (0x18B430, "Nest Labs"),
+    (0x641666, "Nest Labs"),
+    // Amazon
+    (0xF0272D, "Amazon"),
+    (0x44650D, "Amazon"),
+    (0xFC65DE, "Amazon"),
+    (0x6854FD, "Amazon"),
+    (0x0C47C9, "Amazon"),
+    // Espressif (ESP8266/ESP32 based smart home devices)
+    (0x240AC4, "Espressif"),
+    (0x30AEA4, "Espressif"),
+    (0x84F3EB, "Espressif"),
+    (0xA4CF12, "Espressif"),
+    (0x5CCF7F, "Espressif"),
+    (0x18FE34, "Espressif"),
+    (0x600194, "Espressif"),
+    (0xECFABC, "Espressif"),
+    (0x246F28, "Espressif"),
+    (0x3C71BF, "Espressif"),
+    // Media and smart home
+    (0x000E58, "Sonos"),
+    (0x5CAAFD, "Sonos"),
+    (0x949F3E, "Sonos"),
+    (0xB8E937, "Sonos"),
+    (0x48A6B8, "Sonos"),
+    (0x7828CA, "Sonos"),
+    (0x001788, "Signify (Philips Hue)"),
+    (0xECB5FA, "Signify (Philips Hue)"),
+    (0xB0A737, "Roku"),
+    (0xDC3A5E, "Roku"),
+    (0xCC6DA0, "Roku"),
+    (0x080581, "Roku"),
+    (0xAC3A7A, "Roku"),
+    (0x70EE50, "Netatmo"),
+    (0x446132, "ecobee"),
+    (0x0452C7, "Bose"),
+    (0x08DF1F, "Bose"),
+    (0x0005CD, "Denon & Marantz"),
+    (0x00A0DE, "Yamaha"),
+    (0x000420, "Logitech"),
+    (0x640980, "Xiaomi"),
+    (0x286C07, "Xiaomi"),
+    (0x7811DC, "Xiaomi"),
+    // TVs, consoles and phones
+    (0x0012FB, "Samsung"),
+    (0x001632, "Samsung"),
+    (0x5C0A5B, "Samsung"),
+    (0x8C7712, "Samsung"),
+    (0x001C62, "LG Electronics"),
+    (0xA823FE, "LG Electronics"),
+    (0x00041F, "Sony Interactive Entertainment"),
+    (0xFC0FE6, "Sony Interactive Entertainment"),
+    (0x0013A9, "Sony"),
+    (0x0009BF, "Nintendo"),
+    (0x001F32, "Nintendo"),
+    (0x98B6E9, "Nintendo"),
+    // Routers, access points and switches
+    (0x00156D, "Ubiquiti"),
+    (0x002722, "Ubiquiti"),
+    (0x0418D6, "Ubiquiti"),
+    (0x24A43C, "Ubiquiti"),
+    (0x44D9E7, "Ubiquiti"),
+    (0x687251, "Ubiquiti"),
+    (0x788A20, "Ubiquiti"),
+    (0x802AA8, "Ubiquiti"),
+    (0xF09FC2, "Ubiquiti"),
+    (0xFCECDA, "Ubiquiti"),
+    (0x7483C2, "Ubiquiti"),
+    (0xE063DA, "Ubiquiti"),
+    (0xB4FBE4, "Ubiquiti"),
+    (0x50C7BF, "TP-Link"),
+    (0x14CC20, "TP-Link"),
+    (0x98DED0, "TP-Link"),
+    (0xEC086B, "TP-Link"),
+    (0xC04A00, "TP-Link"),
+    (0xF4F26D, "TP-Link"),
+    (0x60E327, "TP-Link"),
+    (0xA0F3C1, "TP-Link"),
+    (0x30B5C2, "TP-Link"),
+    (0x00095B, "Netgear"),
+    (0x00146C, "Netgear"),
+    (0x001B2F, "Netgear"),
+    (0x001E2A, "Netgear"),
+    (0x204E7F, "Netgear"),
+    (0x28C68E, "Netgear"),
+    (0xA040A0, "Netgear"),
+    (0xC03F0E, "Netgear"),
+    (0x9CD36D, "Netgear"),
+    (0x00040E, "AVM (FRITZ!Box)"),
+    (0x3CA62F, "AVM (FRITZ!Box)"),
+    (0xC80E14, "AVM (FRITZ!Box)"),
+    (0x3810D5, "AVM (FRITZ!Box)"),
+    (0x2C91AB, "AVM (FRITZ!Box)"),
+    (0x001A92, "ASUS"),
+    (0x04D4C4, "ASUS"),
+    (0x2C56DC, "ASUS"),
+    (0xAC220B, "ASUS"),
+    (0x00055D, "D-Link"),
+    (0x000D88, "D-Link"),
+    (0x001B11, "D-Link"),
+    (0x1C7EE5, "D-Link"),
+    (0x28107B, "D-Link"),
+    (0xC8BE19, "D-Link"),
+    (0x001A70, "Linksys"),
+    (0xC0C1C0, "Linksys"),
+    (0x94103E, "Belkin"),
+    (0xEC1A59, "Belkin"),
+    (0x08863B, "Belkin"),
+    (0x4C5E0C, "MikroTik"),
+    (0x6C3B6B, "MikroTik"),
+    (0xD4CA6D, "MikroTik"),
+    (0xE48D8C, "MikroTik"),
+    (0x000C42, "MikroTik"),
+    (0x00A0C5, "Zyxel"),
+    (0x00E0FC, "Huawei"),
+    (0x00000C, "Cisco"),
+    (0x000585, "Juniper Networks"),
+    (0x000B86, "Aruba Networks"),
+    (0x24DEC6, "Aruba Networks"),
+    // Storage, printers and cameras
+    (0x001132, "Synology"),
+    (0x245EBE, "QNAP"),
+    (0x00089B, "QNAP"),
+    (0x0090A9, "Western Digital"),
+    (0x008077, "Brother"),
+    (0x30055C, "Brother"),
+    (0x000085, "Canon"),
+    (0x001E8F, "Canon"),
+    (0x0026AB, "Seiko Epson"),
+    (0x64EB8C, "Seiko Epson"),
+    (0x3CD92B, "HP"),
+    (0x2857BE, "Hikvision"),
+    (0x4419B6, "Hikvision"),
+    (0xC056E3, "Hikvision"),
+    (0xBCAD28, "Hikvision"),
+    (0x3CEF8C, "Dahua"),
+    (0x9002A9, "Dahua"),
+    (0x00408C, "Axis Communications"),
+    (0xACCC8E, "Axis Communications"),
+    // Computers and virtual machines
+    (0x001B21, "Intel"),
+    (0x001E67, "Intel"),
+    (0xA0369F, "Intel"),
+    (0x001422, "Dell"),
+    (0xF8DB88, "Dell"),
+    (0xB8AC6F, "Dell"),
+    (0x1866DA, "Dell"),
+    (0x00E04C, "Realtek"),
+    (0x000569, "VMware"),
+    (0x000C29, "VMware"),
+    (0x005056, "VMware"),
+    (0x00155D, "Microsoft (Hyper-V)"),
+    (0x0050F2, "Microsoft"),
+    (0x281878, "Microsoft"),
+];
+
+// Vendor of a MAC address from its OUI (first three octets). Accepts the same
+// formats as `normalize_mac` (colon- or dash-separated, any case).
+pub fn lookup_oui(mac: &str) -> Option<String> {
+    let mac = crate::inventory::normalize_mac(mac)?;
+    let prefix = u32::from_str_radix(&mac[..8].replace(':', ""), 16).ok()?;
+
+    // Randomized (locally administered) addresses aren't registered to anyone
+    if prefix & 0x020000 != 0 {
+        return None;
+    }
+
+    OUI_VENDORS
+        .iter()
+        .find(|(oui, _)| *oui == prefix)
+        .map(|(_, vendor)| vendor.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_router_oui_is_found_in_any_format() {
+        assert_eq!(lookup_oui("14:cc:20:aa:bb:cc").as_deref(), Some("TP-Link"));
+        assert_eq!(lookup_oui("14-CC-20-AA-BB-CC").as_deref(), Some("TP-Link"));
+        assert_eq!(lookup_oui("24:A4:3C:01:02:03").as_deref(), Some("Ubiquiti"));
+    }
+
+    #[test]
+    fn unknown_and_randomized_macs_have_no_vendor() {
+        assert_eq!(lookup_oui("00:00:01:aa:bb:cc"), None);
+        // Locally administered bit set
+        assert_eq!(lookup_oui("16:cc:20:aa:bb:cc"), None);
+        assert_eq!(lookup_oui("not a mac"), None);
+    }
+}
+
@@ -0,0 +1,347 @@
+// port_probe.rs - Per-port enrichment probes run after a port is found open
+//
+// Every probe against a port goes through one PortConnection, so probes share
+// a single TCP connection where the protocol allows (HTTP/1.1 keep-alive) and
+// only reconnect when the server closes it.
+
+use crate::radar_debug;
+use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// Plain-HTTP ports worth fingerprinting
+const HTTP_PORTS: &[u16] = &[80, 3000, 5000, 8000, 8008, 8080, 8081, 8888, 9000];
+
+// Cap on the response bytes read per request; bigger bodies end the connection
+const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+// Services that greet the client on connect (FTP, SSH, SMTP, POP3, IMAP,
+// submission, VNC), so their banner is read without sending anything.
+// HTTP ports get their Server header from the HTTP probe instead, and TLS
+// ports have no plaintext banner to read.
+const GREETING_PORTS: &[u16] = &[21, 22, 25, 110, 143, 587, 5900];
+
+// Bytes of a greeting read for the banner
+const MAX_BANNER_BYTES: usize = 256;
+
+// "ip:port" for connecting, with IPv6 addresses in brackets
+pub(crate) fn socket_address(ip: &str, port: u16) -> String {
+    if ip.contains(':') {
+        format!("[{}]:{}", ip, port)
+    } else {
+        format!("{}:{}", ip, port)
+    }
+}
+
+// Socket address to connect to. Link-local IPv6 addresses carry their
+// interface as a zone ("fe80::1%en0" -> "[fe80::1%en0]:port"), which std can't
+// parse, so the zone is turned into the interface's scope id here.
+pub(crate) fn connect_target(ip: &str, port: u16) -> std::io::Result<SocketAddr> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+    
+    let Some((address, zone)) = ip.split_once('%') else {
+        return socket_address(ip, port)
+            .parse()
+            .map_err(|_| invalid(format!("Invalid address {}", ip)));
+    };
+    let address: Ipv6Addr = address.parse().map_err(|_| invalid(format!("Invalid IPv6 address {}", ip)))?;
+    let scope_id = interface_index(zone).ok_or_else(|| invalid(format!("Unknown interface {}", zone)))?;
+    Ok(SocketAddr::V6(SocketAddrV6::new(address, port, 0, scope_id)))
+}
+
+// Index of a network interface by name ("en0") or number ("12", as Windows writes zones)
+fn interface_index(zone: &str) -> Option<u32> {
+    if let Ok(index) = zone.parse() {
+        return Some(index);
+    }
+    #[cfg(unix)]
+    {
+        let name = std::ffi::CString::new(zone).ok()?;
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        (index != 0).then_some(index)
+    }
+    #[cfg(not(unix))]
+    None
+}
+
+// A lazily (re)connected TCP connection shared by the probes for one port
+pub struct PortConnection {
+    // None when the address can't be connected to (e.g. a zone naming no interface)
+    addr: Option<SocketAddr>,
+    timeout: Duration,
+    stream: Option<TcpStream>,
+}
+
+impl PortConnection {
+    pub fn new(ip: &str, port: u16, timeout: Duration) -> Self {
+        Self {
+            addr: connect_target(ip, port).ok(),
+            timeout,
+            stream: None,
+        }
+    }
+
+    // Start from an already-open connection, e.g. the one that found the port open
+    pub fn with_stream(ip: &str, port: u16, timeout: Duration, stream: TcpStream) -> Self {
+        Self {
+            stream: Some(stream),
+            ..Self::new(ip, port, timeout)
+        }
+    }
+
+    async fn stream(&mut self) -> Option<&mut TcpStream> {
+        if self.stream.is_none() {
+            let stream = tokio::time::timeout(self.timeout, TcpStream::connect(self.addr?))
+                .await
+                .ok()?
+                .ok()?;
+            self.stream = Some(stream);
+        }
+        self.stream.as_mut()
+    }
+
+    // Drop the connection; the next probe reconnects
+    fn close(&mut self) {
+        self.stream = None;
+    }
+}
+
+struct HttpResponse {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    keep_alive: bool,
+}
+
+impl HttpResponse {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+// Decode a chunked body, or None if it isn't complete yet
+fn decode_chunked(data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut pos = 0;
+    loop {
+        let line_end = pos + find_subslice(&data[pos..], b"\r\n")?;
+        let size_str = String::from_utf8_lossy(&data[pos..line_end]);
+        let size = usize::from_str_radix(size_str.split(';').next()?.trim(), 16).ok()?;
+        pos = line_end + 2;
+        if size == 0 {
+            return Some(body);
+        }
+        if data.len() < pos + size + 2 {
+            return None;
+        }
+        body.extend_from_slice(&data[pos..pos + size]);
+        pos += size + 2;
+    }
+}
+
+// Send one HTTP/1.1 request on the shared connection and read the full response
+async fn http_request(conn: &mut PortConnection, host: &str, method: &str, path: &str) -> Option<HttpResponse> {
+    let timeout = conn.timeout;
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: Radar/1.0\r\nAccept: */*\r\nConnection: keep-alive\r\n\r\n",
+        method, path, host
+    );
+
+    let stream = conn.stream().await?;
+    let exchange = async {
+        stream.write_all(request.as_bytes()).await.ok()?;
+
+        let mut data = Vec::new();
+        let mut buf = [0u8; 4096];
+        let mut header_end = None;
+        loop {
+            if header_end.is_none() {
+                header_end = find_subslice(&data, b"\r\n\r\n").map(|i| i + 4);
+            }
+            if let Some(end) = header_end {
+                let head = String::from_utf8_lossy(&data[..end]).to_string();
+                let mut lines = head.lines();
+                let status_line = lines.next()?;
+                let headers: Vec<(String, String)> = lines
+                    .filter_map(|line| line.split_once(':'))
+                    .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                    .collect();
+                let find = |name: &str| {
+                    headers
+                        .iter()
+                        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                        .map(|(_, v)| v.to_lowercase())
+                };
+                let keep_alive = status_line.starts_with("HTTP/1.1")
+                    && find("connection").as_deref() != Some("close");
+
+                // HEAD responses and 1xx/204/304 carry no body
+                let no_body = method == "HEAD"
+                    || status_line.split_whitespace().nth(1).is_some_and(|code| {
+                        code.starts_with('1') || code == "204" || code == "304"
+                    });
+
+                let body = if no_body {
+                    Some(Vec::new())
+                } else if find("transfer-encoding").is_some_and(|te| te.contains("chunked")) {
+                    decode_chunked(&data[end..])
+                } else if let Some(length) = find("content-length").and_then(|l| l.parse::<usize>().ok()) {
+                    (data.len() >= end + length).then(|| data[end..end + length].to_vec())
+                } else {
+                    // Body runs until the server closes the connection
+                    None
+                };
+
+                if let Some(body) = body {
+                    return Some(HttpResponse { headers, body, keep_alive });
+                }
+            }
+
+            if data.len() >= MAX_RESPONSE_BYTES {
+                return None;
+            }
+            let read = stream.read(&mut buf).await.ok()?;
+            if read == 0 {
+                // Closed by the server: whatever arrived is the whole response
+                let end = header_end?;
+                let head = String::from_utf8_lossy(&data[..end]).to_string();
+                let headers = head
+                    .lines()
+                    .skip(1)
+                    .filter_map(|line| line.split_once(':'))
+                    .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                    .collect();
+                return Some(HttpResponse { headers, body: data[end..].to_vec(), keep_alive: false });
+            }
+            data.extend_from_slice(&buf[..read]);
+        }
+    };
+
+    let response = tokio::time::timeout(timeout, exchange).await.ok().flatten();
+    match &response {
+        Some(response) if response.keep_alive => {}
+        _ => conn.close(),
+    }
+    response
+}
+
+// Extract the <title> of an HTML page
+fn html_title(body: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(body);
+    // ASCII-only lowercasing keeps byte offsets valid for slicing `text`
+    let lower = text.to_ascii_lowercase();
+    let start = lower.find("<title")?;
+    let start = start + lower[start..].find('>')? + 1;
+    let end = start + lower[start..].find("</title>")?;
+    let title = text[start..end].split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+// Fetch the server header, page title and favicon over one keep-alive connection
+async fn probe_http(conn: &mut PortConnection, host: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    if let Some(response) = http_request(conn, host, "GET", "/").await {
+        if let Some(server) = response.header("server") {
+            lines.push(format!("HTTP Server: {}", server));
+        }
+        if let Some(title) = html_title(&response.body) {
+            lines.push(format!("HTTP Title: {}", title));
+        }
+    }
+
+    if let Some(response) = http_request(conn, host, "GET", "/favicon.ico").await {
+        let is_icon = response
+            .header("content-type")
+            .is_some_and(|ct| ct.starts_with("image/"));
+        if is_icon && !response.body.is_empty() {
+            lines.push(format!("Favicon: {} bytes", response.body.len()));
+        }
+    }
+
+    lines
+}
+
+// First line of a greeting, cut before any invalid UTF-8 and stripped of
+// control characters, or None if nothing printable is left
+fn banner_line(data: &[u8]) -> Option<String> {
+    let valid = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(e) => std::str::from_utf8(&data[..e.valid_up_to()]).unwrap_or_default(),
+    };
+    let line: String = valid
+        .lines()
+        .next()?
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect();
+    let line = line.trim();
+    (!line.is_empty()).then(|| line.to_string())
+}
+
+// Read the greeting a service sends on connect, e.g. "SSH-2.0-OpenSSH_9.6"
+async fn grab_banner(conn: &mut PortConnection) -> Option<String> {
+    let timeout = conn.timeout;
+    let stream = conn.stream().await?;
+    let mut buf = [0u8; MAX_BANNER_BYTES];
+    let size = match tokio::time::timeout(timeout, stream.read(&mut buf)).await {
+        Ok(Ok(size)) if size > 0 => size,
+        _ => {
+            conn.close();
+            return None;
+        }
+    };
+    banner_line(&buf[..size])
+}
+
+// Run every probe that applies to an open port, returning detail lines to attach
+// to the service. All probes for the port share `conn`.
+pub async fn enrich_port(conn: &mut PortConnection, ip: &str, port: u16) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    if HTTP_PORTS.contains(&port) {
+        lines.extend(probe_http(conn, ip).await);
+    }
+
+    if GREETING_PORTS.contains(&port) {
+        if let Some(banner) = grab_banner(conn).await {
+            lines.push(format!("Banner: {}", banner));
+        }
+    }
+
+    if !lines.is_empty() {
+        radar_debug!("Enriched {}:{} with {} details", ip, port, lines.len());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoned_link_local_address_carries_the_scope_id() {
+        let target = connect_target("fe80::1%3", 22).unwrap();
+        let SocketAddr::V6(v6) = target else {
+            panic!("expected an IPv6 target, got {}", target);
+        };
+        assert_eq!(v6.ip(), &"fe80::1".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(v6.scope_id(), 3);
+        assert_eq!(v6.port(), 22);
+    }
+
+    #[test]
+    fn unzoned_addresses_parse_as_usual() {
+        assert_eq!(connect_target("192.168.1.10", 80).unwrap().to_string(), "192.168.1.10:80");
+        assert_eq!(connect_target("2001:db8::1", 443).unwrap().to_string(), "[2001:db8::1]:443");
+        assert!(connect_target("not-an-ip", 80).is_err());
+        assert!(connect_target("192.168.1.10%en0", 80).is_err());
+    }
+}
@@ -1,16 +1,23 @@
 use reqwest;
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
-use std::net::UdpSocket;
+use tokio::net::UdpSocket;
 use std::process::Command;
 use thiserror::Error;
 use tauri::Manager;
 use crate::router_discovery::{get_router_and_isp_info, RouterInfo};
-use dns_lookup;
 use rand;
 use default_net::get_default_gateway;
 use crate::radar_error;
+use crate::radar_debug;
 use crate::radar_info;
+use crate::radar_trace;
+use once_cell::sync::Lazy;
+use std::future::Future;
+use tokio_util::sync::CancellationToken;
+use crate::whois::WhoisInfo;
+use crate::sink::{ServiceSink, SilentSink};
+use std::time::{Duration, Instant};
 
 // Function to enable or disable network logging (for backward compatibility)
 pub fn set_network_logging(enable: bool) -> bool {
@@ -39,6 +46,9 @@ pub enum NetworkError {
     
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+    
+    #[error("Operation cancelled")]
+    Cancelled,
 }
 
 // Result type alias using our custom error
@@ -69,12 +79,141 @@ pub struct PublicNetworkInfo {
     pub is_vpn: Option<bool>,
     pub is_proxy: Option<bool>,
     pub is_hosting: Option<bool>,
+    pub connection_type: Option<ConnectionType>,
+    pub connection_type_reasons: Vec<String>,   // Evidence behind connection_type, for display
+    pub whois: Option<WhoisInfo>,               // Registration details, only looked up on request
+    #[serde(default)]
+    pub behind_cgnat: Option<bool>,             // Router's WAN address is carrier-grade NAT (100.64.0.0/10)
+    #[serde(default)]
+    pub gateway_latency_ms: Option<f64>,        // Average TCP handshake time to the default gateway
+    #[serde(default)]
+    pub public_latency_ms: Option<f64>,         // Average TCP handshake time to PUBLIC_LATENCY_TARGET
+    #[serde(default)]
+    pub ip_source: Option<String>,              // Where `ip` came from, e.g. "stun:stun.l.google.com:19302" or "http"
+}
+
+// Address ranges that are never the public side of a connection. ASN, geo and
+// WHOIS lookups on them only return nonsense, so they are skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpecialRange {
+    Private,
+    LinkLocal,
+    Cgnat,
+    Loopback,
+    Unspecified,
+}
+
+impl SpecialRange {
+    fn describe(self) -> &'static str {
+        match self {
+            SpecialRange::Private => "a private address",
+            SpecialRange::LinkLocal => "a link-local address",
+            SpecialRange::Cgnat => "a carrier-grade NAT (100.64.0.0/10) address",
+            SpecialRange::Loopback => "a loopback address",
+            SpecialRange::Unspecified => "the unspecified address",
+        }
+    }
+}
+
+// Which special range an address is in, or None for a public address
+fn special_range(ip: std::net::IpAddr) -> Option<SpecialRange> {
+    match ip {
+        std::net::IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            if v4.is_private() {
+                Some(SpecialRange::Private)
+            } else if v4.is_link_local() {
+                Some(SpecialRange::LinkLocal)
+            } else if a == 100 && (64..128).contains(&b) {
+                Some(SpecialRange::Cgnat)
+            } else if v4.is_loopback() {
+                Some(SpecialRange::Loopback)
+            } else if v4.is_unspecified() {
+                Some(SpecialRange::Unspecified)
+            } else {
+                None
+            }
+        },
+        std::net::IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            if first & 0xfe00 == 0xfc00 {
+                Some(SpecialRange::Private)
+            } else if first & 0xffc0 == 0xfe80 {
+                Some(SpecialRange::LinkLocal)
+            } else if v6.is_loopback() {
+                Some(SpecialRange::Loopback)
+            } else if v6.is_unspecified() {
+                Some(SpecialRange::Unspecified)
+            } else {
+                None
+            }
+        },
+    }
+}
+
+// Whether `ip` is an address worth looking up in public registries
+fn is_public_ip(ip: &str) -> bool {
+    match ip.parse::<std::net::IpAddr>() {
+        Ok(addr) => match special_range(addr) {
+            Some(range) => {
+                radar_info!("{} is {}, skipping external lookups", ip, range.describe());
+                false
+            },
+            None => true,
+        },
+        Err(_) => false,
+    }
+}
+
+// What kind of network the public IP belongs to
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionType {
+    Residential,
+    Hosting,
+    Vpn,
+    Mobile,
+    #[default]
+    Unknown,
 }
 
+// How long a full lookup is served from the cache before it is redone
+pub const DEFAULT_PUBLIC_INFO_TTL: Duration = Duration::from_secs(60);
+
 // Thread-safe state using Arc<Mutex<T>> as per guidelines
 #[derive(Debug)]
 pub struct PublicNetworkState {
-    pub state: Arc<Mutex<PublicNetworkInfo>>
+    pub state: Arc<Mutex<PublicNetworkInfo>>,
+    // When `state` was last filled by a full lookup; None until the first one
+    pub fetched_at: Arc<Mutex<Option<Instant>>>,
+    pub ttl: Arc<Mutex<Duration>>,
+}
+
+impl PublicNetworkState {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(PublicNetworkInfo::default())),
+            fetched_at: Arc::new(Mutex::new(None)),
+            ttl: Arc::new(Mutex::new(ttl)),
+        }
+    }
+    
+    // The last full lookup, if it is younger than the TTL
+    fn cached(&self) -> Option<PublicNetworkInfo> {
+        let fetched_at = (*self.fetched_at.lock().unwrap())?;
+        let ttl = *self.ttl.lock().unwrap();
+        (fetched_at.elapsed() < ttl).then(|| self.state.lock().unwrap().clone())
+    }
+    
+    fn store(&self, info: &PublicNetworkInfo) {
+        *self.state.lock().unwrap() = info.clone();
+        *self.fetched_at.lock().unwrap() = Some(Instant::now());
+    }
+    
+    // Change the TTL; zero turns caching off
+    pub fn set_ttl(&self, ttl: Duration) {
+        *self.ttl.lock().unwrap() = ttl;
+    }
 }
 
 // Adding NetworkInfo struct from network_info.rs
@@ -88,6 +227,33 @@ pub struct NetworkInfo {
     pub dns_servers: Vec<String>,
 }
 
+// Parent token for in-flight public network lookups. Each lookup gets a child
+// token; cancelling swaps in a fresh parent so later lookups are unaffected.
+static PUBLIC_INFO_CANCEL: Lazy<Mutex<CancellationToken>> = Lazy::new(|| Mutex::new(CancellationToken::new()));
+
+// Get a cancellation token for a new public network info lookup
+pub fn public_info_cancel_token() -> CancellationToken {
+    PUBLIC_INFO_CANCEL.lock().unwrap().child_token()
+}
+
+// Cancel all in-flight public network info lookups
+pub fn cancel_public_network_info() {
+    let mut token = PUBLIC_INFO_CANCEL.lock().unwrap();
+    token.cancel();
+    *token = CancellationToken::new();
+}
+
+// Run a future until it completes or the token is cancelled
+async fn cancellable<T>(cancel: &CancellationToken, future: impl Future<Output = T>) -> Result<T> {
+    tokio::select! {
+        _ = cancel.cancelled() => {
+            radar_info!("Public network info lookup cancelled");
+            Err(NetworkError::Cancelled)
+        }
+        result = future => Ok(result),
+    }
+}
+
 // Get geolocation information for an IP address using Team Cymru's DNS service
 pub async fn get_geo_info(ip: &str) -> Option<String> {
     // Reverse IP for DNS lookup
@@ -119,7 +285,7 @@ pub async fn get_geo_info(ip: &str) -> Option<String> {
 pub async fn get_public_network_info() -> std::result::Result<PublicNetworkInfo, String> {
     radar_info!("Starting get_public_network_info Tauri command");
     
-    match get_public_network_info_internal().await {
+    match lookup_public_network_info(&SilentSink, public_info_cancel_token()).await {
         Ok(info) => Ok(info),
         Err(e) => {
             let error_message = format!("Failed to get public network info: {}", e);
@@ -129,237 +295,501 @@ pub async fn get_public_network_info() -> std::result::Result<PublicNetworkInfo,
     }
 }
 
-// Internal function that does the actual work
-pub async fn get_public_network_info_internal() -> Result<PublicNetworkInfo> {
-    radar_info!("Starting get_public_network_info_internal() - Using parallel execution");
-    let mut info = PublicNetworkInfo::default();
-    
-    // Create futures for different information fetching tasks
-    let public_ip_future = get_public_ip_via_stun();
-    let dns_servers_future = get_local_dns_servers();
-    let router_info_future = get_router_and_isp_info();
-    let local_hostname_future = get_local_hostname();
+// Fields of PublicNetworkInfo that can be refreshed on their own
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PublicField {
+    Ip,
+    Asn,
+    Geo,
+    Dns,
+    Router,
+    Hostname,
+    Privacy,
+    Whois,
+}
+
+// The refreshed value of a single field, tagged with the field it belongs to
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "field", content = "value")]
+pub enum PublicFieldValue {
+    Ip(Option<String>),
+    Asn {
+        asn: Option<String>,
+        org: Option<String>,
+        isp: Option<String>,
+    },
+    Geo(Option<GeoLocation>),
+    Dns(Vec<String>),
+    Router(Option<Box<RouterInfo>>),
+    Hostname {
+        hostname: Option<String>,
+        local_hostname: Option<String>,
+    },
+    Privacy {
+        connection_type: Option<ConnectionType>,
+        connection_type_reasons: Vec<String>,
+        is_vpn: Option<bool>,
+        is_proxy: Option<bool>,
+        is_hosting: Option<bool>,
+    },
+    Whois(Option<WhoisInfo>),
+}
+
+impl PublicFieldValue {
+    // Read the current value of a field out of the full info
+    pub fn from_info(info: &PublicNetworkInfo, field: PublicField) -> Self {
+        match field {
+            PublicField::Ip => PublicFieldValue::Ip(info.ip.clone()),
+            PublicField::Asn => PublicFieldValue::Asn {
+                asn: info.asn.clone(),
+                org: info.org.clone(),
+                isp: info.isp.clone(),
+            },
+            PublicField::Geo => PublicFieldValue::Geo(info.location.clone()),
+            PublicField::Dns => PublicFieldValue::Dns(info.dns.clone()),
+            PublicField::Router => PublicFieldValue::Router(info.router_info.clone().map(Box::new)),
+            PublicField::Hostname => PublicFieldValue::Hostname {
+                hostname: info.hostname.clone(),
+                local_hostname: info.local_hostname.clone(),
+            },
+            PublicField::Privacy => PublicFieldValue::Privacy {
+                connection_type: info.connection_type,
+                connection_type_reasons: info.connection_type_reasons.clone(),
+                is_vpn: info.is_vpn,
+                is_proxy: info.is_proxy,
+                is_hosting: info.is_hosting,
+            },
+            PublicField::Whois => PublicFieldValue::Whois(info.whois.clone()),
+        }
+    }
+}
+
+// Country from a Cymru record ("ASN | IP Range | Country | ISP | Date")
+fn location_from_cymru(record: &str) -> Option<GeoLocation> {
+    let country = record.split('|').nth(2)?.trim();
+    if country.is_empty() {
+        return None;
+    }
     
-    // Try to get IP from default gateway first
-    if let Ok(gateway) = get_default_gateway() {
-        let gateway_ip = gateway.ip_addr.to_string();
-        radar_info!("Default gateway found: {}", gateway_ip);
-        
-        // Get geolocation info from gateway IP
-        if let Some(geo_info) = get_geo_info(&gateway_ip).await {
-            radar_info!("Geolocation info from gateway IP: {}", geo_info);
-            // Parse the geo info (format: "ASN | IP Range | Country | ISP | Date")
-            let parts: Vec<&str> = geo_info.split('|').collect();
-            if parts.len() >= 3 {
-                let country = parts[2].trim();
-                
-                // Create a GeoLocation object with the country
-                let mut location = GeoLocation::default();
-                location.country = Some(country.to_string());
-                info.location = Some(location);
-                radar_info!("Setting initial location from gateway: country={}", country);
-            }
-        } else {
-            radar_info!("No geolocation info found for gateway IP");
+    Some(GeoLocation {
+        country: Some(country.to_string()),
+        ..GeoLocation::default()
+    })
+}
+
+// Public IP via STUN (which itself falls back to get_public_ip_via_http), with
+// the public IPv6 address looked up in parallel over an IPv6 socket
+async fn fetch_ip(info: &mut PublicNetworkInfo, cancel: &CancellationToken) -> Result<()> {
+    let (ipv4, ipv6) = tokio::join!(
+        cancellable(cancel, get_public_ip_via_stun(cancel)),
+        first_stun_answer(true, cancel),
+    );
+    info.ipv6 = ipv6?.map(|(ip, server)| {
+        radar_info!("Public IPv6 from STUN server {}: {}", server, ip);
+        ip
+    });
+    match ipv4? {
+        // An IPv6-only network can still hand back an IPv6 mapping here
+        Ok((ip, source)) if ip.contains(':') => {
+            radar_info!("{} returned an IPv6 mapping: {}", source, ip);
+            info.ipv6.get_or_insert(ip);
+            info.ip = None;
+            info.ip_source = None;
+        },
+        Ok((ip, source)) => {
+            radar_info!("Public IP from {}: {}", source, ip);
+            info.ip = Some(ip);
+            info.ip_source = Some(source);
+        },
+        Err(NetworkError::Cancelled) => return Err(NetworkError::Cancelled),
+        Err(e) => {
+            radar_info!("Failed to get public IP via STUN: {}", e);
+            info.ip = None;
+            info.ip_source = None;
         }
-    } else {
-        radar_info!("Could not determine default gateway");
     }
+    Ok(())
+}
+
+// Reverse DNS of the public IP, plus the ISP/organization hints in that name
+async fn fetch_public_hostname(info: &mut PublicNetworkInfo, cancel: &CancellationToken) -> Result<()> {
+    let Some(ip_addr) = info.ip.as_deref().filter(|ip| is_public_ip(ip)).and_then(|ip| ip.parse::<std::net::IpAddr>().ok()) else {
+        return Ok(());
+    };
     
-    // Run the STUN IP fetch task first
-    match public_ip_future.await {
-        Ok(ip) => {
-            info.ip = Some(ip.clone());
-            radar_info!("Public IP from STUN: {}", ip);
+    match cancellable(cancel, crate::dns::reverse_lookup(ip_addr)).await? {
+        Some(hostname) => {
+            radar_info!("Hostname from reverse DNS: {}", hostname);
             
-            // Try to get hostname from IP for additional info (reverse DNS lookup)
-            if let Ok(ip_addr) = ip.parse::<std::net::IpAddr>() {
-                match dns_lookup::lookup_addr(&ip_addr) {
-                    Ok(hostname) => {
-                        info.hostname = Some(hostname.clone());
-                        radar_info!("Hostname from reverse DNS: {}", hostname);
-                        
-                        // Try to extract ISP from hostname
-                        if let Some(isp) = extract_isp_from_hostname(&hostname) {
-                            info.isp = Some(isp.clone());
-                            radar_info!("ISP extracted from hostname: {}", isp);
-                        }
-                        
-                        // Try to extract organization from hostname
-                        if let Some(org) = extract_org_from_hostname(&hostname) {
-                            info.org = Some(org.clone());
-                            radar_info!("Organization extracted from hostname: {}", org);
-                        }
-                    },
-                    Err(e) => radar_info!("Reverse DNS lookup failed: {}", e),
-                }
+            // Try to extract ISP from hostname
+            if let Some(isp) = extract_isp_from_hostname(&hostname) {
+                radar_info!("ISP extracted from hostname: {}", isp);
+                info.isp = Some(isp);
             }
             
-            // Get ASN information using our new function
-            if let Some(asn_info) = get_asn(&ip).await {
-                // Parse the ASN info (format: "ASN | IP Range | Country | ISP | Date")
-                let parts: Vec<&str> = asn_info.split('|').collect();
-                if parts.len() >= 4 {
-                    let asn = parts[0].trim();
-                    let org = parts[3].trim();
-                    
-                    info.asn = Some(asn.to_string());
-                    radar_info!("ASN from lookup: {}", asn);
-                    
-                    // If we don't have org info yet, use this
-                    if info.org.is_none() {
-                        info.org = Some(org.to_string());
-                        radar_info!("Organization from ASN lookup: {}", org);
-                    }
-                    
-                    // If we don't have ISP info yet, try to use org as ISP
-                    if info.isp.is_none() {
-                        info.isp = Some(org.to_string());
-                        radar_info!("ISP inferred from ASN org: {}", org);
-                    }
-                    
-                    // If we don't have location info yet, try to get it from the ASN info
-                    if info.location.is_none() && parts.len() >= 3 {
-                        let country = parts[2].trim();
-                        
-                        // Create a GeoLocation object with the country
-                        let mut location = GeoLocation::default();
-                        location.country = Some(country.to_string());
-                        info.location = Some(location);
-                        radar_info!("Location from ASN lookup: country={}", country);
-                    }
-                }
-            } else {
-                info.asn = Some("".to_string());
-                radar_info!("No ASN info found, setting empty ASN");
+            // Try to extract organization from hostname
+            if let Some(org) = extract_org_from_hostname(&hostname) {
+                radar_info!("Organization extracted from hostname: {}", org);
+                info.org = Some(org);
             }
             
-            // Get geolocation info if we don't have it yet
-            if info.location.is_none() {
-                if let Some(geo_info) = get_geo_info(&ip).await {
-                    radar_info!("Additional geo info from IP: {}", geo_info);
-                    // Parse the geo info (format: "ASN | IP Range | Country | ISP | Date")
-                    let parts: Vec<&str> = geo_info.split('|').collect();
-                    if parts.len() >= 3 {
-                        let country = parts[2].trim();
-                        
-                        // Create a GeoLocation object with the country
-                        let mut location = GeoLocation::default();
-                        location.country = Some(country.to_string());
-                        info.location = Some(location);
-                        radar_info!("Setting country from geo API: {}", country);
-                    }
-                }
-            }
+            info.hostname = Some(hostname);
         },
-        Err(e) => {
-            radar_info!("Failed to get public IP via STUN: {}", e);
+        None => {
+            radar_info!("Reverse DNS lookup failed for {}", ip_addr);
+            info.hostname = None;
         }
     }
+    Ok(())
+}
+
+// Latency is timed with TCP handshakes: to the gateway's web UI port, where a
+// refused connection still takes one round trip, and to a well-known anycast host
+const GATEWAY_LATENCY_PORT: u16 = 80;
+const PUBLIC_LATENCY_TARGET: std::net::SocketAddr =
+    std::net::SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::new(1, 1, 1, 1)), 443);
+const LATENCY_ATTEMPTS: u32 = 3;
+const LATENCY_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Average handshake time to `target` in milliseconds, or None if any attempt
+// timed out or failed with something other than a refusal
+async fn tcp_latency_ms(target: std::net::SocketAddr) -> Option<f64> {
+    let mut total = Duration::ZERO;
+    for _ in 0..LATENCY_ATTEMPTS {
+        let started = Instant::now();
+        match tokio::time::timeout(LATENCY_TIMEOUT, tokio::net::TcpStream::connect(target)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {}
+            _ => return None,
+        }
+        total += started.elapsed();
+    }
+    Some(total.as_secs_f64() * 1000.0 / LATENCY_ATTEMPTS as f64)
+}
+
+// Round-trip latency to the default gateway and to the internet
+async fn fetch_latency(info: &mut PublicNetworkInfo, cancel: &CancellationToken) -> Result<()> {
+    let gateway = get_default_gateway()
+        .ok()
+        .map(|gateway| std::net::SocketAddr::new(gateway.ip_addr, GATEWAY_LATENCY_PORT));
+    let gateway_latency = async {
+        match gateway {
+            Some(target) => tcp_latency_ms(target).await,
+            None => None,
+        }
+    };
     
-    // Handle the remaining futures in parallel
-    let (dns_result, router_result, hostname_result) = 
-        tokio::join!(dns_servers_future, router_info_future, local_hostname_future);
-    
-    // Process DNS servers
-    if let Ok(dns_servers) = dns_result {
-        info.dns = dns_servers.clone();
-        radar_info!("DNS servers found: {:?}", dns_servers);
-    } else {
-        radar_info!("Failed to get DNS servers");
+    let (gateway_latency, public_latency) =
+        cancellable(cancel, async { tokio::join!(gateway_latency, tcp_latency_ms(PUBLIC_LATENCY_TARGET)) }).await?;
+    radar_info!("Latency: gateway {:?}ms, public {:?}ms", gateway_latency, public_latency);
+    info.gateway_latency_ms = gateway_latency;
+    info.public_latency_ms = public_latency;
+    Ok(())
+}
+
+// Local system hostname
+async fn fetch_local_hostname(info: &mut PublicNetworkInfo, cancel: &CancellationToken) -> Result<()> {
+    match cancellable(cancel, get_local_hostname()).await? {
+        Ok(hostname) => {
+            radar_info!("Local hostname: {}", hostname);
+            info.local_hostname = Some(hostname);
+        },
+        Err(_) => radar_info!("Failed to get local hostname"),
     }
+    Ok(())
+}
+
+// ASN of the public IP; its organization fills in org/ISP when nothing better is known.
+// An empty ASN means the lookup found nothing.
+async fn fetch_asn(info: &mut PublicNetworkInfo, cancel: &CancellationToken) -> Result<()> {
+    let Some(ip) = info.ip.clone().filter(|ip| is_public_ip(ip)) else {
+        info.asn = Some("".to_string());
+        return Ok(());
+    };
+    
+    let Some(asn_info) = cancellable(cancel, get_asn(&ip)).await? else {
+        info.asn = Some("".to_string());
+        radar_info!("No ASN info found for {}, setting empty ASN", ip);
+        return Ok(());
+    };
     
-    // Process router info
-    if let Ok(router_info) = router_result {
-        radar_info!("Router info obtained: {:?}", router_info);
+    let parts: Vec<&str> = asn_info.split('|').collect();
+    if parts.len() >= 4 {
+        let asn = parts[0].trim();
+        info.asn = Some(asn.to_string());
+        radar_info!("ASN from lookup: {}", asn);
         
-        // If we didn't get a public IP via STUN, try from router
-        if info.ip.is_none() {
-            if let Some(router_ip) = &router_info.public_ip {
-                info.ip = Some(router_ip.clone());
-                radar_info!("Setting public IP from router: {}", router_ip);
-                
-                // Try reverse DNS lookup on this IP too
-                if let Ok(ip_addr) = router_ip.parse::<std::net::IpAddr>() {
-                    if let Ok(hostname) = dns_lookup::lookup_addr(&ip_addr) {
-                        info.hostname = Some(hostname.clone());
-                        radar_info!("Hostname from router's IP reverse DNS: {}", hostname);
-                    }
-                }
-                
-                // Try ASN lookup on router's public IP if we don't have ASN info yet
-                if info.asn.is_none() || info.asn == Some("".to_string()) {
-                    if let Some(asn_info) = get_asn(router_ip).await {
-                        // Parse the ASN info
-                        let parts: Vec<&str> = asn_info.split('|').collect();
-                        if parts.len() >= 4 {
-                            let asn = parts[0].trim();
-                            let org = parts[3].trim();
-                            
-                            info.asn = Some(asn.to_string());
-                            radar_info!("ASN from router's IP: {}", asn);
-                            
-                            // If we don't have org info yet, use this
-                            if info.org.is_none() {
-                                info.org = Some(org.to_string());
-                                radar_info!("Organization from router's IP ASN: {}", org);
-                            }
-                            
-                            // If we don't have ISP info yet, try to use org as ISP
-                            if info.isp.is_none() {
-                                info.isp = Some(org.to_string());
-                                radar_info!("ISP inferred from router's IP ASN: {}", org);
-                            }
-                        }
-                    } else {
-                        info.asn = Some("".to_string());
-                        radar_info!("No ASN info for router's IP, setting empty ASN");
-                    }
-                }
+        // The origin record only has the registry, so the name comes from a
+        // separate lookup that is answered locally for well-known ASNs
+        let name = match parse_asn_number(asn) {
+            Some(number) => cancellable(cancel, crate::asn::asn_name(number)).await?,
+            None => None,
+        };
+        if let Some(org) = name {
+            // If we don't have org info yet, use this
+            if info.org.is_none() {
+                info.org = Some(org.clone());
+                radar_info!("Organization from ASN lookup: {}", org);
+            }
+            
+            // If we don't have ISP info yet, try to use org as ISP
+            if info.isp.is_none() {
+                info.isp = Some(org.clone());
+                radar_info!("ISP inferred from ASN org: {}", org);
             }
         }
         
-        // Log gateway IP if available
-        if let Some(gateway_ip) = &router_info.gateway_ip {
-            radar_info!("Router gateway IP: {}", gateway_ip);
+        // The same record carries the country
+        if info.location.is_none() {
+            info.location = location_from_cymru(&asn_info);
         }
-        
-        // Log manufacturer if available  
-        if let Some(manufacturer) = &router_info.manufacturer {
-            radar_info!("Router manufacturer: {}", manufacturer);
+    }
+    Ok(())
+}
+
+// Country of the default gateway, falling back to the public IP
+async fn fetch_geo(info: &mut PublicNetworkInfo, cancel: &CancellationToken) -> Result<()> {
+    info.location = None;
+    
+    // A gateway with a LAN address has no location of its own; the public IP below does
+    match get_default_gateway() {
+        Ok(gateway) if is_public_ip(&gateway.ip_addr.to_string()) => {
+            let gateway_ip = gateway.ip_addr.to_string();
+            radar_info!("Default gateway found: {}", gateway_ip);
+            
+            if let Some(geo_info) = cancellable(cancel, get_geo_info(&gateway_ip)).await? {
+                radar_info!("Geolocation info from gateway IP: {}", geo_info);
+                info.location = location_from_cymru(&geo_info);
+            } else {
+                radar_info!("No geolocation info found for gateway IP");
+            }
+        },
+        Ok(_) => {},
+        Err(_) => radar_info!("Could not determine default gateway"),
+    }
+    
+    if info.location.is_none() {
+        if let Some(ip) = info.ip.clone().filter(|ip| is_public_ip(ip)) {
+            if let Some(geo_info) = cancellable(cancel, get_geo_info(&ip)).await? {
+                radar_info!("Geo info from public IP: {}", geo_info);
+                info.location = location_from_cymru(&geo_info);
+            }
         }
-        
-        // Log model if available
-        if let Some(model) = &router_info.model {
-            radar_info!("Router model: {}", model);
+    }
+    Ok(())
+}
+
+// DNS servers from the local system configuration
+async fn fetch_dns(info: &mut PublicNetworkInfo, cancel: &CancellationToken) -> Result<()> {
+    if cancel.is_cancelled() {
+        return Err(NetworkError::Cancelled);
+    }
+    info.dns = crate::dns::system_dns_servers(false);
+    radar_info!("DNS servers found: {:?}", info.dns);
+    Ok(())
+}
+
+// Router details; the router's view of the public IP is used when STUN found none
+async fn fetch_router(info: &mut PublicNetworkInfo, cancel: &CancellationToken) -> Result<()> {
+    let router_info = match cancellable(cancel, get_router_and_isp_info()).await? {
+        Ok(router_info) => router_info,
+        Err(e) => {
+            radar_info!("Failed to get router info: {}", e);
+            info.router_info = None;
+            return Ok(());
         }
-        
-        // Log UPnP status
-        radar_info!("UPnP enabled: {:?}", router_info.upnp_enabled);
-        
-        // If we didn't get ISP info yet, try from router's isp_config
-        if info.isp.is_none() {
-            if let Some(isp_config) = &router_info.isp_config {
-                if let Some(isp_name) = &isp_config.isp_name {
-                    info.isp = Some(isp_name.clone());
-                    radar_info!("ISP from router's config: {}", isp_name);
-                }
+    };
+    radar_info!("Router info obtained: {:?}", router_info);
+    
+    // A CGNAT WAN address means the ISP shares one public IP between customers
+    info.behind_cgnat = router_info
+        .public_ip
+        .as_deref()
+        .and_then(|ip| ip.parse::<std::net::IpAddr>().ok())
+        .map(|ip| special_range(ip) == Some(SpecialRange::Cgnat));
+    if info.behind_cgnat == Some(true) {
+        radar_info!("Router's WAN address is in the CGNAT range");
+    }
+    
+    if info.ip.is_none() {
+        if let Some(router_ip) = &router_info.public_ip {
+            radar_info!("Setting public IP from router: {}", router_ip);
+            info.ip = Some(router_ip.clone());
+            fetch_public_hostname(info, cancel).await?;
+            if info.asn.as_deref().unwrap_or("").is_empty() {
+                fetch_asn(info, cancel).await?;
             }
         }
-        
-        info.router_info = Some(router_info);
-        radar_info!("Stored router info in PublicNetworkInfo");
-    } else {
-        radar_info!("Failed to get router info");
     }
     
-    // Process local hostname
-    if let Ok(hostname) = hostname_result {
-        info.local_hostname = Some(hostname.clone());
-        radar_info!("Local hostname: {}", hostname);
-    } else {
-        radar_info!("Failed to get local hostname");
+    radar_info!("UPnP enabled: {:?}", router_info.upnp_enabled);
+    
+    // If we didn't get ISP info yet, try from router's isp_config
+    if info.isp.is_none() {
+        if let Some(isp_name) = router_info.isp_config.as_ref().and_then(|c| c.isp_name.clone()) {
+            radar_info!("ISP from router's config: {}", isp_name);
+            info.isp = Some(isp_name);
+        }
+    }
+    
+    info.router_info = Some(router_info);
+    Ok(())
+}
+
+// Overall time budget for a WHOIS lookup, including referrals between servers
+const WHOIS_TIMEOUT: Duration = Duration::from_secs(10);
+
+// WHOIS registration of the public IP. Outbound port 43 is often blocked,
+// so a failed lookup just leaves the field empty.
+async fn fetch_whois(info: &mut PublicNetworkInfo, cancel: &CancellationToken) -> Result<()> {
+    info.whois = None;
+    let Some(ip) = info.ip.clone().filter(|ip| is_public_ip(ip)) else {
+        return Ok(());
+    };
+    
+    match cancellable(cancel, tokio::time::timeout(WHOIS_TIMEOUT, crate::whois::lookup_whois(&ip))).await? {
+        Ok(Ok(whois)) => {
+            radar_info!("WHOIS for {} from {}: {:?}", ip, whois.server, whois.netname);
+            info.whois = Some(whois);
+        },
+        Ok(Err(e)) => radar_info!("WHOIS lookup for {} failed: {}", ip, e),
+        Err(_) => radar_info!("WHOIS lookup for {} timed out", ip),
+    }
+    Ok(())
+}
+
+// Re-run the lookup behind one field, updating `info` in place
+pub async fn refresh_field(info: &mut PublicNetworkInfo, field: PublicField, cancel: &CancellationToken) -> Result<PublicFieldValue> {
+    radar_info!("Refreshing public network field {:?}", field);
+    
+    match field {
+        PublicField::Ip => fetch_ip(info, cancel).await?,
+        PublicField::Asn => {
+            info.asn = None;
+            fetch_asn(info, cancel).await?
+        },
+        PublicField::Geo => fetch_geo(info, cancel).await?,
+        PublicField::Dns => fetch_dns(info, cancel).await?,
+        PublicField::Router => fetch_router(info, cancel).await?,
+        PublicField::Hostname => {
+            fetch_public_hostname(info, cancel).await?;
+            fetch_local_hostname(info, cancel).await?
+        },
+        PublicField::Privacy => infer_privacy_status(info),
+        PublicField::Whois => fetch_whois(info, cancel).await?,
+    }
+    
+    Ok(PublicFieldValue::from_info(info, field))
+}
+
+// Refresh one field of the cached info held in the app state and return its new value
+pub async fn refresh_public_field(state: &PublicNetworkState, field: PublicField) -> Result<PublicFieldValue> {
+    // Work on a copy so the lock isn't held across the lookup
+    let mut info = state.state.lock().unwrap().clone();
+    let value = refresh_field(&mut info, field, &public_info_cancel_token()).await?;
+    *state.state.lock().unwrap() = info;
+    Ok(value)
+}
+
+// Public network info from the cache while it's fresh, otherwise (or when
+// `force` is set) from a full lookup that then refills the cache
+pub async fn get_public_network_info_internal(
+    state: &PublicNetworkState,
+    force: bool,
+    cancel: CancellationToken,
+) -> Result<PublicNetworkInfo> {
+    stream_public_network_info(&SilentSink, state, force, cancel).await
+}
+
+// Like `get_public_network_info_internal`, also emitting `public-info-update`
+// with the partial info each time a lookup finishes. A cached result is
+// emitted once, complete.
+pub async fn stream_public_network_info<S: ServiceSink>(
+    sink: &S,
+    state: &PublicNetworkState,
+    force: bool,
+    cancel: CancellationToken,
+) -> Result<PublicNetworkInfo> {
+    if !force {
+        if let Some(info) = state.cached() {
+            radar_info!("Returning cached public network info");
+            let _ = sink.emit_event("public-info-update", &info);
+            return Ok(info);
+        }
+    }
+    
+    let info = lookup_public_network_info(sink, cancel).await?;
+    state.store(&info);
+    Ok(info)
+}
+
+// Internal function that does the actual work, composed from the field fetchers.
+// The partial info is emitted as `public-info-update` after each step.
+async fn lookup_public_network_info<S: ServiceSink>(sink: &S, cancel: CancellationToken) -> Result<PublicNetworkInfo> {
+    radar_info!("Starting lookup_public_network_info()");
+    let mut info = PublicNetworkInfo::default();
+    let update = |info: &PublicNetworkInfo| {
+        let _ = sink.emit_event("public-info-update", info);
+    };
+    
+    // The public IP comes first since hostname, ASN and geo lookups depend on it
+    fetch_ip(&mut info, &cancel).await?;
+    update(&info);
+    fetch_public_hostname(&mut info, &cancel).await?;
+    update(&info);
+    fetch_asn(&mut info, &cancel).await?;
+    if info.location.is_none() {
+        fetch_geo(&mut info, &cancel).await?;
     }
+    update(&info);
+    
+    // The remaining lookups are independent of each other, so run them in
+    // parallel, folding each into the shared progress as soon as it finishes
+    let progress = Mutex::new(info.clone());
+    let dns = async {
+        let mut dns_info = info.clone();
+        fetch_dns(&mut dns_info, &cancel).await?;
+        let mut progress = progress.lock().unwrap();
+        progress.dns = dns_info.dns;
+        update(&progress);
+        Ok::<_, NetworkError>(())
+    };
+    let router = async {
+        let mut router_info = info.clone();
+        fetch_router(&mut router_info, &cancel).await?;
+        // The router fetch may have filled in IP-derived fields, so build on its copy
+        let mut progress = progress.lock().unwrap();
+        router_info.dns = std::mem::take(&mut progress.dns);
+        router_info.local_hostname = progress.local_hostname.take();
+        router_info.gateway_latency_ms = progress.gateway_latency_ms;
+        router_info.public_latency_ms = progress.public_latency_ms;
+        *progress = router_info;
+        update(&progress);
+        Ok::<_, NetworkError>(())
+    };
+    let local = async {
+        let mut local_info = PublicNetworkInfo::default();
+        fetch_local_hostname(&mut local_info, &cancel).await?;
+        let mut progress = progress.lock().unwrap();
+        progress.local_hostname = local_info.local_hostname;
+        update(&progress);
+        Ok::<_, NetworkError>(())
+    };
+    let latency = async {
+        let mut latency_info = PublicNetworkInfo::default();
+        fetch_latency(&mut latency_info, &cancel).await?;
+        let mut progress = progress.lock().unwrap();
+        progress.gateway_latency_ms = latency_info.gateway_latency_ms;
+        progress.public_latency_ms = latency_info.public_latency_ms;
+        update(&progress);
+        Ok::<_, NetworkError>(())
+    };
+    let (dns_result, router_result, local_result, latency_result) = tokio::join!(dns, router, local, latency);
+    dns_result?;
+    router_result?;
+    local_result?;
+    latency_result?;
+    info = progress.into_inner().unwrap();
     
     // If we still don't have ASN info, set an empty string
     if info.asn.is_none() {
@@ -370,61 +800,237 @@ pub async fn get_public_network_info_internal() -> Result<PublicNetworkInfo> {
     // Infer privacy status
     radar_info!("Inferring privacy status (VPN/proxy detection)");
     infer_privacy_status(&mut info);
+    radar_info!("Connection type: {:?} ({})", info.connection_type, info.connection_type_reasons.join("; "));
     radar_info!("VPN detected: {:?}, Proxy detected: {:?}, Hosting detected: {:?}", 
                 info.is_vpn, info.is_proxy, info.is_hosting);
     
+    update(&info);
     radar_info!("Returning complete PublicNetworkInfo: {:?}", info);
     Ok(info)
 }
 
-// Infer VPN/proxy/hosting status from available information
-fn infer_privacy_status(info: &mut PublicNetworkInfo) {
-    // Check for known VPN hostnames
-    if let Some(hostname) = &info.hostname {
-        let hostname_lower = hostname.to_lowercase();
-        
-        // Look for VPN provider patterns in hostname
-        let vpn_patterns = ["vpn", "proxy", "tor", "exit", "node", "relay", "tunnel"];
-        let is_vpn = vpn_patterns.iter().any(|&pattern| hostname_lower.contains(pattern));
-        
-        if is_vpn {
-            info.is_vpn = Some(true);
-            info.is_proxy = Some(true);
-        }
-        
-        // Look for hosting providers in hostname
-        let hosting_patterns = ["aws", "amazon", "azure", "google", "cloud", "host", "server", "cdn", "vps"];
-        let is_hosting = hosting_patterns.iter().any(|&pattern| hostname_lower.contains(pattern));
-        
-        if is_hosting {
-            info.is_hosting = Some(true);
+// Networks of commercial VPN providers
+const VPN_ASNS: &[u32] = &[
+    9009,   // M247
+    39351,  // 31173 Services (Mullvad)
+    51852,  // Private Layer
+    60068,  // Datacamp / CDN77
+    136787, // TEFINCOM (NordVPN)
+    209854, // Surfshark
+    212238, // Datacamp
+];
+
+// Cloud and hosting providers
+const HOSTING_ASNS: &[u32] = &[
+    8075,   // Microsoft
+    12876,  // Scaleway
+    13335,  // Cloudflare
+    14061,  // DigitalOcean
+    14618,  // Amazon
+    15169,  // Google
+    16276,  // OVH
+    16509,  // Amazon
+    20473,  // Vultr
+    24940,  // Hetzner
+    51167,  // Contabo
+    63949,  // Linode
+    396982, // Google Cloud
+];
+
+// Mobile carriers
+const MOBILE_ASNS: &[u32] = &[
+    20057, // AT&T Mobility
+    21928, // T-Mobile US
+    22394, // Verizon Wireless
+];
+
+// Consumer ISPs
+const RESIDENTIAL_ASNS: &[u32] = &[
+    209,   // CenturyLink
+    701,   // Verizon
+    2856,  // BT
+    3215,  // Orange
+    3320,  // Deutsche Telekom
+    4134,  // China Telecom
+    5089,  // Virgin Media
+    5650,  // Frontier
+    6830,  // Liberty Global
+    7018,  // AT&T
+    7922,  // Comcast
+    11351, // Charter (Spectrum)
+    11426, // Charter (Spectrum)
+    12322, // Free
+    20115, // Charter
+    22773, // Cox
+];
+
+// Whole hostname labels (split on '.' and '-') and domains seen in reverse DNS
+// of VPN and proxy exits
+const VPN_HOSTNAME_LABELS: &[&str] = &["vpn", "proxy", "tor", "exit", "relay", "tunnel"];
+const VPN_HOSTNAME_SUFFIXES: &[&str] = &["mullvad.net", "nordvpn.com", "protonvpn.net", "torproject.org"];
+
+// Labels and domains seen in reverse DNS of servers
+const HOSTING_HOSTNAME_LABELS: &[&str] = &["vps", "dedicated", "colo", "cdn"];
+const HOSTING_HOSTNAME_SUFFIXES: &[&str] = &[
+    "amazonaws.com",
+    "googleusercontent.com",
+    "cloudapp.azure.com",
+    "cloudapp.net",
+    "linodeusercontent.com",
+    "vultrusercontent.com",
+    "your-server.de",
+    "contaboserver.net",
+];
+
+// Labels seen in reverse DNS of dynamically assigned consumer addresses
+const RESIDENTIAL_HOSTNAME_LABELS: &[&str] = &[
+    "dyn", "dynamic", "dhcp", "pool", "dsl", "adsl", "vdsl", "cable", "ppp", "pppoe", "broadband",
+    "customer", "cpe", "res", "fios",
+];
+
+// Labels seen in reverse DNS of mobile carrier gateways
+const MOBILE_HOSTNAME_LABELS: &[&str] = &["mobile", "wireless", "lte", "cellular", "gprs"];
+
+// What a detection rule looks at
+#[derive(Debug, Clone, Copy)]
+enum RuleMatch {
+    // A whole label, so "node" matches "node-3.example.net" but not "nodejs.example.com"
+    Label(&'static str),
+    // The hostname is this domain or under it
+    Suffix(&'static str),
+    Asn(u32),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DetectionRule {
+    matches: RuleMatch,
+    connection_type: ConnectionType,
+}
+
+impl DetectionRule {
+    fn matches(&self, asn: Option<u32>, labels: &[&str], hostname: Option<&str>) -> bool {
+        match self.matches {
+            RuleMatch::Label(label) => labels.contains(&label),
+            RuleMatch::Suffix(suffix) => hostname
+                .is_some_and(|h| h == suffix || h.strip_suffix(suffix).is_some_and(|rest| rest.ends_with('.'))),
+            RuleMatch::Asn(number) => asn == Some(number),
         }
     }
     
-    // Check for known VPN autonomous systems
-    if let Some(asn) = &info.asn {
-        let asn_lower = asn.to_lowercase();
-        
-        // Known VPN ASNs
-        let vpn_asns = ["as60068", "as16276", "as51852", "as14618"];
-        let is_vpn_asn = vpn_asns.iter().any(|&pattern| asn_lower.contains(pattern));
-        
-        if is_vpn_asn {
-            info.is_vpn = Some(true);
-            info.is_proxy = Some(true);
+    fn reason(&self) -> String {
+        let asn_kind = match self.connection_type {
+            ConnectionType::Vpn => "a known VPN provider network",
+            ConnectionType::Mobile => "a mobile carrier",
+            ConnectionType::Hosting => "a hosting/cloud provider",
+            ConnectionType::Residential => "a consumer ISP",
+            ConnectionType::Unknown => "unclassified",
+        };
+        match self.matches {
+            RuleMatch::Label(label) => format!("Reverse hostname has the label '{}'", label),
+            RuleMatch::Suffix(suffix) => format!("Reverse hostname is under {}", suffix),
+            RuleMatch::Asn(number) => format!("AS{} is {}", number, asn_kind),
         }
     }
+}
+
+// Every rule in priority order; the first match decides. Known VPN networks and
+// exit names come first, then ASNs, then what the reverse hostname looks like.
+static DETECTION_RULES: Lazy<Vec<DetectionRule>> = Lazy::new(|| {
+    let asns = |list: &'static [u32], connection_type| {
+        list.iter().map(move |&n| DetectionRule { matches: RuleMatch::Asn(n), connection_type })
+    };
+    let labels = |list: &'static [&'static str], connection_type| {
+        list.iter().map(move |&l| DetectionRule { matches: RuleMatch::Label(l), connection_type })
+    };
+    let suffixes = |list: &'static [&'static str], connection_type| {
+        list.iter().map(move |&s| DetectionRule { matches: RuleMatch::Suffix(s), connection_type })
+    };
+    
+    asns(VPN_ASNS, ConnectionType::Vpn)
+        .chain(labels(VPN_HOSTNAME_LABELS, ConnectionType::Vpn))
+        .chain(suffixes(VPN_HOSTNAME_SUFFIXES, ConnectionType::Vpn))
+        .chain(asns(MOBILE_ASNS, ConnectionType::Mobile))
+        .chain(asns(HOSTING_ASNS, ConnectionType::Hosting))
+        .chain(asns(RESIDENTIAL_ASNS, ConnectionType::Residential))
+        .chain(labels(MOBILE_HOSTNAME_LABELS, ConnectionType::Mobile))
+        .chain(labels(RESIDENTIAL_HOSTNAME_LABELS, ConnectionType::Residential))
+        .chain(suffixes(HOSTING_HOSTNAME_SUFFIXES, ConnectionType::Hosting))
+        .chain(labels(HOSTING_HOSTNAME_LABELS, ConnectionType::Hosting))
+        .collect()
+});
+
+// Parse the AS number from "AS7922", "7922" or Cymru's "7922 | ..." format
+fn parse_asn_number(asn: &str) -> Option<u32> {
+    let first = asn.split('|').next()?.split_whitespace().next()?;
+    let digits = first.trim_start_matches("AS").trim_start_matches("as");
+    digits.parse().ok()
+}
+
+// Whether the hostname contains the IP address itself, e.g. "c-73-12-34-56.hsd1.example.net"
+fn hostname_embeds_ip(hostname: &str, ip: &str) -> bool {
+    let octets: Vec<&str> = ip.split('.').collect();
+    if octets.len() != 4 {
+        return false;
+    }
+    
+    let reversed: Vec<&str> = octets.iter().rev().copied().collect();
+    [octets, reversed].iter().any(|order| {
+        ["-", ".", ""].iter().any(|sep| hostname.contains(&order.join(sep)))
+    })
+}
+
+// Classify the connection from the ASN, whether reverse DNS exists and what the
+// reverse hostname looks like. Returns the classification and the evidence for it.
+fn classify_connection(ip: Option<&str>, asn: Option<&str>, hostname: Option<&str>) -> (ConnectionType, Vec<String>) {
+    let mut reasons = Vec::new();
+    
+    // A special-range address says nothing about the connection behind it
+    if let Some((ip, range)) = ip.and_then(|ip| Some((ip, special_range(ip.parse().ok()?)?))) {
+        reasons.push(format!("{} is {}, not a public IP", ip, range.describe()));
+        return (ConnectionType::Unknown, reasons);
+    }
+    
+    let asn_number = asn.and_then(parse_asn_number);
+    let hostname_lower = hostname.map(|h| h.trim_end_matches('.').to_lowercase());
+    let labels: Vec<&str> = hostname_lower.as_deref().map(|h| h.split(['.', '-']).collect()).unwrap_or_default();
+    let embeds_ip = hostname_lower.as_deref().zip(ip).is_some_and(|(hostname, ip)| hostname_embeds_ip(hostname, ip));
     
-    // If we haven't set these flags yet, default to false
-    if info.is_vpn.is_none() {
-        info.is_vpn = Some(false);
+    if let Some(rule) = DETECTION_RULES.iter().find(|rule| rule.matches(asn_number, &labels, hostname_lower.as_deref())) {
+        reasons.push(rule.reason());
+        if rule.connection_type == ConnectionType::Residential && embeds_ip && !matches!(rule.matches, RuleMatch::Asn(_)) {
+            reasons.push("Reverse hostname embeds the IP address".to_string());
+        }
+        return (rule.connection_type, reasons);
     }
-    if info.is_proxy.is_none() {
-        info.is_proxy = Some(false);
+    
+    if hostname_lower.is_none() {
+        reasons.push("No reverse DNS for the public IP".to_string());
+        return (ConnectionType::Unknown, reasons);
     }
-    if info.is_hosting.is_none() {
-        info.is_hosting = Some(false);
+    
+    // An IP-derived name with no server keywords is typical of ISP address pools
+    if embeds_ip {
+        reasons.push("Reverse hostname embeds the IP address".to_string());
+        return (ConnectionType::Residential, reasons);
     }
+    
+    reasons.push("Reverse hostname matches no known pattern".to_string());
+    (ConnectionType::Unknown, reasons)
+}
+
+// Infer VPN/proxy/hosting status from the connection classification
+fn infer_privacy_status(info: &mut PublicNetworkInfo) {
+    let (connection_type, reasons) = classify_connection(
+        info.ip.as_deref(),
+        info.asn.as_deref().filter(|asn| !asn.is_empty()),
+        info.hostname.as_deref(),
+    );
+    
+    info.is_vpn = Some(connection_type == ConnectionType::Vpn);
+    info.is_proxy = Some(connection_type == ConnectionType::Vpn);
+    info.is_hosting = Some(connection_type == ConnectionType::Hosting);
+    info.connection_type = Some(connection_type);
+    info.connection_type_reasons = reasons;
 }
 
 // Extract organization name from hostname
@@ -686,119 +1292,79 @@ fn infer_location_from_asn(asn: &str) -> Option<GeoLocation> {
     None
 }
 
-// Get DNS servers from local system configuration
-async fn get_local_dns_servers() -> Result<Vec<String>> {
-    radar_info!("Getting DNS servers from local system");
-    let mut dns_servers = Vec::new();
-    
-    // On Unix-like systems (macOS, Linux), read /etc/resolv.conf
-    #[cfg(unix)]
-    {
-        match std::fs::read_to_string("/etc/resolv.conf") {
-            Ok(contents) => {
-                for line in contents.lines() {
-                    if line.starts_with("nameserver") {
-                        let parts: Vec<&str> = line.split_whitespace().collect();
-                        if parts.len() >= 2 {
-                            radar_info!("Found DNS server in resolv.conf: {}", parts[1]);
-                            dns_servers.push(parts[1].to_string());
-                        }
-                    }
-                }
-            },
-            Err(e) => radar_info!("Error reading resolv.conf: {}", e),
+// How get_public_ip_via_stun picks a STUN server. Race queries every server at
+// once and takes the first answer; sequential tries one at a time, sending
+// fewer packets but waiting out each dead server's timeout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StunMode {
+    #[default]
+    Race,
+    Sequential,
+}
+
+static STUN_MODE: Lazy<Mutex<StunMode>> = Lazy::new(|| Mutex::new(StunMode::default()));
+
+pub fn set_stun_mode(mode: StunMode) {
+    radar_info!("STUN mode set to {:?}", mode);
+    *STUN_MODE.lock().unwrap() = mode;
+}
+
+// Per-server timeout when looking up the public IP
+const STUN_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Try each STUN server in turn, returning the first answer and its server
+async fn sequential_stun_answer(cancel: &CancellationToken) -> Result<Option<(String, String)>> {
+    for server in STUN_SERVERS {
+        if cancel.is_cancelled() {
+            return Err(NetworkError::Cancelled);
+        }
+        radar_debug!("Trying STUN server: {}", server);
+        
+        match cancellable(cancel, tokio::time::timeout(STUN_TIMEOUT, get_ip_from_stun_server(server))).await? {
+            Ok(Ok(ip)) => return Ok(Some((ip, server.to_string()))),
+            Ok(Err(e)) => radar_debug!("Failed to get IP from STUN server {}: {}", server, e),
+            Err(_) => radar_debug!("Timeout when connecting to STUN server: {}", server),
         }
     }
-    
-    // On macOS, can also use scutil
-    #[cfg(target_os = "macos")]
-    if dns_servers.is_empty() {
-        match Command::new("scutil")
-            .args(["--dns"])
-            .output() {
-                Ok(output) => {
-                    if let Ok(output_str) = String::from_utf8(output.stdout) {
-                        for line in output_str.lines() {
-                            if line.trim().starts_with("nameserver[") {
-                                if let Some(server) = line.split_whitespace().last() {
-                                    radar_info!("Found DNS server via scutil: {}", server);
-                                    dns_servers.push(server.to_string());
-                                }
-                            }
-                        }
-                    }
-                },
-                Err(e) => radar_info!("Error running scutil: {}", e),
-            }
-    }
-    
-    // On Windows, use ipconfig /all
-    #[cfg(target_os = "windows")]
-    if dns_servers.is_empty() {
-        match Command::new("ipconfig")
-            .args(["/all"])
-            .output() {
-                Ok(output) => {
-                    if let Ok(output_str) = String::from_utf8(output.stdout) {
-                        for line in output_str.lines() {
-                            if line.contains("DNS Servers") {
-                                if let Some(server) = line.split(':').last() {
-                                    let server = server.trim();
-                                    if !server.is_empty() {
-                                        radar_info!("Found DNS server via ipconfig: {}", server);
-                                        dns_servers.push(server.to_string());
-                                    }
-                                }
-                            }
-                        }
-                    }
-                },
-                Err(e) => radar_info!("Error running ipconfig: {}", e),
-            }
-    }
-    
-    Ok(dns_servers)
+    Ok(None)
 }
 
-// Modify get_public_ip_via_stun to use the log helper
-async fn get_public_ip_via_stun() -> Result<String> {
-    radar_info!("Starting STUN client to get public IP");
-    
-    // List of STUN servers to try, in order of preference
-    let servers = [
-        "stun.l.google.com:19302",
-        "stun1.l.google.com:19302",
-        "stun2.l.google.com:19302", 
-        "stun.stunprotocol.org:3478",
-        "stun.voip.blackberry.com:3478",
-        "stun.sipgate.net:10000"
-    ];
-    
-    // Try each server with a timeout
-    for server in servers {
-        radar_info!("Trying STUN server: {}", server);
-        
-        // Set up a timeout for this specific server
-        let timeout = std::time::Duration::from_secs(3);
-        
-        match tokio::time::timeout(timeout, get_ip_from_stun_server(server)).await {
-            Ok(result) => {
-                match result {
-                    Ok(ip) => {
-                        radar_info!("Successfully obtained public IP from STUN server {}: {}", server, ip);
-                        return Ok(ip);
-                    },
-                    Err(e) => {
-                        radar_info!("Failed to get IP from STUN server {}: {}", server, e);
-                        // Continue to next server
-                    }
-                }
-            },
-            Err(_) => {
-                radar_info!("Timeout when connecting to STUN server: {}", server);
-                // Continue to next server
+// Query every STUN server at once, returning the first answer and its server.
+// The queries still in flight are dropped, which cancels them.
+async fn racing_stun_answer(cancel: &CancellationToken) -> Result<Option<(String, String)>> {
+    use futures::stream::{FuturesUnordered, StreamExt};
+    
+    let mut queries: FuturesUnordered<_> = STUN_SERVERS
+        .iter()
+        .map(|server| async move { (*server, tokio::time::timeout(STUN_TIMEOUT, get_ip_from_stun_server(server)).await) })
+        .collect();
+    let first = async {
+        while let Some((server, answer)) = queries.next().await {
+            match answer {
+                Ok(Ok(ip)) => return Some((ip, server.to_string())),
+                Ok(Err(e)) => radar_debug!("Failed to get IP from STUN server {}: {}", server, e),
+                Err(_) => radar_debug!("Timeout when connecting to STUN server: {}", server),
             }
         }
+        None
+    };
+    cancellable(cancel, first).await
+}
+
+// Public IP from the STUN servers, falling back to get_public_ip_via_http, with
+// where it came from
+async fn get_public_ip_via_stun(cancel: &CancellationToken) -> Result<(String, String)> {
+    let mode = *STUN_MODE.lock().unwrap();
+    radar_info!("Starting STUN client to get public IP ({:?})", mode);
+    
+    let answer = match mode {
+        StunMode::Race => racing_stun_answer(cancel).await?,
+        StunMode::Sequential => sequential_stun_answer(cancel).await?,
+    };
+    if let Some((ip, server)) = answer {
+        radar_info!("Successfully obtained public IP from STUN server {}: {}", server, ip);
+        return Ok((ip, format!("stun:{}", server)));
     }
     
     // If all servers failed, try alternative method - fallback to an HTTP service
@@ -806,12 +1372,12 @@ async fn get_public_ip_via_stun() -> Result<String> {
     
     // HTTP fallback with timeout
     let timeout = std::time::Duration::from_secs(5);
-    match tokio::time::timeout(timeout, get_public_ip_via_http()).await {
+    match cancellable(cancel, tokio::time::timeout(timeout, get_public_ip_via_http())).await? {
         Ok(result) => {
             match result {
                 Ok(ip) => {
                     radar_info!("Successfully obtained public IP from HTTP API: {}", ip);
-                    return Ok(ip);
+                    return Ok((ip, "http".to_string()));
                 },
                 Err(e) => {
                     radar_info!("HTTP API fallback failed: {}", e);
@@ -828,27 +1394,92 @@ async fn get_public_ip_via_stun() -> Result<String> {
     Err(NetworkError::RequestFailed("Could not determine public IP address from any source".to_string()))
 }
 
+// Result of the fast public-IP-only lookup
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PublicIpOnly {
+    pub ipv4: Option<String>,
+    pub ipv6: Option<String>,
+    // Where the address came from, e.g. "stun:stun.l.google.com:19302"
+    pub source: String,
+}
+
+// Per-server timeout for the fast path; a healthy STUN server answers well within it
+const FAST_STUN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+// First STUN server to report our address for one IP family, with the server's name
+async fn first_stun_answer(ipv6: bool, cancel: &CancellationToken) -> Result<Option<(String, String)>> {
+    for server in STUN_SERVERS {
+        let lookup = async {
+            if ipv6 {
+                get_ipv6_from_stun_server(server).await
+            } else {
+                get_ip_from_stun_server(server).await
+            }
+        };
+        match cancellable(cancel, tokio::time::timeout(FAST_STUN_TIMEOUT, lookup)).await? {
+            Ok(Ok(ip)) => return Ok(Some((ip, server.to_string()))),
+            Ok(Err(e)) => radar_debug!("STUN server {} gave no {} address: {}", server, if ipv6 { "IPv6" } else { "IPv4" }, e),
+            Err(_) => radar_debug!("Timeout when connecting to STUN server: {}", server),
+        }
+    }
+    Ok(None)
+}
+
+// Just the public IPv4/IPv6 addresses from STUN, skipping the ASN, geo, router
+// and DNS lookups of the full public network info. Both families are queried in parallel.
+pub async fn get_public_ip_only(cancel: &CancellationToken) -> Result<PublicIpOnly> {
+    let (ipv4, ipv6) = tokio::join!(first_stun_answer(false, cancel), first_stun_answer(true, cancel));
+    let (ipv4, ipv6) = (ipv4?, ipv6?);
+    
+    // Prefer naming the IPv4 server, since that's the address most callers want
+    let source = match ipv4.as_ref().or(ipv6.as_ref()) {
+        Some((_, server)) => format!("stun:{}", server),
+        None => return Err(NetworkError::RequestFailed("No STUN server reported a public IP".to_string())),
+    };
+    
+    Ok(PublicIpOnly {
+        ipv4: ipv4.map(|(ip, _)| ip),
+        ipv6: ipv6.map(|(ip, _)| ip),
+        source,
+    })
+}
+
+// STUN servers to try, in order of preference
+const STUN_SERVERS: [&str; 6] = [
+    "stun.l.google.com:19302",
+    "stun1.l.google.com:19302",
+    "stun2.l.google.com:19302",
+    "stun.stunprotocol.org:3478",
+    "stun.voip.blackberry.com:3478",
+    "stun.sipgate.net:10000",
+];
+
 // Implements the STUN protocol to get the public IP from a given STUN server
-async fn get_ip_from_stun_server(server: &str) -> Result<String> {
-    radar_info!("Connecting to STUN server: {}", server);
+pub(crate) async fn get_ip_from_stun_server(server: &str) -> Result<String> {
+    query_stun_server(server, "0.0.0.0:0").await
+}
+
+// Same as get_ip_from_stun_server, over IPv6 to learn the public IPv6 address
+pub(crate) async fn get_ipv6_from_stun_server(server: &str) -> Result<String> {
+    query_stun_server(server, "[::]:0").await
+}
+
+// Send a Binding Request from a socket bound to `bind_address`; the bound
+// family decides which of the server's addresses is used
+async fn query_stun_server(server: &str, bind_address: &str) -> Result<String> {
+    radar_debug!("Connecting to STUN server: {}", server);
     
-    let socket = match UdpSocket::bind("0.0.0.0:0") {
+    let socket = match UdpSocket::bind(bind_address).await {
         Ok(socket) => {
-            radar_info!("Successfully bound UDP socket to 0.0.0.0:0");
+            radar_trace!("Successfully bound UDP socket to {}", bind_address);
             socket
         },
         Err(e) => {
-            radar_info!("Failed to bind UDP socket: {}", e);
+            radar_debug!("Failed to bind UDP socket: {}", e);
             return Err(NetworkError::IoError(e));
         }
     };
     
-    // Set read timeout to prevent hanging
-    if let Err(e) = socket.set_read_timeout(Some(std::time::Duration::from_secs(2))) {
-        radar_info!("Failed to set socket read timeout: {}", e);
-        return Err(NetworkError::IoError(e));
-    }
-    
     // Prepare STUN request (RFC 5389)
     // Message Type: 0x0001 (Binding Request)
     // Message Length: 0x0000 (no attributes)
@@ -865,105 +1496,138 @@ async fn get_ip_from_stun_server(server: &str) -> Result<String> {
         request.push(rand::random::<u8>());
     }
     
-    radar_info!("STUN request prepared, connecting to server...");
+    radar_trace!("STUN request prepared, connecting to server...");
     
     // Send request to STUN server
-    match socket.connect(server) {
-        Ok(_) => radar_info!("Connected to STUN server: {}", server),
+    match socket.connect(server).await {
+        Ok(_) => radar_trace!("Connected to STUN server: {}", server),
         Err(e) => {
-            radar_info!("Failed to connect to STUN server {}: {}", server, e);
+            radar_debug!("Failed to connect to STUN server {}: {}", server, e);
             return Err(NetworkError::IoError(e));
         }
     }
     
-    match socket.send(&request) {
-        Ok(bytes_sent) => radar_info!("Sent {} bytes to STUN server", bytes_sent),
+    match socket.send(&request).await {
+        Ok(bytes_sent) => radar_trace!("Sent {} bytes to STUN server", bytes_sent),
         Err(e) => {
-            radar_info!("Failed to send request to STUN server: {}", e);
+            radar_debug!("Failed to send request to STUN server: {}", e);
             return Err(NetworkError::IoError(e));
         }
     }
     
-    radar_info!("Waiting for STUN response...");
+    radar_trace!("Waiting for STUN response...");
     
-    // Receive response
+    // Receive response, with a read timeout to prevent hanging
     let mut response = [0u8; 512];
-    let size = match socket.recv(&mut response) {
-        Ok(size) => {
-            radar_info!("Received {} bytes from STUN server", size);
+    let size = match tokio::time::timeout(std::time::Duration::from_secs(2), socket.recv(&mut response)).await {
+        Ok(Ok(size)) => {
+            radar_trace!("Received {} bytes from STUN server", size);
             size
         },
-        Err(e) => {
-            radar_info!("Failed to receive response from STUN server: {}", e);
+        Ok(Err(e)) => {
+            radar_debug!("Failed to receive response from STUN server: {}", e);
             return Err(NetworkError::IoError(e));
+        },
+        Err(_) => {
+            radar_debug!("Timed out waiting for STUN response");
+            return Err(NetworkError::RequestFailed("STUN response timed out".to_string()));
         }
     };
     
     if size < 20 {
-        radar_info!("Invalid STUN response size: {} (should be at least 20 bytes)", size);
+        radar_debug!("Invalid STUN response size: {} (should be at least 20 bytes)", size);
         return Err(NetworkError::RequestFailed(format!("Invalid STUN response size: {}", size)));
     }
     
     // Check if response is a STUN Binding Response (type 0x0101)
     if response[0] != 0x01 || response[1] != 0x01 {
-        radar_info!("Not a STUN Binding Response: got {:02x}{:02x} (expected 0101)", response[0], response[1]);
+        radar_debug!("Not a STUN Binding Response: got {:02x}{:02x} (expected 0101)", response[0], response[1]);
         return Err(NetworkError::RequestFailed("Not a STUN Binding Response".to_string()));
     }
     
-    radar_info!("Received valid STUN Binding Response, parsing attributes...");
+    radar_trace!("Received valid STUN Binding Response, parsing attributes...");
     
-    // Parse the response to extract the mapped address
-    // Skip the 20-byte header and look for the XOR-MAPPED-ADDRESS attribute (type 0x0020)
-    let mut pos = 20;
-    radar_info!("Parsing response of {} bytes, starting from position {}", size, pos);
+    parse_stun_mapped_address(&response[..size])
+}
+
+// Walk the attributes of a STUN Binding Response (after the 20-byte header) and
+// extract the XOR-MAPPED-ADDRESS. Every read is bounds-checked, so a truncated or
+// malformed response is reported as an error instead of panicking.
+fn parse_stun_mapped_address(response: &[u8]) -> Result<String> {
+    let malformed = |what: &str| {
+        radar_debug!("Malformed STUN response: {}", what);
+        NetworkError::RequestFailed(format!("Malformed STUN response: {}", what))
+    };
     
-    while pos + 8 <= size {
-        let attr_type = ((response[pos] as u16) << 8) | (response[pos + 1] as u16);
-        let attr_length = ((response[pos + 2] as u16) << 8) | (response[pos + 3] as u16);
+    let mut pos = 20;
+    radar_trace!("Parsing response of {} bytes, starting from position {}", response.len(), pos);
+    
+    while pos < response.len() {
+        let header = response
+            .get(pos..pos + 4)
+            .ok_or_else(|| malformed("truncated attribute header"))?;
+        let attr_type = u16::from_be_bytes([header[0], header[1]]);
+        let attr_length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let value = response
+            .get(pos + 4..pos + 4 + attr_length)
+            .ok_or_else(|| malformed("attribute runs past the end of the response"))?;
         
-        radar_info!("Found attribute type: 0x{:04x}, length: {}", attr_type, attr_length);
+        radar_trace!("Found attribute type: 0x{:04x}, length: {}", attr_type, attr_length);
         
         // XOR-MAPPED-ADDRESS attribute (type 0x0020)
-        if attr_type == 0x0020 && attr_length >= 8 {
+        if attr_type == 0x0020 {
             // Family: 0x01 for IPv4, 0x02 for IPv6
-            let family = response[pos + 5];
-            radar_info!("XOR-MAPPED-ADDRESS attribute found, family: {}", family);
+            let family = *value
+                .get(1)
+                .ok_or_else(|| malformed("XOR-MAPPED-ADDRESS too short"))?;
+            radar_trace!("XOR-MAPPED-ADDRESS attribute found, family: {}", family);
             
             if family == 0x01 {  // IPv4
-                // Port (XORed with the first 2 bytes of the Magic Cookie)
-                // IP (XORed with the Magic Cookie)
-                let xor_ip = [
-                    response[pos + 8] ^ 0x21,
-                    response[pos + 9] ^ 0x12,
-                    response[pos + 10] ^ 0xA4,
-                    response[pos + 11] ^ 0x42,
-                ];
-                
-                let ip = format!("{}.{}.{}.{}", xor_ip[0], xor_ip[1], xor_ip[2], xor_ip[3]);
-                radar_info!("Successfully extracted IPv4 address: {}", ip);
+                // Port is bytes 2-3, IP is bytes 4-7 (XORed with the Magic Cookie)
+                let xor_ip = value
+                    .get(4..8)
+                    .ok_or_else(|| malformed("XOR-MAPPED-ADDRESS too short for IPv4"))?;
+                let ip = format!(
+                    "{}.{}.{}.{}",
+                    xor_ip[0] ^ 0x21,
+                    xor_ip[1] ^ 0x12,
+                    xor_ip[2] ^ 0xA4,
+                    xor_ip[3] ^ 0x42,
+                );
+                radar_debug!("Successfully extracted IPv4 address: {}", ip);
+                return Ok(ip);
+            } else if family == 0x02 {  // IPv6
+                // IP is bytes 4-19, XORed with the Magic Cookie followed by the Transaction ID
+                let xor_ip = value
+                    .get(4..20)
+                    .ok_or_else(|| malformed("XOR-MAPPED-ADDRESS too short for IPv6"))?;
+                let key = response
+                    .get(4..20)
+                    .ok_or_else(|| malformed("truncated header"))?;
+                let mut octets = [0u8; 16];
+                for (i, octet) in octets.iter_mut().enumerate() {
+                    *octet = xor_ip[i] ^ key[i];
+                }
+                let ip = std::net::Ipv6Addr::from(octets).to_string();
+                radar_debug!("Successfully extracted IPv6 address: {}", ip);
                 return Ok(ip);
-            } else if family == 0x02 {  // IPv6 (simplified handling)
-                radar_info!("IPv6 address found but handling not implemented");
-                return Err(NetworkError::RequestFailed("IPv6 handling not implemented".to_string()));
             } else {
-                radar_info!("Unknown address family: {}", family);
+                radar_debug!("Unknown address family: {}", family);
             }
         }
         
         // Move to the next attribute (attributes are padded to 4-byte boundaries)
         let old_pos = pos;
-        pos += 4 + attr_length as usize;
-        if attr_length % 4 != 0 {
-            pos += 4 - (attr_length % 4) as usize;
-        }
-        radar_info!("Moving from position {} to {}", old_pos, pos);
+        pos += 4 + attr_length + (4 - attr_length % 4) % 4;
+        radar_trace!("Moving from position {} to {}", old_pos, pos);
     }
     
-    radar_info!("XOR-MAPPED-ADDRESS attribute not found in STUN response");
+    radar_debug!("XOR-MAPPED-ADDRESS attribute not found in STUN response");
     Err(NetworkError::RequestFailed("Could not find XOR-MAPPED-ADDRESS in STUN response".to_string()))
 }
 
-// HTTP fallback method for getting public IP with improved logging
+// HTTP fallback for the public IP. Asking external HTTP APIs is disabled, so
+// this asks the router for its WAN address instead (UPnP or ISP config).
 async fn get_public_ip_via_http() -> Result<String> {
     radar_info!("HTTP fallback for public IP is disabled - using local methods only");
     
@@ -1019,7 +1683,151 @@ async fn get_local_hostname() -> Result<String> {
 // Register commands with Tauri
 pub fn init<R: tauri::Runtime>(app: &mut tauri::App<R>) -> std::result::Result<(), Box<dyn std::error::Error>> {
     // Use Arc<Mutex<T>> for thread-safe sharing
-    app.manage(PublicNetworkState { state: Arc::new(Mutex::new(PublicNetworkInfo::default())) });
+    app.manage(PublicNetworkState::new(DEFAULT_PUBLIC_INFO_TTL));
     
     Ok(())
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRANSACTION_ID: [u8; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+
+    // Binding Response header followed by the given attributes
+    fn binding_response(attributes: &[u8]) -> Vec<u8> {
+        let mut response = vec![0x01, 0x01];
+        response.extend_from_slice(&(attributes.len() as u16).to_be_bytes());
+        response.extend_from_slice(&[0x21, 0x12, 0xA4, 0x42]);
+        response.extend_from_slice(&TRANSACTION_ID);
+        response.extend_from_slice(attributes);
+        response
+    }
+
+    #[test]
+    fn ipv4_mapped_address_is_unxored() {
+        let response = binding_response(&[
+            0x00, 0x20, 0x00, 0x08,
+            0x00, 0x01, 0x11, 0x2b, 203 ^ 0x21, 0x12, 113 ^ 0xA4, 5 ^ 0x42,
+        ]);
+        assert_eq!(parse_stun_mapped_address(&response).unwrap(), "203.0.113.5");
+    }
+
+    #[test]
+    fn ipv6_mapped_address_is_unxored_with_transaction_id() {
+        let ip: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let mut key = vec![0x21, 0x12, 0xA4, 0x42];
+        key.extend_from_slice(&TRANSACTION_ID);
+        let mut attribute = vec![0x00, 0x20, 0x00, 0x14, 0x00, 0x02, 0x11, 0x2b];
+        attribute.extend(ip.octets().iter().zip(&key).map(|(octet, k)| octet ^ k));
+
+        assert_eq!(parse_stun_mapped_address(&binding_response(&attribute)).unwrap(), "2001:db8::1");
+    }
+
+    #[test]
+    fn padded_attributes_are_skipped() {
+        let response = binding_response(&[
+            // SOFTWARE, 5 bytes padded to 8
+            0x80, 0x22, 0x00, 0x05, b'r', b'a', b'd', b'a', b'r', 0, 0, 0,
+            0x00, 0x20, 0x00, 0x08,
+            0x00, 0x01, 0x11, 0x2b, 10 ^ 0x21, 0x12, 0xA4, 1 ^ 0x42,
+        ]);
+        assert_eq!(parse_stun_mapped_address(&response).unwrap(), "10.0.0.1");
+    }
+
+    #[test]
+    fn truncated_responses_are_errors() {
+        // Attribute claims more bytes than the response has
+        assert!(parse_stun_mapped_address(&binding_response(&[0x00, 0x20, 0x00, 0x14, 0x00, 0x02])).is_err());
+        // IPv4 address cut short
+        assert!(parse_stun_mapped_address(&binding_response(&[0x00, 0x20, 0x00, 0x04, 0x00, 0x01, 0x11, 0x2b])).is_err());
+        // Partial attribute header
+        assert!(parse_stun_mapped_address(&binding_response(&[0x00, 0x20])).is_err());
+        // No XOR-MAPPED-ADDRESS at all
+        assert!(parse_stun_mapped_address(&binding_response(&[])).is_err());
+    }
+
+    fn range_of(ip: &str) -> Option<SpecialRange> {
+        special_range(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn private_ipv4_ranges_and_their_boundaries() {
+        for ip in ["10.0.0.0", "10.255.255.255", "172.16.0.0", "172.31.255.255", "192.168.0.0", "192.168.255.255"] {
+            assert_eq!(range_of(ip), Some(SpecialRange::Private), "{}", ip);
+        }
+        for ip in ["9.255.255.255", "11.0.0.0", "172.15.255.255", "172.32.0.0", "192.167.255.255", "192.169.0.0"] {
+            assert_eq!(range_of(ip), None, "{}", ip);
+        }
+    }
+
+    #[test]
+    fn other_ipv4_ranges_and_their_boundaries() {
+        assert_eq!(range_of("169.254.0.0"), Some(SpecialRange::LinkLocal));
+        assert_eq!(range_of("169.254.255.255"), Some(SpecialRange::LinkLocal));
+        assert_eq!(range_of("169.253.255.255"), None);
+        assert_eq!(range_of("169.255.0.0"), None);
+
+        assert_eq!(range_of("100.64.0.0"), Some(SpecialRange::Cgnat));
+        assert_eq!(range_of("100.127.255.255"), Some(SpecialRange::Cgnat));
+        assert_eq!(range_of("100.63.255.255"), None);
+        assert_eq!(range_of("100.128.0.0"), None);
+
+        assert_eq!(range_of("127.0.0.0"), Some(SpecialRange::Loopback));
+        assert_eq!(range_of("127.255.255.255"), Some(SpecialRange::Loopback));
+        assert_eq!(range_of("126.255.255.255"), None);
+        assert_eq!(range_of("128.0.0.0"), None);
+
+        assert_eq!(range_of("0.0.0.0"), Some(SpecialRange::Unspecified));
+        assert_eq!(range_of("8.8.8.8"), None);
+    }
+
+    #[test]
+    fn ipv6_ranges_and_their_boundaries() {
+        assert_eq!(range_of("fc00::"), Some(SpecialRange::Private));
+        assert_eq!(range_of("fdff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"), Some(SpecialRange::Private));
+        assert_eq!(range_of("fbff:ffff:ffff:ffff:ffff:ffff:ffff:ffff"), None);
+        assert_eq!(range_of("fe00::"), None);
+
+        assert_eq!(range_of("fe80::"), Some(SpecialRange::LinkLocal));
+        assert_eq!(range_of("febf:ffff:ffff:ffff:ffff:ffff:ffff:ffff"), Some(SpecialRange::LinkLocal));
+        assert_eq!(range_of("fe7f:ffff:ffff:ffff:ffff:ffff:ffff:ffff"), None);
+        assert_eq!(range_of("fec0::"), None);
+
+        assert_eq!(range_of("::1"), Some(SpecialRange::Loopback));
+        assert_eq!(range_of("::"), Some(SpecialRange::Unspecified));
+        assert_eq!(range_of("2001:4860:4860::8888"), None);
+    }
+
+    fn connection_type_of(ip: &str, hostname: &str) -> ConnectionType {
+        classify_connection(Some(ip), None, Some(hostname)).0
+    }
+
+    #[test]
+    fn vpn_hostnames_are_classified_by_domain_and_label() {
+        assert_eq!(connection_type_of("185.65.134.10", "us-nyc-wg-301.mullvad.net."), ConnectionType::Vpn);
+        assert_eq!(connection_type_of("203.0.113.7", "vpn-gw3.example.com"), ConnectionType::Vpn);
+        // Only whole labels count
+        assert_eq!(connection_type_of("203.0.113.8", "torrent.example.com"), ConnectionType::Unknown);
+        assert_eq!(connection_type_of("203.0.113.9", "www.notmullvad.net"), ConnectionType::Unknown);
+    }
+
+    #[test]
+    fn hosting_hostnames_are_classified() {
+        assert_eq!(
+            connection_type_of("52.85.76.55", "ec2-52-85-76-55.compute-1.amazonaws.com"),
+            ConnectionType::Hosting
+        );
+        assert_eq!(connection_type_of("198.51.100.4", "vps-12.example.net"), ConnectionType::Hosting);
+    }
+
+    #[test]
+    fn residential_hostnames_are_classified() {
+        assert_eq!(connection_type_of("71.1.2.3", "pool-71-1-2-3.nycmny.fios.verizon.net"), ConnectionType::Residential);
+        // No keyword, but the name is derived from the address
+        let (connection_type, reasons) =
+            classify_connection(Some("73.12.34.56"), None, Some("c-73-12-34-56.hsd1.ca.comcast.net"));
+        assert_eq!(connection_type, ConnectionType::Residential);
+        assert_eq!(reasons, ["Reverse hostname embeds the IP address"]);
+    }
+}
@@ -1,10 +1,14 @@
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
+use std::time::Duration;
 use thiserror::Error;
 use default_net::{self, Gateway};
 use std::process::Command;
 use reqwest;
+use crate::radar_debug;
 use crate::radar_info;
+use futures::StreamExt;
+use ssdp_client::{SearchTarget, URN};
 
 // Error types specific to router discovery
 #[derive(Error, Debug)]
@@ -21,7 +25,6 @@ pub enum RouterError {
     RouterResponseError(String),
     
     #[error("UPnP error: {0}")]
-    #[allow(dead_code)]
     UPnPError(String),
     
     #[error("HTTP client error: {0}")]
@@ -55,6 +58,21 @@ pub struct RouterInfo {
     pub firmware_version: Option<String>,
     pub isp_config: Option<IspConfig>,
     pub connected_interfaces: Vec<NetworkInterface>,
+    // Port forwards configured on the router, read over UPnP IGD
+    #[serde(default)]
+    pub port_mappings: Vec<PortMapping>,
+}
+
+// A port forward from the router's UPnP port mapping table
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PortMapping {
+    pub external_port: u16,
+    pub internal_client: String,
+    pub internal_port: u16,
+    // "TCP" or "UDP"
+    pub protocol: String,
+    pub description: String,
+    pub enabled: bool,
 }
 
 // ISP configuration
@@ -104,17 +122,37 @@ pub async fn discover_gateway() -> Result<RouterInfo> {
             }
         }
         
-        // Try to get DNS servers
-        match get_dns_servers().await {
-            Ok(dns_servers) => {
-                radar_info!("Found {} DNS servers", dns_servers.len());
-                router_info.dns_servers = dns_servers.clone();
+        // Get DNS servers, falling back to public ones so there is always something to show
+        router_info.dns_servers = crate::dns::system_dns_servers(true);
+        radar_info!("Found {} DNS servers", router_info.dns_servers.len());
+        
+        // Ask the router's Internet Gateway Device for the WAN address and port forwards
+        match find_wan_connection_service(gateway.ip_addr).await {
+            Ok(wan) => {
+                router_info.upnp_enabled = Some(true);
+                router_info.manufacturer = wan.manufacturer.clone();
+                router_info.model = wan.model.clone();
+                match get_external_ip_via_upnp(&wan).await {
+                    Ok(ip) => {
+                        radar_info!("Router reports external IP via UPnP: {}", ip);
+                        router_info.public_ip = Some(ip);
+                    },
+                    Err(e) => radar_info!("Could not get external IP via UPnP: {}", e),
+                }
+                router_info.port_mappings = get_port_mappings(&wan).await;
             },
             Err(e) => {
-                radar_info!("Error getting DNS servers: {}", e);
+                radar_info!("No UPnP WAN connection service: {}", e);
+                router_info.upnp_enabled = Some(!matches!(e, RouterError::NoRouterFound));
             }
         }
         
+        // Routers always have a MAC but don't all speak UPnP, so the MAC vendor
+        // names the make when the IGD description didn't
+        if router_info.manufacturer.is_none() {
+            router_info.manufacturer = crate::oui::lookup_oui(&gateway.mac_addr.to_string());
+        }
+        
         // Try to get ISP configuration
         match get_isp_config(gateway.ip_addr).await {
             Ok(isp_config) => {
@@ -227,88 +265,156 @@ async fn get_network_interfaces() -> Result<Vec<NetworkInterface>> {
     }
 }
 
-// Get DNS servers
-async fn get_dns_servers() -> Result<Vec<String>> {
-    radar_info!("Getting DNS servers using dns-lookup crate...");
-    
-    let mut dns_servers = Vec::new();
+// How long to wait for Internet Gateway Devices to answer the SSDP search
+const IGD_SEARCH_TIMEOUT: Duration = Duration::from_secs(2);
+
+// WAN connection services that implement GetExternalIPAddress
+const WAN_CONNECTION_SERVICES: [&str; 2] = ["WANIPConnection", "WANPPPConnection"];
+
+// Find the description URL of the Internet Gateway Device, preferring the one
+// on the default gateway when several answer
+async fn find_igd_location(gateway_ip: IpAddr) -> Option<String> {
+    let target = SearchTarget::URN(URN::device("schemas-upnp-org", "InternetGatewayDevice", 1));
+    let responses = match ssdp_client::search(&target, IGD_SEARCH_TIMEOUT, 2, Some(crate::network_scanner::get_multicast_ttl())).await {
+        Ok(responses) => responses,
+        Err(e) => {
+            radar_info!("SSDP search for an Internet Gateway Device failed: {}", e);
+            return None;
+        }
+    };
+    tokio::pin!(responses);
     
-    // On Unix-like systems (macOS, Linux), read /etc/resolv.conf
-    #[cfg(unix)]
-    {
-        match std::fs::read_to_string("/etc/resolv.conf") {
-            Ok(contents) => {
-                for line in contents.lines() {
-                    if line.starts_with("nameserver") {
-                        let parts: Vec<&str> = line.split_whitespace().collect();
-                        if parts.len() >= 2 {
-                            radar_info!("Found DNS server in resolv.conf: {}", parts[1]);
-                            dns_servers.push(parts[1].to_string());
-                        }
-                    }
-                }
-            },
-            Err(e) => radar_info!("Error reading resolv.conf: {}", e),
+    let mut fallback = None;
+    while let Some(response) = responses.next().await {
+        let Ok(response) = response else {
+            continue;
+        };
+        let location = response.location().to_string();
+        radar_debug!("Internet Gateway Device answered at {}", location);
+        let on_gateway = reqwest::Url::parse(&location)
+            .ok()
+            .and_then(|url| url.host_str()?.parse::<IpAddr>().ok())
+            == Some(gateway_ip);
+        if on_gateway {
+            return Some(location);
         }
+        fallback.get_or_insert(location);
     }
-    
-    // On macOS, can also use scutil
-    #[cfg(target_os = "macos")]
-    if dns_servers.is_empty() {
-        match Command::new("scutil")
-            .args(["--dns"])
-            .output() {
-                Ok(output) => {
-                    if let Ok(output_str) = String::from_utf8(output.stdout) {
-                        for line in output_str.lines() {
-                            if line.trim().starts_with("nameserver[") {
-                                if let Some(server) = line.split_whitespace().last() {
-                                    radar_info!("Found DNS server via scutil: {}", server);
-                                    dns_servers.push(server.to_string());
-                                }
-                            }
-                        }
-                    }
-                },
-                Err(e) => radar_info!("Error running scutil: {}", e),
+    fallback
+}
+
+// Service type and control URL of the first WAN connection service in the device tree
+fn find_wan_connection(device: &crate::Device) -> Option<(String, String)> {
+    let own = device.service_list.as_ref().and_then(|list| {
+        list.services.iter().find_map(|service| {
+            let service_type = service.service_type.trim();
+            let is_wan = WAN_CONNECTION_SERVICES
+                .iter()
+                .any(|name| service_type.contains(&format!(":service:{}:", name)));
+            if !is_wan {
+                return None;
             }
-    }
+            Some((service_type.to_string(), service.control_url.as_ref()?.trim().to_string()))
+        })
+    });
     
-    // On Windows, use ipconfig /all
-    #[cfg(target_os = "windows")]
-    if dns_servers.is_empty() {
-        match Command::new("ipconfig")
-            .args(["/all"])
-            .output() {
-                Ok(output) => {
-                    if let Ok(output_str) = String::from_utf8(output.stdout) {
-                        for line in output_str.lines() {
-                            if line.contains("DNS Servers") {
-                                if let Some(server) = line.split(':').last() {
-                                    let server = server.trim();
-                                    if !server.is_empty() {
-                                        radar_info!("Found DNS server via ipconfig: {}", server);
-                                        dns_servers.push(server.to_string());
-                                    }
-                                }
-                            }
-                        }
-                    }
-                },
-                Err(e) => radar_info!("Error running ipconfig: {}", e),
-            }
-    }
+    own.or_else(|| {
+        device
+            .device_list
+            .as_ref()?
+            .devices
+            .iter()
+            .find_map(find_wan_connection)
+    })
+}
+
+// WAN connection service of the router's Internet Gateway Device, where the
+// IGD actions are sent
+pub struct WanConnection {
+    pub service_type: String,
+    pub control_url: String,
+    // Make and model from the IGD's device description
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+}
+
+// Discover the Internet Gateway Device and resolve its WAN connection service
+pub async fn find_wan_connection_service(gateway_ip: IpAddr) -> Result<WanConnection> {
+    let location = find_igd_location(gateway_ip).await.ok_or(RouterError::NoRouterFound)?;
     
-    radar_info!("Returning {} DNS servers", dns_servers.len());
+    let description = crate::upnp::fetch_device_description(&location)
+        .await
+        .ok_or_else(|| RouterError::RouterResponseError(format!("No usable device description at {}", location)))?;
+    let (service_type, control_path) = find_wan_connection(&description.device)
+        .ok_or_else(|| RouterError::UPnPError("No WANIPConnection or WANPPPConnection service".to_string()))?;
     
-    // If no DNS servers were found, use fallback public DNS servers
-    if dns_servers.is_empty() {
-        radar_info!("No DNS servers found, using fallback servers");
-        dns_servers.push("8.8.8.8".to_string());  // Google DNS
-        dns_servers.push("1.1.1.1".to_string());  // Cloudflare DNS
+    // Relative control URLs are resolved against URLBase when the device provides one
+    let base = description.url_base.as_deref().unwrap_or(&location);
+    let control_url = crate::upnp::resolve_url(base, &control_path).map_err(RouterError::UPnPError)?;
+    radar_debug!("WAN connection service {} at {}", service_type, control_url);
+    let device = &description.device;
+    Ok(WanConnection {
+        service_type,
+        control_url,
+        manufacturer: device.manufacturer.clone().filter(|m| !m.is_empty()),
+        model: device.model_name.clone().or_else(|| device.model_number.clone()).filter(|m| !m.is_empty()),
+    })
+}
+
+// Ask the router for its WAN address with the UPnP IGD GetExternalIPAddress action
+pub async fn get_external_ip_via_upnp(wan: &WanConnection) -> Result<String> {
+    let response = crate::upnp::invoke_action(&wan.control_url, &wan.service_type, "GetExternalIPAddress", &[])
+        .await
+        .map_err(|e| RouterError::RouterCommunicationError(e.to_string()))?;
+    let ip = crate::upnp::soap_response_value(&response, "NewExternalIPAddress")
+        .ok_or_else(|| RouterError::RouterResponseError("No NewExternalIPAddress in the response".to_string()))?;
+    
+    // Routers without a WAN connection answer with an empty or unspecified address
+    match ip.parse::<IpAddr>() {
+        Ok(addr) if !addr.is_unspecified() => Ok(ip),
+        _ => Err(RouterError::RouterResponseError(format!("Router has no external IP (got '{}')", ip))),
+    }
+}
+
+// Upper bound on the mapping table walk, in case a router never reports the end
+const MAX_PORT_MAPPINGS: u32 = 512;
+
+// Read the router's port mapping table by calling GetGenericPortMappingEntry
+// with increasing indexes until it answers SpecifiedArrayIndexInvalid (713)
+pub async fn get_port_mappings(wan: &WanConnection) -> Vec<PortMapping> {
+    let mut mappings = Vec::new();
+    for index in 0..MAX_PORT_MAPPINGS {
+        let response = match crate::upnp::invoke_action(
+            &wan.control_url,
+            &wan.service_type,
+            "GetGenericPortMappingEntry",
+            &[("NewPortMappingIndex", index.to_string())],
+        )
+        .await
+        {
+            Ok(response) => response,
+            Err(crate::upnp::ActionError::Fault { code: 713, .. }) => break,
+            Err(e) => {
+                radar_info!("Stopped reading port mappings at index {}: {}", index, e);
+                break;
+            }
+        };
+        
+        let value = |name: &str| crate::upnp::soap_response_value(&response, name).unwrap_or_default();
+        let mapping = PortMapping {
+            external_port: value("NewExternalPort").parse().unwrap_or(0),
+            internal_client: value("NewInternalClient"),
+            internal_port: value("NewInternalPort").parse().unwrap_or(0),
+            protocol: value("NewProtocol").to_uppercase(),
+            description: value("NewPortMappingDescription"),
+            enabled: matches!(value("NewEnabled").as_str(), "1" | "true"),
+        };
+        radar_debug!("Port mapping {}: {:?}", index, mapping);
+        mappings.push(mapping);
     }
     
-    Ok(dns_servers)
+    radar_info!("Router has {} UPnP port mappings", mappings.len());
+    mappings
 }
 
 // Get ISP configuration
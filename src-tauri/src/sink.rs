@@ -0,0 +1,202 @@
+// sink.rs - Destinations for events emitted during discovery
+//
+// Discovery functions report what they find through a ServiceSink instead of
+// talking to Tauri directly, so the same code can drive the GUI (AppHandle)
+// or run headless (CollectingSink).
+
+use crate::radar_warn;
+use crate::NetworkService;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Runtime};
+
+pub trait ServiceSink: Clone + Send + Sync + 'static {
+    // Emit a named event with a serializable payload
+    fn emit_event<P: Serialize + Clone>(&self, event: &str, payload: P) -> Result<(), String>;
+}
+
+// Forward events to the frontend
+impl<R: Runtime> ServiceSink for AppHandle<R> {
+    fn emit_event<P: Serialize + Clone>(&self, event: &str, payload: P) -> Result<(), String> {
+        self.emit(event, payload).map_err(|e| e.to_string())
+    }
+}
+
+// Keep events in memory, for headless use where nobody is listening
+#[derive(Debug, Clone, Default)]
+pub struct CollectingSink {
+    events: Arc<Mutex<Vec<(String, serde_json::Value)>>>,
+}
+
+impl CollectingSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // All events received so far, in emission order
+    pub fn events(&self) -> Vec<(String, serde_json::Value)> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl ServiceSink for CollectingSink {
+    fn emit_event<P: Serialize + Clone>(&self, event: &str, payload: P) -> Result<(), String> {
+        let value = serde_json::to_value(payload).map_err(|e| e.to_string())?;
+        self.events.lock().unwrap().push((event.to_string(), value));
+        Ok(())
+    }
+}
+
+// Drop every event, for callers that only want the returned results
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SilentSink;
+
+impl ServiceSink for SilentSink {
+    fn emit_event<P: Serialize + Clone>(&self, _event: &str, _payload: P) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// Tag every event with the scan that produced it, so listeners can drop events
+// from a superseded scan. Object payloads gain a "scan_id" field; anything else
+// is wrapped as { "scan_id", "value" }.
+#[derive(Debug, Clone)]
+pub struct ScanSink<S: ServiceSink> {
+    inner: S,
+    scan_id: u64,
+}
+
+impl<S: ServiceSink> ScanSink<S> {
+    pub fn new(inner: S, scan_id: u64) -> Self {
+        Self { inner, scan_id }
+    }
+
+    pub fn scan_id(&self) -> u64 {
+        self.scan_id
+    }
+}
+
+impl<S: ServiceSink> ServiceSink for ScanSink<S> {
+    fn emit_event<P: Serialize + Clone>(&self, event: &str, payload: P) -> Result<(), String> {
+        let value = match serde_json::to_value(payload).map_err(|e| e.to_string())? {
+            serde_json::Value::Object(mut fields) => {
+                fields.insert("scan_id".to_string(), self.scan_id.into());
+                serde_json::Value::Object(fields)
+            }
+            serde_json::Value::Null => serde_json::json!({ "scan_id": self.scan_id }),
+            other => serde_json::json!({ "scan_id": self.scan_id, "value": other }),
+        };
+        self.inner.emit_event(event, value)
+    }
+}
+
+// A record's identity: address, discovery method, service type and port
+type ServiceKey = (String, String, String, Option<u16>);
+
+// Collapse repeated `service-discovered` events for the same service (e.g. a
+// UPnP device answering several search targets) into one. The first sighting
+// of a key is forwarded as-is; later ones are merged with everything seen for
+// that key and emitted as `service-updated` carrying the consolidated view.
+// Other events pass through untouched.
+#[derive(Debug, Clone)]
+pub struct DedupSink<S: ServiceSink> {
+    inner: S,
+    seen: Arc<Mutex<HashMap<ServiceKey, Vec<NetworkService>>>>,
+}
+
+impl<S: ServiceSink> DedupSink<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            seen: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl<S: ServiceSink> ServiceSink for DedupSink<S> {
+    fn emit_event<P: Serialize + Clone>(&self, event: &str, payload: P) -> Result<(), String> {
+        if event != "service-discovered" {
+            return self.inner.emit_event(event, payload);
+        }
+
+        let value = serde_json::to_value(payload).map_err(|e| e.to_string())?;
+        let Ok(service) = serde_json::from_value::<NetworkService>(value.clone()) else {
+            return self.inner.emit_event(event, value);
+        };
+
+        let key = (
+            service.address.clone(),
+            service.discovery_method.clone(),
+            service.service_type.clone(),
+            service.port,
+        );
+        let merged = {
+            let mut seen = self.seen.lock().unwrap();
+            let records = seen.entry(key).or_default();
+            let first = records.is_empty();
+            if !records.contains(&service) {
+                records.push(service);
+            }
+            if first {
+                None
+            } else {
+                crate::network_scanner::consolidate_services(records.clone()).pop()
+            }
+        };
+
+        match merged {
+            None => self.inner.emit_event(event, value),
+            Some(merged) => self.inner.emit_event("service-updated", merged),
+        }
+    }
+}
+
+// Append every discovery to a JSON Lines file as it happens, one consolidated
+// service per line, before passing the event on.
+#[derive(Debug, Clone)]
+pub struct JsonlSink<S: ServiceSink> {
+    inner: S,
+    path: String,
+    file: Arc<Mutex<std::fs::File>>,
+}
+
+impl<S: ServiceSink> JsonlSink<S> {
+    pub fn open(inner: S, path: &str) -> Result<Self, String> {
+        Ok(Self {
+            inner,
+            path: path.to_string(),
+            file: Arc::new(Mutex::new(crate::export::open_jsonl(path, true)?)),
+        })
+    }
+
+    fn append(&self, service: &crate::ConsolidatedService) {
+        use std::io::Write;
+        let written = crate::export::jsonl_line(service).and_then(|line| {
+            let mut file = self.file.lock().unwrap();
+            file.write_all(line.as_bytes())
+                .and_then(|_| file.flush())
+                .map_err(|e| e.to_string())
+        });
+        if let Err(e) = written {
+            radar_warn!("Failed to append to {}: {}", self.path, e);
+        }
+    }
+}
+
+impl<S: ServiceSink> ServiceSink for JsonlSink<S> {
+    fn emit_event<P: Serialize + Clone>(&self, event: &str, payload: P) -> Result<(), String> {
+        if event != "service-discovered" {
+            return self.inner.emit_event(event, payload);
+        }
+
+        let value = serde_json::to_value(payload).map_err(|e| e.to_string())?;
+        let service = serde_json::from_value::<NetworkService>(value.clone())
+            .ok()
+            .and_then(|record| crate::network_scanner::consolidate_services(vec![record]).pop());
+        if let Some(service) = service {
+            self.append(&service);
+        }
+        self.inner.emit_event(event, value)
+    }
+}
@@ -0,0 +1,462 @@
+// upnp.rs - UPnP description fetching and service introspection
+
+use crate::{Device, DeviceDescription};
+use crate::radar_debug;
+use crate::radar_info;
+use once_cell::sync::Lazy;
+use reqwest::header::{HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE, USER_AGENT};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_USER_AGENT: &str = "Radar/1.0 UPnP/1.0";
+
+// Headers sent with every UPnP HTTP request. Some devices refuse requests
+// without a recognizable User-Agent, and a few answer differently per agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpSettings {
+    pub user_agent: String,
+    // Extra (name, value) headers, e.g. ("Accept-Language", "en")
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            headers: Vec::new(),
+        }
+    }
+}
+
+static HTTP_SETTINGS: Lazy<Mutex<HttpSettings>> = Lazy::new(|| Mutex::new(HttpSettings::default()));
+
+// Replace the HTTP settings, rejecting header names or values that can't be sent
+pub fn set_http_settings(settings: HttpSettings) -> Result<(), String> {
+    if settings.user_agent.trim().is_empty() {
+        return Err("User-Agent must not be empty".to_string());
+    }
+    HeaderValue::from_str(&settings.user_agent)
+        .map_err(|_| format!("Invalid User-Agent '{}'", settings.user_agent))?;
+    for (name, value) in &settings.headers {
+        HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("Invalid header name '{}'", name))?;
+        HeaderValue::from_str(value).map_err(|_| format!("Invalid value for header {}", name))?;
+    }
+    
+    *HTTP_SETTINGS.lock().unwrap() = settings;
+    Ok(())
+}
+
+pub fn get_http_settings() -> HttpSettings {
+    HTTP_SETTINGS.lock().unwrap().clone()
+}
+
+// Shared HTTP client for UPnP description and SCPD fetches
+pub static HTTP_CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new())
+});
+
+// Fetch an XML document (description or SCPD) from a device and return the body as text
+pub(crate) async fn fetch_text(url: &str) -> Result<String, String> {
+    let settings = get_http_settings();
+    let mut request = HTTP_CLIENT
+        .get(url)
+        .header(USER_AGENT, settings.user_agent)
+        .header(ACCEPT, "text/xml");
+    for (name, value) in settings.headers {
+        request = request.header(name, value);
+    }
+    
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Fetching {} returned HTTP {}", url, response.status()));
+    }
+
+    response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response from {}: {}", url, e))
+}
+
+// Why a SOAP action failed. UPnP reports action failures as HTTP 500 with a
+// SOAP fault, whose error code callers can match (e.g. 713 for an index past
+// the end of a list).
+#[derive(Error, Debug)]
+pub enum ActionError {
+    #[error("{action} failed: {code} {description}")]
+    Fault { action: String, code: u32, description: String },
+    
+    #[error("{0}")]
+    Failed(String),
+}
+
+// Invoke a SOAP action on a service's control URL and return the response body
+pub async fn invoke_action(
+    control_url: &str,
+    service_type: &str,
+    action: &str,
+    arguments: &[(&str, String)],
+) -> Result<String, ActionError> {
+    let arguments: String = arguments
+        .iter()
+        .map(|(name, value)| format!("<{name}>{}</{name}>", quick_xml::escape::escape(value.as_str())))
+        .collect();
+    let body = format!(
+        concat!(
+            r#"<?xml version="1.0"?>"#,
+            r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" "#,
+            r#"s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">"#,
+            r#"<s:Body><u:{action} xmlns:u="{service_type}">{arguments}</u:{action}></s:Body></s:Envelope>"#,
+        ),
+        action = action,
+        service_type = service_type,
+        arguments = arguments,
+    );
+    
+    let settings = get_http_settings();
+    let mut request = HTTP_CLIENT
+        .post(control_url)
+        .header(USER_AGENT, settings.user_agent)
+        .header(CONTENT_TYPE, r#"text/xml; charset="utf-8""#)
+        .header("SOAPAction", format!(r#""{}#{}""#, service_type, action))
+        .body(body);
+    for (name, value) in settings.headers {
+        request = request.header(name, value);
+    }
+    
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ActionError::Failed(format!("Failed to invoke {} at {}: {}", action, control_url, e)))?;
+    
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| ActionError::Failed(format!("Failed to read {} response from {}: {}", action, control_url, e)))?;
+    
+    if !status.is_success() {
+        let code = soap_response_value(&text, "errorCode").and_then(|code| code.parse().ok());
+        return Err(match code {
+            Some(code) => ActionError::Fault {
+                action: action.to_string(),
+                code,
+                description: soap_response_value(&text, "errorDescription").unwrap_or_default(),
+            },
+            None => ActionError::Failed(format!("{} at {} returned HTTP {}", action, control_url, status)),
+        });
+    }
+    Ok(text)
+}
+
+// Text of the first element named exactly `name` in a SOAP response, ignoring
+// its namespace prefix and attributes. An empty element gives "".
+pub fn soap_response_value(xml: &str, name: &str) -> Option<String> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::Reader::from_str(xml);
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(element) if element.local_name().as_ref() == name.as_bytes() => {
+                let text = reader.read_text(element.name()).ok()?;
+                return Some(decode_xml_text(&text));
+            }
+            Event::Empty(element) if element.local_name().as_ref() == name.as_bytes() => {
+                return Some(String::new());
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+// Fetch and parse a device description document
+pub async fn fetch_device_description(location_url: &str) -> Option<DeviceDescription> {
+    parse_device_description(&fetch_text(location_url).await.ok()?)
+}
+
+// Parse a device description document
+pub fn parse_device_description(xml: &str) -> Option<DeviceDescription> {
+    let mut description: DeviceDescription = quick_xml::de::from_str(xml).ok()?;
+    decode_device(&mut description.device);
+    Some(description)
+}
+
+// Decode entities left in XML text. The parser already decodes one level, but
+// many devices escape twice ("Tom &amp;amp; Jerry"). Text with a bare '&' that
+// isn't an entity is returned unchanged.
+pub fn decode_xml_text(text: &str) -> String {
+    let mut decoded = text.to_string();
+    while decoded.contains('&') {
+        match quick_xml::escape::unescape(&decoded) {
+            Ok(next) if next != decoded => decoded = next.into_owned(),
+            _ => break,
+        }
+    }
+    decoded.trim().to_string()
+}
+
+// Percent-decode a URL for display ("http://nas/My%20Files" -> "http://nas/My Files").
+// Invalid escapes and non-UTF-8 results leave the URL as it was.
+pub fn decode_url(url: &str) -> String {
+    let url = decode_xml_text(url);
+    let bytes = url.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = url.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok());
+            match hex {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 3;
+                    continue;
+                }
+                None => return url,
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8(decoded).unwrap_or(url)
+}
+
+// Decode the display fields of a device and its embedded devices. Control,
+// event and SCPD URLs are left encoded since they are fetched, not shown.
+fn decode_device(device: &mut Device) {
+    for text in [
+        &mut device.friendly_name,
+        &mut device.manufacturer,
+        &mut device.model_description,
+        &mut device.model_name,
+        &mut device.model_number,
+        &mut device.serial_number,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        *text = decode_xml_text(text);
+    }
+    for url in [&mut device.manufacturer_url, &mut device.model_url, &mut device.presentation_url]
+        .into_iter()
+        .flatten()
+    {
+        *url = decode_url(url);
+    }
+    if let Some(list) = &mut device.device_list {
+        list.devices.iter_mut().for_each(decode_device);
+    }
+}
+
+// Resolve a possibly relative URL from a description against its base URL
+pub fn resolve_url(base: &str, url: &str) -> Result<String, String> {
+    let base = reqwest::Url::parse(base).map_err(|e| format!("Invalid base URL '{}': {}", base, e))?;
+    base.join(url)
+        .map(|u| u.to_string())
+        .map_err(|e| format!("Invalid URL '{}': {}", url, e))
+}
+
+// SCPD (service description) XML structures
+#[derive(Debug, Deserialize)]
+struct Scpd {
+    #[serde(rename = "actionList")]
+    action_list: Option<ScpdActionList>,
+    #[serde(rename = "serviceStateTable")]
+    service_state_table: Option<ScpdStateTable>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScpdActionList {
+    #[serde(rename = "action", default)]
+    actions: Vec<ScpdAction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScpdAction {
+    name: String,
+    #[serde(rename = "argumentList")]
+    argument_list: Option<ScpdArgumentList>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScpdArgumentList {
+    #[serde(rename = "argument", default)]
+    arguments: Vec<ScpdArgument>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScpdArgument {
+    name: String,
+    direction: String,
+    #[serde(rename = "relatedStateVariable")]
+    related_state_variable: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScpdStateTable {
+    #[serde(rename = "stateVariable", default)]
+    variables: Vec<ScpdStateVariable>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScpdStateVariable {
+    name: String,
+    #[serde(rename = "dataType")]
+    data_type: Option<String>,
+    #[serde(rename = "allowedValueList")]
+    allowed_value_list: Option<ScpdAllowedValues>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScpdAllowedValues {
+    #[serde(rename = "allowedValue", default)]
+    values: Vec<String>,
+}
+
+// An action a UPnP service supports, as declared in its SCPD
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpnpAction {
+    pub name: String,
+    pub arguments: Vec<UpnpArgument>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpnpArgument {
+    pub name: String,
+    // "in" or "out"
+    pub direction: String,
+    pub related_state_variable: Option<String>,
+    // Type and allowed values come from the related state variable, for validating input
+    pub data_type: Option<String>,
+    pub allowed_values: Vec<String>,
+}
+
+// Find a service by type anywhere in the device tree, returning its SCPD URL
+fn find_scpd_url(device: &Device, service_type: &str) -> Option<String> {
+    let own = device.service_list.as_ref().and_then(|list| {
+        list.services
+            .iter()
+            .find(|s| s.service_type == service_type)
+            .and_then(|s| s.scpd_url.clone())
+    });
+
+    own.or_else(|| {
+        device
+            .device_list
+            .as_ref()?
+            .devices
+            .iter()
+            .find_map(|d| find_scpd_url(d, service_type))
+    })
+}
+
+// Turn a parsed SCPD into the action list, joining argument types from the state table
+fn actions_from_scpd(scpd: Scpd) -> Vec<UpnpAction> {
+    let variables = scpd
+        .service_state_table
+        .map(|table| table.variables)
+        .unwrap_or_default();
+
+    scpd.action_list
+        .map(|list| list.actions)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|action| UpnpAction {
+            name: action.name.trim().to_string(),
+            arguments: action
+                .argument_list
+                .map(|list| list.arguments)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|arg| {
+                    let related = arg.related_state_variable.map(|v| v.trim().to_string());
+                    let variable = related
+                        .as_ref()
+                        .and_then(|name| variables.iter().find(|v| v.name.trim() == name));
+                    UpnpArgument {
+                        name: arg.name.trim().to_string(),
+                        direction: arg.direction.trim().to_lowercase(),
+                        data_type: variable.and_then(|v| v.data_type.clone()),
+                        allowed_values: variable
+                            .and_then(|v| v.allowed_value_list.as_ref())
+                            .map(|list| list.values.clone())
+                            .unwrap_or_default(),
+                        related_state_variable: related,
+                    }
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+// Fetch the SCPD for a service on a device and list the actions it supports
+pub async fn get_upnp_actions(location_url: &str, service_type: &str) -> Result<Vec<UpnpAction>, String> {
+    radar_info!("Fetching UPnP actions for {} from {}", service_type, location_url);
+
+    let description_xml = fetch_text(location_url).await?;
+    let description: DeviceDescription = quick_xml::de::from_str(&description_xml)
+        .map_err(|e| format!("Malformed device description at {}: {}", location_url, e))?;
+
+    let scpd_path = find_scpd_url(&description.device, service_type)
+        .ok_or_else(|| format!("Service {} not found or has no SCPDURL", service_type))?;
+
+    // Relative SCPD URLs are resolved against URLBase when the device provides one
+    let base = description.url_base.as_deref().unwrap_or(location_url);
+    let scpd_url = resolve_url(base, scpd_path.trim())?;
+    radar_debug!("Fetching SCPD from {}", scpd_url);
+
+    let scpd_xml = fetch_text(&scpd_url).await?;
+    let scpd: Scpd = quick_xml::de::from_str(&scpd_xml)
+        .map_err(|e| format!("Malformed SCPD at {}: {}", scpd_url, e))?;
+
+    let actions = actions_from_scpd(scpd);
+    radar_info!("Service {} supports {} actions", service_type, actions.len());
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_escaped_entities_are_decoded() {
+        assert_eq!(decode_xml_text("Tom &amp;amp; Jerry"), "Tom & Jerry");
+        assert_eq!(decode_xml_text(" Living Room &lt;TV&gt; "), "Living Room <TV>");
+        // A bare '&' isn't an entity, so the text stays as it is
+        assert_eq!(decode_xml_text("Q&A Server"), "Q&A Server");
+    }
+
+    #[test]
+    fn urls_are_percent_decoded() {
+        assert_eq!(decode_url("http://nas/My%20Files"), "http://nas/My Files");
+        assert_eq!(decode_url("http://nas/caf%C3%A9?a=1&amp;b=2"), "http://nas/café?a=1&b=2");
+        // Invalid escapes and non-UTF-8 bytes leave the URL alone
+        assert_eq!(decode_url("http://nas/100%"), "http://nas/100%");
+        assert_eq!(decode_url("http://nas/%zz"), "http://nas/%zz");
+        assert_eq!(decode_url("http://nas/%FF"), "http://nas/%FF");
+    }
+
+    #[test]
+    fn soap_values_match_exact_element_names() {
+        let xml = r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body>
+            <u:GetExternalIPAddressResponse xmlns:u="urn:schemas-upnp-org:service:WANIPConnection:1">
+                <NewExternalIPAddressSource>cache</NewExternalIPAddressSource>
+                <u:NewExternalIPAddress dt="string"> 203.0.113.7 </u:NewExternalIPAddress>
+                <NewNote/>
+            </u:GetExternalIPAddressResponse>
+        </s:Body></s:Envelope>"#;
+        assert_eq!(soap_response_value(xml, "NewExternalIPAddress").as_deref(), Some("203.0.113.7"));
+        assert_eq!(soap_response_value(xml, "NewNote").as_deref(), Some(""));
+        assert_eq!(soap_response_value(xml, "ExternalIPAddress"), None);
+    }
+}
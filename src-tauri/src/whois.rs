@@ -0,0 +1,242 @@
+// whois.rs - WHOIS lookups (TCP port 43) for registration details of an IP
+//
+// Lookups start at IANA, which refers to the responsible RIR (ARIN, RIPE,
+// APNIC, LACNIC, AFRINIC). ARIN may refer on again for blocks transferred to
+// another registry. Responses are plain "key: value" text that differs per RIR,
+// so the parser only picks out the handful of fields we report.
+
+use crate::radar_debug;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const IANA_WHOIS_SERVER: &str = "whois.iana.org";
+const ARIN_WHOIS_SERVER: &str = "whois.arin.net";
+const WHOIS_PORT: u16 = 43;
+const WHOIS_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REFERRALS: usize = 3;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct WhoisInfo {
+    pub netname: Option<String>,
+    pub org: Option<String>,
+    // Allocation block, e.g. "203.0.113.0/24" (or "a - b" when not a single CIDR)
+    pub cidr: Option<String>,
+    pub abuse_email: Option<String>,
+    // WHOIS server the record came from, e.g. "whois.ripe.net"
+    pub server: String,
+}
+
+// Send one query and read the whole response
+async fn query_server(server: &str, query: &str) -> Result<String, String> {
+    let request = async {
+        let mut stream = TcpStream::connect((server, WHOIS_PORT)).await?;
+        stream.write_all(format!("{}\r\n", query).as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, std::io::Error>(String::from_utf8_lossy(&response).into_owned())
+    };
+
+    match tokio::time::timeout(WHOIS_QUERY_TIMEOUT, request).await {
+        Ok(Ok(response)) => Ok(response),
+        Ok(Err(e)) => Err(format!("WHOIS query to {} failed: {}", server, e)),
+        Err(_) => Err(format!("WHOIS query to {} timed out", server)),
+    }
+}
+
+// ARIN returns only a summary unless asked for network records with "n +"
+fn query_for(server: &str, ip: &str) -> String {
+    if server == ARIN_WHOIS_SERVER {
+        format!("n + {}", ip)
+    } else {
+        ip.to_string()
+    }
+}
+
+// Next server to ask, from IANA's "refer:" or ARIN's "ReferralServer: whois://host"
+fn referral(response: &str) -> Option<String> {
+    response.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        let key = key.trim().to_lowercase();
+        if key != "refer" && key != "referralserver" {
+            return None;
+        }
+
+        let value = value.trim();
+        // rwhois:// and http(s):// referrals speak other protocols
+        let host = match value.strip_prefix("whois://") {
+            Some(host) => host,
+            None if !value.contains("://") => value,
+            None => return None,
+        };
+        let host = host.split([':', '/']).next()?.trim();
+        (!host.is_empty()).then(|| host.to_lowercase())
+    })
+}
+
+// Express an IPv4 range "a - b" as CIDR when it covers exactly one block
+fn range_to_cidr(range: &str) -> Option<String> {
+    let (start, end) = range.split_once('-')?;
+    let start: u32 = start.trim().parse::<std::net::Ipv4Addr>().ok()?.into();
+    let end: u32 = end.trim().parse::<std::net::Ipv4Addr>().ok()?.into();
+    let size = end.checked_sub(start)?.checked_add(1)?;
+    if !size.is_power_of_two() || !start.is_multiple_of(size) {
+        return None;
+    }
+    Some(format!("{}/{}", std::net::Ipv4Addr::from(start), 32 - size.trailing_zeros()))
+}
+
+// Pick netname, org, block and abuse contact out of an ARIN, RIPE, APNIC,
+// LACNIC or AFRINIC response. Later records are more specific, so they win.
+pub fn parse_whois_response(response: &str) -> WhoisInfo {
+    let mut netname = None;
+    let mut org = None;
+    let mut descr = None;
+    let mut cidr = None;
+    let mut range = None;
+    let mut route = None;
+    let mut abuse_email = None;
+
+    for line in response.lines() {
+        let line = line.trim();
+
+        // RIPE, APNIC and AFRINIC: "% Abuse contact for '...' is 'abuse@example.net'"
+        if let Some(rest) = line.strip_prefix("% Abuse contact for") {
+            if let Some(email) = rest.split('\'').nth(3).filter(|e| e.contains('@')) {
+                abuse_email = Some(email.to_string());
+            }
+            continue;
+        }
+        if line.starts_with('%') || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        let value = Some(value.to_string());
+
+        match key.trim().to_lowercase().as_str() {
+            "netname" => netname = value,
+            "orgname" | "org-name" | "owner" => org = value,
+            "descr" if descr.is_none() => descr = value,
+            "cidr" => cidr = value,
+            "netrange" | "inetnum" | "inet6num" => range = value,
+            "route" | "route6" => route = value,
+            "orgabuseemail" | "abuse-mailbox" if abuse_email.is_none() => abuse_email = value,
+            _ => {}
+        }
+    }
+
+    // The network block is more specific than any covering route object
+    let block = range.map(|r| if r.contains('/') { r } else { range_to_cidr(&r).unwrap_or(r) });
+    WhoisInfo {
+        netname,
+        org: org.or(descr),
+        cidr: cidr.or(block).or(route),
+        abuse_email,
+        server: String::new(),
+    }
+}
+
+// Look up an IP's registration, following referrals from IANA to the RIR holding it
+pub async fn lookup_whois(ip: &str) -> Result<WhoisInfo, String> {
+    let mut server = IANA_WHOIS_SERVER.to_string();
+    let mut visited = Vec::new();
+
+    loop {
+        radar_debug!("Querying WHOIS server {} for {}", server, ip);
+        let response = query_server(&server, &query_for(&server, ip)).await?;
+        visited.push(server.clone());
+
+        match referral(&response) {
+            Some(next) if !visited.contains(&next) && visited.len() <= MAX_REFERRALS => server = next,
+            _ => {
+                let mut info = parse_whois_response(&response);
+                info.server = server;
+                return Ok(info);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ripe_response_is_parsed() {
+        let response = "% This is the RIPE Database query service.
+% Abuse contact for '193.0.0.0 - 193.0.7.255' is 'abuse@ripe.net'
+
+inetnum:        193.0.0.0 - 193.0.7.255
+netname:        RIPE-NCC
+descr:          RIPE Network Coordination Centre
+org:            ORG-RIEN1-RIPE
+country:        NL
+
+organisation:   ORG-RIEN1-RIPE
+org-name:       Reseaux IP Europeens Network Coordination Centre (RIPE NCC)
+
+route:          193.0.0.0/21
+origin:         AS3333
+";
+        let info = parse_whois_response(response);
+        assert_eq!(info.netname.as_deref(), Some("RIPE-NCC"));
+        assert_eq!(info.org.as_deref(), Some("Reseaux IP Europeens Network Coordination Centre (RIPE NCC)"));
+        assert_eq!(info.cidr.as_deref(), Some("193.0.0.0/21"));
+        assert_eq!(info.abuse_email.as_deref(), Some("abuse@ripe.net"));
+    }
+
+    #[test]
+    fn arin_response_is_parsed() {
+        let response = "#
+# ARIN WHOIS data and services are subject to the Terms of Use
+#
+
+NetRange:       8.8.8.0 - 8.8.8.255
+CIDR:           8.8.8.0/24
+NetName:        GOGL
+Organization:   Google LLC (GOGL)
+
+OrgName:        Google LLC
+OrgId:          GOGL
+
+OrgAbuseHandle: ABUSE5250-ARIN
+OrgAbuseEmail:  network-abuse@google.com
+";
+        let info = parse_whois_response(response);
+        assert_eq!(info.netname.as_deref(), Some("GOGL"));
+        assert_eq!(info.org.as_deref(), Some("Google LLC"));
+        assert_eq!(info.cidr.as_deref(), Some("8.8.8.0/24"));
+        assert_eq!(info.abuse_email.as_deref(), Some("network-abuse@google.com"));
+    }
+
+    #[test]
+    fn unaligned_ranges_stay_ranges() {
+        assert_eq!(range_to_cidr("203.0.113.0 - 203.0.113.255").as_deref(), Some("203.0.113.0/24"));
+        assert_eq!(range_to_cidr("10.0.0.5-10.0.0.5").as_deref(), Some("10.0.0.5/32"));
+        // Not a power of two, not starting on a block boundary, or backwards
+        assert_eq!(range_to_cidr("10.0.0.0 - 10.0.0.2"), None);
+        assert_eq!(range_to_cidr("10.0.0.128 - 10.0.1.127"), None);
+        assert_eq!(range_to_cidr("10.0.0.255 - 10.0.0.0"), None);
+
+        let info = parse_whois_response("inetnum: 10.0.0.128 - 10.0.1.127\n");
+        assert_eq!(info.cidr.as_deref(), Some("10.0.0.128 - 10.0.1.127"));
+    }
+
+    #[test]
+    fn referrals_name_the_next_whois_host() {
+        assert_eq!(referral("refer:        whois.ripe.net\n").as_deref(), Some("whois.ripe.net"));
+        assert_eq!(referral("ReferralServer:  whois://WHOIS.APNIC.NET:43\n").as_deref(), Some("whois.apnic.net"));
+        assert_eq!(referral("ReferralServer: whois://whois.lacnic.net/\n").as_deref(), Some("whois.lacnic.net"));
+        // Other protocols can't be followed
+        assert_eq!(referral("ReferralServer: rwhois://rwhois.example.net:4321\n"), None);
+        assert_eq!(referral("NetName: GOGL\n"), None);
+    }
+}
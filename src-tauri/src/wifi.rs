@@ -0,0 +1,322 @@
+// wifi.rs - Details of the WiFi network this machine is connected to
+//
+// There is no portable API for this, so the platform's own tools are run and
+// their text output parsed: `airport -I` (falling back to `system_profiler
+// SPAirPortDataType`, since newer macOS releases removed airport) on macOS, and
+// `iw dev <if> link` (falling back to `nmcli`) on Linux. Other platforms, and
+// machines on Ethernet, report no WiFi info.
+
+use crate::radar_debug;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct WifiInfo {
+    pub interface: Option<String>,
+    pub ssid: Option<String>,
+    // MAC address of the access point's radio
+    pub bssid: Option<String>,
+    pub signal_dbm: Option<i32>,
+    pub channel: Option<u32>,
+    // Receive rate in Mbit/s
+    pub rx_rate: Option<f64>,
+    // Address of the discovered device that is the access point, matched by BSSID
+    pub access_point: Option<String>,
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Leading number of a value like "-55 dBm", "433.3 MBit/s" or "36 (5GHz, 80MHz)"
+fn leading_number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    let end = value
+        .char_indices()
+        .find(|(i, c)| !(c.is_ascii_digit() || *c == '.' || (*i == 0 && *c == '-')))
+        .map_or(value.len(), |(i, _)| i);
+    value[..end].parse().ok()
+}
+
+// WiFi channel for a frequency in MHz (2.4, 5 and 6 GHz bands)
+fn channel_for_frequency(mhz: u32) -> Option<u32> {
+    match mhz {
+        2484 => Some(14),
+        2412..=2472 => Some((mhz - 2407) / 5),
+        5000..=5900 => Some((mhz - 5000) / 5),
+        5955..=7115 => Some((mhz - 5950) / 5),
+        _ => None,
+    }
+}
+
+// Parse `airport -I`:
+//      agrCtlRSSI: -55
+//      lastTxRate: 585
+//           BSSID: 14:cc:20:aa:bb:cc
+//            SSID: HomeNet
+//         channel: 36,80
+pub fn parse_airport(output: &str) -> Option<WifiInfo> {
+    let mut info = WifiInfo::default();
+    for line in output.lines() {
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "SSID" => info.ssid = Some(value.to_string()),
+            "BSSID" => info.bssid = crate::inventory::normalize_mac(value),
+            "agrCtlRSSI" => info.signal_dbm = leading_number(value),
+            "channel" => info.channel = leading_number(value),
+            // airport only reports the transmit rate, the closest it has to a link rate
+            "lastTxRate" => info.rx_rate = leading_number(value),
+            _ => {}
+        }
+    }
+    info.ssid.is_some().then_some(info)
+}
+
+// Parse the current network from `system_profiler SPAirPortDataType`, which
+// has no BSSID:
+//       Current Network Information:
+//         HomeNet:
+//           Channel: 36 (5GHz, 80MHz)
+//           Signal / Noise: -55 dBm / -90 dBm
+//           Transmit Rate: 585
+pub fn parse_system_profiler(output: &str) -> Option<WifiInfo> {
+    let mut lines = output.lines().skip_while(|line| line.trim() != "Current Network Information:");
+    lines.next()?;
+    let ssid = lines.next()?.trim().strip_suffix(':')?.to_string();
+
+    let mut info = WifiInfo { ssid: Some(ssid), ..Default::default() };
+    for line in lines {
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        // The next network's heading ends the current one
+        if value.trim().is_empty() {
+            break;
+        }
+        match key.trim() {
+            "Channel" => info.channel = leading_number(value.trim()),
+            "Signal / Noise" => info.signal_dbm = leading_number(value.trim()),
+            "Transmit Rate" => info.rx_rate = leading_number(value.trim()),
+            _ => {}
+        }
+    }
+    Some(info)
+}
+
+// Parse `iw dev <if> link`, or None when not connected:
+//   Connected to 14:cc:20:aa:bb:cc (on wlan0)
+//           SSID: HomeNet
+//           freq: 5180
+//           signal: -55 dBm
+//           rx bitrate: 433.3 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 1
+pub fn parse_iw_link(output: &str) -> Option<WifiInfo> {
+    let mut lines = output.lines();
+    let header = lines.next()?.trim();
+    let rest = header.strip_prefix("Connected to ")?;
+    let (bssid, interface) = match rest.split_once(" (on ") {
+        Some((bssid, interface)) => (bssid, Some(interface.trim_end_matches(')').to_string())),
+        None => (rest, None),
+    };
+
+    let mut info = WifiInfo {
+        interface,
+        bssid: crate::inventory::normalize_mac(bssid.trim()),
+        ..Default::default()
+    };
+    for line in lines {
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "SSID" => info.ssid = Some(value.to_string()),
+            "freq" => info.channel = leading_number(value).and_then(channel_for_frequency),
+            "signal" => info.signal_dbm = leading_number(value),
+            "rx bitrate" => info.rx_rate = leading_number(value),
+            _ => {}
+        }
+    }
+    Some(info)
+}
+
+// Wireless interface names from `iw dev` ("Interface wlan0" lines)
+#[cfg(target_os = "linux")]
+fn parse_iw_interfaces(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("Interface "))
+        .map(|name| name.trim().to_string())
+        .collect()
+}
+
+// Split a terse nmcli line on unescaped ':' ("\:" is a literal colon)
+fn split_nmcli_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => fields.last_mut().unwrap().extend(chars.next()),
+            ':' => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+// Parse the active network from
+// `nmcli -t -f ACTIVE,SSID,BSSID,SIGNAL,CHAN,RATE,DEVICE dev wifi`:
+//   yes:HomeNet:14\:CC\:20\:AA\:BB\:CC:90:36:540 Mbit/s:wlan0
+// nmcli reports signal as a 0-100 quality, converted here with NetworkManager's
+// own mapping (quality = 2 * (dBm + 100)).
+pub fn parse_nmcli(output: &str) -> Option<WifiInfo> {
+    output.lines().find_map(|line| {
+        let fields = split_nmcli_fields(line);
+        let [active, ssid, bssid, signal, channel, rate, device] = fields.as_slice() else {
+            return None;
+        };
+        if active != "yes" {
+            return None;
+        }
+        Some(WifiInfo {
+            interface: (!device.is_empty()).then(|| device.clone()),
+            ssid: (!ssid.is_empty()).then(|| ssid.clone()),
+            bssid: crate::inventory::normalize_mac(bssid),
+            signal_dbm: signal.parse::<f64>().ok().map(|quality| (quality / 2.0 - 100.0).round() as i32),
+            channel: channel.parse().ok(),
+            rx_rate: leading_number(rate),
+            access_point: None,
+        })
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn read_wifi_info() -> Option<WifiInfo> {
+    const AIRPORT: &str =
+        "/System/Library/PrivateFrameworks/Apple80211.framework/Versions/Current/Resources/airport";
+    run(AIRPORT, &["-I"])
+        .and_then(|output| parse_airport(&output))
+        .or_else(|| run("system_profiler", &["SPAirPortDataType"]).and_then(|output| parse_system_profiler(&output)))
+}
+
+#[cfg(target_os = "linux")]
+fn read_wifi_info() -> Option<WifiInfo> {
+    let interfaces = run("iw", &["dev"]).map(|output| parse_iw_interfaces(&output)).unwrap_or_default();
+    interfaces
+        .iter()
+        .find_map(|interface| run("iw", &["dev", interface, "link"]).and_then(|output| parse_iw_link(&output)))
+        .or_else(|| {
+            run("nmcli", &["-t", "-f", "ACTIVE,SSID,BSSID,SIGNAL,CHAN,RATE,DEVICE", "dev", "wifi"])
+                .and_then(|output| parse_nmcli(&output))
+        })
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn read_wifi_info() -> Option<WifiInfo> {
+    None
+}
+
+// WiFi network this machine is connected to, or None on Ethernet or an
+// unsupported platform. The access point is looked up among the hosts in the
+// ARP table by its BSSID.
+pub fn get_wifi_info() -> Option<WifiInfo> {
+    let mut info = read_wifi_info()?;
+    if let Some(bssid) = &info.bssid {
+        info.access_point = crate::network_scanner::read_arp_macs()
+            .remove(bssid)
+            .and_then(|addresses| addresses.into_iter().next());
+    }
+    radar_debug!("WiFi info: {:?}", info);
+    Some(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn airport_output_is_parsed() {
+        let output = "     agrCtlRSSI: -55
+     agrExtRSSI: 0
+    agrCtlNoise: -90
+          state: running
+     lastTxRate: 585
+        maxRate: 867
+          BSSID: 14:cc:20:aa:bb:c
+           SSID: HomeNet
+            MCS: 9
+        channel: 36,80
+";
+        let info = parse_airport(output).unwrap();
+        assert_eq!(info.ssid.as_deref(), Some("HomeNet"));
+        assert_eq!(info.bssid.as_deref(), Some("14:cc:20:aa:bb:0c"));
+        assert_eq!(info.signal_dbm, Some(-55));
+        assert_eq!(info.channel, Some(36));
+        assert_eq!(info.rx_rate, Some(585.0));
+        assert!(parse_airport("AirPort: Off\n").is_none());
+    }
+
+    #[test]
+    fn system_profiler_current_network_is_parsed() {
+        let output = "Wi-Fi:
+      Interfaces:
+        en0:
+          Status: Connected
+          Current Network Information:
+            HomeNet:
+              PHY Mode: 802.11ac
+              Channel: 36 (5GHz, 80MHz)
+              Signal / Noise: -55 dBm / -90 dBm
+              Transmit Rate: 585
+          Other Local Wi-Fi Networks:
+            Neighbor:
+              Channel: 6 (2GHz, 20MHz)
+";
+        let info = parse_system_profiler(output).unwrap();
+        assert_eq!(info.ssid.as_deref(), Some("HomeNet"));
+        assert_eq!(info.bssid, None);
+        assert_eq!(info.channel, Some(36));
+        assert_eq!(info.signal_dbm, Some(-55));
+        assert_eq!(info.rx_rate, Some(585.0));
+    }
+
+    #[test]
+    fn iw_link_output_is_parsed() {
+        let output = "Connected to 14:cc:20:aa:bb:cc (on wlan0)
+\tSSID: HomeNet
+\tfreq: 5180
+\tRX: 123456 bytes (789 packets)
+\tsignal: -55 dBm
+\trx bitrate: 433.3 MBit/s VHT-MCS 9 80MHz short GI VHT-NSS 1
+";
+        let info = parse_iw_link(output).unwrap();
+        assert_eq!(info.interface.as_deref(), Some("wlan0"));
+        assert_eq!(info.bssid.as_deref(), Some("14:cc:20:aa:bb:cc"));
+        assert_eq!(info.ssid.as_deref(), Some("HomeNet"));
+        assert_eq!(info.channel, Some(36));
+        assert_eq!(info.signal_dbm, Some(-55));
+        assert_eq!(info.rx_rate, Some(433.3));
+        assert!(parse_iw_link("Not connected.\n").is_none());
+    }
+
+    #[test]
+    fn nmcli_active_network_is_parsed() {
+        let output = "no:Neighbor:AA\\:BB\\:CC\\:00\\:11\\:22:40:6:130 Mbit/s:wlan0
+yes:Home\\:Net:14\\:CC\\:20\\:AA\\:BB\\:CC:91:36:540 Mbit/s:wlan0
+";
+        let info = parse_nmcli(output).unwrap();
+        assert_eq!(info.ssid.as_deref(), Some("Home:Net"));
+        assert_eq!(info.bssid.as_deref(), Some("14:cc:20:aa:bb:cc"));
+        // Quality 91 is -54.5 dBm
+        assert_eq!(info.signal_dbm, Some(-55));
+        assert_eq!(info.channel, Some(36));
+        assert_eq!(info.rx_rate, Some(540.0));
+        assert_eq!(info.interface.as_deref(), Some("wlan0"));
+    }
+}
+
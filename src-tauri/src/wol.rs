@@ -0,0 +1,82 @@
+// wol.rs - Wake-on-LAN magic packets
+//
+// A magic packet is six 0xFF bytes followed by the target's MAC address
+// repeated 16 times, sent as a UDP broadcast. The NIC of a sleeping machine
+// with WoL enabled watches for it and powers the machine on.
+
+use crate::inventory::normalize_mac;
+use crate::radar_info;
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+
+// Conventional "discard" port for magic packets
+const WOL_PORT: u16 = 9;
+
+// Limited broadcast, used when no subnet broadcast address is given
+const DEFAULT_BROADCAST: Ipv4Addr = Ipv4Addr::BROADCAST;
+
+// Build the 102-byte magic packet for a MAC address in any common notation
+pub fn magic_packet(mac: &str) -> Result<Vec<u8>, String> {
+    let normalized = normalize_mac(mac).ok_or_else(|| format!("Invalid MAC address '{}'", mac))?;
+    let octets = normalized
+        .split(':')
+        .map(|o| u8::from_str_radix(o, 16).map_err(|e| e.to_string()))
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    let mut packet = vec![0xFF; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&octets);
+    }
+    Ok(packet)
+}
+
+// Send a magic packet for `mac` to port 9 on `broadcast` (255.255.255.255 by
+// default; a subnet broadcast such as 192.168.1.255 reaches further on some networks)
+pub fn wake_on_lan(mac: &str, broadcast: Option<&str>) -> Result<(), String> {
+    let packet = magic_packet(mac)?;
+    let broadcast = match broadcast.map(str::trim).filter(|b| !b.is_empty()) {
+        Some(address) => address
+            .parse::<Ipv4Addr>()
+            .map_err(|_| format!("Invalid broadcast address '{}'", address))?,
+        None => DEFAULT_BROADCAST,
+    };
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).map_err(|e| format!("Failed to open UDP socket: {}", e))?;
+    socket.set_broadcast(true).map_err(|e| format!("Failed to enable broadcast: {}", e))?;
+    socket
+        .send_to(&packet, SocketAddrV4::new(broadcast, WOL_PORT))
+        .map_err(|e| format!("Failed to send magic packet to {}: {}", broadcast, e))?;
+
+    radar_info!("Sent Wake-on-LAN packet for {} to {}:{}", mac, broadcast, WOL_PORT);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_packet_repeats_the_mac_after_a_sync_stream() {
+        let packet = magic_packet("00-1A-2b-3C-4d-5E").unwrap();
+        assert_eq!(packet.len(), 102);
+        assert_eq!(packet[..6], [0xFF; 6]);
+        let mac = [0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e];
+        for copy in packet[6..].chunks(6) {
+            assert_eq!(copy, mac);
+        }
+    }
+
+    #[test]
+    fn malformed_macs_are_rejected() {
+        for mac in [
+            "00:1a:2b:3c:4d",
+            "00:1a:2b:3c:4d:5e:6f",
+            "001a2b3c4d",
+            "00:1a:2b:3c:4d:zz",
+            "g01a2b3c4d5e",
+            "00:1a-2b:3c-4d:5e",
+            "",
+        ] {
+            assert!(magic_packet(mac).is_err(), "accepted {}", mac);
+        }
+    }
+}